@@ -0,0 +1,398 @@
+use crate::back::commands::{generate_function, GeneratedFunction};
+use crate::back::names::Names;
+use crate::front::ast_types::{Attribute, ResolvedName};
+use crate::middle::register_allocation::allocate_registers;
+use crate::middle::stack_frames::insert_stack_frames;
+use crate::middle::types::IRModule;
+use camino::Utf8PathBuf;
+use std::collections::BTreeMap;
+
+mod commands;
+mod names;
+
+#[derive(Debug, PartialEq)]
+pub enum BackendError {
+    DuplicateFunction(String), // two functions would be written to the same location
+    InvalidNamespace(String),
+    NameCollision(String, ResolvedName, ResolvedName), // two items would get the same name
+}
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+pub struct BackendOptions {
+    pub namespace: String, // the namespace of internal functions and storages
+    pub objective: String, // the scoreboard objective holding all scores
+    pub short_names: bool, // hashes the names of functions and variables, to keep the commands short
+}
+
+impl Default for BackendOptions {
+    fn default() -> Self {
+        BackendOptions {
+            namespace: "blastfurnace".to_string(),
+            objective: "bf".to_string(),
+            short_names: false,
+        }
+    }
+}
+
+const PACK_FORMAT: u32 = 48;
+
+// all files of the generated datapack, relative to the datapack root
+#[derive(Debug, PartialEq, Default)]
+pub struct Datapack {
+    pub files: BTreeMap<Utf8PathBuf, String>,
+    // the `#[test]` functions, from their name (e.g. `pkg::main::adds`) to their location
+    pub tests: BTreeMap<String, String>,
+}
+
+// ns:path/to/fn => data/ns/function/path/to/fn.mcfunction
+fn function_file(location: &str) -> Utf8PathBuf {
+    let (namespace, path) = location.split_once(':').unwrap();
+    Utf8PathBuf::from(format!("data/{}/function/{}.mcfunction", namespace, path))
+}
+
+fn function_tag(values: &[String]) -> String {
+    let values = values
+        .iter()
+        .map(|value| format!("    \"{}\"", value))
+        .collect::<Vec<_>>();
+    format!("{{\n  \"values\": [\n{}\n  ]\n}}\n", values.join(",\n"))
+}
+
+/* Turns the IR of all modules into a datapack.
+
+Functions marked with `#[export("ns:path")]` are placed at that location, every other function gets a mangled
+name inside the namespace given in the options. The `minecraft:load` tag runs the initialization function, the
+main function and every `#[load]` function, while `minecraft:tick` runs every `#[tick]` function.
+
+The variables of recursive functions are saved around their recursive calls, and the registers of all functions are
+allocated to shared slots, before any command is generated.
+ */
+pub fn generate_datapack(
+    modules: &[IRModule],
+    main: Option<&ResolvedName>,
+    options: &BackendOptions,
+) -> BackendResult<Datapack> {
+    if options.namespace.is_empty()
+        || !options
+            .namespace
+            .chars()
+            .all(|ch| matches!(ch, 'a'..='z' | '0'..='9' | '_' | '-' | '.'))
+    {
+        return Err(BackendError::InvalidNamespace(options.namespace.clone()));
+    }
+
+    let mut modules = modules.to_vec();
+    insert_stack_frames(&mut modules);
+    allocate_registers(&mut modules);
+
+    let mut names = Names::new(options, &modules)?;
+    let mut load = vec![format!("{}:__init", options.namespace)];
+    let mut tick = vec![];
+    let mut tests = BTreeMap::new();
+
+    for function in modules.iter().flat_map(|module| module.functions.iter()) {
+        for attribute in function.attributes.iter() {
+            if let Attribute::Export(location) = attribute {
+                names.set_function_location(&function.name, location.clone());
+            }
+        }
+    }
+
+    if let Some(main) = main {
+        load.push(names.function_location(main));
+    }
+
+    let mut generated: Vec<GeneratedFunction> = vec![(
+        format!("{}:__init", options.namespace),
+        vec![format!(
+            "scoreboard objectives add {} dummy",
+            options.objective
+        )],
+    )];
+
+    for function in modules.iter().flat_map(|module| module.functions.iter()) {
+        for attribute in function.attributes.iter() {
+            match attribute {
+                Attribute::Load => load.push(names.function_location(&function.name)),
+                Attribute::Tick => tick.push(names.function_location(&function.name)),
+                // tests are not run by the game, but each of them is an entry point for the test runner
+                Attribute::Test => {
+                    let name = function.name.item_name.rsplit(':').next().unwrap();
+                    tests.insert(
+                        format!("{}::{}", function.name.module_id, name),
+                        names.function_location(&function.name),
+                    );
+                }
+                Attribute::Export(_) | Attribute::Inline(_) => {}
+            }
+        }
+        generated.extend(generate_function(function, &names));
+    }
+
+    let mut files = BTreeMap::new();
+    for (location, commands) in generated {
+        let mut content = commands.join("\n");
+        content.push('\n');
+        if files.insert(function_file(&location), content).is_some() {
+            return Err(BackendError::DuplicateFunction(location));
+        }
+    }
+
+    files.insert(
+        Utf8PathBuf::from("pack.mcmeta"),
+        format!(
+            "{{\n  \"pack\": {{\n    \"pack_format\": {},\n    \"description\": \"\"\n  }}\n}}\n",
+            PACK_FORMAT
+        ),
+    );
+    files.insert(
+        Utf8PathBuf::from("data/minecraft/tags/function/load.json"),
+        function_tag(&load),
+    );
+    if !tick.is_empty() {
+        files.insert(
+            Utf8PathBuf::from("data/minecraft/tags/function/tick.json"),
+            function_tag(&tick),
+        );
+    }
+
+    Ok(Datapack { files, tests })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::types::{BasicBlock, IRFunction, IRInstruction, Terminator};
+
+    fn function(item_name: &str, attributes: Vec<Attribute>) -> IRFunction {
+        IRFunction {
+            name: ResolvedName::new("pkg::main".to_string(), item_name.to_string()),
+            attributes,
+            params: vec![],
+            locals: vec![],
+            blocks: vec![BasicBlock {
+                instructions: vec![IRInstruction::MCommand("say hi".to_string())],
+                terminator: Terminator::Return,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_entry_point_attributes() {
+        let modules = vec![IRModule {
+            id: "pkg::main".to_string(),
+            statics: vec![],
+            functions: vec![
+                function("0:0:main", vec![]),
+                function("0:0:on_load", vec![Attribute::Load]),
+                function(
+                    "0:0:on_tick",
+                    vec![Attribute::Tick, Attribute::Export("map:tick".to_string())],
+                ),
+                function(
+                    "0:0:door",
+                    vec![Attribute::Export("map:doors/open".to_string())],
+                ),
+            ],
+        }];
+        let main = ResolvedName::new("pkg::main".to_string(), "0:0:main".to_string());
+
+        let datapack =
+            generate_datapack(&modules, Some(&main), &BackendOptions::default()).unwrap();
+        let file = |path: &str| datapack.files.get(&Utf8PathBuf::from(path)).unwrap();
+
+        assert_eq!(
+            file("data/minecraft/tags/function/load.json"),
+            &function_tag(&[
+                "blastfurnace:__init".to_string(),
+                "blastfurnace:pkg/main/0_0_main".to_string(),
+                "blastfurnace:pkg/main/0_0_on_load".to_string(),
+            ])
+        );
+        assert_eq!(
+            file("data/minecraft/tags/function/tick.json"),
+            &function_tag(&["map:tick".to_string()])
+        );
+        assert_eq!(file("data/map/function/doors/open.mcfunction"), "say hi\n");
+        assert_eq!(
+            file("data/blastfurnace/function/__init.mcfunction"),
+            "scoreboard objectives add bf dummy\n"
+        );
+        assert!(datapack.files.contains_key(&Utf8PathBuf::from(
+            "data/blastfurnace/function/pkg/main/0_0_main.mcfunction"
+        )));
+        assert!(!datapack.files.contains_key(&Utf8PathBuf::from(
+            "data/blastfurnace/function/pkg/main/0_0_door.mcfunction"
+        )));
+    }
+
+    #[test]
+    fn test_duplicate_export() {
+        let modules = vec![IRModule {
+            id: "pkg::main".to_string(),
+            statics: vec![],
+            functions: vec![
+                function("0:0:a", vec![Attribute::Export("map:f".to_string())]),
+                function("0:0:b", vec![Attribute::Export("map:f".to_string())]),
+            ],
+        }];
+
+        assert_eq!(
+            generate_datapack(&modules, None, &BackendOptions::default()),
+            Err(BackendError::DuplicateFunction("map:f".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_interpolated_command() {
+        use crate::middle::types::{IRCommandPart, Score};
+
+        let var = ResolvedName::new("pkg::main".to_string(), "0:0:x".to_string());
+        let modules = vec![IRModule {
+            id: "pkg::main".to_string(),
+            statics: vec![],
+            functions: vec![IRFunction {
+                name: ResolvedName::new("pkg::main".to_string(), "0:0:f".to_string()),
+                attributes: vec![],
+                params: vec![],
+                locals: vec![],
+                blocks: vec![BasicBlock {
+                    instructions: vec![IRInstruction::InterpolatedCommand(vec![
+                        IRCommandPart::Text("say ".to_string()),
+                        IRCommandPart::ScoreValue(Score::Var(var), 1),
+                    ])],
+                    terminator: Terminator::Return,
+                }],
+            }],
+        }];
+
+        let datapack = generate_datapack(&modules, None, &BackendOptions::default()).unwrap();
+        let file = |path: &str| datapack.files.get(&Utf8PathBuf::from(path)).unwrap();
+
+        assert_eq!(
+            file("data/blastfurnace/function/pkg/main/0_0_f.mcfunction"),
+            "execute store result storage blastfurnace:macro arg0 int 1 run scoreboard players get $pkg.main.0_0_x bf\n\
+             function blastfurnace:pkg/main/0_0_f/cmd_0 with storage blastfurnace:macro\n"
+        );
+        assert_eq!(
+            file("data/blastfurnace/function/pkg/main/0_0_f/cmd_0.mcfunction"),
+            "$say $(arg0)\n"
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        use crate::middle::types::Score;
+
+        let name = ResolvedName::new("pkg::main".to_string(), "0:0:f".to_string());
+        let block = |instructions, terminator| BasicBlock {
+            instructions,
+            terminator,
+        };
+        let modules = vec![IRModule {
+            id: "pkg::main".to_string(),
+            statics: vec![],
+            functions: vec![IRFunction {
+                name: name.clone(),
+                attributes: vec![Attribute::Export("map:f".to_string())],
+                params: vec![],
+                locals: vec![],
+                blocks: vec![
+                    block(
+                        vec![IRInstruction::ScoreSet(Score::Reg(0), 1)],
+                        Terminator::Branch(Score::Reg(0), 1, 2),
+                    ),
+                    block(
+                        vec![IRInstruction::MCommand("say then".to_string())],
+                        Terminator::Jump(2),
+                    ),
+                    block(vec![], Terminator::TailCall(name)),
+                ],
+            }],
+        }];
+
+        let datapack = generate_datapack(&modules, None, &BackendOptions::default()).unwrap();
+        let file = |path: &str| datapack.files.get(&Utf8PathBuf::from(path)).unwrap();
+
+        // the first block is at the location of the function, the others are internal
+        assert_eq!(
+            file("data/map/function/f.mcfunction"),
+            "scoreboard players set $r0 bf 1\n\
+             execute unless score $r0 bf matches 0 run return run function blastfurnace:pkg/main/0_0_f/block_1\n\
+             return run function blastfurnace:pkg/main/0_0_f/block_2\n"
+        );
+        assert_eq!(
+            file("data/blastfurnace/function/pkg/main/0_0_f/block_1.mcfunction"),
+            "say then\nreturn run function blastfurnace:pkg/main/0_0_f/block_2\n"
+        );
+        assert_eq!(
+            file("data/blastfurnace/function/pkg/main/0_0_f/block_2.mcfunction"),
+            "return run function map:f\n"
+        );
+    }
+
+    #[test]
+    fn test_loop_blocks() {
+        use crate::middle::types::{CompareOperation, Score, ScoreOperation};
+        use crate::simulator::Simulator;
+
+        // i = 0; while i < 6000 { i += 1 }
+        let block = |instructions, terminator| BasicBlock {
+            instructions,
+            terminator,
+        };
+        let modules = vec![IRModule {
+            id: "pkg::main".to_string(),
+            statics: vec![],
+            functions: vec![IRFunction {
+                name: ResolvedName::new("pkg::main".to_string(), "0:0:f".to_string()),
+                attributes: vec![Attribute::Export("map:f".to_string())],
+                params: vec![],
+                locals: vec![],
+                blocks: vec![
+                    block(
+                        vec![
+                            IRInstruction::ScoreSet(Score::Reg(0), 0),
+                            IRInstruction::ScoreSet(Score::Reg(1), 6000),
+                            IRInstruction::ScoreSet(Score::Reg(2), 1),
+                        ],
+                        Terminator::Jump(1),
+                    ),
+                    block(
+                        vec![IRInstruction::ScoreCompare(
+                            Score::Reg(3),
+                            Score::Reg(0),
+                            CompareOperation::Lt,
+                            Score::Reg(1),
+                        )],
+                        Terminator::Branch(Score::Reg(3), 2, 3),
+                    ),
+                    block(
+                        vec![IRInstruction::ScoreOperation(
+                            Score::Reg(0),
+                            ScoreOperation::Add,
+                            Score::Reg(2),
+                        )],
+                        Terminator::Jump(1),
+                    ),
+                    block(vec![], Terminator::Return),
+                ],
+            }],
+        }];
+        let datapack = generate_datapack(&modules, None, &BackendOptions::default()).unwrap();
+
+        // every block is entered with `return run function`, so an iteration returns into the next one instead of
+        // nesting it
+        for content in datapack.files.values() {
+            for line in content.lines().filter(|line| line.contains("function ")) {
+                assert!(line.contains("return run function"), "{}", line);
+            }
+        }
+
+        let mut simulator = Simulator::new(&datapack);
+        simulator.run_function("blastfurnace:__init").unwrap();
+        simulator.run_function("map:f").unwrap();
+        assert_eq!(simulator.score("$r0", "bf"), Some(6000));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::back::names::Names;
+use crate::middle::types::{
+    BlockId, CompareOperation, IRCommandPart, IRFunction, IRInstruction, Location, ScoreOperation,
+    Terminator,
+};
+
+// an mcfunction file, given by its location and its commands
+pub type GeneratedFunction = (String, Vec<String>);
+
+fn score_operation(operation: ScoreOperation) -> &'static str {
+    match operation {
+        ScoreOperation::Assign => "=",
+        ScoreOperation::Add => "+=",
+        ScoreOperation::Sub => "-=",
+        ScoreOperation::Mul => "*=",
+        ScoreOperation::Div => "/=",
+        ScoreOperation::Mod => "%=",
+        ScoreOperation::Min => "<",
+        ScoreOperation::Max => ">",
+    }
+}
+
+fn compare_operation(operation: CompareOperation) -> &'static str {
+    match operation {
+        CompareOperation::Eq | CompareOperation::Ne => "=",
+        CompareOperation::Lt => "<",
+        CompareOperation::Le => "<=",
+        CompareOperation::Gt => ">",
+        CompareOperation::Ge => ">=",
+    }
+}
+
+// the type and scale to store a score with, e.g. `int 1`, so that the stored value is the score divided by the scale
+fn store_type(scale: i32) -> String {
+    if scale == 1 {
+        "int 1".to_string()
+    } else {
+        format!("double {}", 1.0 / scale as f64)
+    }
+}
+
+// quotes a string as an SNBT string
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/* Generates the commands of a function.
+
+Every basic block is a function of its own, and the first one is placed at the location of the function. Control
+is passed to another block with `return run function`, so that nothing runs after the other block is done, which
+makes a branch an `execute unless score ... matches 0` in front of it.
+
+Commands that interpolate the value of a variable need function macros, so each of them is placed in its own helper
+function. The returned list starts with the blocks of the function, followed by the helpers.
+ */
+pub fn generate_function(function: &IRFunction, names: &Names) -> Vec<GeneratedFunction> {
+    let block_location = |block: BlockId| {
+        if block == 0 {
+            names.function_location(&function.name)
+        } else {
+            format!(
+                "{}/block_{}",
+                names.internal_function_location(&function.name),
+                block
+            )
+        }
+    };
+    let mut blocks = vec![];
+    let mut helpers = vec![];
+
+    let score = |score| names.score(score);
+    let storage = |storage| names.storage(storage);
+
+    for (id, block) in function.blocks.iter().enumerate() {
+        let mut commands = vec![];
+        for instruction in block.instructions.iter() {
+            match instruction {
+                IRInstruction::MCommand(command) => commands.push(command.clone()),
+                IRInstruction::InterpolatedCommand(parts) => {
+                    let mut macro_line = String::new();
+                    let mut is_macro = false;
+
+                    for part in parts.iter() {
+                        match part {
+                            IRCommandPart::Text(text) => macro_line.push_str(text),
+                            IRCommandPart::ScoreLocation(s) => macro_line.push_str(&score(s)),
+                            IRCommandPart::StorageLocation(s) => macro_line.push_str(&storage(s)),
+                            IRCommandPart::ScoreValue(..) | IRCommandPart::StorageValue(_) => {
+                                let argument = format!("arg{}", commands.len());
+                                commands.push(match part {
+                                    IRCommandPart::ScoreValue(s, scale) => format!(
+                                        "execute store result storage {} {} {} run scoreboard players get {}",
+                                        names.macro_storage(),
+                                        argument,
+                                        store_type(*scale),
+                                        score(s)
+                                    ),
+                                    IRCommandPart::StorageValue(s) => format!(
+                                        "data modify storage {} {} set from storage {}",
+                                        names.macro_storage(),
+                                        argument,
+                                        storage(s)
+                                    ),
+                                    _ => unreachable!(),
+                                });
+                                macro_line.push_str(&format!("$({})", argument));
+                                is_macro = true;
+                            }
+                        }
+                    }
+
+                    if is_macro {
+                        let helper_location = format!(
+                            "{}/cmd_{}",
+                            names.internal_function_location(&function.name),
+                            helpers.len()
+                        );
+                        commands.push(format!(
+                            "function {} with storage {}",
+                            helper_location,
+                            names.macro_storage()
+                        ));
+                        helpers.push((helper_location, vec![format!("${}", macro_line)]));
+                    } else {
+                        commands.push(macro_line);
+                    }
+                }
+                IRInstruction::ScoreSet(target, value) => commands.push(format!(
+                    "scoreboard players set {} {}",
+                    score(target),
+                    value
+                )),
+                IRInstruction::ScoreOperation(target, operation, source) => commands.push(format!(
+                    "scoreboard players operation {} {} {}",
+                    score(target),
+                    score_operation(*operation),
+                    score(source)
+                )),
+                IRInstruction::ScoreCompare(target, lhs, operation, rhs) => commands.push(format!(
+                    "execute store success score {} {} score {} {} {}",
+                    score(target),
+                    if *operation == CompareOperation::Ne {
+                        "unless"
+                    } else {
+                        "if"
+                    },
+                    score(lhs),
+                    compare_operation(*operation),
+                    score(rhs)
+                )),
+                IRInstruction::StorageSetString(target, value) => commands.push(format!(
+                    "data modify storage {} set value {}",
+                    storage(target),
+                    quote(value)
+                )),
+                IRInstruction::StorageCopy(target, source) => commands.push(format!(
+                    "data modify storage {} set from storage {}",
+                    storage(target),
+                    storage(source)
+                )),
+                IRInstruction::Call(name) => {
+                    commands.push(format!("function {}", names.function_location(name)))
+                }
+                IRInstruction::Assert(assertion) => {
+                    let failed =
+                        format!("execute if score {} matches 0", score(&assertion.condition));
+                    commands.push(format!(
+                        "{} run data modify storage {} append value {{module:{},offset:{},message:{}}}",
+                        failed,
+                        names.test_storage(),
+                        quote(&assertion.module_id),
+                        assertion.offset,
+                        quote(&assertion.message)
+                    ));
+                    if let Some((lhs, rhs, scale)) = &assertion.values {
+                        for (key, value) in [("left", lhs), ("right", rhs)] {
+                            commands.push(format!(
+                                "{} store result storage {}[-1].{} {} run scoreboard players get {}",
+                                failed,
+                                names.test_storage(),
+                                key,
+                                store_type(*scale),
+                                score(value)
+                            ));
+                        }
+                    }
+                    commands.push(format!("{} run return fail", failed));
+                }
+                // every value is kept under its index in the frame
+                IRInstruction::PushFrame(locations) => {
+                    commands.push(format!(
+                        "data modify storage {} append value {{}}",
+                        names.stack()
+                    ));
+                    for (index, location) in locations.iter().enumerate() {
+                        commands.push(match location {
+                            Location::Score(s) => format!(
+                                "execute store result storage {}[-1].v{} int 1 run scoreboard players get {}",
+                                names.stack(),
+                                index,
+                                score(s)
+                            ),
+                            Location::Storage(s) => format!(
+                                "data modify storage {}[-1].v{} set from storage {}",
+                                names.stack(),
+                                index,
+                                storage(s)
+                            ),
+                        });
+                    }
+                }
+                IRInstruction::PopFrame(locations) => {
+                    for (index, location) in locations.iter().enumerate() {
+                        commands.push(match location {
+                            Location::Score(s) => format!(
+                                "execute store result score {} run data get storage {}[-1].v{}",
+                                score(s),
+                                names.stack(),
+                                index
+                            ),
+                            Location::Storage(s) => format!(
+                                "data modify storage {} set from storage {}[-1].v{}",
+                                storage(s),
+                                names.stack(),
+                                index
+                            ),
+                        });
+                    }
+                    commands.push(format!("data remove storage {}[-1]", names.stack()));
+                }
+            }
+        }
+
+        match &block.terminator {
+            Terminator::Jump(target) => {
+                commands.push(format!("return run function {}", block_location(*target)))
+            }
+            Terminator::Branch(condition, then_block, else_block) => {
+                commands.push(format!(
+                    "execute unless score {} matches 0 run return run function {}",
+                    score(condition),
+                    block_location(*then_block)
+                ));
+                commands.push(format!(
+                    "return run function {}",
+                    block_location(*else_block)
+                ));
+            }
+            // the block ends here anyway
+            Terminator::Return => {}
+            Terminator::TailCall(name) => commands.push(format!(
+                "return run function {}",
+                names.function_location(name)
+            )),
+        }
+        blocks.push((block_location(id), commands));
+    }
+
+    blocks.extend(helpers);
+    blocks
+}
@@ -0,0 +1,285 @@
+use crate::back::{BackendError, BackendOptions, BackendResult};
+use crate::front::ast_types::ResolvedName;
+use crate::middle::liveness::{terminator_uses, uses_and_defs};
+use crate::middle::types::{IRModule, Location, Score, Storage};
+use crate::modules::ModuleId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// the number of characters of a hashed name, two names with the same start are reported as a collision
+const SHORT_NAME_LENGTH: usize = 8;
+
+// FNV-1a, which (unlike the hasher of the standard library) gives the same hash in every build
+fn hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/* The last digits of the hash in base 36, i.e. the hash modulo `36^length`, padded to that length.
+
+The low-order digits are kept, since the leading digit of a whole 64-bit hash (13 digits) is only ever 0 to 3.
+ */
+fn hash_text(value: &str, length: usize) -> String {
+    let mut hash = hash(value);
+    let mut digits = vec![];
+    for _ in 0..length {
+        digits.push(char::from_digit((hash % 36) as u32, 36).unwrap());
+        hash /= 36;
+    }
+    digits.into_iter().rev().collect()
+}
+
+fn is_plain(segment: &str) -> bool {
+    segment.starts_with(|ch: char| matches!(ch, 'a'..='z' | '_'))
+        && segment
+            .chars()
+            .all(|ch| matches!(ch, 'a'..='z' | '0'..='9' | '_'))
+}
+
+// the name of an item in a scope, e.g. `0:0:fn_a`
+fn is_scoped(segment: &str) -> bool {
+    let mut parts = segment.splitn(3, ':');
+    let is_number = |part: Option<&str>| {
+        part.is_some_and(|part| !part.is_empty() && part.chars().all(|ch| ch.is_ascii_digit()))
+    };
+    is_number(parts.next()) && is_number(parts.next()) && parts.next().is_some_and(is_plain)
+}
+
+/* Turns a part of a name into lowercase letters, digits, `_` and `-`, which are allowed in resource locations, score
+holders and storage paths alike. Plain names are kept, and the colons of names in a scope become underscores, e.g.
+`0:0:fn_a` becomes `0_0_fn_a`. These cannot be confused, since plain names do not start with a digit.
+
+Any other name, e.g. with uppercase letters, is lowercased and followed by a hash of what it was, as in
+`fn_a--1x9f4k2b`, which keeps it apart from the other names.
+ */
+fn mangle_segment(segment: &str) -> String {
+    if is_plain(segment) || is_scoped(segment) {
+        return segment.replace(':', "_");
+    }
+    let sanitized = segment
+        .chars()
+        .map(|ch| match ch.to_ascii_lowercase() {
+            ch @ ('a'..='z' | '0'..='9' | '_') => ch,
+            _ => '_',
+        })
+        .collect::<String>();
+    format!("{}--{}", sanitized, hash_text(segment, 8))
+}
+
+// package_a::module_a + 0:0:fn_a => [package_a, module_a, 0_0_fn_a]
+fn mangle(module_id: &ModuleId, item_name: &str) -> Vec<String> {
+    module_id
+        .split("::")
+        .chain([item_name])
+        .map(mangle_segment)
+        .collect()
+}
+
+// a hash of the name, so that it does not depend on the other names
+fn short_name(name: &ResolvedName) -> String {
+    hash_text(
+        &format!("{}+{}", name.module_id, name.item_name),
+        SHORT_NAME_LENGTH,
+    )
+}
+
+// names every name with the given function, checking that no two of them are the same
+fn unique_names(
+    names: &BTreeSet<ResolvedName>,
+    name_of: impl Fn(&ResolvedName) -> String,
+) -> BackendResult<HashMap<ResolvedName, String>> {
+    let mut unique_names: BTreeMap<String, &ResolvedName> = BTreeMap::new();
+    for name in names.iter() {
+        let unique_name = name_of(name);
+        if let Some(other) = unique_names.insert(unique_name.clone(), name) {
+            return Err(BackendError::NameCollision(
+                unique_name,
+                other.clone(),
+                name.clone(),
+            ));
+        }
+    }
+    Ok(unique_names
+        .into_iter()
+        .map(|(unique_name, name)| (name.clone(), unique_name))
+        .collect())
+}
+
+/* Maps the names used in the IR to the names used in the generated commands.
+
+Functions are placed at their exported location if they have one, otherwise under a path derived from their module.
+Every variable gets its own score holder (or storage path). Registers are the slots given by the register allocator,
+which are shared by all functions.
+
+Names only depend on the item they are for, so they stay the same between builds. With `short_names`, they are
+hashes instead, and variables start with `v` to keep them apart from registers.
+ */
+pub struct Names<'a> {
+    options: &'a BackendOptions,
+    function_locations: HashMap<ResolvedName, String>,
+    internal_functions: HashMap<ResolvedName, String>, // the paths in the namespace
+    variables: HashMap<ResolvedName, String>,          // the score holders and storage paths
+}
+
+impl<'a> Names<'a> {
+    // names every function and variable of the modules
+    pub fn new(options: &'a BackendOptions, modules: &[IRModule]) -> BackendResult<Names<'a>> {
+        let mut functions = BTreeSet::new();
+        let mut variables = BTreeSet::new();
+        for module in modules.iter() {
+            variables.extend(module.statics.iter().cloned());
+            for function in module.functions.iter() {
+                functions.insert(function.name.clone());
+                variables.extend(
+                    function
+                        .params
+                        .iter()
+                        .chain(function.locals.iter())
+                        .cloned(),
+                );
+
+                // the variables of inlined functions are only declared by the functions, which may have been left out
+                let mut locations = vec![];
+                for block in function.blocks.iter() {
+                    for instruction in block.instructions.iter() {
+                        let (uses, defs) = uses_and_defs(instruction);
+                        locations.extend(uses.into_iter().chain(defs));
+                    }
+                    locations.extend(terminator_uses(&block.terminator));
+                }
+                for location in locations {
+                    if let Location::Score(Score::Var(name))
+                    | Location::Storage(Storage::Var(name)) = location
+                    {
+                        variables.insert(name);
+                    }
+                }
+            }
+        }
+
+        let (internal_functions, variables) = if options.short_names {
+            (
+                unique_names(&functions, short_name)?,
+                unique_names(&variables, |name| format!("v{}", short_name(name)))?,
+            )
+        } else {
+            (
+                unique_names(&functions, |name| {
+                    mangle(&name.module_id, &name.item_name).join("/")
+                })?,
+                unique_names(&variables, |name| {
+                    mangle(&name.module_id, &name.item_name).join(".")
+                })?,
+            )
+        };
+
+        Ok(Names {
+            options,
+            function_locations: HashMap::new(),
+            internal_functions,
+            variables,
+        })
+    }
+
+    pub fn set_function_location(&mut self, name: &ResolvedName, location: String) {
+        self.function_locations.insert(name.clone(), location);
+    }
+
+    // the location of generated functions that are not directly visible to the user
+    pub fn internal_function_location(&self, name: &ResolvedName) -> String {
+        format!(
+            "{}:{}",
+            self.options.namespace, self.internal_functions[name]
+        )
+    }
+
+    pub fn function_location(&self, name: &ResolvedName) -> String {
+        self.function_locations
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| self.internal_function_location(name))
+    }
+
+    // `<score holder> <objective>`
+    pub fn score(&self, score: &Score) -> String {
+        let holder = match score {
+            Score::Var(name) => format!("${}", self.variables[name]),
+            Score::Reg(register) => format!("$r{}", register),
+            Score::Return => "$__ret".to_string(),
+        };
+        format!("{} {}", holder, self.options.objective)
+    }
+
+    // `<storage> <path>`
+    pub fn storage(&self, storage: &Storage) -> String {
+        let path = match storage {
+            Storage::Var(name) => self.variables[name].clone(),
+            Storage::Reg(register) => format!("r{}", register),
+            Storage::Return => "__ret".to_string(),
+        };
+        format!("{}:vars {}", self.options.namespace, path)
+    }
+
+    // the list of failed assertions, as `<storage> <path>`
+    pub fn test_storage(&self) -> String {
+        format!("{}:test failures", self.options.namespace)
+    }
+
+    // the frames of the call stack, as `<storage> <path>`
+    pub fn stack(&self) -> String {
+        format!("{}:stack frames", self.options.namespace)
+    }
+
+    // the storage holding the arguments of macro functions
+    pub fn macro_storage(&self) -> String {
+        format!("{}:macro", self.options.namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mangle() {
+        assert_eq!(
+            mangle(&"package_a::module_a".to_string(), "0:0:fn_a"),
+            ["package_a", "module_a", "0_0_fn_a"]
+        );
+        assert_eq!(mangle_segment("12:3:_x1"), "12_3__x1");
+
+        // names that are not kept are told apart by their hash
+        let upper = mangle_segment("0:0:Fn_a");
+        assert!(upper.starts_with("0_0_fn_a--"));
+        assert_ne!(upper, mangle_segment("0:0:FN_A"));
+        assert_ne!(mangle_segment("0_0_fn_a"), "0_0_fn_a");
+        assert_eq!(mangle_segment("my-pack").len(), "my_pack--".len() + 8);
+    }
+
+    #[test]
+    fn test_short_names() {
+        let names = (0..1000)
+            .map(|i| ResolvedName::new("pkg::main".to_string(), format!("0:{}:x", i)))
+            .collect::<BTreeSet<_>>();
+        let short_names = unique_names(&names, short_name).unwrap();
+        assert!(short_names
+            .values()
+            .all(|name| name.len() == SHORT_NAME_LENGTH));
+
+        // every digit is used, including the first one
+        let first_digits = short_names
+            .values()
+            .map(|name| name.chars().next().unwrap())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(first_digits.len(), 36);
+
+        // a name only depends on its item
+        let name = names.first().unwrap();
+        let alone = unique_names(&BTreeSet::from([name.clone()]), short_name).unwrap();
+        assert_eq!(alone[name], short_names[name]);
+
+        // names that would be the same are reported instead
+        let collision = unique_names(&names, |name| short_name(name)[..1].to_string());
+        assert!(matches!(collision, Err(BackendError::NameCollision(..))));
+    }
+}
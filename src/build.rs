@@ -0,0 +1,512 @@
+use crate::back::{generate_datapack, BackendError, BackendOptions, Datapack};
+use crate::file_system::{FileSystem, FileSystemError};
+use crate::front::ast_types::ResolvedName;
+use crate::middle::dead_code::RemovedItems;
+use crate::middle::global_definition_table::GlobalDefinitionTable;
+use crate::middle::passes::PassError;
+use crate::middle::types::IRModule;
+use crate::middle::{generate_ir, IRGenError, IRGenOptions};
+use crate::modules::types::ModuleGraph;
+use crate::modules::{ModuleBuildError, ModuleBuilder, ModuleId};
+use camino::Utf8PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+
+#[derive(Debug)]
+pub enum BuildError {
+    ModuleBuild(ModuleBuildError),
+    IRGen(Box<IRGenError>),
+    Pass(PassError),
+    Backend(BackendError),
+    FileSystem(FileSystemError),
+    Write(Utf8PathBuf), // the file could not be written
+}
+
+pub type BuildResult<T> = Result<T, BuildError>;
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::ModuleBuild(error) => write!(f, "{}", error),
+            BuildError::IRGen(error) => write!(f, "cannot generate the IR: {:?}", error),
+            BuildError::Pass(PassError::InvariantBroken(pass, invariant, _)) => write!(
+                f,
+                "optimization failed: the `{}` pass broke the {:?} invariant",
+                pass, invariant
+            ),
+            BuildError::Backend(error) => write!(f, "cannot generate the datapack: {:?}", error),
+            BuildError::FileSystem(error) => write!(f, "{:?}", error),
+            BuildError::Write(path) => write!(f, "cannot write `{}`", path),
+        }
+    }
+}
+
+pub struct BuildOptions {
+    pub package_name: String,
+    pub package_path: Utf8PathBuf,
+    pub output_path: Utf8PathBuf,
+    pub cache: Option<Utf8PathBuf>,
+    pub ir_gen: IRGenOptions,
+    pub backend: BackendOptions,
+}
+
+// compiles the package into a datapack, without writing it
+#[cfg(test)]
+pub fn compile<T: FileSystem>(
+    file_system: &mut T,
+    options: &BuildOptions,
+) -> BuildResult<Datapack> {
+    compile_with_sources(file_system, options).map(|(datapack, _)| datapack)
+}
+
+// like `compile`, but also returns the source file of every module, to report locations in them
+pub fn compile_with_sources<T: FileSystem>(
+    file_system: &mut T,
+    options: &BuildOptions,
+) -> BuildResult<(Datapack, HashMap<ModuleId, Utf8PathBuf>)> {
+    compile_package(file_system, options).map(|(datapack, sources, _)| (datapack, sources))
+}
+
+// like `compile`, but also returns the functions and statics that were left out of the datapack
+pub fn compile_with_report<T: FileSystem>(
+    file_system: &mut T,
+    options: &BuildOptions,
+) -> BuildResult<(Datapack, RemovedItems)> {
+    compile_package(file_system, options).map(|(datapack, _, removed)| (datapack, removed))
+}
+
+fn compile_package<T: FileSystem>(
+    file_system: &mut T,
+    options: &BuildOptions,
+) -> BuildResult<(Datapack, HashMap<ModuleId, Utf8PathBuf>, RemovedItems)> {
+    let mut module_builder = load_package(file_system, options)?;
+    let module_graph = module_builder.get_module_graph();
+    let global_definition_table = create_global_definition_table(module_graph);
+    let mut modules = generate_modules(module_graph, &global_definition_table, options)?;
+
+    let main = find_main(module_graph, &global_definition_table);
+    let sources = module_graph
+        .nodes
+        .iter()
+        .map(|(id, node)| {
+            let package_path = &module_graph.package_map[&node.package_name];
+            (id.clone(), package_path.join(&node.rel_path))
+        })
+        .collect();
+
+    let removed = optimize_program(
+        module_builder.get_file_system(),
+        &mut modules,
+        main.as_ref(),
+        &options.ir_gen,
+    )?;
+    let datapack = generate_datapack(&modules, main.as_ref(), &options.backend)
+        .map_err(BuildError::Backend)?;
+
+    module_builder.save_cache();
+    Ok((datapack, sources, removed))
+}
+
+// compiles the package only to the IR of its modules, ordered by their id, and the items that were left out of it
+pub fn compile_ir<T: FileSystem>(
+    file_system: &mut T,
+    options: &BuildOptions,
+) -> BuildResult<(Vec<IRModule>, RemovedItems)> {
+    let mut module_builder = load_package(file_system, options)?;
+    let module_graph = module_builder.get_module_graph();
+    let global_definition_table = create_global_definition_table(module_graph);
+    let mut modules = generate_modules(module_graph, &global_definition_table, options)?;
+
+    let main = find_main(module_graph, &global_definition_table);
+    let removed = optimize_program(
+        module_builder.get_file_system(),
+        &mut modules,
+        main.as_ref(),
+        &options.ir_gen,
+    )?;
+
+    module_builder.save_cache();
+    Ok((modules, removed))
+}
+
+/* Runs the passes on the IR of the whole program, and returns the items that were left out.
+
+The IR dumped by the passes is written even if one of them fails, since that is when it is needed most.
+ */
+fn optimize_program<T: FileSystem>(
+    file_system: &mut T,
+    modules: &mut [IRModule],
+    main: Option<&ResolvedName>,
+    options: &IRGenOptions,
+) -> BuildResult<RemovedItems> {
+    let result = options.passes.run(modules, main);
+    if let Some(path) = &options.passes.dump_ir {
+        let dumps = match &result {
+            Ok(output) => &output.dumps,
+            Err(PassError::InvariantBroken(_, _, dumps)) => dumps,
+        };
+        write_files(file_system, dumps, path)?;
+    }
+    result
+        .map(|output| output.removed)
+        .map_err(BuildError::Pass)
+}
+
+fn load_package<'p, T: FileSystem>(
+    file_system: &'p mut T,
+    options: &BuildOptions,
+) -> BuildResult<ModuleBuilder<'p, T>> {
+    let mut module_builder = ModuleBuilder::new(file_system, options.cache.clone());
+    module_builder.load_cache();
+    module_builder
+        .add_fs_package(&options.package_name, &options.package_path, true)
+        .map_err(BuildError::ModuleBuild)?;
+    module_builder
+        .load_module_bodies()
+        .map_err(BuildError::ModuleBuild)?;
+    Ok(module_builder)
+}
+
+fn generate_modules(
+    module_graph: &ModuleGraph,
+    global_definition_table: &GlobalDefinitionTable,
+    options: &BuildOptions,
+) -> BuildResult<Vec<IRModule>> {
+    let mut module_ids = module_graph.nodes.keys().collect::<Vec<_>>();
+    module_ids.sort();
+
+    let mut modules = vec![];
+    for id in module_ids {
+        modules.push(
+            generate_ir(id, global_definition_table, &options.ir_gen)
+                .map_err(|e| BuildError::IRGen(Box::new(e)))?,
+        );
+    }
+    Ok(modules)
+}
+
+fn create_global_definition_table(module_graph: &ModuleGraph) -> GlobalDefinitionTable<'_> {
+    let mut global_definition_table = GlobalDefinitionTable::new();
+    for (id, node) in module_graph.nodes.iter() {
+        if let Some(body) = &node.body {
+            global_definition_table.add_definition_table(id.clone(), &body.definitions);
+        }
+    }
+    global_definition_table
+}
+
+fn find_main(
+    module_graph: &ModuleGraph,
+    global_definition_table: &GlobalDefinitionTable,
+) -> Option<ResolvedName> {
+    module_graph.root.as_ref().and_then(|root| {
+        global_definition_table
+            .definition_tables
+            .get(root)
+            .and_then(|definition_table| definition_table.find_by_raw_name("main"))
+    })
+}
+
+pub fn write_datapack<T: FileSystem>(
+    file_system: &mut T,
+    datapack: &Datapack,
+    output_path: &Utf8PathBuf,
+) -> BuildResult<()> {
+    write_files(file_system, &datapack.files, output_path)
+}
+
+fn write_files<T: FileSystem>(
+    file_system: &mut T,
+    files: &BTreeMap<Utf8PathBuf, String>,
+    output_path: &Utf8PathBuf,
+) -> BuildResult<()> {
+    for (path, content) in files.iter() {
+        let path = output_path.join(path);
+        let mut writer = file_system
+            .get_writer(&path)
+            .map_err(BuildError::FileSystem)?;
+        writer
+            .write_all(content.as_bytes())
+            .or(Err(BuildError::Write(path)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub fn build<T: FileSystem>(file_system: &mut T, options: &BuildOptions) -> BuildResult<()> {
+    let datapack = compile(file_system, options)?;
+    write_datapack(file_system, &datapack, &options.output_path)
+}
+
+// keeps the module graph and the generated code between builds, so only modified modules are compiled again
+pub struct IncrementalBuilder<'p, T: FileSystem> {
+    module_builder: ModuleBuilder<'p, T>,
+    options: BuildOptions,
+    loaded: bool, // whether the module bodies were loaded once
+
+    modules: BTreeMap<ModuleId, IRModule>,
+    outputs: BTreeMap<Utf8PathBuf, String>, // the datapack files as they were last written
+}
+
+impl<'p, T: FileSystem> IncrementalBuilder<'p, T> {
+    pub fn new(file_system: &'p mut T, options: BuildOptions) -> BuildResult<Self> {
+        let mut module_builder = ModuleBuilder::new(file_system, options.cache.clone());
+        module_builder.load_cache();
+        module_builder
+            .add_fs_package(&options.package_name, &options.package_path, true)
+            .map_err(BuildError::ModuleBuild)?;
+
+        Ok(IncrementalBuilder {
+            module_builder,
+            options,
+            loaded: false,
+            modules: BTreeMap::new(),
+            outputs: BTreeMap::new(),
+        })
+    }
+
+    // builds the modified modules, and returns the output files that were written or removed
+    pub fn rebuild(&mut self) -> BuildResult<Vec<Utf8PathBuf>> {
+        let reloaded = if self.loaded {
+            self.module_builder.reload_module_bodies()
+        } else {
+            self.module_builder.load_module_bodies()
+        };
+        self.loaded = true;
+
+        // the modules loaded before the error will not be reported as reloaded again
+        let reloaded = reloaded.map_err(|e| {
+            self.modules.clear();
+            BuildError::ModuleBuild(e)
+        })?;
+
+        let module_graph = self.module_builder.get_module_graph();
+        let global_definition_table = create_global_definition_table(module_graph);
+
+        // modules whose code could not be generated last time are missing, and are generated again
+        self.modules
+            .retain(|id, _| module_graph.nodes.contains_key(id) && !reloaded.contains(id));
+        for id in module_graph.nodes.keys() {
+            if !self.modules.contains_key(id) {
+                let module = generate_ir(id, &global_definition_table, &self.options.ir_gen)
+                    .map_err(|e| BuildError::IRGen(Box::new(e)))?;
+                self.modules.insert(id.clone(), module);
+            }
+        }
+
+        let main = find_main(module_graph, &global_definition_table);
+        let mut modules = self.modules.values().cloned().collect::<Vec<_>>();
+        optimize_program(
+            self.module_builder.get_file_system(),
+            &mut modules,
+            main.as_ref(),
+            &self.options.ir_gen,
+        )?;
+        let datapack = generate_datapack(&modules, main.as_ref(), &self.options.backend)
+            .map_err(BuildError::Backend)?;
+
+        let mut changed = Datapack::default();
+        for (path, content) in datapack.files.iter() {
+            if self.outputs.get(path) != Some(content) {
+                changed.files.insert(path.clone(), content.clone());
+            }
+        }
+        let removed = self
+            .outputs
+            .keys()
+            .filter(|path| !datapack.files.contains_key(*path))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let file_system = self.module_builder.get_file_system();
+        write_datapack(file_system, &changed, &self.options.output_path)?;
+        for path in removed.iter() {
+            file_system
+                .remove_file(&self.options.output_path.join(path))
+                .map_err(BuildError::FileSystem)?;
+        }
+
+        self.outputs = datapack.files;
+        Ok(changed.files.into_keys().chain(removed).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use crate::middle::passes::{OptLevel, PassManager};
+    use std::io::Read;
+
+    fn read(file_system: &MockFileSystem, path: &str) -> String {
+        let mut content = String::new();
+        file_system
+            .get_reader(&Utf8PathBuf::from(path))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn test_build() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_dir(Utf8PathBuf::from("pkg/package_a"));
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"
+            #[inline(never)]
+            fn main() {}
+
+            #[tick]
+            #[export("map:loop")]
+            fn game_loop() {
+                main();
+            }
+            "#,
+        );
+
+        let options = BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions::default(),
+            backend: BackendOptions::default(),
+        };
+        build(&mut mock_fs, &options).unwrap();
+
+        assert_eq!(
+            read(&mock_fs, "out/data/map/function/loop.mcfunction"),
+            "function blastfurnace:package_a/main/0_0_main\n"
+        );
+        assert!(read(&mock_fs, "out/data/minecraft/tags/function/load.json")
+            .contains("\"blastfurnace:package_a/main/0_0_main\""));
+        assert!(
+            read(&mock_fs, "out/data/minecraft/tags/function/tick.json").contains("\"map:loop\"")
+        );
+    }
+
+    #[test]
+    fn test_remove_unreachable() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            "use root::lib::used; fn main() { used(); }",
+        );
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/lib.ing"),
+            r#"
+            static calls: int;
+            static cache: int;
+
+            #[inline(never)]
+            pub fn used() {
+                calls = calls + 1;
+            }
+
+            pub fn unused() {
+                cache = 0;
+            }
+            "#,
+        );
+
+        let mut options = BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions::default(),
+            backend: BackendOptions::default(),
+        };
+        let unused_file =
+            Utf8PathBuf::from("data/blastfurnace/function/package_a/lib/0_0_unused.mcfunction");
+
+        let (datapack, removed) = compile_with_report(&mut mock_fs, &options).unwrap();
+        assert!(!datapack.files.contains_key(&unused_file));
+        assert_eq!(
+            removed.functions,
+            [ResolvedName::new(
+                "package_a::lib".to_string(),
+                "0:0:unused".to_string()
+            )]
+        );
+        assert_eq!(
+            removed.statics,
+            [ResolvedName::new(
+                "package_a::lib".to_string(),
+                "0:0:cache".to_string()
+            )]
+        );
+
+        options.ir_gen.passes = PassManager::new(OptLevel::O0);
+        options.ir_gen.passes.dump_ir = Some(Utf8PathBuf::from("passes"));
+        let (datapack, removed) = compile_with_report(&mut mock_fs, &options).unwrap();
+        assert!(datapack.files.contains_key(&unused_file));
+        assert_eq!(removed, RemovedItems::default());
+        assert!(read(&mock_fs, "passes/00-input.ir").contains("0:0:unused"));
+    }
+
+    #[test]
+    fn test_incremental_build() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            "use root::util::helper; fn main() { helper(); }",
+        );
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/util.ing"),
+            "pub fn helper() { other(); } pub fn other() {}",
+        );
+
+        let options = BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            // inlined functions would not be written
+            ir_gen: IRGenOptions {
+                passes: PassManager::new(OptLevel::O1),
+                ..IRGenOptions::default()
+            },
+            backend: BackendOptions::default(),
+        };
+        let mut builder = IncrementalBuilder::new(&mut mock_fs, options).unwrap();
+
+        let written = builder.rebuild().unwrap();
+        assert!(written.contains(&Utf8PathBuf::from(
+            "data/blastfurnace/function/package_a/util/0_0_other.mcfunction"
+        )));
+        assert!(builder.rebuild().unwrap().is_empty());
+
+        // only the outputs that changed are written, removed functions (and those no longer called) are deleted
+        builder.module_builder.get_file_system().insert_file(
+            Utf8PathBuf::from("pkg/package_a/util.ing"),
+            "pub fn helper() { let x: int = 1; }",
+        );
+        assert_eq!(
+            builder.rebuild().unwrap(),
+            vec![
+                Utf8PathBuf::from(
+                    "data/blastfurnace/function/package_a/util/0_0_helper.mcfunction"
+                ),
+                Utf8PathBuf::from("data/blastfurnace/function/package_a/util/0_0_other.mcfunction"),
+            ]
+        );
+        drop(builder);
+
+        assert!(mock_fs
+            .get_reader(&Utf8PathBuf::from(
+                "out/data/blastfurnace/function/package_a/util/0_0_other.mcfunction"
+            ))
+            .is_err());
+        assert_eq!(
+            read(
+                &mock_fs,
+                "out/data/blastfurnace/function/package_a/main/0_0_main.mcfunction"
+            ),
+            "function blastfurnace:package_a/util/0_0_helper\n"
+        );
+    }
+}
@@ -0,0 +1,829 @@
+use crate::back::BackendOptions;
+use crate::build::{
+    compile_ir, compile_with_report, write_datapack, BuildOptions, IncrementalBuilder,
+};
+use crate::doc::{document_package, DocFormat};
+use crate::file_system::concrete::system_fs::SystemFs;
+use crate::front::ast_types::ResolvedName;
+use crate::front::diagnostics::{diagnose, line_column};
+use crate::front::formatter::format_source;
+use crate::lsp::serve;
+use crate::middle::dead_code::RemovedItems;
+use crate::middle::ir_text::print_modules;
+use crate::middle::passes::{OptLevel, PassManager};
+use crate::middle::IRGenOptions;
+use crate::modules::ModuleBuilder;
+use crate::symbol_index::SymbolIndex;
+use crate::testing::{run_tests, TestOutcome};
+use camino::Utf8PathBuf;
+use std::time::Duration;
+
+const USAGE: &str =
+    "usage: blastfurnace <build | watch> <package path> [--out <path>] [--namespace <namespace>] [--short-names]
+       blastfurnace build <package path> --emit <datapack | ir> [--out <path>] [--report-removed]
+       blastfurnace <build | watch> <package path> [-O0 | -O1 | -O2] [--dump-ir <path>]
+       blastfurnace lsp
+       blastfurnace query <definition | references> <file>:<line>:<column> [--package <path>]...
+       blastfurnace fmt [--check] <path>...
+       blastfurnace doc <package path>... [--out <path>] [--format <html | markdown>]
+       blastfurnace test <package path> [<filter>] [-O0 | -O1 | -O2]";
+
+// how often the files are checked for modifications in watch mode
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Build {
+        package_path: Utf8PathBuf,
+        output_path: Utf8PathBuf,
+        namespace: Option<String>,
+        short_names: bool, // hashes the names of functions and variables
+        emit: Emit,
+        report_removed: bool, // prints the functions and statics left out as they cannot be reached
+        opt_level: OptLevel,
+        dump_ir: Option<Utf8PathBuf>, // the directory the IR is written to before and after every pass
+    },
+    Watch {
+        package_path: Utf8PathBuf,
+        output_path: Utf8PathBuf,
+        namespace: Option<String>,
+        short_names: bool,
+        opt_level: OptLevel,
+        dump_ir: Option<Utf8PathBuf>,
+    },
+    Lsp, // a language server, speaking over stdio
+    Query {
+        query: Query,
+        file: Utf8PathBuf,
+        line: usize,                // starting from 1
+        column: usize,              // starting from 1, in characters
+        packages: Vec<Utf8PathBuf>, // the directory of the file if none are given
+    },
+    Fmt {
+        paths: Vec<Utf8PathBuf>, // files, or directories containing them
+        check: bool,             // only reports the files that are not formatted
+    },
+    Doc {
+        packages: Vec<Utf8PathBuf>, // documented together, so that they can link to each other
+        output_path: Utf8PathBuf,
+        format: DocFormat,
+    },
+    Test {
+        package_path: Utf8PathBuf,
+        filter: Option<String>, // only the tests whose name contains it are run
+        opt_level: OptLevel, // a lower level helps to tell whether a failure comes from an optimization
+    },
+}
+
+// the arguments shared by `build` and `watch`, see `Command::Build`
+struct BuildArgs {
+    package_path: Utf8PathBuf,
+    output_path: Utf8PathBuf,
+    namespace: Option<String>,
+    short_names: bool,
+    emit: Emit,
+    report_removed: bool,
+    opt_level: OptLevel,
+    dump_ir: Option<Utf8PathBuf>,
+}
+
+// what a build writes to the output path
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Emit {
+    Datapack,
+    Ir, // the textual IR of every module, in a single file named after the package
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Query {
+    Definition,
+    References,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut args = args.iter();
+
+    match args.next().map(String::as_str) {
+        Some("build") => {
+            let BuildArgs {
+                package_path,
+                output_path,
+                namespace,
+                short_names,
+                emit,
+                report_removed,
+                opt_level,
+                dump_ir,
+            } = parse_build_args(args)?;
+            Ok(Command::Build {
+                package_path,
+                output_path,
+                namespace,
+                short_names,
+                emit,
+                report_removed,
+                opt_level,
+                dump_ir,
+            })
+        }
+        Some("watch") => {
+            let BuildArgs {
+                package_path,
+                output_path,
+                namespace,
+                short_names,
+                emit,
+                report_removed,
+                opt_level,
+                dump_ir,
+            } = parse_build_args(args)?;
+            if emit != Emit::Datapack {
+                return Err("only datapacks can be built in watch mode".to_string());
+            }
+            if report_removed {
+                return Err("removed items are not reported in watch mode".to_string());
+            }
+            Ok(Command::Watch {
+                package_path,
+                output_path,
+                namespace,
+                short_names,
+                opt_level,
+                dump_ir,
+            })
+        }
+        Some("lsp") => Ok(Command::Lsp),
+        Some("query") => {
+            let query = match args.next().map(String::as_str) {
+                Some("definition") => Query::Definition,
+                Some("references") => Query::References,
+                _ => return Err(USAGE.to_string()),
+            };
+
+            // file:line:column, where the file may contain colons itself
+            let position = args.next().ok_or("missing position")?;
+            let mut parts = position.rsplitn(3, ':');
+            let (Some(column), Some(line), Some(file)) = (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(format!(
+                    "`{}` is not a position like file:line:column",
+                    position
+                ));
+            };
+            let (Ok(line), Ok(column)) = (line.parse(), column.parse()) else {
+                return Err(format!(
+                    "`{}` is not a position like file:line:column",
+                    position
+                ));
+            };
+
+            let mut packages = vec![];
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--package" => packages.push(
+                        args.next()
+                            .map(Utf8PathBuf::from)
+                            .ok_or("missing value for --package")?,
+                    ),
+                    _ => return Err(format!("unexpected argument `{}`", arg)),
+                }
+            }
+
+            Ok(Command::Query {
+                query,
+                file: Utf8PathBuf::from(file),
+                line,
+                column,
+                packages,
+            })
+        }
+        Some("fmt") => {
+            let mut paths = vec![];
+            let mut check = false;
+            for arg in args {
+                match arg.as_str() {
+                    "--check" => check = true,
+                    _ => paths.push(Utf8PathBuf::from(arg)),
+                }
+            }
+            if paths.is_empty() {
+                return Err("missing path".to_string());
+            }
+            Ok(Command::Fmt { paths, check })
+        }
+        Some("doc") => {
+            let mut packages = vec![];
+            let mut output_path = Utf8PathBuf::from("doc");
+            let mut format = DocFormat::Html;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--out" => {
+                        output_path = args.next().map(Utf8PathBuf::from).ok_or("missing path")?
+                    }
+                    "--format" => {
+                        format = match args.next().map(String::as_str) {
+                            Some("html") => DocFormat::Html,
+                            Some("markdown") => DocFormat::Markdown,
+                            _ => return Err("expected `html` or `markdown`".to_string()),
+                        }
+                    }
+                    _ => packages.push(Utf8PathBuf::from(arg)),
+                }
+            }
+            if packages.is_empty() {
+                return Err("missing package path".to_string());
+            }
+            Ok(Command::Doc {
+                packages,
+                output_path,
+                format,
+            })
+        }
+        Some("test") => {
+            let package_path = args
+                .next()
+                .map(Utf8PathBuf::from)
+                .ok_or("missing package path")?;
+            let mut filter = None;
+            let mut opt_level = OptLevel::O2;
+            for arg in args {
+                match arg.as_str() {
+                    "-O0" => opt_level = OptLevel::O0,
+                    "-O1" => opt_level = OptLevel::O1,
+                    "-O2" => opt_level = OptLevel::O2,
+                    _ if filter.is_none() => filter = Some(arg.clone()),
+                    _ => return Err(format!("unexpected argument `{}`", arg)),
+                }
+            }
+            Ok(Command::Test {
+                package_path,
+                filter,
+                opt_level,
+            })
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// <package path> [--out <path>] [--namespace <namespace>] [--short-names] [--emit <datapack | ir>]
+// [--report-removed] [-O0 | -O1 | -O2] [--dump-ir <path>]
+fn parse_build_args<'a>(mut args: impl Iterator<Item = &'a String>) -> Result<BuildArgs, String> {
+    let mut package_path = None;
+    let mut output_path = None;
+    let mut namespace = None;
+    let mut short_names = false;
+    let mut emit = Emit::Datapack;
+    let mut report_removed = false;
+    let mut opt_level = OptLevel::O2;
+    let mut dump_ir = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => {
+                output_path = Some(args.next().map(Utf8PathBuf::from).ok_or("missing path")?)
+            }
+            "--namespace" => namespace = Some(args.next().cloned().ok_or("missing namespace")?),
+            "--short-names" => short_names = true,
+            "--report-removed" => report_removed = true,
+            "-O0" => opt_level = OptLevel::O0,
+            "-O1" => opt_level = OptLevel::O1,
+            "-O2" => opt_level = OptLevel::O2,
+            "--dump-ir" => {
+                dump_ir = Some(args.next().map(Utf8PathBuf::from).ok_or("missing path")?)
+            }
+            "--emit" => {
+                emit = match args.next().map(String::as_str) {
+                    Some("datapack") => Emit::Datapack,
+                    Some("ir") => Emit::Ir,
+                    _ => return Err("expected `datapack` or `ir`".to_string()),
+                }
+            }
+            _ if package_path.is_none() => package_path = Some(Utf8PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    let package_path = package_path.ok_or("missing package path")?;
+    let output_path = output_path.unwrap_or_else(|| package_path.join("out"));
+    Ok(BuildArgs {
+        package_path,
+        output_path,
+        namespace,
+        short_names,
+        emit,
+        report_removed,
+        opt_level,
+        dump_ir,
+    })
+}
+
+// packages are named after their directory
+fn package_name(package_path: &Utf8PathBuf) -> Result<String, String> {
+    package_path
+        .canonicalize_utf8()
+        .ok()
+        .and_then(|path| path.file_name().map(str::to_string))
+        .ok_or_else(|| format!("`{}` is not a package", package_path))
+}
+
+fn build_options(
+    package_path: Utf8PathBuf,
+    output_path: Utf8PathBuf,
+    namespace: Option<String>,
+    short_names: bool,
+    opt_level: OptLevel,
+    dump_ir: Option<Utf8PathBuf>,
+) -> Result<BuildOptions, String> {
+    let package_name = package_name(&package_path)?;
+
+    let mut backend = BackendOptions::default();
+    if let Some(namespace) = namespace {
+        backend.namespace = namespace;
+    }
+    backend.short_names = short_names;
+
+    let mut passes = PassManager::new(opt_level);
+    passes.dump_ir = dump_ir;
+
+    Ok(BuildOptions {
+        package_name,
+        package_path,
+        output_path,
+        cache: None,
+        ir_gen: IRGenOptions {
+            passes,
+            ..IRGenOptions::default()
+        },
+        backend,
+    })
+}
+
+// e.g. `removed function pkg::main::helper`
+fn print_removed(removed: &RemovedItems) {
+    let name = |name: &ResolvedName| {
+        format!(
+            "{}::{}",
+            name.module_id,
+            name.item_name.rsplit(':').next().unwrap()
+        )
+    };
+    for function in removed.functions.iter() {
+        println!("removed function {}", name(function));
+    }
+    for static_var in removed.statics.iter() {
+        println!("removed static {}", name(static_var));
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    match parse_args(args)? {
+        Command::Build {
+            package_path,
+            output_path,
+            namespace,
+            short_names,
+            emit: Emit::Datapack,
+            report_removed,
+            opt_level,
+            dump_ir,
+        } => {
+            let options = build_options(
+                package_path,
+                output_path,
+                namespace,
+                short_names,
+                opt_level,
+                dump_ir,
+            )?;
+            let mut file_system = SystemFs::new();
+            let (datapack, removed) = compile_with_report(&mut file_system, &options)
+                .map_err(|e| format!("build failed: {}", e))?;
+            write_datapack(&mut file_system, &datapack, &options.output_path)
+                .map_err(|e| format!("build failed: {}", e))?;
+            if report_removed {
+                print_removed(&removed);
+            }
+            Ok(())
+        }
+        Command::Build {
+            package_path,
+            output_path,
+            namespace,
+            short_names,
+            emit: Emit::Ir,
+            report_removed,
+            opt_level,
+            dump_ir,
+        } => {
+            let options = build_options(
+                package_path,
+                output_path,
+                namespace,
+                short_names,
+                opt_level,
+                dump_ir,
+            )?;
+            let (modules, removed) = compile_ir(&mut SystemFs::new(), &options)
+                .map_err(|e| format!("build failed: {}", e))?;
+            if report_removed {
+                print_removed(&removed);
+            }
+
+            std::fs::create_dir_all(&options.output_path)
+                .map_err(|e| format!("cannot create `{}`: {}", options.output_path, e))?;
+            let path = options
+                .output_path
+                .join(format!("{}.ir", options.package_name));
+            std::fs::write(&path, print_modules(&modules))
+                .map_err(|e| format!("cannot write `{}`: {}", path, e))?;
+            println!("{}", path);
+            Ok(())
+        }
+        Command::Watch {
+            package_path,
+            output_path,
+            namespace,
+            short_names,
+            opt_level,
+            dump_ir,
+        } => {
+            let options = build_options(
+                package_path,
+                output_path,
+                namespace,
+                short_names,
+                opt_level,
+                dump_ir,
+            )?;
+            let mut file_system = SystemFs::new();
+            let mut builder = IncrementalBuilder::new(&mut file_system, options)
+                .map_err(|e| format!("build failed: {}", e))?;
+
+            // errors are only reported once, the next modification may fix them
+            let mut last_error = None;
+            loop {
+                match builder.rebuild() {
+                    Ok(written) => {
+                        if !written.is_empty() {
+                            println!("updated {} file(s)", written.len());
+                        }
+                        last_error = None;
+                    }
+                    Err(e) => {
+                        let message = format!("build failed: {}", e);
+                        if last_error.as_ref() != Some(&message) {
+                            eprintln!("{}", message);
+                        }
+                        last_error = Some(message);
+                    }
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+        Command::Lsp => serve(
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout().lock(),
+            SystemFs::new(),
+        )
+        .map_err(|e| format!("language server failed: {}", e)),
+        Command::Query {
+            query,
+            file,
+            line,
+            column,
+            packages,
+        } => {
+            let canonicalize = |path: &Utf8PathBuf| {
+                path.canonicalize_utf8()
+                    .map_err(|_| format!("`{}` does not exist", path))
+            };
+            let file = canonicalize(&file)?;
+            let packages = if packages.is_empty() {
+                vec![file.parent().unwrap().to_path_buf()]
+            } else {
+                packages
+                    .iter()
+                    .map(canonicalize)
+                    .collect::<Result<_, _>>()?
+            };
+
+            let mut file_system = SystemFs::new();
+            let mut module_builder = ModuleBuilder::new(&mut file_system, None);
+            for package_path in packages {
+                module_builder
+                    .add_fs_package(&package_name(&package_path)?, &package_path, false)
+                    .map_err(|e| format!("query failed: {}", e))?;
+            }
+            // the modules that could be loaded are still queried
+            if let Err(e) = module_builder.load_module_bodies() {
+                eprintln!("warning: {}", e);
+            }
+
+            let index = SymbolIndex::new(module_builder.get_module_graph());
+            let read = |path: &Utf8PathBuf| std::fs::read_to_string(path).unwrap_or_default();
+            let offset = offset_of(&read(&file), line, column)
+                .ok_or_else(|| format!("{}:{}:{} is outside of the file", file, line, column))?;
+
+            let locations = match query {
+                Query::Definition => index
+                    .definition(&file, offset)
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+                Query::References => index.references(&file, offset, true),
+            };
+            for location in locations {
+                let (line, column) = line_column(&read(&location.path), location.lo);
+                println!("{}:{}:{}", location.path, line, column);
+            }
+            Ok(())
+        }
+        Command::Fmt { paths, check } => {
+            let mut files = vec![];
+            for path in paths.iter() {
+                source_files(path, &mut files)?;
+            }
+
+            let mut unformatted = 0;
+            for file in files {
+                let src = std::fs::read_to_string(&file)
+                    .map_err(|e| format!("cannot read `{}`: {}", file, e))?;
+                let formatted = format_source(&src).map_err(|e| {
+                    let diagnostic = &diagnose(&src, &e)[0];
+                    let (line, column) = line_column(&src, diagnostic.lo);
+                    format!("{}:{}:{}: {}", file, line, column, diagnostic.message)
+                })?;
+                if formatted == src {
+                    continue;
+                }
+
+                // the files that are, or would be, changed
+                println!("{}", file);
+                unformatted += 1;
+                if !check {
+                    std::fs::write(&file, formatted)
+                        .map_err(|e| format!("cannot write `{}`: {}", file, e))?;
+                }
+            }
+
+            if check && unformatted > 0 {
+                return Err(format!("{} file(s) are not formatted", unformatted));
+            }
+            Ok(())
+        }
+        Command::Doc {
+            packages,
+            output_path,
+            format,
+        } => {
+            let mut file_system = SystemFs::new();
+            let mut module_builder = ModuleBuilder::new(&mut file_system, None);
+            let mut package_names = vec![];
+            for package_path in packages {
+                let package_name = package_name(&package_path)?;
+                module_builder
+                    .add_fs_package(&package_name, &package_path, false)
+                    .map_err(|e| format!("doc failed: {}", e))?;
+                package_names.push(package_name);
+            }
+            // the modules that could be loaded are still documented
+            if let Err(e) = module_builder.load_module_bodies() {
+                eprintln!("warning: {}", e);
+            }
+
+            std::fs::create_dir_all(&output_path)
+                .map_err(|e| format!("cannot create `{}`: {}", output_path, e))?;
+            for package_name in package_names {
+                let pages =
+                    document_package(module_builder.get_module_graph(), &package_name, format);
+                for page in pages.iter() {
+                    let path = output_path.join(&page.path);
+                    std::fs::write(&path, &page.contents)
+                        .map_err(|e| format!("cannot write `{}`: {}", path, e))?;
+                }
+                // the page of the package, listing its modules
+                println!("{}", output_path.join(&pages[0].path));
+            }
+            Ok(())
+        }
+        Command::Test {
+            package_path,
+            filter,
+            opt_level,
+        } => {
+            let output_path = package_path.join("out");
+            let options = build_options(package_path, output_path, None, false, opt_level, None)?;
+            let results = run_tests(&mut SystemFs::new(), options, filter.as_deref())
+                .map_err(|e| format!("build failed: {}", e))?;
+
+            let mut failed = 0;
+            for result in results.iter() {
+                match &result.outcome {
+                    TestOutcome::Passed => println!("test {} ... ok", result.name),
+                    TestOutcome::Failed(failures) => {
+                        println!("test {} ... FAILED", result.name);
+                        for failure in failures {
+                            println!("  {}", failure.replace('\n', "\n  "));
+                        }
+                        failed += 1;
+                    }
+                    TestOutcome::Error(e) => {
+                        println!("test {} ... FAILED", result.name);
+                        println!("  could not be run: {:?}", e);
+                        failed += 1;
+                    }
+                }
+            }
+
+            println!("\n{} passed, {} failed", results.len() - failed, failed);
+            if failed > 0 {
+                return Err(format!("{} test(s) failed", failed));
+            }
+            Ok(())
+        }
+    }
+}
+
+// the source files at the path, searching directories recursively
+fn source_files(path: &Utf8PathBuf, files: &mut Vec<Utf8PathBuf>) -> Result<(), String> {
+    if !path.is_dir() {
+        if !path.exists() {
+            return Err(format!("`{}` does not exist", path));
+        }
+        files.push(path.clone());
+        return Ok(());
+    }
+
+    let mut entries = path
+        .read_dir_utf8()
+        .map_err(|e| format!("cannot read `{}`: {}", path, e))?
+        .filter_map(|entry| Some(entry.ok()?.into_path()))
+        .collect::<Vec<_>>();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension() == Some("ing") {
+            source_files(&entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+// the byte offset of a line and column, both starting from 1
+fn offset_of(src: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start = match line {
+        0 => return None,
+        1 => 0,
+        _ => src.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    let line_end = src[line_start..]
+        .find('\n')
+        .map_or(src.len(), |end| line_start + end);
+
+    let mut offsets = src[line_start..line_end]
+        .char_indices()
+        .map(|(index, _)| line_start + index)
+        .chain([line_end]);
+    offsets.nth(column.checked_sub(1)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_build() {
+        assert_eq!(
+            parse_args(&args(&["build", "pkg", "--namespace", "map"])),
+            Ok(Command::Build {
+                package_path: Utf8PathBuf::from("pkg"),
+                output_path: Utf8PathBuf::from("pkg/out"),
+                namespace: Some("map".to_string()),
+                short_names: false,
+                emit: Emit::Datapack,
+                report_removed: false,
+                opt_level: OptLevel::O2,
+                dump_ir: None,
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&[
+                "build",
+                "pkg",
+                "--emit",
+                "ir",
+                "--out",
+                "ir",
+                "--report-removed",
+                "--short-names",
+                "-O1",
+                "--dump-ir",
+                "passes"
+            ])),
+            Ok(Command::Build {
+                package_path: Utf8PathBuf::from("pkg"),
+                output_path: Utf8PathBuf::from("ir"),
+                namespace: None,
+                short_names: true,
+                emit: Emit::Ir,
+                report_removed: true,
+                opt_level: OptLevel::O1,
+                dump_ir: Some(Utf8PathBuf::from("passes")),
+            })
+        );
+        assert!(parse_args(&args(&["watch", "pkg", "--emit", "ir"])).is_err());
+        assert!(parse_args(&args(&["watch", "pkg", "--report-removed"])).is_err());
+        assert!(parse_args(&args(&["build", "pkg", "--dump-ir"])).is_err());
+        assert_eq!(
+            parse_args(&args(&["build", "pkg", "--out"])),
+            Err("missing path".to_string())
+        );
+        assert_eq!(
+            parse_args(&args(&["watch", "pkg", "--namespace"])),
+            Err("missing namespace".to_string())
+        );
+        assert_eq!(
+            parse_args(&args(&[
+                "watch",
+                "pkg",
+                "--out",
+                "world/datapacks/pkg",
+                "-O0"
+            ])),
+            Ok(Command::Watch {
+                package_path: Utf8PathBuf::from("pkg"),
+                output_path: Utf8PathBuf::from("world/datapacks/pkg"),
+                namespace: None,
+                short_names: false,
+                opt_level: OptLevel::O0,
+                dump_ir: None,
+            })
+        );
+        assert_eq!(parse_args(&args(&["lsp"])), Ok(Command::Lsp));
+        assert_eq!(
+            parse_args(&args(&["query", "definition", "pkg/main.ing:3:5"])),
+            Ok(Command::Query {
+                query: Query::Definition,
+                file: Utf8PathBuf::from("pkg/main.ing"),
+                line: 3,
+                column: 5,
+                packages: vec![],
+            })
+        );
+        assert!(parse_args(&args(&["query", "references", "pkg/main.ing:3"])).is_err());
+        assert_eq!(
+            parse_args(&args(&[
+                "query",
+                "definition",
+                "pkg/main.ing:3:5",
+                "--package"
+            ])),
+            Err("missing value for --package".to_string())
+        );
+        assert_eq!(
+            parse_args(&args(&["fmt", "--check", "pkg", "lib/util.ing"])),
+            Ok(Command::Fmt {
+                paths: vec![Utf8PathBuf::from("pkg"), Utf8PathBuf::from("lib/util.ing")],
+                check: true,
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["doc", "lib", "std", "--format", "markdown"])),
+            Ok(Command::Doc {
+                packages: vec![Utf8PathBuf::from("lib"), Utf8PathBuf::from("std")],
+                output_path: Utf8PathBuf::from("doc"),
+                format: DocFormat::Markdown,
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["test", "pkg", "math"])),
+            Ok(Command::Test {
+                package_path: Utf8PathBuf::from("pkg"),
+                filter: Some("math".to_string()),
+                opt_level: OptLevel::O2,
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["test", "pkg", "-O0"])),
+            Ok(Command::Test {
+                package_path: Utf8PathBuf::from("pkg"),
+                filter: None,
+                opt_level: OptLevel::O0,
+            })
+        );
+        assert!(parse_args(&args(&["test", "pkg", "math", "vec"])).is_err());
+        assert!(parse_args(&args(&["run"])).is_err());
+    }
+
+    #[test]
+    fn test_line_column() {
+        let src = "fn a() {}\nlet é = b;";
+        let offset = src.find('b').unwrap();
+        assert_eq!(line_column(src, offset), (2, 9));
+        assert_eq!(offset_of(src, 2, 9), Some(offset));
+        assert_eq!(offset_of(src, 3, 1), None);
+    }
+}
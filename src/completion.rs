@@ -0,0 +1,213 @@
+use crate::front::ast_types::{Definition, ResolvedName, Type};
+use crate::front::definition_table::ModuleExports;
+use crate::front::query_scope;
+use crate::middle::global_definition_table::GlobalDefinitionTable;
+use crate::modules::types::ModuleGraph;
+use crate::symbol_index::describe;
+use camino::Utf8PathBuf;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CompletionKind {
+    Variable,
+    Function,
+    Struct,
+    Field,
+    Module,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>, // the definition of the name
+}
+
+// where the name that ends at the offset starts
+fn name_start(src: &str, offset: usize) -> usize {
+    src[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(offset, |(index, _)| index)
+}
+
+/* The names that can be written at the offset of a file, given with its unsaved contents. After `name.`, these are
+the fields of the struct that the variable holds.
+
+The name being written is removed from the source before its scope is queried, as it would not parse otherwise.
+ */
+pub fn complete(
+    module_graph: &ModuleGraph,
+    path: &Utf8PathBuf,
+    src: &str,
+    offset: usize,
+) -> Vec<Completion> {
+    let Some(file_module) = module_graph.file_module(path) else {
+        return vec![];
+    };
+
+    let start = name_start(src, offset);
+    let prefix = &src[start..offset];
+    let receiver = src[..start]
+        .strip_suffix('.')
+        .map(|before| &before[name_start(before, before.len())..])
+        .filter(|receiver| !receiver.is_empty());
+    let cut_start = if receiver.is_some() { start - 1 } else { start };
+    let mut edited = src.to_string();
+    edited.replace_range(cut_start..offset, "");
+
+    let module_exports = module_graph
+        .nodes
+        .iter()
+        .filter_map(|(id, node)| Some((id.clone(), node.body.as_ref()?.definitions.exports())))
+        .collect::<ModuleExports>();
+    let Ok(scope) = query_scope(file_module, &edited, cut_start, &module_exports) else {
+        return vec![];
+    };
+
+    let mut global_definition_table = GlobalDefinitionTable::new();
+    for (id, node) in module_graph.nodes.iter() {
+        if let Some(body) = &node.body {
+            global_definition_table.add_definition_table(id.clone(), &body.definitions);
+        }
+    }
+    // the loaded module may be older than the unsaved contents
+    global_definition_table.add_definition_table(scope.module_id.clone(), &scope.definitions);
+
+    let mut completions = match receiver {
+        Some(receiver) => scope
+            .names
+            .iter()
+            .find(|(raw_name, _)| raw_name == receiver)
+            .map(|(_, name)| field_completions(&global_definition_table, name))
+            .unwrap_or_default(),
+        None => scope
+            .names
+            .iter()
+            .filter_map(|(raw_name, name)| {
+                let Some(definition) = global_definition_table.get_definition(name) else {
+                    // imported modules are not definitions, and neither are names that cannot be found
+                    let module_id = format!("{}::{}", name.module_id, name.item_name);
+                    return module_graph
+                        .nodes
+                        .contains_key(&module_id)
+                        .then(|| Completion {
+                            label: raw_name.clone(),
+                            kind: CompletionKind::Module,
+                            detail: None,
+                        });
+                };
+                let kind = match definition {
+                    Definition::StaticVarDef(_) | Definition::VarDef(_) => CompletionKind::Variable,
+                    Definition::StructDef(_) => CompletionKind::Struct,
+                    Definition::FnDef(_) => CompletionKind::Function,
+                };
+                Some(Completion {
+                    label: raw_name.clone(),
+                    kind,
+                    detail: Some(describe(&definition)),
+                })
+            })
+            .collect(),
+    };
+    completions.retain(|completion| completion.label.starts_with(prefix));
+    completions
+}
+
+fn field_completions(
+    global_definition_table: &GlobalDefinitionTable,
+    name: &ResolvedName,
+) -> Vec<Completion> {
+    let ty = match global_definition_table.get_definition(name) {
+        Some(Definition::StaticVarDef(def)) => def.ty,
+        Some(Definition::VarDef(def)) => def.ty,
+        _ => return vec![],
+    };
+    let Type::Struct(struct_name) = ty else {
+        return vec![];
+    };
+    let Some(Definition::StructDef(struct_def)) = struct_name
+        .resolved
+        .and_then(|name| global_definition_table.get_definition(&name))
+    else {
+        return vec![];
+    };
+
+    let mut fields = struct_def
+        .field_types
+        .into_iter()
+        .map(|(field, ty)| Completion {
+            detail: Some(format!("{}: {}", field, ty)),
+            label: field,
+            kind: CompletionKind::Field,
+        })
+        .collect::<Vec<_>>();
+    fields.sort_by(|a, b| a.label.cmp(&b.label));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use crate::modules::ModuleBuilder;
+
+    fn labels(completions: Vec<Completion>) -> Vec<(String, CompletionKind)> {
+        completions
+            .into_iter()
+            .map(|completion| (completion.label, completion.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_complete() {
+        let main = "use package_b::util::*;\nfn main() {\n    let pos: vec = new_vec();\n}";
+        let util = "pub struct vec {\n    x: int,\n    y: int,\n}\npub fn new_vec() -> vec {\n}\nfn hidden() {}";
+
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/package_a/main.ing"), main);
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/package_b/util.ing"), util);
+
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, None);
+        module_builder
+            .add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)
+            .unwrap();
+        module_builder
+            .add_fs_package("package_b", &Utf8PathBuf::from("pkg/package_b"), false)
+            .unwrap();
+        module_builder.load_module_bodies().unwrap();
+        let module_graph = module_builder.get_module_graph();
+        let main_path = Utf8PathBuf::from("pkg/package_a/main.ing");
+
+        // names in scope, including glob imports, while one is being written
+        let edited = main.replace("    let", "    po\n    let");
+        let offset = edited.find("po\n").unwrap() + 2;
+        assert_eq!(
+            labels(complete(module_graph, &main_path, &edited, offset)),
+            vec![("pos".to_string(), CompletionKind::Variable)]
+        );
+        let offset = main.find("new_vec").unwrap();
+        assert_eq!(
+            labels(complete(module_graph, &main_path, main, offset)),
+            vec![
+                ("main".to_string(), CompletionKind::Function),
+                ("new_vec".to_string(), CompletionKind::Function),
+                ("pos".to_string(), CompletionKind::Variable),
+                ("vec".to_string(), CompletionKind::Struct),
+            ]
+        );
+
+        // fields after `.`
+        let edited = main.replace("new_vec()", "pos.");
+        let offset = edited.find("pos.").unwrap() + 4;
+        let completions = complete(module_graph, &main_path, &edited, offset);
+        assert_eq!(
+            labels(completions),
+            vec![
+                ("x".to_string(), CompletionKind::Field),
+                ("y".to_string(), CompletionKind::Field),
+            ]
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::front::ast_types::{
+    Attribute, FnDef, ResolvedName, StaticVarDef, StructDef, Type, Visibility,
+};
+use crate::middle::global_definition_table::GlobalDefinitionTable;
+use crate::modules::types::ModuleGraph;
+use crate::modules::ModuleId;
+use camino::Utf8PathBuf;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DocFormat {
+    Html,
+    Markdown,
+}
+
+impl DocFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            DocFormat::Html => "html",
+            DocFormat::Markdown => "md",
+        }
+    }
+}
+
+// a page of the documentation, relative to the output directory
+#[derive(Debug, PartialEq)]
+pub struct DocPage {
+    pub path: Utf8PathBuf,
+    pub contents: String,
+}
+
+// part of a declaration, where the types that are documented link to their definition
+enum Piece {
+    Code(String),
+    Link(String, String), // the text, and the page it links to
+}
+
+/* The pages documenting the items of a package that can be imported by other modules. The package gets a page
+listing its modules, `package.ext`, and every module gets a page of its own next to it, `package.module.ext`, so
+that every page can link to any other one by its file name.
+
+Every package that is linked to from this one is expected to be documented into the same directory.
+ */
+pub fn document_package(
+    module_graph: &ModuleGraph,
+    package_name: &str,
+    format: DocFormat,
+) -> Vec<DocPage> {
+    let mut global_definition_table = GlobalDefinitionTable::new();
+    for (id, node) in module_graph.nodes.iter() {
+        if let Some(body) = &node.body {
+            global_definition_table.add_definition_table(id.clone(), &body.definitions);
+        }
+    }
+    let documenter = Documenter {
+        module_graph,
+        global_definition_table,
+        format,
+    };
+
+    let mut top_level = module_graph
+        .nodes
+        .iter()
+        .filter(|(id, node)| {
+            node.package_name == package_name
+                && id
+                    .rsplit_once("::")
+                    .is_none_or(|(parent, _)| !module_graph.nodes.contains_key(parent))
+        })
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    top_level.sort();
+
+    let mut pages = vec![documenter.package_page(package_name, &top_level)];
+    let mut ids = module_graph
+        .nodes
+        .iter()
+        .filter(|(_, node)| node.package_name == package_name && node.body.is_some())
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    ids.sort();
+    pages.extend(ids.into_iter().map(|id| documenter.module_page(id)));
+    pages
+}
+
+struct Documenter<'a> {
+    module_graph: &'a ModuleGraph,
+    global_definition_table: GlobalDefinitionTable<'a>,
+    format: DocFormat,
+}
+
+impl Documenter<'_> {
+    fn page_name(&self, module_id: &str) -> String {
+        format!(
+            "{}.{}",
+            module_id.replace("::", "."),
+            self.format.extension()
+        )
+    }
+
+    fn package_page(&self, package_name: &str, top_level: &[&ModuleId]) -> DocPage {
+        let mut body = self.heading(1, &format!("Package {}", package_name), None);
+        body += &self.heading(2, "Modules", None);
+        body += &self.module_tree(top_level, 0);
+        DocPage {
+            path: Utf8PathBuf::from(self.page_name(package_name)),
+            contents: self.page(&format!("Package {}", package_name), &body),
+        }
+    }
+
+    // the modules and, below each of them, the modules declared inside of it
+    fn module_tree(&self, ids: &[&ModuleId], depth: usize) -> String {
+        let mut tree = String::new();
+        for id in ids {
+            let node = &self.module_graph.nodes[*id];
+            let name = id.rsplit_once("::").map_or(id.as_str(), |(_, name)| name);
+            // modules that could not be loaded have no page
+            let item = if node.body.is_some() {
+                self.inline(&[Piece::Link(name.to_string(), self.page_name(id))])
+            } else {
+                self.inline(&[Piece::Code(name.to_string())])
+            };
+
+            let mut children = node.children.iter().collect::<Vec<_>>();
+            children.sort();
+            let subtree = if children.is_empty() {
+                String::new()
+            } else {
+                self.module_tree(&children, depth + 1)
+            };
+
+            tree += &match self.format {
+                DocFormat::Html => format!("<li>{}\n{}</li>\n", item, subtree),
+                DocFormat::Markdown => format!("{}- {}\n{}", "  ".repeat(depth), item, subtree),
+            };
+        }
+        match self.format {
+            DocFormat::Html => format!("<ul>\n{}</ul>\n", tree),
+            DocFormat::Markdown if depth == 0 => format!("{}\n", tree),
+            DocFormat::Markdown => tree,
+        }
+    }
+
+    fn module_page(&self, module_id: &ModuleId) -> DocPage {
+        let node = &self.module_graph.nodes[module_id];
+        let definitions = &node.body.as_ref().unwrap().definitions;
+        let is_documented = |visibility: &Visibility| *visibility != Visibility::Private;
+
+        let mut body = self.heading(1, &format!("Module {}", module_id), None);
+        body += &self.paragraph(&format!(
+            "In package {}",
+            self.inline(&[Piece::Link(
+                node.package_name.clone(),
+                self.page_name(&node.package_name),
+            )])
+        ));
+
+        let mut children = node.children.iter().collect::<Vec<_>>();
+        children.sort();
+        if !children.is_empty() {
+            body += &self.heading(2, "Modules", None);
+            body += &self.module_tree(&children, 0);
+        }
+
+        let mut structs = definitions
+            .struct_map
+            .values()
+            .filter(|def| is_documented(&def.visibility))
+            .collect::<Vec<_>>();
+        structs.sort_by_key(|def| &def.name.raw.0);
+        if !structs.is_empty() {
+            body += &self.heading(2, "Structs", None);
+        }
+        for def in structs {
+            body += &self.struct_section(def);
+        }
+
+        let mut functions = definitions
+            .fn_map
+            .values()
+            .filter(|def| is_documented(&def.visibility))
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|def| &def.name.raw.0);
+        if !functions.is_empty() {
+            body += &self.heading(2, "Functions", None);
+        }
+        for def in functions {
+            body += &self.fn_section(def);
+        }
+
+        let mut statics = definitions
+            .static_var_map
+            .values()
+            .filter(|def| is_documented(&def.visibility))
+            .collect::<Vec<_>>();
+        statics.sort_by_key(|def| &def.name.raw.0);
+        if !statics.is_empty() {
+            body += &self.heading(2, "Statics", None);
+        }
+        for def in statics {
+            body += &self.static_section(def);
+        }
+
+        DocPage {
+            path: Utf8PathBuf::from(self.page_name(module_id)),
+            contents: self.page(&format!("Module {}", module_id), &body),
+        }
+    }
+
+    fn struct_section(&self, def: &StructDef) -> String {
+        let name = &def.name.raw.0;
+        let mut section = self.heading(3, name, Some(&format!("struct.{}", name)));
+        section += &self.declaration(&[Piece::Code(format!(
+            "{}struct {}",
+            visibility(def.visibility),
+            name
+        ))]);
+        section += &self.doc(&def.doc);
+
+        let mut fields = def.field_types.iter().collect::<Vec<_>>();
+        fields.sort_by_key(|(field, _)| field.as_str());
+        let fields = fields
+            .into_iter()
+            .map(|(field, ty)| {
+                let mut pieces = vec![Piece::Code(format!("{}: ", field))];
+                pieces.push(self.type_piece(ty));
+                self.inline(&pieces)
+            })
+            .collect::<Vec<_>>();
+        section += &self.list(&fields);
+        section
+    }
+
+    fn fn_section(&self, def: &FnDef) -> String {
+        let name = &def.name.raw.0;
+        let mut section = self.heading(3, name, Some(&format!("fn.{}", name)));
+
+        let attributes = def
+            .attributes
+            .iter()
+            .map(|attribute| match attribute {
+                Attribute::Load => "#[load] ".to_string(),
+                Attribute::Tick => "#[tick] ".to_string(),
+                Attribute::Test => "#[test] ".to_string(),
+                Attribute::Export(location) => format!("#[export({:?})] ", location),
+                Attribute::Inline(hint) => format!("#[inline({})] ", hint),
+            })
+            .collect::<String>();
+        let mut pieces = vec![Piece::Code(format!(
+            "{}{}fn {}(",
+            attributes,
+            visibility(def.visibility),
+            name
+        ))];
+        for (i, arg) in def.args.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            pieces.push(Piece::Code(format!("{}{}: ", separator, arg.name.raw.0)));
+            pieces.push(self.type_piece(&arg.ty));
+        }
+        pieces.push(Piece::Code(")".to_string()));
+        if def.return_type != Type::Void {
+            pieces.push(Piece::Code(" -> ".to_string()));
+            pieces.push(self.type_piece(&def.return_type));
+        }
+
+        section += &self.declaration(&pieces);
+        section += &self.doc(&def.doc);
+        section
+    }
+
+    fn static_section(&self, def: &StaticVarDef) -> String {
+        let name = &def.name.raw.0;
+        let mut section = self.heading(3, name, Some(&format!("static.{}", name)));
+        section += &self.declaration(&[
+            Piece::Code(format!("{}static {}: ", visibility(def.visibility), name)),
+            self.type_piece(&def.ty),
+        ]);
+        section += &self.doc(&def.doc);
+        section
+    }
+
+    // structs link to their definition, if it is documented
+    fn type_piece(&self, ty: &Type) -> Piece {
+        let link = match ty {
+            Type::Struct(name) => name
+                .resolved
+                .as_ref()
+                .and_then(|name| self.struct_link(name)),
+            _ => None,
+        };
+        match link {
+            Some(link) => Piece::Link(ty.to_string(), link),
+            None => Piece::Code(ty.to_string()),
+        }
+    }
+
+    fn struct_link(&self, name: &ResolvedName) -> Option<String> {
+        let name = self.global_definition_table.canonical_name(name);
+        let def = self.global_definition_table.get_struct_definition(&name)?;
+        (def.visibility != Visibility::Private).then(|| {
+            format!(
+                "{}#struct.{}",
+                self.page_name(&name.module_id),
+                def.name.raw.0
+            )
+        })
+    }
+
+    fn page(&self, title: &str, body: &str) -> String {
+        match self.format {
+            DocFormat::Html => format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+                escape_html(title),
+                body
+            ),
+            DocFormat::Markdown => body.to_string(),
+        }
+    }
+
+    fn heading(&self, level: usize, text: &str, anchor: Option<&str>) -> String {
+        match (self.format, anchor) {
+            (DocFormat::Html, Some(anchor)) => {
+                format!(
+                    "<h{0} id=\"{1}\">{2}</h{0}>\n",
+                    level,
+                    anchor,
+                    escape_html(text)
+                )
+            }
+            (DocFormat::Html, None) => format!("<h{0}>{1}</h{0}>\n", level, escape_html(text)),
+            (DocFormat::Markdown, Some(anchor)) => {
+                format!(
+                    "<a id=\"{}\"></a>\n\n{} {}\n\n",
+                    anchor,
+                    "#".repeat(level),
+                    text
+                )
+            }
+            (DocFormat::Markdown, None) => format!("{} {}\n\n", "#".repeat(level), text),
+        }
+    }
+
+    fn paragraph(&self, text: &str) -> String {
+        match self.format {
+            DocFormat::Html => format!("<p>{}</p>\n", text),
+            DocFormat::Markdown => format!("{}\n\n", text),
+        }
+    }
+
+    // doc comments are written in Markdown, so they are only split into paragraphs for HTML
+    fn doc(&self, doc: &Option<String>) -> String {
+        let Some(doc) = doc else {
+            return String::new();
+        };
+        match self.format {
+            DocFormat::Html => doc
+                .split("\n\n")
+                .map(|paragraph| self.paragraph(&escape_html(paragraph.trim())))
+                .collect(),
+            DocFormat::Markdown => self.paragraph(doc.trim()),
+        }
+    }
+
+    fn declaration(&self, pieces: &[Piece]) -> String {
+        match self.format {
+            DocFormat::Html => format!("<pre>{}</pre>\n", self.inline(pieces)),
+            DocFormat::Markdown => self.paragraph(&self.inline(pieces)),
+        }
+    }
+
+    fn list(&self, items: &[String]) -> String {
+        if items.is_empty() {
+            return String::new();
+        }
+        let items = items.iter().map(|item| match self.format {
+            DocFormat::Html => format!("<li>{}</li>\n", item),
+            DocFormat::Markdown => format!("- {}\n", item),
+        });
+        match self.format {
+            DocFormat::Html => format!("<ul>\n{}</ul>\n", items.collect::<String>()),
+            DocFormat::Markdown => format!("{}\n", items.collect::<String>()),
+        }
+    }
+
+    fn inline(&self, pieces: &[Piece]) -> String {
+        // code next to code is written as a single span
+        let mut merged: Vec<(String, Option<&str>)> = vec![];
+        for piece in pieces {
+            match (piece, merged.last_mut()) {
+                (Piece::Code(text), Some((last, None))) => last.push_str(text),
+                (Piece::Code(text), _) => merged.push((text.clone(), None)),
+                (Piece::Link(text, link), _) => merged.push((text.clone(), Some(link))),
+            }
+        }
+
+        let pieces = merged
+            .into_iter()
+            .map(|(text, link)| match (self.format, link) {
+                (DocFormat::Html, None) => escape_html(&text),
+                (DocFormat::Html, Some(link)) => {
+                    format!("<a href=\"{}\">{}</a>", link, escape_html(&text))
+                }
+                (DocFormat::Markdown, None) => code_span(&text),
+                (DocFormat::Markdown, Some(link)) => format!("[{}]({})", code_span(&text), link),
+            });
+        match self.format {
+            DocFormat::Html => format!("<code>{}</code>", pieces.collect::<String>()),
+            DocFormat::Markdown => pieces.collect(),
+        }
+    }
+}
+
+fn visibility(visibility: Visibility) -> &'static str {
+    match visibility {
+        Visibility::Private => "",
+        Visibility::Package => "pub(package) ",
+        Visibility::Public => "pub ",
+    }
+}
+
+// spaces at either end are kept outside of the backticks, where Markdown does not remove them
+fn code_span(text: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let start = text.find(trimmed).unwrap();
+    format!(
+        "{}`{}`{}",
+        &text[..start],
+        trimmed,
+        &text[start + trimmed.len()..]
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use crate::modules::ModuleBuilder;
+
+    #[test]
+    fn test_document_package() {
+        let main = "use package_b::util::{vec, zero};\n/// Makes one.\npub fn make(x: int) -> vec {\n    return zero();\n}\nfn hidden() {}";
+        let util = "/// A position.\n///\n/// Kept in storage.\npub struct vec {\n    x: int,\n}\npub fn zero() -> vec {}\nmod inner {\n    pub(package) static count: int;\n}";
+
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/package_a/main.ing"), main);
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/package_b/util.ing"), util);
+
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, None);
+        module_builder
+            .add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)
+            .unwrap();
+        module_builder
+            .add_fs_package("package_b", &Utf8PathBuf::from("pkg/package_b"), false)
+            .unwrap();
+        module_builder.load_module_bodies().unwrap();
+        let module_graph = module_builder.get_module_graph();
+
+        let pages = document_package(module_graph, "package_b", DocFormat::Markdown);
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| page.path.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "package_b.md",
+                "package_b.util.md",
+                "package_b.util.inner.md"
+            ]
+        );
+        assert_eq!(
+            pages[0].contents,
+            "# Package package_b\n\n## Modules\n\n- [`util`](package_b.util.md)\n  - [`inner`](package_b.util.inner.md)\n\n"
+        );
+        assert_eq!(
+            pages[1].contents,
+            "# Module package_b::util\n\n\
+            In package [`package_b`](package_b.md)\n\n\
+            ## Modules\n\n\
+            - [`inner`](package_b.util.inner.md)\n\n\
+            ## Structs\n\n\
+            <a id=\"struct.vec\"></a>\n\n\
+            ### vec\n\n\
+            `pub struct vec`\n\n\
+            A position.\n\nKept in storage.\n\n\
+            - `x: int`\n\n\
+            ## Functions\n\n\
+            <a id=\"fn.zero\"></a>\n\n\
+            ### zero\n\n\
+            `pub fn zero() ->` [`vec`](package_b.util.md#struct.vec)\n\n"
+        );
+
+        // types link to the package that defines them, and private items are left out
+        let pages = document_package(module_graph, "package_a", DocFormat::Html);
+        let main_page = &pages[1].contents;
+        assert_eq!(pages[1].path, Utf8PathBuf::from("package_a.main.html"));
+        assert!(main_page.contains(
+            "<pre><code>pub fn make(x: int) -&gt; <a href=\"package_b.util.html#struct.vec\">vec</a></code></pre>\n<p>Makes one.</p>"
+        ));
+        assert!(!main_page.contains("hidden"));
+    }
+}
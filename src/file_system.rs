@@ -12,8 +12,10 @@ pub enum FileSystemError {
 pub type FileSystemResult<T> = Result<T, FileSystemError>;
 
 pub trait FileSystem {
+    // lists the files directly inside the directory. The returned paths start with the given path
     fn list_files_with_extension(&self, path: &Utf8PathBuf, extension: &str) -> Vec<Utf8PathBuf>;
     fn get_reader(&self, file_path: &Utf8PathBuf) -> FileSystemResult<Box<dyn Read>>;
     fn get_file_age(&self, file_path: &Utf8PathBuf) -> FileSystemResult<u128>;
     fn get_writer(&mut self, file_path: &Utf8PathBuf) -> FileSystemResult<Box<dyn Write>>;
+    fn remove_file(&mut self, file_path: &Utf8PathBuf) -> FileSystemResult<()>;
 }
@@ -1,2 +1,4 @@
+#[cfg(test)]
 pub mod mock_fs;
+pub mod overlay_fs;
 pub mod system_fs;
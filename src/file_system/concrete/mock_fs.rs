@@ -48,6 +48,10 @@ type Files = Arc<Mutex<HashMap<Utf8PathBuf, String>>>;
 pub struct MockFileSystem {
     files: Files,
     dirs: HashSet<Utf8PathBuf>,
+
+    // inserted files get increasing ages, so modifications can be detected
+    ages: HashMap<Utf8PathBuf, u128>,
+    clock: u128,
 }
 
 impl MockFileSystem {
@@ -55,9 +59,13 @@ impl MockFileSystem {
         MockFileSystem {
             files: Default::default(),
             dirs: Default::default(),
+            ages: Default::default(),
+            clock: 0,
         }
     }
     pub fn insert_file(&mut self, path: Utf8PathBuf, content: &str) {
+        self.clock += 1;
+        self.ages.insert(path.clone(), self.clock);
         self.files.lock().unwrap().insert(path, content.to_string());
     }
 
@@ -70,7 +78,7 @@ impl FileSystem for MockFileSystem {
     fn list_files_with_extension(&self, path: &Utf8PathBuf, extension: &str) -> Vec<Utf8PathBuf> {
         let mut files = Vec::new();
         for (file_path, _) in self.files.lock().unwrap().iter() {
-            match file_path.strip_prefix(&path) {
+            match file_path.strip_prefix(path) {
                 Ok(stripped_path) => {
                     if stripped_path.extension() == Some(extension)
                         && stripped_path.parent() == Some(&Utf8PathBuf::new())
@@ -104,7 +112,19 @@ impl FileSystem for MockFileSystem {
         }))
     }
 
-    fn get_file_age(&self, _file_path: &Utf8PathBuf) -> FileSystemResult<u128> {
-        return Ok(1);
+    fn get_file_age(&self, file_path: &Utf8PathBuf) -> FileSystemResult<u128> {
+        if !self.files.lock().unwrap().contains_key(file_path) {
+            return Err(FileSystemError::FileNotFound);
+        }
+        // files created through a writer have not been modified since the start
+        Ok(self.ages.get(file_path).copied().unwrap_or(0))
+    }
+
+    fn remove_file(&mut self, file_path: &Utf8PathBuf) -> FileSystemResult<()> {
+        self.ages.remove(file_path);
+        match self.files.lock().unwrap().remove(file_path) {
+            Some(_) => Ok(()),
+            None => Err(FileSystemError::FileNotFound),
+        }
     }
 }
@@ -0,0 +1,123 @@
+use crate::file_system::{FileSystem, FileSystemResult};
+use camino::Utf8PathBuf;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+// buffer ages have the highest bit set, so they are never equal to the age of a file in the base file system
+const BUFFER_AGE: u128 = 1 << 127;
+
+struct Buffer {
+    content: String,
+    age: u128,
+}
+
+// a file system where files can be replaced with in-memory buffers, such as the unsaved files of an editor
+pub struct OverlayFs<T: FileSystem> {
+    base: T,
+    buffers: HashMap<Utf8PathBuf, Buffer>,
+    edits: u128, // the number of buffer edits, used as their age
+}
+
+impl<T: FileSystem> OverlayFs<T> {
+    pub fn new(base: T) -> OverlayFs<T> {
+        OverlayFs {
+            base,
+            buffers: HashMap::new(),
+            edits: 0,
+        }
+    }
+
+    pub fn set_buffer(&mut self, path: Utf8PathBuf, content: String) {
+        self.edits += 1;
+        self.buffers.insert(
+            path,
+            Buffer {
+                content,
+                age: BUFFER_AGE | self.edits,
+            },
+        );
+    }
+
+    // the file is read from the base file system again
+    pub fn remove_buffer(&mut self, path: &Utf8PathBuf) {
+        self.buffers.remove(path);
+    }
+}
+
+impl<T: FileSystem> FileSystem for OverlayFs<T> {
+    fn list_files_with_extension(&self, path: &Utf8PathBuf, extension: &str) -> Vec<Utf8PathBuf> {
+        let mut files = self.base.list_files_with_extension(path, extension);
+        // buffers of files that were not saved yet
+        for buffer_path in self.buffers.keys() {
+            if buffer_path.parent() == Some(path.as_path())
+                && buffer_path.extension() == Some(extension)
+                && !files.contains(buffer_path)
+            {
+                files.push(buffer_path.clone());
+            }
+        }
+        files
+    }
+
+    fn get_reader(&self, file_path: &Utf8PathBuf) -> FileSystemResult<Box<dyn Read>> {
+        match self.buffers.get(file_path) {
+            Some(buffer) => Ok(Box::new(Cursor::new(buffer.content.clone()))),
+            None => self.base.get_reader(file_path),
+        }
+    }
+
+    fn get_file_age(&self, file_path: &Utf8PathBuf) -> FileSystemResult<u128> {
+        match self.buffers.get(file_path) {
+            Some(buffer) => Ok(buffer.age),
+            None => self.base.get_file_age(file_path),
+        }
+    }
+
+    fn get_writer(&mut self, file_path: &Utf8PathBuf) -> FileSystemResult<Box<dyn Write>> {
+        self.base.get_writer(file_path)
+    }
+
+    fn remove_file(&mut self, file_path: &Utf8PathBuf) -> FileSystemResult<()> {
+        self.base.remove_file(file_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+
+    fn read(file_system: &impl FileSystem, path: &str) -> String {
+        let mut content = String::new();
+        file_system
+            .get_reader(&Utf8PathBuf::from(path))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn test_overlay_fs() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/main.ing"), "saved");
+        let mut overlay_fs = OverlayFs::new(mock_fs);
+
+        overlay_fs.set_buffer(Utf8PathBuf::from("pkg/main.ing"), "unsaved".to_string());
+        overlay_fs.set_buffer(Utf8PathBuf::from("pkg/new.ing"), "new".to_string());
+        assert_eq!(read(&overlay_fs, "pkg/main.ing"), "unsaved");
+
+        let mut files = overlay_fs.list_files_with_extension(&Utf8PathBuf::from("pkg"), "ing");
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                Utf8PathBuf::from("pkg/main.ing"),
+                Utf8PathBuf::from("pkg/new.ing")
+            ]
+        );
+
+        overlay_fs.remove_buffer(&Utf8PathBuf::from("pkg/main.ing"));
+        assert_eq!(read(&overlay_fs, "pkg/main.ing"), "saved");
+    }
+}
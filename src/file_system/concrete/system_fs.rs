@@ -6,8 +6,8 @@ use std::io::{Read, Write};
 pub struct SystemFs;
 
 impl SystemFs {
-    fn new() -> FileSystemResult<SystemFs> {
-        Ok(SystemFs)
+    pub fn new() -> SystemFs {
+        SystemFs
     }
 }
 
@@ -18,13 +18,11 @@ impl FileSystem for SystemFs {
         extension: &str,
     ) -> Vec<Utf8PathBuf> {
         let mut files = Vec::new();
-        if let Ok(paths) = fs::read_dir(&folder_path) {
-            for dir_entry_res in paths {
-                if let Ok(dir_entry) = dir_entry_res {
-                    if let Ok(path) = Utf8PathBuf::try_from(dir_entry.path()) {
-                        if path.extension() == Some(extension) {
-                            files.push(path.strip_prefix(&folder_path).unwrap().to_path_buf());
-                        }
+        if let Ok(paths) = fs::read_dir(folder_path) {
+            for dir_entry in paths.flatten() {
+                if let Ok(path) = Utf8PathBuf::try_from(dir_entry.path()) {
+                    if path.extension() == Some(extension) {
+                        files.push(path);
                     }
                 }
             }
@@ -54,9 +52,16 @@ impl FileSystem for SystemFs {
     }
 
     fn get_writer(&mut self, file_path: &Utf8PathBuf) -> FileSystemResult<Box<dyn Write>> {
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent).or(Err(FileSystemError::DirectoryNotFound))?;
+        }
         match File::create(file_path) {
             Ok(file) => Ok(Box::new(file)),
             Err(_) => Err(FileSystemError::FileNotFound),
         }
     }
+
+    fn remove_file(&mut self, file_path: &Utf8PathBuf) -> FileSystemResult<()> {
+        fs::remove_file(file_path).or(Err(FileSystemError::FileNotFound))
+    }
 }
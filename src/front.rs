@@ -1,27 +1,125 @@
 mod ast_creator;
 pub mod ast_types;
 pub mod definition_table;
+pub mod diagnostics;
+pub mod formatter;
 mod passes;
 
-use crate::front::ast_creator::create_ast;
-use crate::front::ast_types::FullItemPath;
-use crate::front::definition_table::DefinitionTable;
+pub use crate::front::passes::collect_symbols::Symbols;
+
+use crate::front::ast_creator::token_types::{Span, TokenError};
+use crate::front::ast_creator::{create_asts, ParseError};
+use crate::front::ast_types::{
+    Definition, FullItemPath, InlineModule, Module, RawNameRoot, ResolvedName,
+};
+use crate::front::definition_table::{DefinitionTable, Exports, ModuleExports};
 use crate::front::passes::collect_definitions::collect_definitions;
 use crate::front::passes::collect_dependencies::collect_dependencies;
-use crate::front::passes::name_resolution::resolve_names;
-use crate::modules::{module_id_from_local, ModuleDependencies};
+use crate::front::passes::collect_symbols::collect_symbols;
+use crate::front::passes::name_resolution::{
+    query_visible_names, resolve_names, NameResolutionError,
+};
+use crate::modules::{module_id_from_local, ModuleDependencies, ModuleId, ModuleImports};
 
-pub fn parse_file(
-    module_path: FullItemPath,
+#[derive(Debug)]
+pub enum FrontError {
+    Token(Vec<(TokenError, Span)>),
+    Parse(ParseError),
+    NameResolution(NameResolutionError),
+}
+
+pub type FrontResult<T> = Result<T, FrontError>;
+
+// the file's module, along with the modules declared inside of it
+pub fn parse_ast(
+    package_name: &str,
     file_contents: &str,
-) -> (ModuleDependencies, DefinitionTable) {
+) -> FrontResult<(Module, Vec<InlineModule>)> {
+    create_asts(package_name, file_contents)
+}
+
+// the definitions of a module that other modules can glob import, known before its names are resolved
+pub fn collect_exports(module: &Module) -> Exports {
+    module
+        .definitions
+        .iter()
+        .flatten()
+        .filter_map(|definition| match definition {
+            Definition::StaticVarDef(def) => Some((def.name.raw.0.clone(), def.visibility)),
+            Definition::StructDef(def) => Some((def.name.raw.0.clone(), def.visibility)),
+            Definition::FnDef(def) => Some((def.name.raw.0.clone(), def.visibility)),
+            Definition::VarDef(_) => None,
+        })
+        .collect()
+}
+
+pub fn resolve_module(
+    module_path: FullItemPath,
+    mut module: Module,
+    module_exports: &ModuleExports,
+) -> FrontResult<(ModuleDependencies, ModuleImports, DefinitionTable, Symbols)> {
     let module_id = module_id_from_local(&module_path.package_name, &module_path.item_path);
-    let mut module = create_ast(&module_path.package_name, file_contents);
 
-    // TODO: error handling
-    resolve_names(module_path, &mut module).unwrap();
-    let module_dependencies = collect_dependencies(module_id, &mut module);
+    resolve_names(module_path, &mut module, module_exports).map_err(FrontError::NameResolution)?;
+    let (module_dependencies, module_imports) = collect_dependencies(module_id, &mut module);
+    let symbols = collect_symbols(&mut module);
     let definition_table = collect_definitions(&mut module);
 
-    (module_dependencies, definition_table)
+    Ok((
+        module_dependencies,
+        module_imports,
+        definition_table,
+        symbols,
+    ))
+}
+
+// what can be seen from a position in a file, found even if some names of the file cannot be resolved
+pub struct ScopeQuery {
+    pub module_id: ModuleId, // the innermost module around the position, which may be declared inline
+    pub names: Vec<(RawNameRoot, ResolvedName)>,
+    pub definitions: DefinitionTable, // the definitions of that module, as they are written in the file
+}
+
+pub fn query_scope(
+    file_path: FullItemPath,
+    file_contents: &str,
+    offset: usize,
+    module_exports: &ModuleExports,
+) -> FrontResult<ScopeQuery> {
+    let (module, inline_modules) = create_asts(&file_path.package_name, file_contents)?;
+    let (inline_path, mut module) = inline_modules
+        .into_iter()
+        .filter(|(_, module)| {
+            module
+                .span
+                .is_some_and(|span| span.lo <= offset && offset <= span.hi)
+        })
+        .min_by_key(|(_, module)| module.span.map(|span| span.hi - span.lo))
+        .unwrap_or((vec![], module));
+
+    let module_path = FullItemPath::new(
+        file_path.package_name.clone(),
+        [file_path.item_path, inline_path].concat(),
+    );
+    let module_id = module_id_from_local(&module_path.package_name, &module_path.item_path);
+    let names = query_visible_names(module_path, &mut module, module_exports, offset)
+        .map_err(FrontError::NameResolution)?;
+
+    Ok(ScopeQuery {
+        module_id,
+        names,
+        definitions: collect_definitions(&mut module),
+    })
+}
+
+// parses a module that does not glob import anything, panicking if it is invalid
+#[cfg(test)]
+pub fn parse_file(
+    module_path: FullItemPath,
+    file_contents: &str,
+) -> (ModuleDependencies, ModuleImports, DefinitionTable) {
+    let module = ast_creator::create_ast(&module_path.package_name, file_contents);
+    let (dependencies, imports, definitions, _) =
+        resolve_module(module_path, module, &ModuleExports::new()).unwrap();
+    (dependencies, imports, definitions)
 }
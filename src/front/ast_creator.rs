@@ -1,42 +1,172 @@
-use crate::front::ast_creator::lexer::get_tokens;
-use crate::front::ast_types::Module;
+use crate::front::ast_creator::lexer::{get_tokens, get_tokens_and_comments};
+use crate::front::ast_creator::token_types::{Comment, Token};
+use crate::front::ast_types::{InlineModule, Module};
+use crate::front::{FrontError, FrontResult};
 
 mod lexer;
 mod parser;
-mod token_types;
+pub mod token_types;
 
+pub use parser::{precedence, ParseError};
+
+// creates the AST of the file's module, along with the modules declared inside of it
+pub fn create_asts(
+    file_root_package_name: &str,
+    src: &str,
+) -> FrontResult<(Module, Vec<InlineModule>)> {
+    let tokens = get_tokens(src).map_err(FrontError::Token)?;
+
+    parser::parse_tokens(file_root_package_name, tokens).map_err(FrontError::Parse)
+}
+
+// a file with everything that is needed to print its source again
+pub struct SourceFile {
+    pub module: Module,
+    pub inline_modules: Vec<InlineModule>,
+    pub tokens: Vec<Token>,
+    pub comments: Vec<Comment>,
+}
+
+pub fn create_source_file(file_root_package_name: &str, src: &str) -> FrontResult<SourceFile> {
+    let (tokens, comments) = get_tokens_and_comments(src).map_err(FrontError::Token)?;
+
+    let (module, inline_modules) =
+        parser::parse_tokens(file_root_package_name, tokens.clone()).map_err(FrontError::Parse)?;
+    Ok(SourceFile {
+        module,
+        inline_modules,
+        tokens,
+        comments,
+    })
+}
+
+// creates the AST of the file's module only
+#[cfg(test)]
 pub fn create_ast(file_root_package_name: &str, src: &str) -> Module {
-    // TODO: error handling
-    let tokens = get_tokens(src).unwrap();
-    let ast = parser::parse_tokens(file_root_package_name, tokens).unwrap();
+    create_asts(file_root_package_name, src).unwrap().0
+}
 
-    return ast;
+// the tokens of a file that could be lexed, for finding names in the source
+pub fn tokenize(src: &str) -> Vec<Token> {
+    get_tokens(src).unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::front::ast_creator::create_ast;
+    use crate::front::ast_creator::{create_ast, create_asts};
     use crate::front::ast_types::{
-        Definition, FnDef, FullItemPath, FunctionReference, Module, RawName, Statement,
-        StaticVarDef, StructDef, Type, TypeReference, VarDef, VarReference,
+        Attribute, BinOp, Definition, Expression, FnCall, FnDef, FullItemPath, FunctionReference,
+        InlineHint, Literal, Module, RawName, Statement, StaticVarDef, StructDef, Type,
+        TypeReference, UnOp, VarAssign, VarDef, VarReference, Visibility,
     };
+    use crate::front::formatter::format_source;
     use std::collections::HashMap;
 
-    #[test]
-    fn test_create_ast_use() {
-        let current_package = "package_a";
-        let src = r#"
+    const USE_SRC: &str = r#"
         use root::struct_a;
         use root::path::path2::{struct_b, struct_c};
         use package_b::path::path2::{struct_d, struct_e};
         "#;
+    const USE_TREE_SRC: &str = r#"
+        use package_b::path::*;
+        use root::struct_a as struct_b;
+        use package_b::{path::struct_c, path2::{struct_d as struct_e, path3::*}};
+        use super::struct_f;
+        "#;
+    const INLINE_MODULES_SRC: &str = r#"
+        mod mod_a {
+            static val: int;
+            mod mod_b {}
+        }
+        fn fn_a() {}
+        "#;
+    const STRUCT_SRC: &str = r#"
+        struct struct_a {
+            field_a: int,
+            field_b: struct_b,
+        }
+        "#;
+    const STATIC_SRC: &str = r#"
+        static val: int;
+        "#;
+    const VOID_FN_SRC: &str = r#"
+        fn fn_a() {
+        }
+        "#;
+    const FN_ATTRIBUTES_SRC: &str = r#"
+        #[load]
+        #[tick]
+        #[export("map:doors/open")]
+        #[inline(never)]
+        fn fn_a() {
+        }
+        "#;
+    const VISIBILITY_SRC: &str = r#"
+        pub fn fn_a() {}
+        pub(package) struct struct_a {}
+        static var_a: int;
+        #[load]
+        pub fn fn_b() {}
+        "#;
+    const LET_SRC: &str = r#"
+        fn fn_a() {
+            let val: int;
+        }
+        "#;
+    const FN_SRC: &str = r#"
+        fn fn_a(arg_a: int, arg_b: struct_b) -> struct_c {
+        }
+        "#;
+    const SCOPE_INTERMEDIATE_SRC: &str = r#"
+        fn fn_a() {
+        {}
+        }
+        "#;
+    const LAYERED_DEFINITION_SRC: &str = r#"
+        fn fn_a() {
+        {
+        struct struct_a {
+            field_a: int,
+            field_b: struct_b,
+        }
+        }
+        }
+        "#;
+    const EXPRESSION_SRC: &str = r#"
+        fn fn_a() -> float {
+            let val: float = -1.5 * 2 as float + fn_b(3);
+            return val;
+        }
+        "#;
+    const DOC_COMMENTS_SRC: &str = r#"
+        /// A position.
+        ///
+        /// Kept in storage.
+        pub struct point {
+            /// fields are not documented
+            x: int,
+        }
+        /// The origin.
+        static origin: point;
+        /// Moves it.
+        #[load]
+        fn fn_a() {
+            /// not kept either
+            let val: int;
+        }
+        "#;
+
+    #[test]
+    fn test_create_ast_use() {
+        let current_package = "package_a";
+        let src = USE_SRC;
 
         let uses: Vec<(RawName, FullItemPath)> = vec![
             (
                 ("struct_a".to_string(), None),
                 FullItemPath::new(
                     "package_a".to_string(),
-                    vec!["struct_a"]
+                    ["struct_a"]
                         .iter()
                         .map(|x| x.to_string())
                         .collect::<Vec<String>>(),
@@ -46,7 +176,7 @@ mod tests {
                 ("struct_b".to_string(), None),
                 FullItemPath::new(
                     "package_a".to_string(),
-                    vec!["path", "path2", "struct_b"]
+                    ["path", "path2", "struct_b"]
                         .iter()
                         .map(|x| x.to_string())
                         .collect::<Vec<String>>(),
@@ -56,7 +186,7 @@ mod tests {
                 ("struct_c".to_string(), None),
                 FullItemPath::new(
                     "package_a".to_string(),
-                    vec!["path", "path2", "struct_c"]
+                    ["path", "path2", "struct_c"]
                         .iter()
                         .map(|x| x.to_string())
                         .collect::<Vec<String>>(),
@@ -66,7 +196,7 @@ mod tests {
                 ("struct_d".to_string(), None),
                 FullItemPath::new(
                     "package_b".to_string(),
-                    vec!["path", "path2", "struct_d"]
+                    ["path", "path2", "struct_d"]
                         .iter()
                         .map(|x| x.to_string())
                         .collect::<Vec<String>>(),
@@ -76,7 +206,7 @@ mod tests {
                 ("struct_e".to_string(), None),
                 FullItemPath::new(
                     "package_b".to_string(),
-                    vec!["path", "path2", "struct_e"]
+                    ["path", "path2", "struct_e"]
                         .iter()
                         .map(|x| x.to_string())
                         .collect::<Vec<String>>(),
@@ -88,20 +218,87 @@ mod tests {
         assert_eq!(uses, ast.uses.unwrap());
     }
 
+    #[test]
+    fn test_create_ast_use_tree() {
+        let current_package = "package_a";
+        let src = USE_TREE_SRC;
+
+        let path = |package_name: &str, item_path: &[&str]| {
+            FullItemPath::new(
+                package_name.to_string(),
+                item_path.iter().map(|x| x.to_string()).collect(),
+            )
+        };
+        let uses: Vec<(RawName, FullItemPath)> = vec![
+            (("*".to_string(), None), path("package_b", &["path"])),
+            (
+                ("struct_b".to_string(), None),
+                path("package_a", &["struct_a"]),
+            ),
+            (
+                ("struct_c".to_string(), None),
+                path("package_b", &["path", "struct_c"]),
+            ),
+            (
+                ("struct_e".to_string(), None),
+                path("package_b", &["path2", "struct_d"]),
+            ),
+            (
+                ("*".to_string(), None),
+                path("package_b", &["path2", "path3"]),
+            ),
+            (("struct_f".to_string(), None), path("super", &["struct_f"])),
+        ];
+
+        let ast = create_ast(current_package, src);
+        assert_eq!(uses, ast.uses.unwrap());
+    }
+
+    #[test]
+    fn test_create_ast_inline_modules() {
+        let current_package = "package_a";
+        let src = INLINE_MODULES_SRC;
+
+        let (ast, inline_modules) = create_asts(current_package, src).unwrap();
+        let self_use = |name: &str| {
+            (
+                (name.to_string(), None),
+                FullItemPath::new("self".to_string(), vec![name.to_string()]),
+            )
+        };
+
+        assert_eq!(ast.uses.unwrap(), vec![self_use("mod_a")]);
+        assert_eq!(ast.definitions.unwrap().len(), 1);
+
+        let paths = inline_modules
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                vec!["mod_a".to_string(), "mod_b".to_string()],
+                vec!["mod_a".to_string()]
+            ]
+        );
+        assert_eq!(
+            inline_modules[1].1.uses.as_ref().unwrap(),
+            &vec![self_use("mod_b")]
+        );
+        assert_eq!(inline_modules[1].1.definitions.as_ref().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_create_ast_struct() {
         let current_package = "package_a";
-        let src = r#"
-        struct struct_a {
-            field_a: int,
-            field_b: struct_b,
-        }
-        "#;
+        let src = STRUCT_SRC;
 
         let expected = Module {
             uses: Some(vec![]),
             definitions: Some(vec![
                 (Definition::StructDef(StructDef {
+                    doc: None,
+                    visibility: Visibility::Private,
                     name: TypeReference::new(("struct_a".to_string(), None)),
                     field_types: {
                         let mut field_types = HashMap::new();
@@ -115,6 +312,8 @@ mod tests {
                 })),
             ]),
             statements: vec![],
+            span: None,
+            layout: vec![],
         };
 
         let ast = create_ast(current_package, src);
@@ -124,19 +323,21 @@ mod tests {
     #[test]
     fn test_create_ast_static() {
         let current_package = "package_a";
-        let src = r#"
-        static val: int;
-        "#;
+        let src = STATIC_SRC;
 
         let expected_ast = Module {
             uses: Some(vec![]),
             definitions: Some(vec![
                 (Definition::StaticVarDef(StaticVarDef {
+                    doc: None,
+                    visibility: Visibility::Private,
                     name: VarReference::new(("val".to_string(), None)),
                     ty: Type::Int,
                 })),
             ]),
             statements: vec![],
+            span: None,
+            layout: vec![],
         };
 
         let ast = create_ast(current_package, src);
@@ -146,15 +347,53 @@ mod tests {
     #[test]
     fn test_create_ast_void_fn() {
         let current_package = "package_a";
-        let src = r#"
-        fn fn_a() {
-        }
-        "#;
+        let src = VOID_FN_SRC;
+
+        let expected_ast = Module {
+            uses: Some(vec![]),
+            definitions: Some(vec![
+                (Definition::FnDef(FnDef {
+                    doc: None,
+                    attributes: vec![],
+                    visibility: Visibility::Private,
+                    return_type: Type::Void,
+                    name: FunctionReference::new(("fn_a".to_string(), None)),
+                    args: vec![],
+                    body: Module {
+                        uses: Some(vec![]),
+                        definitions: Some(vec![]),
+                        statements: vec![],
+                        span: None,
+                        layout: vec![],
+                    },
+                })),
+            ]),
+            statements: vec![],
+            span: None,
+            layout: vec![],
+        };
+
+        let ast = create_ast(current_package, src);
+        assert_eq!(expected_ast, ast);
+    }
+
+    #[test]
+    fn test_create_ast_fn_attributes() {
+        let current_package = "package_a";
+        let src = FN_ATTRIBUTES_SRC;
 
         let expected_ast = Module {
             uses: Some(vec![]),
             definitions: Some(vec![
                 (Definition::FnDef(FnDef {
+                    doc: None,
+                    attributes: vec![
+                        Attribute::Load,
+                        Attribute::Tick,
+                        Attribute::Export("map:doors/open".to_string()),
+                        Attribute::Inline(InlineHint::Never),
+                    ],
+                    visibility: Visibility::Private,
                     return_type: Type::Void,
                     name: FunctionReference::new(("fn_a".to_string(), None)),
                     args: vec![],
@@ -162,29 +401,61 @@ mod tests {
                         uses: Some(vec![]),
                         definitions: Some(vec![]),
                         statements: vec![],
+                        span: None,
+                        layout: vec![],
                     },
                 })),
             ]),
             statements: vec![],
+            span: None,
+            layout: vec![],
         };
 
         let ast = create_ast(current_package, src);
         assert_eq!(expected_ast, ast);
     }
 
+    #[test]
+    fn test_create_ast_visibility() {
+        let current_package = "package_a";
+        let src = VISIBILITY_SRC;
+
+        let ast = create_ast(current_package, src);
+        let visibilities = ast
+            .definitions
+            .unwrap()
+            .iter()
+            .map(|definition| match definition {
+                Definition::FnDef(def) => def.visibility,
+                Definition::StructDef(def) => def.visibility,
+                Definition::StaticVarDef(def) => def.visibility,
+                Definition::VarDef(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            visibilities,
+            vec![
+                Visibility::Public,
+                Visibility::Package,
+                Visibility::Private,
+                Visibility::Public
+            ]
+        );
+    }
+
     #[test]
     fn test_create_ast_let() {
         let current_package = "package_a";
-        let src = r#"
-        fn fn_a() {
-            let val: int;
-        }
-        "#;
+        let src = LET_SRC;
 
         let expected_ast = Module {
             uses: Some(vec![]),
             definitions: Some(vec![
                 (Definition::FnDef(FnDef {
+                    doc: None,
+                    attributes: vec![],
+                    visibility: Visibility::Private,
                     return_type: Type::Void,
                     name: FunctionReference::new(("fn_a".to_string(), None)),
                     args: vec![],
@@ -197,10 +468,14 @@ mod tests {
                             })),
                         ]),
                         statements: vec![],
+                        span: None,
+                        layout: vec![],
                     },
                 })),
             ]),
             statements: vec![],
+            span: None,
+            layout: vec![],
         };
 
         let ast = create_ast(current_package, src);
@@ -210,15 +485,15 @@ mod tests {
     #[test]
     fn test_create_ast_fn() {
         let current_package = "package_a";
-        let src = r#"
-        fn fn_a(arg_a: int, arg_b: struct_b) -> struct_c {
-        }
-        "#;
+        let src = FN_SRC;
 
         let expected_ast = Module {
             uses: Some(vec![]),
             definitions: Some(vec![
                 (Definition::FnDef(FnDef {
+                    doc: None,
+                    attributes: vec![],
+                    visibility: Visibility::Private,
                     return_type: Type::Struct(TypeReference::new(("struct_c".to_string(), None))),
                     name: FunctionReference::new(("fn_a".to_string(), None)),
                     args: vec![
@@ -235,10 +510,14 @@ mod tests {
                         uses: Some(vec![]),
                         definitions: Some(vec![]),
                         statements: vec![],
+                        span: None,
+                        layout: vec![],
                     },
                 })),
             ]),
             statements: vec![],
+            span: None,
+            layout: vec![],
         };
 
         let ast = create_ast(current_package, src);
@@ -248,16 +527,15 @@ mod tests {
     #[test]
     fn test_create_ast_scope_intermediate() {
         let current_package = "package_a";
-        let src = r#"
-        fn fn_a() {
-        {}
-        }
-        "#;
+        let src = SCOPE_INTERMEDIATE_SRC;
 
         let expected_ast = Module {
             uses: Some(vec![]),
             definitions: Some(vec![
                 (Definition::FnDef(FnDef {
+                    doc: None,
+                    attributes: vec![],
+                    visibility: Visibility::Private,
                     return_type: Type::Void,
                     name: FunctionReference::new(("fn_a".to_string(), None)),
                     args: vec![],
@@ -269,12 +547,18 @@ mod tests {
                                 uses: Some(vec![]),
                                 definitions: Some(vec![]),
                                 statements: vec![],
+                                span: None,
+                                layout: vec![],
                             })),
                         ],
+                        span: None,
+                        layout: vec![],
                     },
                 })),
             ]),
             statements: vec![],
+            span: None,
+            layout: vec![],
         };
 
         let ast = create_ast(current_package, src);
@@ -284,21 +568,15 @@ mod tests {
     #[test]
     fn test_create_ast_layered_definition() {
         let current_package = "package_a";
-        let src = r#"
-        fn fn_a() {
-        {
-        struct struct_a {
-            field_a: int,
-            field_b: struct_b,
-        }
-        }
-        }
-        "#;
+        let src = LAYERED_DEFINITION_SRC;
 
         let expected_ast = Module {
             uses: Some(vec![]),
             definitions: Some(vec![
                 (Definition::FnDef(FnDef {
+                    doc: None,
+                    attributes: vec![],
+                    visibility: Visibility::Private,
                     return_type: Type::Void,
                     name: FunctionReference::new(("fn_a".to_string(), None)),
                     args: vec![],
@@ -310,6 +588,8 @@ mod tests {
                                 uses: Some(vec![]),
                                 definitions: Some(vec![
                                     (Definition::StructDef(StructDef {
+                                        doc: None,
+                                        visibility: Visibility::Private,
                                         name: TypeReference::new(("struct_a".to_string(), None)),
                                         field_types: {
                                             let mut field_types = HashMap::new();
@@ -326,15 +606,141 @@ mod tests {
                                     })),
                                 ]),
                                 statements: vec![],
+                                span: None,
+                                layout: vec![],
+                            })),
+                        ],
+                        span: None,
+                        layout: vec![],
+                    },
+                })),
+            ]),
+            statements: vec![],
+            span: None,
+            layout: vec![],
+        };
+
+        let ast = create_ast(current_package, src);
+        assert_eq!(expected_ast, ast);
+    }
+
+    #[test]
+    fn test_create_ast_expression() {
+        let current_package = "package_a";
+        let src = EXPRESSION_SRC;
+
+        let expected_ast = Module {
+            uses: Some(vec![]),
+            definitions: Some(vec![
+                (Definition::FnDef(FnDef {
+                    doc: None,
+                    attributes: vec![],
+                    visibility: Visibility::Private,
+                    return_type: Type::Float,
+                    name: FunctionReference::new(("fn_a".to_string(), None)),
+                    args: vec![],
+                    body: Module {
+                        uses: Some(vec![]),
+                        definitions: Some(vec![
+                            (Definition::VarDef(VarDef {
+                                name: VarReference::new(("val".to_string(), None)),
+                                ty: Type::Float,
                             })),
+                        ]),
+                        statements: vec![
+                            Statement::VarAssign(VarAssign {
+                                name: VarReference::new(("val".to_string(), None)),
+                                expr: Expression::Binary(
+                                    Box::new(Expression::Binary(
+                                        Box::new(Expression::Unary(
+                                            UnOp::Neg,
+                                            Box::new(Expression::Literal(Literal::Float(1.5))),
+                                        )),
+                                        BinOp::Mul,
+                                        Box::new(Expression::Cast(
+                                            Box::new(Expression::Literal(Literal::Int(2))),
+                                            Type::Float,
+                                        )),
+                                    )),
+                                    BinOp::Add,
+                                    Box::new(Expression::FnCall(FnCall {
+                                        name: FunctionReference::new(("fn_b".to_string(), None)),
+                                        args: vec![Expression::Literal(Literal::Int(3))],
+                                    })),
+                                ),
+                            }),
+                            Statement::Return(Some(Expression::Var(VarReference::new((
+                                "val".to_string(),
+                                None,
+                            ))))),
                         ],
+                        span: None,
+                        layout: vec![],
                     },
                 })),
             ]),
             statements: vec![],
+            span: None,
+            layout: vec![],
         };
 
         let ast = create_ast(current_package, src);
         assert_eq!(expected_ast, ast);
     }
+
+    #[test]
+    fn test_create_ast_doc_comments() {
+        let ast = create_ast("package_a", DOC_COMMENTS_SRC);
+
+        let docs = ast
+            .definitions
+            .unwrap()
+            .into_iter()
+            .map(|definition| match definition {
+                Definition::StructDef(def) => def.doc,
+                Definition::StaticVarDef(def) => def.doc,
+                Definition::FnDef(def) => {
+                    assert!(def.body.definitions.unwrap().len() == 1);
+                    def.doc
+                }
+                Definition::VarDef(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            docs,
+            vec![
+                Some("A position.\n\nKept in storage.".to_string()),
+                Some("The origin.".to_string()),
+                Some("Moves it.".to_string()),
+            ]
+        );
+    }
+
+    // formatting keeps the meaning of the source, and formatting it again does not change it
+    #[test]
+    fn test_format_idempotence() {
+        for src in [
+            USE_SRC,
+            USE_TREE_SRC,
+            INLINE_MODULES_SRC,
+            STRUCT_SRC,
+            STATIC_SRC,
+            VOID_FN_SRC,
+            FN_ATTRIBUTES_SRC,
+            VISIBILITY_SRC,
+            LET_SRC,
+            FN_SRC,
+            SCOPE_INTERMEDIATE_SRC,
+            LAYERED_DEFINITION_SRC,
+            EXPRESSION_SRC,
+            DOC_COMMENTS_SRC,
+        ] {
+            let formatted = format_source(src).unwrap();
+            assert_eq!(
+                create_asts("package_a", &formatted).unwrap(),
+                create_asts("package_a", src).unwrap()
+            );
+            assert_eq!(format_source(&formatted).unwrap(), formatted);
+        }
+    }
 }
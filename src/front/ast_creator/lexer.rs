@@ -1,9 +1,15 @@
-use crate::front::ast_creator::token_types::{Span, Token, TokenError, TokenKind};
+use crate::front::ast_creator::token_types::{Comment, Span, Token, TokenError, TokenKind};
 use std::collections::VecDeque;
 
 use std::str::CharIndices;
 
-pub fn get_tokens(src: &str) -> Result<Vec<Token>, Vec<TokenError>> {
+type LexResult<T> = Result<T, Vec<(TokenError, Span)>>;
+
+pub fn get_tokens(src: &str) -> LexResult<Vec<Token>> {
+    get_tokens_and_comments(src).map(|(tokens, _)| tokens)
+}
+
+pub fn get_tokens_and_comments(src: &str) -> LexResult<(Vec<Token>, Vec<Comment>)> {
     let mut lexer = Lexer::new(src);
     let mut tokens = Vec::new();
     let mut errors = Vec::new();
@@ -24,7 +30,7 @@ pub fn get_tokens(src: &str) -> Result<Vec<Token>, Vec<TokenError>> {
     }
 
     if errors.is_empty() {
-        Ok(tokens)
+        Ok((tokens, lexer.comments))
     } else {
         Err(errors)
     }
@@ -37,6 +43,8 @@ struct Lexer<'src> {
     pos: usize,
 
     peeked_chars: VecDeque<(usize, char)>,
+
+    comments: Vec<Comment>, // the comments skipped so far
 }
 
 impl<'src> Lexer<'src> {
@@ -47,6 +55,7 @@ impl<'src> Lexer<'src> {
             curr: '\0',
             pos: 0,
             peeked_chars: Default::default(),
+            comments: vec![],
         };
         lexer.eat();
         lexer
@@ -73,16 +82,22 @@ impl<'src> Lexer<'src> {
         self.peeked_chars[offset - 1].1
     }
 
-    pub fn get_token(&mut self) -> Result<Token, TokenError> {
+    pub fn get_token(&mut self) -> Result<Token, (TokenError, Span)> {
         self.skip_ignoreable();
         let lo = self.pos;
-        return Ok(Token {
-            kind: self.parse_token()?,
-            span: Span {
+        let kind = self.parse_token();
+        let span = match kind {
+            // without the line break
+            Ok(TokenKind::DocComment(_)) => self.comments.last().unwrap().span,
+            _ => Span {
                 lo,
                 hi: self.pos - 1,
             },
-        });
+        };
+        match kind {
+            Ok(kind) => Ok(Token { kind, span }),
+            Err(err) => Err((err, span)),
+        }
     }
 
     fn skip_ignoreable(&mut self) {
@@ -93,16 +108,9 @@ impl<'src> Lexer<'src> {
                 self.eat();
             }
             let mut is_comment_start = false;
-            if self.curr == '/' && self.peek(1) == '/' {
+            if self.curr == '/' && self.peek(1) == '/' && !self.is_doc_comment_start() {
                 is_comment_start = true;
-                // comment until end of line
-                loop {
-                    self.eat();
-                    if self.curr == '\n' || self.curr == '\r' {
-                        self.eat();
-                        break;
-                    }
-                }
+                self.eat_comment();
             }
 
             if !is_whitespace && !is_comment_start {
@@ -111,6 +119,35 @@ impl<'src> Lexer<'src> {
         }
     }
 
+    // `///`, but not `////`
+    fn is_doc_comment_start(&mut self) -> bool {
+        self.curr == '/' && self.peek(1) == '/' && self.peek(2) == '/' && self.peek(3) != '/'
+    }
+
+    // a comment until the end of the line, returning its text
+    fn eat_comment(&mut self) -> String {
+        let lo = self.pos;
+        let mut text = String::new();
+        loop {
+            text.push(self.eat());
+            if self.curr == '\0' {
+                break;
+            }
+            if self.curr == '\n' || self.curr == '\r' {
+                self.eat();
+                break;
+            }
+        }
+        self.comments.push(Comment {
+            span: Span {
+                lo,
+                hi: lo + text.len() - 1,
+            },
+            text: text.clone(),
+        });
+        text
+    }
+
     fn parse_token(&mut self) -> Result<TokenKind, TokenError> {
         // check for EOF
         if self.curr == '\0' {
@@ -118,13 +155,21 @@ impl<'src> Lexer<'src> {
             return Ok(TokenKind::Eof);
         }
 
+        // check for doc comment, the text after `///` and an optional space
+        if self.is_doc_comment_start() {
+            let text = self.eat_comment();
+            let text = &text["///".len()..];
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            return Ok(TokenKind::DocComment(text.trim_end().to_string()));
+        }
+
         // check for identifier
         // identifier: [a-zA-Z_][a-zA-Z0-9_]*
-        if self.curr.is_alphabetic() {
+        if self.curr.is_alphabetic() || self.curr == '_' {
             let mut ident = String::new();
 
             // read word and set to ident
-            while self.curr.is_alphanumeric() || self.curr == '_' || self.curr == '-' {
+            while self.curr.is_alphanumeric() || self.curr == '_' {
                 ident.push(self.eat());
             }
 
@@ -133,27 +178,56 @@ impl<'src> Lexer<'src> {
 
                 "void" => TokenKind::TVoid,
                 "int" => TokenKind::TInt,
+                "float" => TokenKind::TFloat,
+                "bool" => TokenKind::TBool,
+                "string" => TokenKind::TString,
 
                 "static" => TokenKind::Static,
                 "let" => TokenKind::Let,
                 "struct" => TokenKind::Struct,
                 "fn" => TokenKind::Fn,
+                "mod" => TokenKind::Mod,
+
+                "pub" => TokenKind::Pub,
+
+                "return" => TokenKind::Return,
+                "if" => TokenKind::If,
+                "else" => TokenKind::Else,
+                "while" => TokenKind::While,
+                "as" => TokenKind::As,
+
+                "true" => TokenKind::LBool(true),
+                "false" => TokenKind::LBool(false),
                 _ => TokenKind::Ident(ident),
             });
         }
 
+        // check for number literal
+        // int: [0-9]+, float: [0-9]+.[0-9]+
+        if self.curr.is_ascii_digit() {
+            return self.parse_number();
+        }
+
+        if self.curr == '"' {
+            return self.parse_string();
+        }
+
         let prev = self.eat();
 
-        match (prev, self.curr) {
-            (':', ':') => {
-                self.eat();
-                return Ok(TokenKind::DoubleColon);
-            }
-            ('-', '>') => {
-                self.eat();
-                return Ok(TokenKind::Arrow);
-            }
-            _ => {}
+        let double = match (prev, self.curr) {
+            (':', ':') => Some(TokenKind::DoubleColon),
+            ('-', '>') => Some(TokenKind::Arrow),
+            ('=', '=') => Some(TokenKind::Eq),
+            ('!', '=') => Some(TokenKind::Ne),
+            ('<', '=') => Some(TokenKind::Le),
+            ('>', '=') => Some(TokenKind::Ge),
+            ('&', '&') => Some(TokenKind::And),
+            ('|', '|') => Some(TokenKind::Or),
+            _ => None,
+        };
+        if let Some(kind) = double {
+            self.eat();
+            return Ok(kind);
         }
 
         // match singletons
@@ -162,14 +236,85 @@ impl<'src> Lexer<'src> {
             ';' => TokenKind::SemiColon,
             ',' => TokenKind::Comma,
 
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '=' => TokenKind::Assign,
+            '<' => TokenKind::Lt,
+            '>' => TokenKind::Gt,
+            '!' => TokenKind::Not,
+
             '{' => TokenKind::LBrace,
             '}' => TokenKind::RBrace,
 
             '(' => TokenKind::LParen,
             ')' => TokenKind::RParen,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+
+            '#' => TokenKind::Hash,
             _ => return Err(TokenError::InvalidToken(format!("{}", prev))),
         })
     }
+
+    // string: "([^"\\]|\\["\\nt])*"
+    fn parse_string(&mut self) -> Result<TokenKind, TokenError> {
+        self.eat(); // opening quote
+        let mut string = String::new();
+        // an invalid escape is only reported once the whole string is consumed, so lexing can continue after it
+        let mut invalid_escape = None;
+
+        loop {
+            match self.eat() {
+                '"' => break,
+                '\0' => return Err(TokenError::UnterminatedString),
+                '\\' => match self.eat() {
+                    '"' => string.push('"'),
+                    '\\' => string.push('\\'),
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    '\0' => return Err(TokenError::UnterminatedString),
+                    ch => invalid_escape = invalid_escape.or(Some(ch)),
+                },
+                ch => string.push(ch),
+            }
+        }
+
+        match invalid_escape {
+            Some(ch) => Err(TokenError::InvalidEscape(ch)),
+            None => Ok(TokenKind::LString(string)),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<TokenKind, TokenError> {
+        let mut number = String::new();
+        let mut decimals = 0;
+
+        while self.curr.is_ascii_digit() || (self.curr == '.' && self.peek(1).is_ascii_digit()) {
+            if self.curr == '.' {
+                decimals += 1;
+            }
+            number.push(self.eat());
+        }
+
+        if decimals > 1 {
+            return Err(TokenError::MultipleDecimals);
+        }
+
+        if decimals == 1 {
+            number
+                .parse::<f64>()
+                .map(TokenKind::LFloat)
+                .or(Err(TokenError::InvalidToken(number)))
+        } else {
+            number
+                .parse::<i32>()
+                .map(TokenKind::LInt)
+                .or(Err(TokenError::InvalidToken(number)))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -252,4 +397,121 @@ mod tests {
         assert_eq!(tokens[5].span, Span { lo: 27, hi: 28 });
         assert_eq!(tokens[6].span, Span { lo: 29, hi: 29 });
     }
+
+    #[test]
+    fn test_get_number_tokens() {
+        let src = "12 3.25 x-1.5 as float";
+        let tokens = get_tokens(src).unwrap();
+        let kinds: Vec<TokenKind> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::LInt(12),
+                TokenKind::LFloat(3.25),
+                TokenKind::Ident("x".to_string()),
+                TokenKind::Minus,
+                TokenKind::LFloat(1.5),
+                TokenKind::As,
+                TokenKind::TFloat,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_bool_and_string_tokens() {
+        let src = r#"true false bool string "a \"b\" \\ c\n" a <= b != !c && d || e"#;
+        let tokens = get_tokens(src).unwrap();
+        let kinds: Vec<TokenKind> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::LBool(true),
+                TokenKind::LBool(false),
+                TokenKind::TBool,
+                TokenKind::TString,
+                TokenKind::LString("a \"b\" \\ c\n".to_string()),
+                TokenKind::Ident("a".to_string()),
+                TokenKind::Le,
+                TokenKind::Ident("b".to_string()),
+                TokenKind::Ne,
+                TokenKind::Not,
+                TokenKind::Ident("c".to_string()),
+                TokenKind::And,
+                TokenKind::Ident("d".to_string()),
+                TokenKind::Or,
+                TokenKind::Ident("e".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_errors() {
+        assert!(matches!(
+            get_tokens(r#""abc"#).unwrap_err()[..],
+            [(TokenError::UnterminatedString, Span { lo: 0, hi: 3 })]
+        ));
+        assert!(matches!(
+            get_tokens(r#""a\qc""#).unwrap_err()[..],
+            [(TokenError::InvalidEscape('q'), _)]
+        ));
+    }
+
+    #[test]
+    fn test_multiple_decimals() {
+        let src = "1.2.3";
+        assert!(matches!(
+            get_tokens(src).unwrap_err()[..],
+            [(TokenError::MultipleDecimals, _)]
+        ));
+    }
+
+    #[test]
+    fn test_comments() {
+        let src = "// first\nfn a() {} // second\r\n// last";
+        let (tokens, comments) = get_tokens_and_comments(src).unwrap();
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(
+            comments
+                .iter()
+                .map(|comment| (
+                    comment.text.as_str(),
+                    &src[comment.span.lo..=comment.span.hi]
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("// first", "// first"),
+                ("// second", "// second"),
+                ("// last", "// last")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let src = "/// Adds one.\n///\n////   not a doc comment\nfn a() {}";
+        let (tokens, comments) = get_tokens_and_comments(src).unwrap();
+        assert_eq!(
+            tokens[..3]
+                .iter()
+                .map(|token| token.kind.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                TokenKind::DocComment("Adds one.".to_string()),
+                TokenKind::DocComment("".to_string()),
+                TokenKind::Fn
+            ]
+        );
+        assert_eq!(&src[tokens[0].span.lo..=tokens[0].span.hi], "/// Adds one.");
+        // still printed again with the other comments
+        assert_eq!(comments.len(), 3);
+    }
+
+    #[test]
+    fn test_comment_at_end_of_file() {
+        let tokens = get_tokens("fn // no newline").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[1].kind, TokenKind::Eof);
+    }
 }
@@ -1,21 +1,27 @@
-use crate::front::ast_creator::token_types::{Token, TokenKind};
+use crate::front::ast_creator::token_types::{Span, Token, TokenKind};
 use crate::front::ast_types::{
-    Definition, FnDef, FullItemPath, FunctionReference, Module, RawName, Statement, StaticVarDef,
-    StructDef, Type, TypeReference, VarDef, VarReference,
+    Assert, AssertKind, Attribute, BinOp, Command, CommandPart, Definition, Expression, FnCall,
+    FnDef, FullItemPath, FunctionReference, If, InlineHint, InlineModule, ItemPath, LayoutItem,
+    Literal, Module, RawName, Statement, StaticVarDef, StructDef, Type, TypeReference, UnOp,
+    VarAssign, VarDef, VarReference, Visibility, While, GLOB_IMPORT,
 };
 use std::cmp::min;
 use std::collections::HashMap;
 use std::mem;
 
-pub fn parse_tokens(package_name: &str, tokens: Vec<Token>) -> ParseResult<Module> {
+// parses a file into its module, and the modules declared inside of it
+pub fn parse_tokens(
+    package_name: &str,
+    tokens: Vec<Token>,
+) -> ParseResult<(Module, Vec<InlineModule>)> {
     let mut parser = Parser::new(tokens);
-    parser.parse_top_level(package_name)
+    let module = parser.parse_top_level(package_name)?;
+    Ok((module, parser.inline_modules))
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ParseError {
     Unexpected(Token, String),
-    Unknown,
 }
 
 pub type ParseResult<T> = Result<T, ParseError>;
@@ -24,21 +30,31 @@ struct Parser {
     tokens: Vec<Token>,
 
     curr_index: usize,
+
+    // the path of the inline module being parsed, relative to the file
+    module_path: ItemPath,
+    inline_modules: Vec<InlineModule>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        let parser = Self {
+        Self {
             tokens,
             curr_index: 0,
-        };
-        parser
+            module_path: vec![],
+            inline_modules: vec![],
+        }
     }
 
     fn get_token(&self) -> &Token {
         &self.tokens[self.curr_index]
     }
 
+    // the span of the token that was eaten last
+    fn prev_span(&self) -> Span {
+        self.tokens[self.curr_index.saturating_sub(1)].span
+    }
+
     fn eat_any(&mut self) -> &TokenKind {
         let old_token = &self.tokens[self.curr_index].kind;
         if self.curr_index < self.tokens.len() - 1 {
@@ -50,15 +66,15 @@ impl Parser {
     fn eat(&mut self, type_: &TokenKind) -> ParseResult<&TokenKind> {
         let old_token = &self.tokens[self.curr_index];
         // return old token kind, set new token
-        if mem::discriminant(&old_token.kind) == mem::discriminant(&type_) {
+        if mem::discriminant(&old_token.kind) == mem::discriminant(type_) {
             if self.curr_index < self.tokens.len() - 1 {
                 self.curr_index += 1;
             }
-            return Ok(&old_token.kind);
+            Ok(&old_token.kind)
         } else {
             Err(ParseError::Unexpected(
                 old_token.clone(),
-                format!("Tried to eat {:?}, ate {:?}", type_, old_token),
+                format!("Expected {:?}, found {:?}", type_, old_token.kind),
             ))
         }
     }
@@ -68,56 +84,57 @@ impl Parser {
     }
 
     fn parse_top_level(&mut self, package_name: &str) -> ParseResult<Module> {
+        let module = self.parse_module_items(package_name)?;
+        self.eat(&TokenKind::Eof)?;
+        Ok(module)
+    }
+
+    // the items of a file or of an inline module, up to the end of the file or the closing brace
+    fn parse_module_items(&mut self, package_name: &str) -> ParseResult<Module> {
         let mut module = Module {
             uses: Some(Default::default()),
             definitions: Some(Default::default()),
             statements: Default::default(),
+            span: None,
+            layout: vec![],
         };
 
         loop {
-            match self.peek(0) {
+            let doc = self.parse_doc_comments();
+            let lo = self.get_token().span.lo;
+            let item = match self.peek(0) {
                 TokenKind::Use => {
-                    module
-                        .uses
-                        .as_mut()
-                        .unwrap()
-                        .extend(self.parse_use(package_name)?);
-                }
-                TokenKind::Fn => {
-                    let definition = self.parse_fn_definition(package_name)?;
-                    module
-                        .definitions
-                        .as_mut()
-                        .unwrap()
-                        .push(Definition::FnDef(definition));
+                    let uses = self.parse_use(package_name)?;
+                    let count = uses.len();
+                    module.uses.as_mut().unwrap().extend(uses);
+                    LayoutItem::Use(count)
                 }
-                TokenKind::Struct => {
-                    let definition = self.parse_struct_definition()?;
-                    module
-                        .definitions
-                        .as_mut()
-                        .unwrap()
-                        .push(Definition::StructDef(definition));
+                TokenKind::Fn
+                | TokenKind::Hash
+                | TokenKind::Pub
+                | TokenKind::Struct
+                | TokenKind::Static => {
+                    let definition = self.parse_top_level_definition(package_name, doc)?;
+                    module.definitions.as_mut().unwrap().push(definition);
+                    LayoutItem::Definition
                 }
-                TokenKind::Static => {
-                    let definition = self.parse_static_var_definition()?;
-                    module
-                        .definitions
-                        .as_mut()
-                        .unwrap()
-                        .push(Definition::StaticVarDef(definition));
+                TokenKind::Mod => {
+                    self.parse_inline_module(package_name, &mut module)?;
+                    LayoutItem::InlineModule
                 }
-                TokenKind::Eof => {
+                TokenKind::Eof | TokenKind::RBrace => {
                     break;
                 }
-                TokenKind::Let | _ => {
-                    // this is added to explicitly show that we are ignoring Let s
+                _ => {
+                    // `let` (and anything else) is not allowed at the top level
                     return Err(ParseError::Unexpected(
                         self.get_token().clone(),
                         "Cannot be used for top level".to_string(),
                     ));
                 }
-            }
+            };
+            let hi = self.prev_span().hi;
+            module.layout.push((item, Span { lo, hi }));
         }
 
         Ok(module)
@@ -128,63 +145,134 @@ impl Parser {
             uses: Some(Default::default()),
             definitions: Some(Default::default()),
             statements: Default::default(),
+            span: None,
+            layout: vec![],
         };
         self.eat(&TokenKind::LBrace)?;
+        let lo = self.prev_span().lo;
         loop {
-            match self.peek(0) {
+            let doc = self.parse_doc_comments();
+            let item_lo = self.get_token().span.lo;
+            let item = match self.peek(0) {
                 TokenKind::Use => {
-                    module
-                        .uses
-                        .as_mut()
-                        .unwrap()
-                        .extend(self.parse_use(package_name)?);
+                    let uses = self.parse_use(package_name)?;
+                    let count = uses.len();
+                    module.uses.as_mut().unwrap().extend(uses);
+                    LayoutItem::Use(count)
                 }
-                TokenKind::Fn => {
-                    let definition = self.parse_fn_definition(package_name)?;
+                TokenKind::Fn | TokenKind::Hash => {
+                    let attributes = self.parse_attributes()?;
+                    let definition = self.parse_fn_definition(
+                        package_name,
+                        doc,
+                        attributes,
+                        Visibility::Private,
+                    )?;
                     module
                         .definitions
                         .as_mut()
                         .unwrap()
                         .push(Definition::FnDef(definition));
+                    LayoutItem::Definition
                 }
                 TokenKind::Struct => {
-                    let definition = self.parse_struct_definition()?;
+                    let definition = self.parse_struct_definition(doc, Visibility::Private)?;
                     module
                         .definitions
                         .as_mut()
                         .unwrap()
                         .push(Definition::StructDef(definition));
+                    LayoutItem::Definition
                 }
                 TokenKind::Let => {
-                    let definition = self.parse_var_definition()?;
+                    let (definition, init) = self.parse_var_definition()?;
+                    let item = if init.is_some() {
+                        LayoutItem::Let
+                    } else {
+                        LayoutItem::Definition
+                    };
+                    // the initializer is kept in place as an assignment so that statement order is preserved
+                    if let Some(expr) = init {
+                        module.statements.push(Statement::VarAssign(VarAssign {
+                            name: VarReference::new(definition.name.raw.clone()),
+                            expr,
+                        }));
+                    }
                     module
                         .definitions
                         .as_mut()
                         .unwrap()
                         .push(Definition::VarDef(definition));
+                    item
+                }
+                TokenKind::Ident(ident) if ident == "cmd" && self.peek(1) == &TokenKind::Not => {
+                    let statement = self.parse_command()?;
+                    module.statements.push(statement);
+                    LayoutItem::Statement
+                }
+                TokenKind::Ident(ident)
+                    if (ident == "assert" || ident == "assert_eq")
+                        && self.peek(1) == &TokenKind::Not =>
+                {
+                    let statement = self.parse_assert()?;
+                    module.statements.push(statement);
+                    LayoutItem::Statement
+                }
+                TokenKind::Ident(_) => {
+                    let statement = self.parse_ident_statement()?;
+                    module.statements.push(statement);
+                    LayoutItem::Statement
+                }
+                TokenKind::Return => {
+                    let statement = self.parse_return()?;
+                    module.statements.push(statement);
+                    LayoutItem::Statement
+                }
+                TokenKind::If => {
+                    let statement = self.parse_if(package_name)?;
+                    module.statements.push(statement);
+                    LayoutItem::Statement
+                }
+                TokenKind::While => {
+                    self.eat(&TokenKind::While)?;
+                    let condition = self.parse_expression()?;
+                    let body = self.parse_intermediate_level(package_name)?;
+                    module
+                        .statements
+                        .push(Statement::While(While { condition, body }));
+                    LayoutItem::Statement
                 }
                 TokenKind::LBrace => {
                     let submodule = self.parse_intermediate_level(package_name)?;
                     module.statements.push(Statement::Module(submodule));
+                    LayoutItem::Statement
                 }
                 TokenKind::RBrace => {
                     break;
                 }
-                TokenKind::Static | _ => {
-                    // this is added to explicitly show that we are ignoring Static s
+                _ => {
+                    // `static` (and anything else) is not allowed inside of functions
                     return Err(ParseError::Unexpected(
                         self.get_token().clone(),
                         "Cannot be used for intermediate level".to_string(),
                     ));
                 }
-            }
+            };
+            let hi = self.prev_span().hi;
+            module.layout.push((item, Span { lo: item_lo, hi }));
         }
         self.eat(&TokenKind::RBrace)?;
+        module.span = Some(Span {
+            lo,
+            hi: self.prev_span().hi,
+        });
 
         Ok(module)
     }
 
-    fn parse_reference_name(&mut self, head: &str) -> ParseResult<RawName> {
+    // the head of the name was eaten already
+    fn parse_reference_name(&mut self, head: &str) -> ParseResult<(RawName, Span)> {
+        let lo = self.prev_span().lo;
         let mut nodes = vec![];
         loop {
             if self.eat(&TokenKind::DoubleColon).is_err() {
@@ -197,8 +285,14 @@ impl Parser {
             }
         }
         Ok((
-            head.to_string(),
-            if nodes.is_empty() { None } else { Some(nodes) },
+            (
+                head.to_string(),
+                if nodes.is_empty() { None } else { Some(nodes) },
+            ),
+            Span {
+                lo,
+                hi: self.prev_span().hi,
+            },
         ))
     }
 
@@ -206,10 +300,13 @@ impl Parser {
         Ok(match self.eat_any() {
             TokenKind::TVoid => Type::Void,
             TokenKind::TInt => Type::Int,
+            TokenKind::TFloat => Type::Float,
+            TokenKind::TBool => Type::Bool,
+            TokenKind::TString => Type::String,
             TokenKind::Ident(head) => {
                 let head_cpy = head.clone();
-                let raw_name = self.parse_reference_name(&head_cpy)?;
-                Type::Struct(TypeReference::new(raw_name))
+                let (raw_name, span) = self.parse_reference_name(&head_cpy)?;
+                Type::Struct(TypeReference::spanned(raw_name, span))
             }
             _ => {
                 return Err(ParseError::Unexpected(
@@ -220,10 +317,176 @@ impl Parser {
         })
     }
 
-    fn parse_fn_definition(&mut self, package_name: &str) -> ParseResult<FnDef> {
+    /* Consecutive `///` comments, joined by line breaks. They are kept for the definition that follows them, and
+    discarded before anything else.
+     */
+    fn parse_doc_comments(&mut self) -> Option<String> {
+        let mut lines = vec![];
+        while let TokenKind::DocComment(line) = self.peek(0) {
+            lines.push(line.clone());
+            self.eat_any();
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    // #[name], #[name("argument")] or #[name(argument)]
+    fn parse_attributes(&mut self) -> ParseResult<Vec<Attribute>> {
+        let mut attributes = vec![];
+        while self.eat(&TokenKind::Hash).is_ok() {
+            self.eat(&TokenKind::LBracket)?;
+            let name =
+                if let TokenKind::Ident(name) = self.eat(&TokenKind::Ident("".to_string()))? {
+                    name.clone()
+                } else {
+                    unreachable!("Can't happen");
+                };
+
+            let argument = if self.eat(&TokenKind::LParen).is_ok() {
+                let argument = match self.peek(0) {
+                    TokenKind::Ident(_) => self.eat(&TokenKind::Ident("".to_string()))?,
+                    _ => self.eat(&TokenKind::LString("".to_string()))?,
+                }
+                .clone();
+                self.eat(&TokenKind::RParen)?;
+                Some(argument)
+            } else {
+                None
+            };
+
+            attributes.push(match (name.as_str(), argument) {
+                ("load", None) => Attribute::Load,
+                ("tick", None) => Attribute::Tick,
+                ("test", None) => Attribute::Test,
+                ("export", Some(TokenKind::LString(location)))
+                    if is_resource_location(&location) =>
+                {
+                    Attribute::Export(location)
+                }
+                ("export", _) => {
+                    return Err(ParseError::Unexpected(
+                        self.get_token().clone(),
+                        "Expected a function location like \"namespace:path\"".to_string(),
+                    ))
+                }
+                ("inline", Some(TokenKind::Ident(hint))) if hint == "always" => {
+                    Attribute::Inline(InlineHint::Always)
+                }
+                ("inline", Some(TokenKind::Ident(hint))) if hint == "never" => {
+                    Attribute::Inline(InlineHint::Never)
+                }
+                ("inline", _) => {
+                    return Err(ParseError::Unexpected(
+                        self.get_token().clone(),
+                        "Expected `always` or `never`".to_string(),
+                    ))
+                }
+                _ => {
+                    return Err(ParseError::Unexpected(
+                        self.get_token().clone(),
+                        format!("Unknown attribute {}", name),
+                    ))
+                }
+            });
+            self.eat(&TokenKind::RBracket)?;
+        }
+        Ok(attributes)
+    }
+
+    /* mod name { ... }
+
+    The module is parsed on its own and put aside, and the enclosing module gets an implicit `use self::name;` so
+    that its items can be referred to as `name::item`.
+     */
+    fn parse_inline_module(&mut self, package_name: &str, parent: &mut Module) -> ParseResult<()> {
+        self.eat(&TokenKind::Mod)?;
+        let name = if let TokenKind::Ident(name) = self.eat(&TokenKind::Ident("".to_string()))? {
+            name.clone()
+        } else {
+            unreachable!("Can't happen");
+        };
+        self.eat(&TokenKind::LBrace)?;
+        let lo = self.prev_span().lo;
+
+        self.module_path.push(name.clone());
+        let mut module = self.parse_module_items(package_name)?;
+        self.eat(&TokenKind::RBrace)?;
+        module.span = Some(Span {
+            lo,
+            hi: self.prev_span().hi,
+        });
+        self.inline_modules.push((self.module_path.clone(), module));
+        self.module_path.pop();
+
+        parent.uses.as_mut().unwrap().push((
+            (name.clone(), None),
+            FullItemPath::new("self".to_string(), vec![name]),
+        ));
+        Ok(())
+    }
+
+    // definitions that can be imported by other modules, optionally preceded by attributes and a visibility
+    fn parse_top_level_definition(
+        &mut self,
+        package_name: &str,
+        doc: Option<String>,
+    ) -> ParseResult<Definition> {
+        let attributes = self.parse_attributes()?;
+        let visibility = self.parse_visibility()?;
+
+        Ok(match self.peek(0) {
+            TokenKind::Fn => Definition::FnDef(self.parse_fn_definition(
+                package_name,
+                doc,
+                attributes,
+                visibility,
+            )?),
+            TokenKind::Struct if attributes.is_empty() => {
+                Definition::StructDef(self.parse_struct_definition(doc, visibility)?)
+            }
+            TokenKind::Static if attributes.is_empty() => {
+                Definition::StaticVarDef(self.parse_static_var_definition(doc, visibility)?)
+            }
+            _ => {
+                return Err(ParseError::Unexpected(
+                    self.get_token().clone(),
+                    "Expected a function, struct or static definition".to_string(),
+                ))
+            }
+        })
+    }
+
+    // `pub`, `pub(package)` or nothing
+    fn parse_visibility(&mut self) -> ParseResult<Visibility> {
+        if self.eat(&TokenKind::Pub).is_err() {
+            return Ok(Visibility::Private);
+        }
+        if self.eat(&TokenKind::LParen).is_err() {
+            return Ok(Visibility::Public);
+        }
+        match self.eat_any() {
+            TokenKind::Ident(ident) if ident == "package" => {}
+            _ => {
+                return Err(ParseError::Unexpected(
+                    self.get_token().clone(),
+                    "Expected `package`".to_string(),
+                ))
+            }
+        }
+        self.eat(&TokenKind::RParen)?;
+        Ok(Visibility::Package)
+    }
+
+    fn parse_fn_definition(
+        &mut self,
+        package_name: &str,
+        doc: Option<String>,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+    ) -> ParseResult<FnDef> {
         self.eat(&TokenKind::Fn)?;
         if let TokenKind::Ident(fn_name) = self.eat_any() {
             let fn_name = fn_name.clone();
+            let fn_span = self.prev_span();
 
             self.eat(&TokenKind::LParen)?;
             let mut args = vec![];
@@ -234,12 +497,13 @@ impl Parser {
 
                 if let TokenKind::Ident(arg_name) = self.eat_any() {
                     let arg_name = arg_name.clone();
+                    let arg_span = self.prev_span();
 
                     self.eat(&TokenKind::Colon)?;
                     let ty = self.parse_type()?;
 
                     args.push(VarDef {
-                        name: VarReference::new((arg_name, None)),
+                        name: VarReference::spanned((arg_name, None), arg_span),
                         ty,
                     });
 
@@ -265,8 +529,11 @@ impl Parser {
             let body = self.parse_intermediate_level(package_name)?;
 
             Ok(FnDef {
+                doc,
+                attributes,
+                visibility,
                 return_type,
-                name: FunctionReference::new((fn_name, None)),
+                name: FunctionReference::spanned((fn_name, None), fn_span),
                 args,
                 body,
             })
@@ -278,15 +545,22 @@ impl Parser {
         }
     }
 
-    fn parse_struct_definition(&mut self) -> ParseResult<StructDef> {
+    fn parse_struct_definition(
+        &mut self,
+        doc: Option<String>,
+        visibility: Visibility,
+    ) -> ParseResult<StructDef> {
         self.eat(&TokenKind::Struct)?;
         if let TokenKind::Ident(struct_name) = self.eat_any() {
             let struct_name = struct_name.clone();
+            let struct_span = self.prev_span();
 
             let mut field_types = HashMap::new();
 
             self.eat(&TokenKind::LBrace)?;
             loop {
+                // fields are not documented on their own
+                self.parse_doc_comments();
                 if self.peek(0) == &TokenKind::RBrace {
                     break;
                 }
@@ -312,7 +586,9 @@ impl Parser {
 
             self.eat(&TokenKind::RBrace)?;
             Ok(StructDef {
-                name: TypeReference::new((struct_name, None)),
+                doc,
+                visibility,
+                name: TypeReference::spanned((struct_name, None), struct_span),
                 field_types,
             })
         } else {
@@ -326,13 +602,16 @@ impl Parser {
     fn parse_var_definition_helper(&mut self) -> ParseResult<(VarReference, Type)> {
         if let TokenKind::Ident(variable_name) = self.eat_any() {
             let variable_name = variable_name.clone();
+            let variable_span = self.prev_span();
 
             // TODO: currently no type inference, so type is required
             self.eat(&TokenKind::Colon)?;
             let ty = self.parse_type()?;
 
-            self.eat(&TokenKind::SemiColon)?;
-            Ok((VarReference::new((variable_name, None)), ty))
+            Ok((
+                VarReference::spanned((variable_name, None), variable_span),
+                ty,
+            ))
         } else {
             Err(ParseError::Unexpected(
                 self.get_token().clone(),
@@ -341,29 +620,265 @@ impl Parser {
         }
     }
 
-    fn parse_var_definition(&mut self) -> ParseResult<VarDef> {
+    fn parse_var_definition(&mut self) -> ParseResult<(VarDef, Option<Expression>)> {
         self.eat(&TokenKind::Let)?;
         let var_def = self.parse_var_definition_helper()?;
-        Ok(VarDef {
-            name: var_def.0,
-            ty: var_def.1,
-        })
+        let init = if self.eat(&TokenKind::Assign).is_ok() {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        self.eat(&TokenKind::SemiColon)?;
+        Ok((
+            VarDef {
+                name: var_def.0,
+                ty: var_def.1,
+            },
+            init,
+        ))
     }
 
-    fn parse_static_var_definition(&mut self) -> ParseResult<StaticVarDef> {
+    fn parse_static_var_definition(
+        &mut self,
+        doc: Option<String>,
+        visibility: Visibility,
+    ) -> ParseResult<StaticVarDef> {
         self.eat(&TokenKind::Static)?;
         let var_def = self.parse_var_definition_helper()?;
+        self.eat(&TokenKind::SemiColon)?;
         Ok(StaticVarDef {
+            doc,
+            visibility,
             name: var_def.0,
             ty: var_def.1,
         })
     }
 
+    // statements starting with an identifier: either an assignment or a function call
+    fn parse_ident_statement(&mut self) -> ParseResult<Statement> {
+        let expr = self.parse_expression()?;
+
+        let statement = if self.eat(&TokenKind::Assign).is_ok() {
+            if let Expression::Var(name) = expr {
+                Statement::VarAssign(VarAssign {
+                    name,
+                    expr: self.parse_expression()?,
+                })
+            } else {
+                return Err(ParseError::Unexpected(
+                    self.get_token().clone(),
+                    "Invalid left hand side of assignment".to_string(),
+                ));
+            }
+        } else if let Expression::FnCall(fn_call) = expr {
+            Statement::FnCall(fn_call)
+        } else {
+            return Err(ParseError::Unexpected(
+                self.get_token().clone(),
+                "Expected assignment or function call".to_string(),
+            ));
+        };
+
+        self.eat(&TokenKind::SemiColon)?;
+        Ok(statement)
+    }
+
+    // cmd!("...")
+    fn parse_command(&mut self) -> ParseResult<Statement> {
+        self.eat(&TokenKind::Ident("".to_string()))?;
+        self.eat(&TokenKind::Not)?;
+        self.eat(&TokenKind::LParen)?;
+
+        let command = if let TokenKind::LString(text) = self.peek(0) {
+            parse_interpolation(text)
+                .map_err(|message| ParseError::Unexpected(self.get_token().clone(), message))?
+        } else {
+            return Err(ParseError::Unexpected(
+                self.get_token().clone(),
+                "Expected string".to_string(),
+            ));
+        };
+        self.eat_any();
+
+        self.eat(&TokenKind::RParen)?;
+        self.eat(&TokenKind::SemiColon)?;
+        Ok(Statement::Command(command))
+    }
+
+    // assert!(condition) or assert_eq!(left, right)
+    fn parse_assert(&mut self) -> ParseResult<Statement> {
+        let is_eq = self.eat(&TokenKind::Ident("".to_string()))?
+            == &TokenKind::Ident("assert_eq".to_string());
+        let lo = self.prev_span().lo;
+        self.eat(&TokenKind::Not)?;
+        self.eat(&TokenKind::LParen)?;
+
+        let lhs = self.parse_expression()?;
+        let kind = if is_eq {
+            self.eat(&TokenKind::Comma)?;
+            AssertKind::Eq(lhs, self.parse_expression()?)
+        } else {
+            AssertKind::True(lhs)
+        };
+
+        self.eat(&TokenKind::RParen)?;
+        let span = Span {
+            lo,
+            hi: self.prev_span().hi,
+        };
+        self.eat(&TokenKind::SemiColon)?;
+        Ok(Statement::Assert(Assert {
+            kind,
+            span: Some(span),
+        }))
+    }
+
+    // if condition { ... } else if condition { ... } else { ... }
+    fn parse_if(&mut self, package_name: &str) -> ParseResult<Statement> {
+        self.eat(&TokenKind::If)?;
+        let condition = self.parse_expression()?;
+        let body = self.parse_intermediate_level(package_name)?;
+
+        let else_body = if self.eat(&TokenKind::Else).is_ok() {
+            Some(Box::new(if self.peek(0) == &TokenKind::If {
+                self.parse_if(package_name)?
+            } else {
+                Statement::Module(self.parse_intermediate_level(package_name)?)
+            }))
+        } else {
+            None
+        };
+
+        Ok(Statement::If(If {
+            condition,
+            body,
+            else_body,
+        }))
+    }
+
+    fn parse_return(&mut self) -> ParseResult<Statement> {
+        self.eat(&TokenKind::Return)?;
+        let expr = if self.peek(0) == &TokenKind::SemiColon {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        self.eat(&TokenKind::SemiColon)?;
+        Ok(Statement::Return(expr))
+    }
+
+    // binary operators and their precedence
+    fn binary_op(kind: &TokenKind) -> Option<(BinOp, u8)> {
+        let op = match kind {
+            TokenKind::Or => BinOp::Or,
+            TokenKind::And => BinOp::And,
+            TokenKind::Eq => BinOp::Eq,
+            TokenKind::Ne => BinOp::Ne,
+            TokenKind::Lt => BinOp::Lt,
+            TokenKind::Le => BinOp::Le,
+            TokenKind::Gt => BinOp::Gt,
+            TokenKind::Ge => BinOp::Ge,
+            TokenKind::Plus => BinOp::Add,
+            TokenKind::Minus => BinOp::Sub,
+            TokenKind::Star => BinOp::Mul,
+            TokenKind::Slash => BinOp::Div,
+            TokenKind::Percent => BinOp::Mod,
+            _ => return None,
+        };
+        Some((op, precedence(op)))
+    }
+
+    fn parse_expression(&mut self) -> ParseResult<Expression> {
+        self.parse_binary_expression(0)
+    }
+
+    // precedence climbing, all binary operators are left associative
+    fn parse_binary_expression(&mut self, min_precedence: u8) -> ParseResult<Expression> {
+        let mut lhs = self.parse_cast_expression()?;
+
+        while let Some((op, precedence)) = Self::binary_op(self.peek(0)) {
+            if precedence <= min_precedence {
+                break;
+            }
+            self.eat_any();
+            let rhs = self.parse_binary_expression(precedence)?;
+            lhs = Expression::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_cast_expression(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.parse_unary_expression()?;
+        while self.eat(&TokenKind::As).is_ok() {
+            expr = Expression::Cast(Box::new(expr), self.parse_type()?);
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary_expression(&mut self) -> ParseResult<Expression> {
+        if self.eat(&TokenKind::Minus).is_ok() {
+            let expr = self.parse_unary_expression()?;
+            return Ok(Expression::Unary(UnOp::Neg, Box::new(expr)));
+        }
+        if self.eat(&TokenKind::Not).is_ok() {
+            let expr = self.parse_unary_expression()?;
+            return Ok(Expression::Unary(UnOp::Not, Box::new(expr)));
+        }
+        self.parse_primary_expression()
+    }
+
+    fn parse_primary_expression(&mut self) -> ParseResult<Expression> {
+        Ok(match self.eat_any() {
+            TokenKind::LInt(value) => Expression::Literal(Literal::Int(*value)),
+            TokenKind::LFloat(value) => Expression::Literal(Literal::Float(*value)),
+            TokenKind::LBool(value) => Expression::Literal(Literal::Bool(*value)),
+            TokenKind::LString(value) => Expression::Literal(Literal::String(value.clone())),
+            TokenKind::LParen => {
+                let expr = self.parse_expression()?;
+                self.eat(&TokenKind::RParen)?;
+                expr
+            }
+            TokenKind::Ident(head) => {
+                let head_cpy = head.clone();
+                let (raw_name, span) = self.parse_reference_name(&head_cpy)?;
+
+                if self.eat(&TokenKind::LParen).is_ok() {
+                    let mut args = vec![];
+                    loop {
+                        if self.peek(0) == &TokenKind::RParen {
+                            break;
+                        }
+                        args.push(self.parse_expression()?);
+                        if self.eat(&TokenKind::Comma).is_err() {
+                            break;
+                        }
+                    }
+                    self.eat(&TokenKind::RParen)?;
+
+                    Expression::FnCall(FnCall {
+                        name: FunctionReference::spanned(raw_name, span),
+                        args,
+                    })
+                } else {
+                    Expression::Var(VarReference::spanned(raw_name, span))
+                }
+            }
+            _ => {
+                return Err(ParseError::Unexpected(
+                    self.get_token().clone(),
+                    "Expected expression".to_string(),
+                ))
+            }
+        })
+    }
+
     // maps
     fn parse_use(&mut self, package_name: &str) -> ParseResult<Vec<(RawName, FullItemPath)>> {
         self.eat(&TokenKind::Use)?;
 
         if let TokenKind::Ident(use_package_name) = self.eat_any() {
+            // `self` and `super` are kept as the package name, they are made absolute during name resolution
             let package_name = if use_package_name == "root" {
                 package_name.to_string()
             } else {
@@ -371,55 +886,8 @@ impl Parser {
             };
 
             let mut res = vec![];
-
-            let mut path = vec![];
             self.eat(&TokenKind::DoubleColon)?;
-
-            loop {
-                match self.eat_any() {
-                    TokenKind::Ident(ident) => {
-                        let ident = ident.clone();
-                        if self.peek(0) == &TokenKind::SemiColon {
-                            path.push(ident.clone());
-                            res.push((
-                                (ident.clone(), None),
-                                FullItemPath::new(package_name, path),
-                            ));
-                            break;
-                        } else {
-                            path.push(ident);
-                            self.eat(&TokenKind::DoubleColon)?;
-                        }
-                    }
-                    TokenKind::LBrace => {
-                        loop {
-                            if let TokenKind::Ident(ident) =
-                                self.eat(&TokenKind::Ident("".to_string()))?
-                            {
-                                let mut path = path.clone();
-                                path.push(ident.clone());
-                                res.push((
-                                    (ident.clone(), None),
-                                    FullItemPath::new(package_name.clone(), path),
-                                ));
-                                if self.eat(&TokenKind::Comma).is_err() {
-                                    break;
-                                }
-                            } else {
-                                panic!("Can't happen");
-                            }
-                        }
-                        self.eat(&TokenKind::RBrace)?;
-                        break;
-                    }
-                    _ => {
-                        return Err(ParseError::Unexpected(
-                            self.get_token().clone(),
-                            "Expected ident or ::".to_string(),
-                        ));
-                    }
-                }
-            }
+            self.parse_use_tree(&package_name, vec![], &mut res)?;
 
             self.eat(&TokenKind::SemiColon)?;
             Ok(res)
@@ -430,6 +898,163 @@ impl Parser {
             ))
         }
     }
+
+    // a::b, a::b as c, a::* or a::{<tree>, <tree>, ...}
+    fn parse_use_tree(
+        &mut self,
+        package_name: &str,
+        mut path: Vec<String>,
+        res: &mut Vec<(RawName, FullItemPath)>,
+    ) -> ParseResult<()> {
+        loop {
+            match self.eat_any() {
+                TokenKind::Ident(ident) => {
+                    let ident = ident.clone();
+                    path.push(ident.clone());
+                    if self.eat(&TokenKind::DoubleColon).is_ok() {
+                        continue;
+                    }
+
+                    let alias = if self.eat(&TokenKind::As).is_ok() {
+                        if let TokenKind::Ident(alias) =
+                            self.eat(&TokenKind::Ident("".to_string()))?
+                        {
+                            alias.clone()
+                        } else {
+                            unreachable!("Can't happen");
+                        }
+                    } else {
+                        ident
+                    };
+                    res.push((
+                        (alias, None),
+                        FullItemPath::new(package_name.to_string(), path),
+                    ));
+                    return Ok(());
+                }
+                TokenKind::Star => {
+                    res.push((
+                        (GLOB_IMPORT.to_string(), None),
+                        FullItemPath::new(package_name.to_string(), path),
+                    ));
+                    return Ok(());
+                }
+                TokenKind::LBrace => {
+                    while self.peek(0) != &TokenKind::RBrace {
+                        self.parse_use_tree(package_name, path.clone(), res)?;
+                        if self.eat(&TokenKind::Comma).is_err() {
+                            break;
+                        }
+                    }
+                    self.eat(&TokenKind::RBrace)?;
+                    return Ok(());
+                }
+                _ => {
+                    return Err(ParseError::Unexpected(
+                        self.get_token().clone(),
+                        "Expected ident, * or {".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+// higher binds tighter
+pub fn precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => 1,
+        BinOp::And => 2,
+        BinOp::Eq | BinOp::Ne => 3,
+        BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 4,
+        BinOp::Add | BinOp::Sub => 5,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 6,
+    }
+}
+
+// namespace:path, where the namespace is [a-z0-9_.-]+ and the path is [a-z0-9_.-]+(/[a-z0-9_.-]+)*
+fn is_resource_location(location: &str) -> bool {
+    let is_valid_char = |ch: char| matches!(ch, 'a'..='z' | '0'..='9' | '_' | '.' | '-');
+    match location.split_once(':') {
+        Some((namespace, path)) => {
+            !namespace.is_empty()
+                && namespace.chars().all(is_valid_char)
+                && path
+                    .split('/')
+                    .all(|node| !node.is_empty() && node.chars().all(is_valid_char))
+        }
+        None => false,
+    }
+}
+
+/* Splits the text of a raw command into text and interpolated variables.
+
+`{name}` is replaced by the value of the variable and `{&name}` by where it is stored. Names may be paths (`a::b`).
+Literal braces are written as `{{` and `}}`.
+ */
+fn parse_interpolation(text: &str) -> Result<Command, String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                current.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                current.push('}');
+            }
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => inner.push(ch),
+                        None => return Err("Unclosed { in command".to_string()),
+                    }
+                }
+
+                let inner = inner.trim();
+                let (is_location, name) = match inner.strip_prefix('&') {
+                    Some(name) => (true, name.trim()),
+                    None => (false, inner),
+                };
+
+                let mut nodes = name.split("::").map(|node| node.trim().to_string());
+                let head = nodes.next().unwrap();
+                let tail: Vec<String> = nodes.collect();
+                let is_ident = |node: &String| {
+                    node.starts_with(|ch: char| ch.is_alphabetic() || ch == '_')
+                        && node.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+                };
+                if !is_ident(&head) || !tail.iter().all(is_ident) {
+                    return Err(format!("Invalid interpolated name {{{}}}", inner));
+                }
+
+                let reference =
+                    VarReference::new((head, if tail.is_empty() { None } else { Some(tail) }));
+
+                if !current.is_empty() {
+                    parts.push(CommandPart::Text(mem::take(&mut current)));
+                }
+                parts.push(if is_location {
+                    CommandPart::Location(reference)
+                } else {
+                    CommandPart::Value(reference)
+                });
+            }
+            '}' => return Err("Unmatched } in command, use }} for a literal }".to_string()),
+            ch => current.push(ch),
+        }
+    }
+
+    if !current.is_empty() {
+        parts.push(CommandPart::Text(current));
+    }
+    Ok(Command { parts })
 }
 
 #[cfg(test)]
@@ -479,4 +1104,33 @@ mod tests {
 
         assert_eq!(parser.eat(&TokenKind::Eof).unwrap(), &TokenKind::Eof);
     }
+
+    #[test]
+    fn test_parse_interpolation() {
+        assert_eq!(
+            parse_interpolation(r#"tp @s ~ ~{y} ~"#),
+            Ok(Command {
+                parts: vec![
+                    CommandPart::Text("tp @s ~ ~".to_string()),
+                    CommandPart::Value(VarReference::new(("y".to_string(), None))),
+                    CommandPart::Text(" ~".to_string()),
+                ]
+            })
+        );
+        assert_eq!(
+            parse_interpolation(r#"tellraw @a {{"text":"hi"}} {&a::b}"#),
+            Ok(Command {
+                parts: vec![
+                    CommandPart::Text(r#"tellraw @a {"text":"hi"} "#.to_string()),
+                    CommandPart::Location(VarReference::new((
+                        "a".to_string(),
+                        Some(vec!["b".to_string()])
+                    ))),
+                ]
+            })
+        );
+        assert!(parse_interpolation("say {").is_err());
+        assert!(parse_interpolation("say }").is_err());
+        assert!(parse_interpolation("say {1x}").is_err());
+    }
 }
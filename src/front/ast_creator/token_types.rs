@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
     // import
@@ -7,18 +9,51 @@ pub enum TokenKind {
     Ident(String),
 
     // literals
-    LNull,
     LInt(i32),
+    LFloat(f64),
+    LBool(bool),
+    LString(String),
 
     // type keyword
     TVoid,
     TInt,
+    TFloat,
+    TBool,
+    TString,
 
     // definition declaration
     Static,
     Let,
     Struct,
     Fn,
+    Mod,
+
+    // visibility
+    Pub,
+
+    // statements
+    Return,
+    If,
+    Else,
+    While,
+
+    // operators
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Assign,
+    As,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Not,
 
     // misc
     Colon,
@@ -29,9 +64,16 @@ pub enum TokenKind {
     RBrace,
     LParen,
     RParen,
+    LBracket,
+    RBracket,
+
+    Hash,
 
     Arrow,
 
+    // `/// ...`, documenting the definition that follows it
+    DocComment(String),
+
     Eof,
 }
 
@@ -39,10 +81,11 @@ pub enum TokenKind {
 pub enum TokenError {
     InvalidToken(String),
     MultipleDecimals,
-    Unknown,
+    UnterminatedString,
+    InvalidEscape(char),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Span {
     pub lo: usize,
     pub hi: usize,
@@ -53,3 +96,11 @@ pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
 }
+
+// a `// ...` comment, which is skipped by the parser but kept for printing the source again. Doc comments are also
+// kept here, even though they are tokens as well
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String, // including the slashes, without the line break
+    pub span: Span,
+}
@@ -1,3 +1,4 @@
+use crate::front::ast_creator::token_types::Span;
 use crate::modules::ModuleId;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
@@ -6,10 +7,11 @@ use std::fmt::Debug;
 use std::marker::PhantomData;
 
 // Reference<T, R> type idea from https://thume.ca/2019/04/18/writing-a-compiler-in-rust/
-#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Reference<T, R, D> {
     pub raw: T,
     pub resolved: Option<R>,
+    pub span: Option<Span>, // where the name is written in the source, if it was parsed from one
     phantom: PhantomData<D>, // this dummy type is used to make the type unique
 }
 
@@ -18,9 +20,24 @@ impl<T, R, D> Reference<T, R, D> {
         Reference {
             raw,
             resolved: None,
+            span: None,
             phantom: PhantomData,
         }
     }
+
+    pub fn spanned(raw: T, span: Span) -> Reference<T, R, D> {
+        Reference {
+            span: Some(span),
+            ..Reference::new(raw)
+        }
+    }
+}
+
+// the span is not compared, so that parsed references are equal to the ones written by hand
+impl<T: PartialEq, R: PartialEq, D> PartialEq for Reference<T, R, D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw && self.resolved == other.resolved
+    }
 }
 
 impl<T: Debug, R: Debug, D> Debug for Reference<T, R, D> {
@@ -55,7 +72,7 @@ pub type RawNameRoot = String;
 pub type RawNameTailNode = String;
 pub type RawName = (RawNameRoot, Option<Vec<RawNameTailNode>>);
 pub type ItemName = String;
-#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct ResolvedName {
     pub module_id: ModuleId,
     pub item_name: ItemName,
@@ -116,8 +133,39 @@ pub enum Type {
     Struct(TypeReference),
 }
 
+// the type as it is written in the source
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Struct(name) => {
+                write!(f, "{}", name.raw.0)?;
+                for node in name.raw.1.iter().flatten() {
+                    write!(f, "::{}", node)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// who can import a definition from outside of its module
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Visibility {
+    #[default]
+    Private, // only the module it is defined in
+    Package, // `pub(package)`, every module in the same package
+    Public,  // `pub`, every module
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct StaticVarDef {
+    pub doc: Option<String>, // the `///` comments before the definition, one line each
+    pub visibility: Visibility,
     pub name: VarReference,
     pub ty: Type,
 }
@@ -130,18 +178,48 @@ pub struct VarDef {
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct StructDef {
+    pub doc: Option<String>,
+    pub visibility: Visibility,
     pub name: TypeReference,
     pub field_types: HashMap<String, Type>,
 }
 
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Attribute {
+    Load,               // `#[load]`, runs the function when the datapack is loaded
+    Tick,               // `#[tick]`, runs the function every tick
+    Export(String),     // `#[export("namespace:path")]`, gives the function a stable name
+    Test,               // `#[test]`, only compiled when running the tests of the package
+    Inline(InlineHint), // `#[inline(always)]` or `#[inline(never)]`, overrides the inlining heuristic
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum InlineHint {
+    Always,
+    Never,
+}
+
+impl fmt::Display for InlineHint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InlineHint::Always => write!(f, "always"),
+            InlineHint::Never => write!(f, "never"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FnDef {
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub visibility: Visibility,
     pub return_type: Type,
     pub name: FunctionReference,
     pub args: Vec<VarDef>,
     pub body: Module,
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Definition {
     StaticVarDef(StaticVarDef),
@@ -150,17 +228,169 @@ pub enum Definition {
     FnDef(FnDef),
 }
 
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Literal {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    String(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct FnCall {
+    pub name: FunctionReference,
+    pub args: Vec<Expression>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Expression {
+    Literal(Literal),
+    Var(VarReference),
+    Binary(Box<Expression>, BinOp, Box<Expression>),
+    Unary(UnOp, Box<Expression>),
+    Cast(Box<Expression>, Type),
+    FnCall(FnCall),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct VarAssign {
+    pub name: VarReference,
+    pub expr: Expression,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum CommandPart {
+    Text(String),
+    Value(VarReference),    // `{name}`, the value of the variable
+    Location(VarReference), // `{&name}`, where the variable is stored
+}
+
+// a raw command, written as `cmd!("...")`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Command {
+    pub parts: Vec<CommandPart>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum AssertKind {
+    True(Expression),           // `assert!(condition)`
+    Eq(Expression, Expression), // `assert_eq!(left, right)`
+}
+
+// an assertion, which fails the running test if it does not hold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assert {
+    pub kind: AssertKind,
+    pub span: Option<Span>, // from the name to the closing parenthesis, to report where the test failed
+}
+
+// like references, the span is not compared
+impl PartialEq for Assert {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+// `if condition { ... }`, optionally followed by `else { ... }` or `else if ...`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct If {
+    pub condition: Expression,
+    pub body: Module,
+    pub else_body: Option<Box<Statement>>, // either a block or another `if`
+}
+
+// `while condition { ... }`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct While {
+    pub condition: Expression,
+    pub body: Module,
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Statement {
-    VarAssign(String), // TODO
-    FnCall(String),    // TODO
-    Return,
+    VarAssign(VarAssign),
+    FnCall(FnCall),
+    Command(Command),
+    Assert(Assert),
+    Return(Option<Expression>),
+    If(If),
+    While(While),
     Module(Module),
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+impl Statement {
+    // the blocks directly inside of the statement, including the ones of an `else if`
+    pub fn blocks(&self) -> Vec<&Module> {
+        match self {
+            Statement::Module(block) => vec![block],
+            Statement::If(if_statement) => {
+                let mut blocks = vec![&if_statement.body];
+                if let Some(else_body) = &if_statement.else_body {
+                    blocks.extend(else_body.blocks());
+                }
+                blocks
+            }
+            Statement::While(while_statement) => vec![&while_statement.body],
+            _ => vec![],
+        }
+    }
+}
+
+// a module declared inside of a file with `mod name { ... }`, given by its path relative to the file's module
+pub type InlineModule = (ItemPath, Module);
+
+// the raw name of `use a::b::*`, which imports every visible item of the module `a::b`
+pub const GLOB_IMPORT: &str = "*";
+
+/* The items of a module in the order they are written, along with their spans, as the uses, definitions and
+statements are kept apart. This is only used to print the source again.
+ */
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum LayoutItem {
+    Use(usize), // a `use`, and the number of names it imports
+    Definition,
+    Let,          // a `let` with an initial value, which is also an assignment in the statements
+    Statement,    // a statement, which may be a block
+    InlineModule, // a `mod name { ... }`, which is also a `use` of the module
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Module {
     pub uses: Option<Vec<(RawName, FullItemPath)>>,
     pub definitions: Option<Vec<Definition>>,
     pub statements: Vec<Statement>,
+    pub span: Option<Span>, // from the opening to the closing brace, if the module has them
+    pub layout: Vec<(LayoutItem, Span)>,
+}
+
+// like references, the span and the layout are not compared
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        self.uses == other.uses
+            && self.definitions == other.definitions
+            && self.statements == other.statements
+    }
 }
@@ -1,7 +1,15 @@
-use crate::front::ast_types::{FnDef, ResolvedName, StaticVarDef, StructDef, VarDef};
+use crate::front::ast_types::{
+    Definition, FnDef, Module, RawNameRoot, ResolvedName, Statement, StaticVarDef, StructDef,
+    VarDef, Visibility,
+};
+use crate::modules::ModuleId;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+// the top level definitions of a module that can be imported by a glob import, with their visibility
+pub type Exports = Vec<(RawNameRoot, Visibility)>;
+pub type ModuleExports = HashMap<ModuleId, Exports>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DefinitionTable {
     pub static_var_map: HashMap<ResolvedName, StaticVarDef>,
@@ -19,4 +27,98 @@ impl DefinitionTable {
             fn_map: HashMap::new(),
         }
     }
+
+    pub fn contains(&self, name: &ResolvedName) -> bool {
+        self.static_var_map.contains_key(name)
+            || self.var_map.contains_key(name)
+            || self.struct_map.contains_key(name)
+            || self.fn_map.contains_key(name)
+    }
+
+    // finds the resolved name of the definition that was declared with the given raw name
+    pub fn find_by_raw_name(&self, raw_name: &str) -> Option<ResolvedName> {
+        self.static_var_map
+            .iter()
+            .map(|(name, def)| (name, &def.name.raw))
+            .chain(self.var_map.iter().map(|(name, def)| (name, &def.name.raw)))
+            .chain(
+                self.struct_map
+                    .iter()
+                    .map(|(name, def)| (name, &def.name.raw)),
+            )
+            .chain(self.fn_map.iter().map(|(name, def)| (name, &def.name.raw)))
+            .find(|(_, raw)| raw.0 == raw_name)
+            .map(|(name, _)| name.clone())
+    }
+
+    pub fn exports(&self) -> Exports {
+        self.static_var_map
+            .values()
+            .map(|def| (def.name.raw.0.clone(), def.visibility))
+            .chain(
+                self.struct_map
+                    .values()
+                    .map(|def| (def.name.raw.0.clone(), def.visibility)),
+            )
+            .chain(
+                self.fn_map
+                    .values()
+                    .map(|def| (def.name.raw.0.clone(), def.visibility)),
+            )
+            .collect()
+    }
+
+    // the definitions inside of functions, such as arguments and local variables, are only kept in their bodies
+    pub fn find_local(&self, name: &ResolvedName) -> Option<Definition> {
+        self.fn_map
+            .values()
+            .find_map(|fn_def| find_in_function(fn_def, name))
+    }
+
+    // the visibility of an importable definition, given by its raw name
+    pub fn get_visibility(&self, raw_name: &str) -> Option<Visibility> {
+        let name = self.find_by_raw_name(raw_name)?;
+        if let Some(def) = self.static_var_map.get(&name) {
+            Some(def.visibility)
+        } else if let Some(def) = self.struct_map.get(&name) {
+            Some(def.visibility)
+        } else {
+            self.fn_map.get(&name).map(|def| def.visibility)
+        }
+    }
+}
+
+fn find_in_function(fn_def: &FnDef, name: &ResolvedName) -> Option<Definition> {
+    if let Some(arg) = fn_def
+        .args
+        .iter()
+        .find(|arg| arg.name.resolved.as_ref() == Some(name))
+    {
+        return Some(Definition::VarDef(arg.clone()));
+    }
+    find_in_module(&fn_def.body, name)
+}
+
+fn find_in_module(module: &Module, name: &ResolvedName) -> Option<Definition> {
+    for definition in module.definitions.iter().flatten() {
+        let defined = match definition {
+            Definition::StaticVarDef(def) => &def.name.resolved,
+            Definition::VarDef(def) => &def.name.resolved,
+            Definition::StructDef(def) => &def.name.resolved,
+            Definition::FnDef(def) => &def.name.resolved,
+        };
+        if defined.as_ref() == Some(name) {
+            return Some(definition.clone());
+        }
+        if let Definition::FnDef(fn_def) = definition {
+            if let Some(found) = find_in_function(fn_def, name) {
+                return Some(found);
+            }
+        }
+    }
+    module
+        .statements
+        .iter()
+        .flat_map(Statement::blocks)
+        .find_map(|block| find_in_module(block, name))
 }
@@ -0,0 +1,141 @@
+use crate::front::ast_creator::token_types::{Span, TokenError, TokenKind};
+use crate::front::ast_creator::{tokenize, ParseError};
+use crate::front::passes::name_resolution::NameResolutionError;
+use crate::front::FrontError;
+
+// an error in a source file, between the byte offsets lo (inclusive) and hi (exclusive)
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub lo: usize,
+    pub hi: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(span: &Span, message: String) -> Diagnostic {
+        Diagnostic {
+            lo: span.lo,
+            hi: span.hi + 1,
+            message,
+        }
+    }
+}
+
+// the line and column of a byte offset, both starting from 1, with columns counted in characters
+pub fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset.min(src.len())];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+// the diagnostics of an error in the given source file
+pub fn diagnose(src: &str, error: &FrontError) -> Vec<Diagnostic> {
+    match error {
+        FrontError::Token(errors) => errors
+            .iter()
+            .map(|(error, span)| Diagnostic::new(span, token_error_message(error)))
+            .collect(),
+        FrontError::Parse(ParseError::Unexpected(token, message)) => {
+            vec![Diagnostic::new(&token.span, message.clone())]
+        }
+        FrontError::NameResolution(error) => name_resolution_diagnostics(src, error),
+    }
+}
+
+fn token_error_message(error: &TokenError) -> String {
+    match error {
+        TokenError::InvalidToken(token) => format!("Invalid token `{}`", token),
+        TokenError::MultipleDecimals => "Number with multiple decimal points".to_string(),
+        TokenError::UnterminatedString => "Unterminated string".to_string(),
+        TokenError::InvalidEscape(ch) => format!("Invalid escape `\\{}`", ch),
+    }
+}
+
+fn name_resolution_diagnostics(src: &str, error: &NameResolutionError) -> Vec<Diagnostic> {
+    let (names, message): (Vec<&String>, fn(&str) -> String) = match error {
+        NameResolutionError::Redefinition(name) => {
+            (vec![name], |name| format!("`{}` is already defined", name))
+        }
+        NameResolutionError::UnresolvedNames(names) => {
+            let mut names = names.iter().collect::<Vec<_>>();
+            names.sort();
+            (names, |name| format!("Cannot find `{}`", name))
+        }
+        NameResolutionError::UndefinedLookup(name) => {
+            (vec![name], |name| format!("Cannot find `{}`", name))
+        }
+        NameResolutionError::UnresolvedImport(path) => {
+            (path.item_path.last().into_iter().collect(), |name| {
+                format!("Cannot resolve the import of `{}`", name)
+            })
+        }
+    };
+
+    names
+        .into_iter()
+        .map(|name| diagnose_name(src, name, message(name)))
+        .collect()
+}
+
+// the AST does not keep spans, so names are found by their first use in the source
+pub fn diagnose_name(src: &str, name: &str, message: String) -> Diagnostic {
+    let span = tokenize(src)
+        .into_iter()
+        .find(|token| matches!(&token.kind, TokenKind::Ident(ident) if ident == name))
+        .map_or(Span { lo: 0, hi: 0 }, |token| token.span);
+    Diagnostic::new(&span, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front::ast_types::FullItemPath;
+    use crate::front::definition_table::ModuleExports;
+    use crate::front::{parse_ast, resolve_module};
+
+    fn check(src: &str) -> Vec<Diagnostic> {
+        let result = parse_ast("package_a", src).and_then(|(module, _)| {
+            resolve_module(
+                FullItemPath::new("package_a".to_string(), vec!["main".to_string()]),
+                module,
+                &ModuleExports::new(),
+            )
+        });
+        match result {
+            Ok(_) => vec![],
+            Err(error) => diagnose(src, &error),
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        assert_eq!(check("fn main() {}"), vec![]);
+        assert_eq!(
+            check("fn main() { \"abc }"),
+            vec![Diagnostic {
+                lo: 12,
+                hi: 18,
+                message: "Unterminated string".to_string()
+            }]
+        );
+        assert_eq!(
+            check("fn main() {} }"),
+            vec![Diagnostic {
+                lo: 13,
+                hi: 14,
+                message: "Expected Eof, found RBrace".to_string()
+            }]
+        );
+        assert_eq!(
+            check("fn main() { missing(); }"),
+            vec![Diagnostic {
+                lo: 12,
+                hi: 19,
+                message: "Cannot find `missing`".to_string()
+            }]
+        );
+    }
+}
@@ -0,0 +1,573 @@
+use crate::front::ast_creator::create_source_file;
+use crate::front::ast_creator::precedence;
+use crate::front::ast_creator::token_types::{Comment, Span, Token, TokenKind};
+use crate::front::ast_types::{
+    Assert, AssertKind, Attribute, BinOp, CommandPart, Definition, Expression, FnCall,
+    FullItemPath, If, InlineModule, ItemPath, LayoutItem, Literal, Module, RawName, Statement,
+    Type, UnOp, Visibility, GLOB_IMPORT,
+};
+use crate::front::FrontResult;
+
+const INDENT: &str = "    ";
+
+// `root::` paths are parsed into the name of the package, so naming the package `root` prints them unchanged
+const PACKAGE_NAME: &str = "root";
+
+/* Prints the source of a file again from its AST, in the canonical style. Comments are kept before the item that
+follows them, or at the end of the line they were on, and single blank lines between items are kept.
+ */
+pub fn format_source(src: &str) -> FrontResult<String> {
+    let source_file = create_source_file(PACKAGE_NAME, src)?;
+
+    let mut printer = Printer {
+        src,
+        tokens: source_file.tokens,
+        comments: source_file.comments,
+        next_comment: 0,
+        inline_modules: source_file.inline_modules,
+        out: String::new(),
+        depth: 0,
+        last_end: None,
+        join_line: false,
+    };
+    printer.print_items(&source_file.module, &vec![]);
+    printer.print_comments_before(src.len());
+    Ok(printer.out)
+}
+
+struct Printer<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    comments: Vec<Comment>,
+    next_comment: usize, // the first comment that was not printed yet
+    inline_modules: Vec<InlineModule>,
+
+    out: String,
+    depth: usize,
+    last_end: Option<usize>, // where the last printed item or comment ends in the source
+    join_line: bool,         // whether the next line continues the last one, like `} else {`
+}
+
+impl Printer<'_> {
+    fn line(&mut self, text: &str) {
+        if self.join_line {
+            self.out.pop();
+            self.out.push(' ');
+            self.join_line = false;
+        } else {
+            for _ in 0..self.depth {
+                self.out.push_str(INDENT);
+            }
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn blank_line_before(&mut self, position: usize) {
+        let Some(end) = self.last_end else {
+            return;
+        };
+        let between = self.src.get(end + 1..position).unwrap_or_default();
+        if between.matches('\n').count() > 1 && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn has_comments_before(&self, position: usize) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.span.lo < position)
+    }
+
+    fn print_comments_before(&mut self, position: usize) {
+        while self.has_comments_before(position) {
+            let comment = self.comments[self.next_comment].clone();
+            self.next_comment += 1;
+
+            // a comment after code on the same line stays there
+            let is_trailing = self.last_end.is_some_and(|end| {
+                end < comment.span.lo && !self.src[end + 1..comment.span.lo].contains('\n')
+            });
+            if is_trailing && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push(' ');
+                self.out.push_str(&comment.text);
+                self.out.push('\n');
+            } else {
+                self.blank_line_before(comment.span.lo);
+                self.line(&comment.text);
+            }
+            self.last_end = Some(comment.span.hi);
+        }
+    }
+
+    fn print_items(&mut self, module: &Module, path: &ItemPath) {
+        let mut uses = module.uses.iter().flatten();
+        let mut definitions = module.definitions.iter().flatten();
+        let mut statements = module.statements.iter();
+        let mut children = self
+            .inline_modules
+            .iter()
+            .filter(|(child_path, _)| {
+                child_path.len() == path.len() + 1 && child_path.starts_with(path)
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        for (item, span) in module.layout.iter() {
+            self.print_comments_before(span.lo);
+            self.blank_line_before(span.lo);
+            match item {
+                LayoutItem::Use(count) => {
+                    let uses = uses.by_ref().take(*count).collect::<Vec<_>>();
+                    self.line(&format_use(&uses));
+                }
+                LayoutItem::Definition => {
+                    self.print_definition(definitions.next().unwrap(), *span, path)
+                }
+                LayoutItem::Let => {
+                    let (Some(Definition::VarDef(def)), Some(Statement::VarAssign(assign))) =
+                        (definitions.next(), statements.next())
+                    else {
+                        unreachable!("A let is a definition and an assignment");
+                    };
+                    self.line(&format!(
+                        "let {}: {} = {};",
+                        def.name.raw.0,
+                        def.ty,
+                        format_expression(&assign.expr)
+                    ));
+                }
+                LayoutItem::Statement => self.print_statement(statements.next().unwrap(), path),
+                LayoutItem::InlineModule => {
+                    // the module is also imported by its parent
+                    uses.next();
+                    let (child_path, child) = children.next().unwrap();
+                    let name = child_path.last().unwrap().clone();
+                    self.print_block(&format!("mod {} ", name), &child, &child_path);
+                }
+            }
+            self.last_end = Some(span.hi);
+        }
+    }
+
+    // the header is printed before the braces
+    fn print_block(&mut self, header: &str, module: &Module, path: &ItemPath) {
+        let span = module.span.unwrap();
+        if module.layout.is_empty() && !self.has_comments_before(span.hi) {
+            self.line(&format!("{}{{}}", header));
+            return;
+        }
+
+        self.line(&format!("{}{{", header));
+        self.depth += 1;
+        self.last_end = Some(span.lo);
+        self.print_items(module, path);
+        self.print_comments_before(span.hi);
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn print_definition(&mut self, definition: &Definition, span: Span, path: &ItemPath) {
+        match definition {
+            Definition::StaticVarDef(def) => self.line(&format!(
+                "{}static {}: {};",
+                format_visibility(def.visibility),
+                def.name.raw.0,
+                def.ty
+            )),
+            Definition::VarDef(def) => self.line(&format!("let {}: {};", def.name.raw.0, def.ty)),
+            Definition::StructDef(def) => {
+                let header = format!(
+                    "{}struct {} ",
+                    format_visibility(def.visibility),
+                    def.name.raw.0
+                );
+                let (open, fields) = self.struct_fields(span);
+                if fields.is_empty() && !self.has_comments_before(span.hi) {
+                    self.line(&format!("{}{{}}", header));
+                    return;
+                }
+
+                self.line(&format!("{}{{", header));
+                self.depth += 1;
+                self.last_end = Some(open);
+                for (field, position) in fields {
+                    self.print_comments_before(position);
+                    self.blank_line_before(position);
+                    self.line(&format!("{}: {},", field, def.field_types[&field]));
+                    self.last_end = Some(position);
+                }
+                self.print_comments_before(span.hi);
+                self.depth -= 1;
+                self.line("}");
+            }
+            Definition::FnDef(def) => {
+                for attribute in def.attributes.iter() {
+                    self.line(&match attribute {
+                        Attribute::Load => "#[load]".to_string(),
+                        Attribute::Tick => "#[tick]".to_string(),
+                        Attribute::Test => "#[test]".to_string(),
+                        Attribute::Export(location) => {
+                            format!("#[export({})]", format_string(location))
+                        }
+                        Attribute::Inline(hint) => format!("#[inline({})]", hint),
+                    });
+                }
+                let args = def
+                    .args
+                    .iter()
+                    .map(|arg| format!("{}: {}", arg.name.raw.0, arg.ty))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let return_type = match &def.return_type {
+                    Type::Void => String::new(),
+                    ty => format!(" -> {}", ty),
+                };
+                let header = format!(
+                    "{}fn {}({}){} ",
+                    format_visibility(def.visibility),
+                    def.name.raw.0,
+                    args,
+                    return_type
+                );
+                self.print_block(&header, &def.body, path);
+            }
+        }
+    }
+
+    // where the struct's body opens, and its fields in the order they are written
+    fn struct_fields(&self, span: Span) -> (usize, Vec<(String, usize)>) {
+        let tokens = self
+            .tokens
+            .iter()
+            .filter(|token| span.lo <= token.span.lo && token.span.hi <= span.hi)
+            .collect::<Vec<_>>();
+        let open = tokens
+            .iter()
+            .position(|token| token.kind == TokenKind::LBrace)
+            .unwrap();
+
+        // field types never contain braces, so every `name:` inside of them is a field
+        let fields = tokens[open..]
+            .windows(2)
+            .filter_map(|pair| match (&pair[0].kind, &pair[1].kind) {
+                (TokenKind::Ident(name), TokenKind::Colon) => Some((name.clone(), pair[0].span.lo)),
+                _ => None,
+            })
+            .collect();
+        (tokens[open].span.lo, fields)
+    }
+
+    fn print_statement(&mut self, statement: &Statement, path: &ItemPath) {
+        match statement {
+            Statement::VarAssign(assign) => self.line(&format!(
+                "{} = {};",
+                format_name(&assign.name.raw),
+                format_expression(&assign.expr)
+            )),
+            Statement::FnCall(call) => self.line(&format!("{};", format_call(call))),
+            Statement::Command(command) => {
+                let text = command
+                    .parts
+                    .iter()
+                    .map(|part| match part {
+                        CommandPart::Text(text) => text.replace('{', "{{").replace('}', "}}"),
+                        CommandPart::Value(name) => format!("{{{}}}", format_name(&name.raw)),
+                        CommandPart::Location(name) => format!("{{&{}}}", format_name(&name.raw)),
+                    })
+                    .collect::<String>();
+                self.line(&format!("cmd!({});", format_string(&text)));
+            }
+            Statement::Assert(assert) => self.line(&format!("{};", format_assert(assert))),
+            Statement::Return(None) => self.line("return;"),
+            Statement::Return(Some(expr)) => {
+                self.line(&format!("return {};", format_expression(expr)))
+            }
+            Statement::If(if_statement) => self.print_if(if_statement, path),
+            Statement::While(while_statement) => self.print_block(
+                &format!("while {} ", format_expression(&while_statement.condition)),
+                &while_statement.body,
+                path,
+            ),
+            Statement::Module(block) => self.print_block("", block, path),
+        }
+    }
+
+    fn print_if(&mut self, if_statement: &If, path: &ItemPath) {
+        self.print_block(
+            &format!("if {} ", format_expression(&if_statement.condition)),
+            &if_statement.body,
+            path,
+        );
+        match if_statement.else_body.as_deref() {
+            Some(Statement::If(else_if)) => {
+                self.join_line = true;
+                self.line("else");
+                self.join_line = true;
+                self.print_if(else_if, path);
+            }
+            Some(Statement::Module(block)) => {
+                self.join_line = true;
+                self.print_block("else ", block, path);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn format_visibility(visibility: Visibility) -> &'static str {
+    match visibility {
+        Visibility::Private => "",
+        Visibility::Package => "pub(package) ",
+        Visibility::Public => "pub ",
+    }
+}
+
+fn format_name(raw_name: &RawName) -> String {
+    let mut name = raw_name.0.clone();
+    for node in raw_name.1.iter().flatten() {
+        name.push_str("::");
+        name.push_str(node);
+    }
+    name
+}
+
+fn format_string(text: &str) -> String {
+    let mut string = String::from('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => string.push_str("\\\""),
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            '\t' => string.push_str("\\t"),
+            ch => string.push(ch),
+        }
+    }
+    string.push('"');
+    string
+}
+
+// the names imported by one `use`, grouped again by the modules they are in
+fn format_use(uses: &[&(RawName, FullItemPath)]) -> String {
+    let paths = uses
+        .iter()
+        .map(|(raw_name, full_item_path)| {
+            let mut path = full_item_path.item_path.clone();
+            if raw_name.0 == GLOB_IMPORT {
+                path.push(GLOB_IMPORT.to_string());
+            } else if path.last() != Some(&raw_name.0) {
+                let last = path.pop().unwrap();
+                path.push(format!("{} as {}", last, raw_name.0));
+            }
+            path
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "use {}::{};",
+        uses[0].1.package_name,
+        format_use_tree(&paths)
+    )
+}
+
+fn format_use_tree(paths: &[ItemPath]) -> String {
+    if paths.len() == 1 {
+        return paths[0].join("::");
+    }
+
+    // the modules that every path goes through
+    let common = (0..paths[0].len())
+        .take_while(|&i| {
+            paths
+                .iter()
+                .all(|path| i + 1 < path.len() && path[i] == paths[0][i])
+        })
+        .count();
+
+    // adjacent paths continuing through the same module are grouped, so that the order of the names is kept
+    let mut groups: Vec<Vec<ItemPath>> = vec![];
+    for path in paths {
+        let rest = path[common..].to_vec();
+        match groups.last_mut() {
+            Some(group) if rest.len() > 1 && group[0].len() > 1 && group[0][0] == rest[0] => {
+                group.push(rest)
+            }
+            _ => groups.push(vec![rest]),
+        }
+    }
+
+    let prefix = paths[0][..common]
+        .iter()
+        .map(|node| format!("{}::", node))
+        .collect::<String>();
+    let trees = groups
+        .iter()
+        .map(|group| format_use_tree(group))
+        .collect::<Vec<_>>();
+    format!("{}{{{}}}", prefix, trees.join(", "))
+}
+
+fn format_call(call: &FnCall) -> String {
+    let args = call
+        .args
+        .iter()
+        .map(format_expression)
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({})", format_name(&call.name.raw), args)
+}
+
+// `assert!(condition)` or `assert_eq!(left, right)`, without the semicolon
+pub fn format_assert(assert: &Assert) -> String {
+    match &assert.kind {
+        AssertKind::True(condition) => format!("assert!({})", format_expression(condition)),
+        AssertKind::Eq(lhs, rhs) => format!(
+            "assert_eq!({}, {})",
+            format_expression(lhs),
+            format_expression(rhs)
+        ),
+    }
+}
+
+fn format_expression(expr: &Expression) -> String {
+    match expr {
+        Expression::Literal(Literal::Int(value)) => value.to_string(),
+        Expression::Literal(Literal::Float(value)) => {
+            let value = value.to_string();
+            if value.contains('.') {
+                value
+            } else {
+                format!("{}.0", value)
+            }
+        }
+        Expression::Literal(Literal::Bool(value)) => value.to_string(),
+        Expression::Literal(Literal::String(value)) => format_string(value),
+        Expression::Var(name) => format_name(&name.raw),
+        Expression::Binary(lhs, op, rhs) => {
+            // binary operators are left associative
+            let lhs = format_operand(lhs, precedence(*op), false);
+            let rhs = format_operand(rhs, precedence(*op), true);
+            format!("{} {} {}", lhs, format_binary_op(*op), rhs)
+        }
+        Expression::Unary(op, operand) => {
+            let op = match op {
+                UnOp::Neg => "-",
+                UnOp::Not => "!",
+            };
+            match **operand {
+                Expression::Binary(..) | Expression::Cast(..) => {
+                    format!("{}({})", op, format_expression(operand))
+                }
+                _ => format!("{}{}", op, format_expression(operand)),
+            }
+        }
+        Expression::Cast(operand, ty) => match **operand {
+            Expression::Binary(..) => format!("({}) as {}", format_expression(operand), ty),
+            _ => format!("{} as {}", format_expression(operand), ty),
+        },
+        Expression::FnCall(call) => format_call(call),
+    }
+}
+
+// an operand of a binary operator, in parentheses if it binds looser than the operator
+fn format_operand(expr: &Expression, parent_precedence: u8, is_rhs: bool) -> String {
+    match expr {
+        Expression::Binary(_, op, _)
+            if precedence(*op) < parent_precedence
+                || (is_rhs && precedence(*op) == parent_precedence) =>
+        {
+            format!("({})", format_expression(expr))
+        }
+        _ => format_expression(expr),
+    }
+}
+
+fn format_binary_op(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Le => "<=",
+        BinOp::Gt => ">",
+        BinOp::Ge => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_source() {
+        let src = r#"// header
+
+use root::util::{a,b as c};
+use package_b::{path::x, path::y, z};
+  static   count : int ; // counter
+
+
+pub fn main( arg: int )->int {
+    // before
+    let x: int = -(1 + 2) * arg as float - (3 - 4);
+    x = fn_a(x,1);cmd!("say {x} {{literal}}");
+    { let y: bool = !(true && false); }
+    while x>0 { x = x-1; }
+    if x==0 { return 1; } else if x<0 {} else { x = 2; }
+    return x; // trailing
+    // at the end
+}
+struct s { b: int, // second
+a: s }
+mod inner { fn f() {} #[test] fn t() { assert_eq!(1+1,2); assert!( true ); } }
+"#;
+        let expected = r#"// header
+
+use root::util::{a, b as c};
+use package_b::{path::{x, y}, z};
+static count: int; // counter
+
+pub fn main(arg: int) -> int {
+    // before
+    let x: int = -(1 + 2) * arg as float - (3 - 4);
+    x = fn_a(x, 1);
+    cmd!("say {x} {{literal}}");
+    {
+        let y: bool = !(true && false);
+    }
+    while x > 0 {
+        x = x - 1;
+    }
+    if x == 0 {
+        return 1;
+    } else if x < 0 {} else {
+        x = 2;
+    }
+    return x; // trailing
+    // at the end
+}
+struct s {
+    b: int, // second
+    a: s,
+}
+mod inner {
+    fn f() {}
+    #[test]
+    fn t() {
+        assert_eq!(1 + 1, 2);
+        assert!(true);
+    }
+}
+"#;
+        let formatted = format_source(src).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), expected);
+    }
+}
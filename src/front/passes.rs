@@ -1,4 +1,5 @@
 pub mod collect_definitions;
 pub mod collect_dependencies;
+pub mod collect_symbols;
 pub mod name_resolution;
 mod visitor;
@@ -30,5 +30,5 @@ pub fn collect_definitions(module: &mut Module) -> DefinitionTable {
         }
     }
 
-    return definition_table;
+    definition_table
 }
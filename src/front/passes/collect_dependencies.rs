@@ -3,12 +3,21 @@ mod visitor;
 use crate::front::ast_types::Module;
 use crate::front::passes::collect_dependencies::visitor::DependencyVisitor;
 use crate::front::passes::visitor::Visitable;
-use crate::modules::{ModuleDependencies, ModuleId};
+use crate::modules::{ModuleDependencies, ModuleId, ModuleImports};
 
-pub fn collect_dependencies(module_id: ModuleId, module: &mut Module) -> ModuleDependencies {
+// collects the modules this module depends on, and every item it uses from another module
+pub fn collect_dependencies(
+    module_id: ModuleId,
+    module: &mut Module,
+) -> (ModuleDependencies, ModuleImports) {
     let mut dependencies = ModuleDependencies::new();
+    let mut imports = ModuleImports::new();
     module
-        .visit(&mut DependencyVisitor::new(module_id, &mut dependencies))
+        .visit(&mut DependencyVisitor::new(
+            module_id,
+            &mut dependencies,
+            &mut imports,
+        ))
         .unwrap();
-    dependencies
+    (dependencies, imports)
 }
@@ -1,41 +1,69 @@
+use crate::front::ast_types::{ResolvedName, GLOB_IMPORT};
 use crate::front::passes::visitor::{ASTNodeEnum, GenericVisitApplyResult, Visitor};
-use crate::modules::{ModuleDependencies, ModuleId};
+use crate::modules::{module_id_from_local, ModuleDependencies, ModuleId, ModuleImports};
 
 #[derive(Debug)]
-pub enum DependencyError {
-    Unknown,
-}
+pub enum DependencyError {} // collecting dependencies cannot fail
 
 pub type ResolveResult<T> = GenericVisitApplyResult<T, DependencyError>;
 
 pub struct DependencyVisitor<'a> {
     module_id: ModuleId,
     dependencies: &'a mut ModuleDependencies,
+    imports: &'a mut ModuleImports,
 }
 
 impl DependencyVisitor<'_> {
-    pub fn new(module_id: ModuleId, dependencies: &mut ModuleDependencies) -> DependencyVisitor {
+    pub fn new<'a>(
+        module_id: ModuleId,
+        dependencies: &'a mut ModuleDependencies,
+        imports: &'a mut ModuleImports,
+    ) -> DependencyVisitor<'a> {
         DependencyVisitor {
             module_id,
             dependencies,
+            imports,
         }
     }
 }
 
 impl Visitor<(), DependencyError> for DependencyVisitor<'_> {
     fn apply(&mut self, ast_node: &mut ASTNodeEnum) -> ResolveResult<()> {
-        let ref_module_id = match ast_node {
-            ASTNodeEnum::VarReference(name) => Some(&name.resolved.as_ref().unwrap().module_id),
-            ASTNodeEnum::TypeReference(name) => Some(&name.resolved.as_ref().unwrap().module_id),
-            ASTNodeEnum::FunctionReference(name) => {
-                Some(&name.resolved.as_ref().unwrap().module_id)
+        let ref_name = match ast_node {
+            ASTNodeEnum::VarReference(name) => name.resolved.as_ref(),
+            ASTNodeEnum::TypeReference(name) => name.resolved.as_ref(),
+            ASTNodeEnum::FunctionReference(name) => name.resolved.as_ref(),
+            ASTNodeEnum::Module(module) => {
+                // imports are recorded even if they are never used
+                for (raw_name, full_item_path) in module.uses.iter().flatten() {
+                    if raw_name.0 == GLOB_IMPORT {
+                        // the names brought in by a glob depend on the definitions of the whole module
+                        let module_id = module_id_from_local(
+                            &full_item_path.package_name,
+                            &full_item_path.item_path,
+                        );
+                        if self.module_id != module_id {
+                            self.dependencies.insert(module_id);
+                        }
+                    } else if let Some((item_name, path)) = full_item_path.item_path.split_last() {
+                        let name = ResolvedName::new(
+                            module_id_from_local(&full_item_path.package_name, path),
+                            item_name.clone(),
+                        );
+                        if self.module_id != name.module_id {
+                            self.imports.insert(name);
+                        }
+                    }
+                }
+                None
             }
             _ => None,
         };
 
-        if let Some(module_id) = ref_module_id {
-            if &self.module_id != module_id {
-                self.dependencies.insert(module_id.clone());
+        if let Some(name) = ref_name {
+            if self.module_id != name.module_id {
+                self.dependencies.insert(name.module_id.clone());
+                self.imports.insert(name.clone());
             }
         }
 
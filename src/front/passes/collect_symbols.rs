@@ -0,0 +1,27 @@
+mod visitor;
+
+use crate::front::ast_creator::token_types::Span;
+use crate::front::ast_types::{Module, ResolvedName};
+use crate::front::passes::collect_symbols::visitor::SymbolVisitor;
+use crate::front::passes::visitor::Visitable;
+use serde::{Deserialize, Serialize};
+
+// a resolved name written in the source
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Symbol {
+    pub span: Span,
+    pub name: ResolvedName,
+    pub is_definition: bool, // whether this is where the item is defined, instead of a use of it
+}
+
+pub type Symbols = Vec<Symbol>;
+
+// collects every resolved name of the module that has a span, ordered by position
+pub fn collect_symbols(module: &mut Module) -> Symbols {
+    let mut visitor = SymbolVisitor::new();
+    module.visit(&mut visitor).unwrap();
+
+    let mut symbols = visitor.symbols;
+    symbols.sort_by_key(|symbol| symbol.span.lo);
+    symbols
+}
@@ -0,0 +1,66 @@
+use crate::front::ast_creator::token_types::Span;
+use crate::front::passes::collect_symbols::Symbol;
+use crate::front::passes::visitor::{ASTNodeEnum, GenericVisitApplyResult, Visitor};
+use std::collections::HashSet;
+
+#[derive(Debug)]
+pub enum SymbolError {} // collecting symbols cannot fail
+
+pub type SymbolResult<T> = GenericVisitApplyResult<T, SymbolError>;
+
+pub struct SymbolVisitor {
+    pub symbols: Vec<Symbol>,
+    definition_spans: HashSet<(usize, usize)>, // the names of definitions, which are visited after the definition itself
+}
+
+impl SymbolVisitor {
+    pub fn new() -> SymbolVisitor {
+        SymbolVisitor {
+            symbols: vec![],
+            definition_spans: HashSet::new(),
+        }
+    }
+
+    fn add_definition(&mut self, span: Option<Span>) {
+        if let Some(span) = span {
+            self.definition_spans.insert((span.lo, span.hi));
+        }
+    }
+}
+
+impl Visitor<(), SymbolError> for SymbolVisitor {
+    fn apply(&mut self, ast_node: &mut ASTNodeEnum) -> SymbolResult<()> {
+        let (span, name) = match ast_node {
+            ASTNodeEnum::StaticVarDef(def) => {
+                self.add_definition(def.name.span);
+                return Ok((true, None));
+            }
+            ASTNodeEnum::VarDef(def) => {
+                self.add_definition(def.name.span);
+                return Ok((true, None));
+            }
+            ASTNodeEnum::StructDef(def) => {
+                self.add_definition(def.name.span);
+                return Ok((true, None));
+            }
+            ASTNodeEnum::FnDef(def) => {
+                self.add_definition(def.name.span);
+                return Ok((true, None));
+            }
+            ASTNodeEnum::VarReference(name) => (name.span, &name.resolved),
+            ASTNodeEnum::TypeReference(name) => (name.span, &name.resolved),
+            ASTNodeEnum::FunctionReference(name) => (name.span, &name.resolved),
+            _ => return Ok((true, None)),
+        };
+
+        if let (Some(span), Some(name)) = (span, name) {
+            self.symbols.push(Symbol {
+                span,
+                name: name.clone(),
+                is_definition: self.definition_spans.contains(&(span.lo, span.hi)),
+            });
+        }
+
+        Ok((true, None))
+    }
+}
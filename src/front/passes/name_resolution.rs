@@ -1,7 +1,8 @@
 mod scope_table;
 mod visitor;
 
-use crate::front::ast_types::{FullItemPath, Module, RawNameRoot};
+use crate::front::ast_types::{FullItemPath, Module, RawNameRoot, ResolvedName};
+use crate::front::definition_table::ModuleExports;
 use crate::front::passes::name_resolution::scope_table::ScopeTable;
 use crate::front::passes::visitor::Visitable;
 
@@ -9,10 +10,10 @@ use std::collections::HashSet;
 
 #[derive(Debug, PartialEq)]
 pub enum NameResolutionError {
-    UndefinedVariable(String),
     Redefinition(RawNameRoot),
     UnresolvedNames(HashSet<RawNameRoot>),
     UndefinedLookup(RawNameRoot),
+    UnresolvedImport(FullItemPath), // a relative path leaving the package, or a glob import of an unknown module
 }
 
 type NameResolutionResult<T> = Result<T, NameResolutionError>;
@@ -24,18 +25,32 @@ type NameResolutionResult<T> = Result<T, NameResolutionError>;
  */
 pub fn resolve_names(
     module_path: FullItemPath,
-    module: &mut Module, // the ASTFile containing the definitions
+    module: &mut Module,            // the ASTFile containing the definitions
+    module_exports: &ModuleExports, // the definitions of other modules, for glob imports
 ) -> NameResolutionResult<()> {
-    let mut scope_table = ScopeTable::new(module_path);
+    let mut scope_table = ScopeTable::new(module_path, module_exports);
     module.visit(&mut scope_table)?;
 
     Ok(())
 }
 
+// resolves the module as far as possible and returns the names visible at the offset of its source
+pub fn query_visible_names(
+    module_path: FullItemPath,
+    module: &mut Module,
+    module_exports: &ModuleExports,
+    offset: usize,
+) -> NameResolutionResult<Vec<(RawNameRoot, ResolvedName)>> {
+    let mut scope_table = ScopeTable::new_query(module_path, module_exports, offset);
+    module.visit(&mut scope_table)?;
+
+    Ok(scope_table.visible_names())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::front::ast_creator::create_ast;
-    use crate::front::ast_types::{Definition, ResolvedName, Type};
+    use crate::front::ast_types::{Definition, ResolvedName, Statement, Type, Visibility};
     use crate::modules::ModuleId;
 
     use super::*;
@@ -51,7 +66,7 @@ mod tests {
 
         let module_path =
             FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
-        let err = resolve_names(module_path, &mut module);
+        let err = resolve_names(module_path, &mut module, &ModuleExports::new());
 
         assert_eq!(
             err,
@@ -77,7 +92,7 @@ mod tests {
 
         let module_path =
             FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
-        let err = resolve_names(module_path, &mut module);
+        let err = resolve_names(module_path, &mut module, &ModuleExports::new());
 
         assert_eq!(
             err,
@@ -100,7 +115,7 @@ mod tests {
 
         let module_path =
             FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
-        let err = resolve_names(module_path, &mut module);
+        let err = resolve_names(module_path, &mut module, &ModuleExports::new());
 
         assert_eq!(
             err,
@@ -126,7 +141,7 @@ mod tests {
 
         let module_path =
             FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
-        resolve_names(module_path, &mut module).unwrap();
+        resolve_names(module_path, &mut module, &ModuleExports::new()).unwrap();
 
         let definitions = module.definitions.unwrap();
 
@@ -192,7 +207,7 @@ mod tests {
 
         let module_path =
             FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
-        let err = resolve_names(module_path, &mut module);
+        let err = resolve_names(module_path, &mut module, &ModuleExports::new());
 
         assert_eq!(
             err,
@@ -218,7 +233,7 @@ mod tests {
 
         let module_path =
             FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
-        let err = resolve_names(module_path, &mut module);
+        let err = resolve_names(module_path, &mut module, &ModuleExports::new());
 
         assert_eq!(
             err,
@@ -246,7 +261,7 @@ mod tests {
 
         let module_path =
             FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
-        resolve_names(module_path, &mut module).unwrap();
+        resolve_names(module_path, &mut module, &ModuleExports::new()).unwrap();
 
         let definitions = module.definitions.unwrap();
 
@@ -291,4 +306,203 @@ mod tests {
             _ => panic!("Expected FunctionDef"),
         }
     }
+
+    #[test]
+    fn test_command_interpolation_name_resolution() {
+        let current_package = "package_a";
+        let src = r#"
+        static y: int;
+        fn fn_a() {
+            cmd!("tp @s ~ ~{y} ~");
+            cmd!("say {z}");
+        }
+        "#;
+        let mut module = create_ast(current_package, src);
+
+        let module_path =
+            FullItemPath::new(current_package.to_string(), vec!["module_a".to_string()]);
+        let err = resolve_names(module_path, &mut module, &ModuleExports::new());
+
+        assert_eq!(
+            err,
+            Err(NameResolutionError::UndefinedLookup(RawNameRoot::from("z")))
+        );
+    }
+
+    // resolves the module and returns the functions called by its first function
+    fn resolve_calls(
+        src: &str,
+        module_exports: &ModuleExports,
+    ) -> NameResolutionResult<Vec<ResolvedName>> {
+        let mut module = create_ast("package_a", src);
+        let module_path = FullItemPath::new(
+            "package_a".to_string(),
+            vec!["dir".to_string(), "module_a".to_string()],
+        );
+        resolve_names(module_path, &mut module, module_exports)?;
+
+        let Some(Definition::FnDef(fn_def)) = module.definitions.unwrap().pop() else {
+            panic!("Expected FnDef");
+        };
+        Ok(fn_def
+            .body
+            .statements
+            .into_iter()
+            .map(|statement| match statement {
+                Statement::FnCall(call) => call.name.resolved.unwrap(),
+                _ => panic!("Expected FnCall"),
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_use_aliases_and_relative_paths() {
+        let src = r#"
+        use package_b::{util::{a, b as c}, d};
+        use self::e;
+        use super::f;
+        use super::super::g;
+        fn fn_a() {
+            a();
+            c();
+            d();
+            e();
+            f();
+            g();
+        }
+        "#;
+        let name = |module_id: &str, item_name: &str| {
+            ResolvedName::new(module_id.to_string(), item_name.to_string())
+        };
+
+        assert_eq!(
+            resolve_calls(src, &ModuleExports::new()),
+            Ok(vec![
+                name("package_b::util", "a"),
+                name("package_b::util", "b"),
+                name("package_b", "d"),
+                name("package_a::dir::module_a", "e"),
+                name("package_a::dir", "f"),
+                name("package_a", "g"),
+            ])
+        );
+
+        assert_eq!(
+            resolve_calls(
+                "use super::super::super::x; fn fn_a() {}",
+                &ModuleExports::new()
+            ),
+            Err(NameResolutionError::UnresolvedImport(FullItemPath::new(
+                "super".to_string(),
+                vec!["super".to_string(), "super".to_string(), "x".to_string()]
+            )))
+        );
+    }
+
+    #[test]
+    fn test_glob_import() {
+        let module_exports = ModuleExports::from([(
+            "package_b::util".to_string(),
+            vec![
+                ("a".to_string(), Visibility::Public),
+                ("b".to_string(), Visibility::Public),
+                ("hidden".to_string(), Visibility::Private),
+                ("internal".to_string(), Visibility::Package),
+            ],
+        )]);
+
+        // explicit imports and local definitions take precedence over glob imports
+        let src = r#"
+        use package_b::util::*;
+        use package_c::b;
+        fn fn_a() {
+            a();
+            b();
+        }
+        "#;
+        assert_eq!(
+            resolve_calls(src, &module_exports),
+            Ok(vec![
+                ResolvedName::new("package_b::util".to_string(), "a".to_string()),
+                ResolvedName::new("package_c".to_string(), "b".to_string()),
+            ])
+        );
+        assert_eq!(
+            resolve_calls(
+                "use package_b::util::*; fn a() {} fn fn_a() { a(); }",
+                &module_exports
+            ),
+            Ok(vec![ResolvedName::new(
+                "package_a::dir::module_a".to_string(),
+                "0:0:a".to_string()
+            )])
+        );
+
+        // items that are not visible are not imported
+        assert_eq!(
+            resolve_calls(
+                "use package_b::util::*; fn fn_a() { hidden(); }",
+                &module_exports
+            ),
+            Err(NameResolutionError::UndefinedLookup(RawNameRoot::from(
+                "hidden"
+            )))
+        );
+        assert_eq!(
+            resolve_calls(
+                "use package_b::util::*; fn fn_a() { internal(); }",
+                &module_exports
+            ),
+            Err(NameResolutionError::UndefinedLookup(RawNameRoot::from(
+                "internal"
+            )))
+        );
+
+        assert_eq!(
+            resolve_calls("use package_b::missing::*; fn fn_a() {}", &module_exports),
+            Err(NameResolutionError::UnresolvedImport(FullItemPath::new(
+                "package_b".to_string(),
+                vec!["missing".to_string()]
+            )))
+        );
+    }
+
+    #[test]
+    fn test_query_visible_names() {
+        let src = r#"
+        use package_b::util::helper;
+        static count: int;
+        fn fn_a(arg: int) {
+            let local: int = missing;
+            {
+                let inner: int = 0;
+            }
+        }
+        fn fn_b() {}
+        "#;
+        let mut module = create_ast("package_a", src);
+        let module_path = FullItemPath::new("package_a".to_string(), vec!["main".to_string()]);
+
+        let names = |module: &mut Module, offset: usize| {
+            query_visible_names(module_path.clone(), module, &ModuleExports::new(), offset)
+                .unwrap()
+                .into_iter()
+                .map(|(raw_name, _)| raw_name)
+                .collect::<Vec<_>>()
+        };
+
+        // unresolved names are not errors, and inner scopes are not visible from outside
+        assert_eq!(
+            names(&mut module.clone(), src.find("missing").unwrap()),
+            vec!["arg", "count", "fn_a", "fn_b", "helper", "local"]
+        );
+        assert_eq!(
+            names(&mut module.clone(), src.find("inner").unwrap()),
+            vec!["arg", "count", "fn_a", "fn_b", "helper", "inner", "local"]
+        );
+        assert_eq!(
+            names(&mut module, src.find("fn fn_b").unwrap()),
+            vec!["count", "fn_a", "fn_b", "helper"]
+        );
+    }
 }
@@ -1,4 +1,8 @@
-use crate::front::ast_types::{FullItemPath, RawName, RawNameRoot, RawNameTailNode, ResolvedName};
+use crate::front::ast_creator::token_types::Span;
+use crate::front::ast_types::{
+    FullItemPath, RawName, RawNameRoot, RawNameTailNode, ResolvedName, Visibility,
+};
+use crate::front::definition_table::ModuleExports;
 use crate::front::passes::name_resolution::NameResolutionError::UndefinedLookup;
 use crate::front::passes::name_resolution::{NameResolutionError, NameResolutionResult};
 use crate::modules::module_id_from_local;
@@ -9,9 +13,9 @@ fn stitch_path(
     tail: &Option<Vec<RawNameTailNode>>,
 ) -> ResolvedName {
     if let Some(tail_unwrap) = tail {
-        if tail_unwrap.len() != 0 {
-            for i in 0..tail_unwrap.len() - 1 {
-                full_item_path.item_path.push(tail_unwrap[i].clone());
+        if !tail_unwrap.is_empty() {
+            for node in tail_unwrap[..tail_unwrap.len() - 1].iter() {
+                full_item_path.item_path.push(node.clone());
             }
 
             return ResolvedName::new(
@@ -22,10 +26,10 @@ fn stitch_path(
     }
 
     let item = full_item_path.item_path.pop().unwrap_or("".to_string());
-    return ResolvedName::new(
+    ResolvedName::new(
         module_id_from_local(&full_item_path.package_name, &full_item_path.item_path),
         item,
-    );
+    )
 }
 
 struct ScopeTableLayer {
@@ -35,20 +39,138 @@ struct ScopeTableLayer {
     unresolved: HashSet<RawNameRoot>,
 }
 
-pub struct ScopeTable {
+// looks for the names visible at an offset of the source instead of checking the names
+struct ScopeQuery {
+    offset: usize,
+    depth: usize, // the depth of the innermost scope around the offset found so far
+    visible: Vec<(RawNameRoot, ResolvedName)>,
+}
+
+pub struct ScopeTable<'a> {
     module_path: FullItemPath,
     stack: Vec<ScopeTableLayer>,
 
     global_count: HashMap<RawNameRoot, i32>,
+
+    // the definitions of other modules, used by glob imports
+    module_exports: &'a ModuleExports,
+
+    // in query mode, names that cannot be resolved or are defined twice are not errors
+    query: Option<ScopeQuery>,
 }
 
-impl ScopeTable {
-    pub fn new(module_path: FullItemPath) -> ScopeTable {
+impl ScopeTable<'_> {
+    pub fn new(module_path: FullItemPath, module_exports: &ModuleExports) -> ScopeTable<'_> {
         ScopeTable {
             module_path,
             stack: vec![],
             global_count: HashMap::new(),
+            module_exports,
+            query: None,
+        }
+    }
+
+    pub fn new_query(
+        module_path: FullItemPath,
+        module_exports: &ModuleExports,
+        offset: usize,
+    ) -> ScopeTable<'_> {
+        ScopeTable {
+            query: Some(ScopeQuery {
+                offset,
+                depth: 0,
+                visible: vec![],
+            }),
+            ..ScopeTable::new(module_path, module_exports)
+        }
+    }
+
+    // the names visible at the offset of the query, sorted by their raw name
+    pub fn visible_names(self) -> Vec<(RawNameRoot, ResolvedName)> {
+        self.query.map(|query| query.visible).unwrap_or_default()
+    }
+
+    /* Remembers the names visible in the current scope if the offset of the query is inside of it, as the
+    innermost scope around the offset is entered last. A module without a span is the whole file.
+     */
+    pub fn query_scope(&mut self, span: Option<Span>) {
+        let depth = self.stack.len();
+        let Some(query) = &mut self.query else {
+            return;
+        };
+        let contains = span.is_none_or(|span| span.lo <= query.offset && query.offset <= span.hi);
+        if !contains || depth < query.depth {
+            return;
+        }
+
+        // inner scopes shadow the outer ones
+        let mut visible = HashMap::new();
+        for layer in self.stack.iter() {
+            for (raw_name, full_item_path) in layer.symbols.iter() {
+                if !layer.unresolved.contains(raw_name) {
+                    visible.insert(raw_name.clone(), full_item_path.clone());
+                }
+            }
+        }
+        let mut visible = visible
+            .into_iter()
+            .map(|(raw_name, full_item_path)| (raw_name, stitch_path(full_item_path, &None)))
+            .collect::<Vec<_>>();
+        visible.sort_by(|a, b| a.0.cmp(&b.0));
+
+        query.depth = depth;
+        query.visible = visible;
+    }
+
+    // turns `self::a` and `super::a` into paths starting from the package
+    pub fn absolute_path(&self, path: &FullItemPath) -> NameResolutionResult<FullItemPath> {
+        let mut item_path = self.module_path.item_path.clone();
+        let mut rest = path.item_path.iter().peekable();
+        match path.package_name.as_str() {
+            "self" => {}
+            "super" => {
+                item_path.pop();
+                while rest.next_if(|node| node.as_str() == "super").is_some() {
+                    if item_path.pop().is_none() {
+                        if self.query.is_some() {
+                            return Ok(path.clone());
+                        }
+                        return Err(NameResolutionError::UnresolvedImport(path.clone()));
+                    }
+                }
+            }
+            _ => return Ok(path.clone()),
         }
+        item_path.extend(rest.cloned());
+        Ok(FullItemPath::new(
+            self.module_path.package_name.clone(),
+            item_path,
+        ))
+    }
+
+    // the names that a glob import of the module can see
+    pub fn glob_names(&self, path: &FullItemPath) -> NameResolutionResult<Vec<RawNameRoot>> {
+        let module_id = module_id_from_local(&path.package_name, &path.item_path);
+        let Some(exports) = self.module_exports.get(&module_id) else {
+            if self.query.is_some() {
+                return Ok(vec![]);
+            }
+            return Err(NameResolutionError::UnresolvedImport(path.clone()));
+        };
+
+        Ok(exports
+            .iter()
+            .filter(|(_, visibility)| match visibility {
+                Visibility::Public => true,
+                Visibility::Package => path.package_name == self.module_path.package_name,
+                Visibility::Private => false,
+            })
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    pub fn is_bound_in_scope(&self, raw_name: &RawNameRoot) -> bool {
+        self.stack.last().unwrap().symbols.contains_key(raw_name)
     }
 
     pub fn scope_enter(&mut self) {
@@ -61,7 +183,7 @@ impl ScopeTable {
     pub fn scope_exit(&mut self) -> NameResolutionResult<()> {
         let layer = self.stack.pop().unwrap();
 
-        if !layer.unresolved.is_empty() {
+        if !layer.unresolved.is_empty() && self.query.is_none() {
             return Err(NameResolutionError::UnresolvedNames(layer.unresolved));
         }
         Ok(())
@@ -90,7 +212,7 @@ impl ScopeTable {
                     if force_name.is_none() {
                         force_name = Some(resolved_name.clone());
                     }
-                } else {
+                } else if self.query.is_none() {
                     return Err(NameResolutionError::Redefinition(raw_name.clone()));
                 }
             }
@@ -141,6 +263,12 @@ impl ScopeTable {
             let layer = self.stack.last_mut().unwrap();
             layer.unresolved.insert(raw_name_root.clone());
             Ok(self.scope_bind(&raw_name_root, true, None)?)
+        } else if self.query.is_some() {
+            // the name is left as it is written, which does not refer to any definition
+            Ok(stitch_path(
+                self.module_path.clone(),
+                &Some(vec![raw_name_root]),
+            ))
         } else {
             Err(UndefinedLookup(raw_name_root.clone()))
         }
@@ -1,17 +1,89 @@
-use crate::front::ast_types::Type;
+use crate::front::ast_types::{Definition, Module, Type, GLOB_IMPORT};
 use crate::front::passes::name_resolution::scope_table::ScopeTable;
-use crate::front::passes::name_resolution::NameResolutionError;
+use crate::front::passes::name_resolution::{NameResolutionError, NameResolutionResult};
 use crate::front::passes::visitor::{ASTNodeEnum, GenericVisitApplyResult, Visitable, Visitor};
+use std::collections::HashSet;
 
 pub type ResolveResult<T> = GenericVisitApplyResult<T, NameResolutionError>;
-impl Visitor<(), NameResolutionError> for ScopeTable {
+
+impl ScopeTable<'_> {
+    // binds the contents of a module to the current scope and visits them
+    fn visit_module_body(&mut self, module: &mut Module) -> NameResolutionResult<()> {
+        // relative paths are made absolute, so that later passes do not need to know where the module is
+        for (_, full_item_path) in module.uses.iter_mut().flatten() {
+            *full_item_path = self.absolute_path(full_item_path)?;
+        }
+        // load the "use" statements into the scope table. There should not be any duplicates
+        for (raw_name, full_item_path) in module.uses.iter().flatten() {
+            if raw_name.0 != GLOB_IMPORT {
+                self.scope_bind(&raw_name.0, true, Some(full_item_path.clone()))?;
+            }
+        }
+        // statics and functions are bound before anything else so that they can be referenced before their definition
+        for definition in module.definitions.iter_mut().flatten() {
+            match definition {
+                Definition::StaticVarDef(def) => {
+                    def.name.resolved = Some(self.scope_bind(&def.name.raw.0, true, None)?);
+                }
+                Definition::FnDef(def) => {
+                    def.name.resolved = Some(self.scope_bind(&def.name.raw.0, true, None)?);
+                }
+                Definition::VarDef(_) | Definition::StructDef(_) => {}
+            }
+        }
+        // glob imports come last, and only bind the names that are not defined or imported explicitly
+        let defined = module
+            .definitions
+            .iter()
+            .flatten()
+            .map(|definition| match definition {
+                Definition::StaticVarDef(def) => def.name.raw.0.clone(),
+                Definition::VarDef(def) => def.name.raw.0.clone(),
+                Definition::StructDef(def) => def.name.raw.0.clone(),
+                Definition::FnDef(def) => def.name.raw.0.clone(),
+            })
+            .collect::<HashSet<_>>();
+        for (raw_name, full_item_path) in module.uses.iter().flatten() {
+            if raw_name.0 != GLOB_IMPORT {
+                continue;
+            }
+            for name in self.glob_names(full_item_path)? {
+                if !defined.contains(&name) && !self.is_bound_in_scope(&name) {
+                    let mut path = full_item_path.clone();
+                    path.item_path.push(name.clone());
+                    self.scope_bind(&name, true, Some(path))?;
+                }
+            }
+        }
+        // then we visit each definition in the Module
+        for definition in module.definitions.iter_mut().flatten() {
+            definition.visit(self)?;
+        }
+        // every definition of the module is visible in its statements, including the later `let`s
+        self.query_scope(module.span);
+        // then we visit each statement in the Module
+        for statement in module.statements.iter_mut() {
+            statement.visit(self)?;
+        }
+        Ok(())
+    }
+}
+
+impl Visitor<(), NameResolutionError> for ScopeTable<'_> {
     fn apply(&mut self, ast_node: &mut ASTNodeEnum) -> ResolveResult<()> {
         Ok((
             match ast_node {
-                ASTNodeEnum::VarReference(_)
-                | ASTNodeEnum::TypeReference(_)
-                | ASTNodeEnum::FunctionReference(_) => {
-                    panic!("Reference should not be visited directly")
+                // references that are visited directly come from statements and expressions, so they are uses of a name
+                ASTNodeEnum::VarReference(name) => {
+                    name.resolved = Some(self.scope_lookup(&name.raw, false)?);
+                    false
+                }
+                ASTNodeEnum::FunctionReference(name) => {
+                    name.resolved = Some(self.scope_lookup(&name.raw, false)?);
+                    false
+                }
+                ASTNodeEnum::TypeReference(_) => {
+                    panic!("Type reference should not be visited directly")
                 }
 
                 ASTNodeEnum::Type(ty) => {
@@ -21,7 +93,9 @@ impl Visitor<(), NameResolutionError> for ScopeTable {
                     false
                 }
                 ASTNodeEnum::StaticVarDef(def) => {
-                    def.name.resolved = Some(self.scope_bind(&def.name.raw.0, true, None)?);
+                    if def.name.resolved.is_none() {
+                        def.name.resolved = Some(self.scope_bind(&def.name.raw.0, true, None)?);
+                    }
                     def.ty.visit(self)?;
                     false
                 }
@@ -31,12 +105,17 @@ impl Visitor<(), NameResolutionError> for ScopeTable {
                     false
                 }
                 ASTNodeEnum::FnDef(def) => {
-                    def.name.resolved = Some(self.scope_bind(&def.name.raw.0, true, None)?);
+                    if def.name.resolved.is_none() {
+                        def.name.resolved = Some(self.scope_bind(&def.name.raw.0, true, None)?);
+                    }
+                    // the arguments share a scope with the function body
+                    self.scope_enter();
                     for var_def in def.args.iter_mut() {
                         var_def.visit(self)?;
                     }
                     def.return_type.visit(self)?;
-                    def.body.visit(self)?;
+                    self.visit_module_body(&mut def.body)?;
+                    self.scope_exit()?;
                     false
                 }
                 ASTNodeEnum::StructDef(def) => {
@@ -48,20 +127,13 @@ impl Visitor<(), NameResolutionError> for ScopeTable {
                 }
                 ASTNodeEnum::Definition(_) => true,
                 ASTNodeEnum::Statement(_) => true,
+                ASTNodeEnum::VarAssign(_) => true,
+                ASTNodeEnum::FnCall(_) => true,
+                ASTNodeEnum::Command(_) => true,
+                ASTNodeEnum::Expression(_) => true,
                 ASTNodeEnum::Module(module) => {
                     self.scope_enter();
-                    // load the "use" statements into the scope table. There should not be any duplicates
-                    for (raw_name, resolved_name) in module.uses.take().unwrap() {
-                        self.scope_bind(&raw_name.0, true, Some(resolved_name))?;
-                    }
-                    // then we visit each definition in the Module
-                    for definition in module.definitions.iter_mut().flatten() {
-                        definition.visit(self)?;
-                    }
-                    // then we visit each definition in the Module
-                    for statement in module.statements.iter_mut() {
-                        statement.visit(self)?;
-                    }
+                    self.visit_module_body(module)?;
                     self.scope_exit()?;
                     false
                 }
@@ -1,6 +1,7 @@
 use crate::front::ast_types::{
-    Definition, FnDef, FunctionReference, Module, Statement, StaticVarDef, StructDef, Type,
-    TypeReference, VarDef, VarReference,
+    AssertKind, Command, CommandPart, Definition, Expression, FnCall, FnDef, FunctionReference,
+    Module, Statement, StaticVarDef, StructDef, Type, TypeReference, VarAssign, VarDef,
+    VarReference,
 };
 /*
 The current file sets up the infrastructure for the visitor pattern.
@@ -8,6 +9,8 @@ The current file sets up the infrastructure for the visitor pattern.
 The visitor will recursively visit every node in the AST. A developer may implement
  */
 
+// every node is visited, even the kinds that no visitor looks into yet
+#[allow(dead_code)]
 pub enum ASTNodeEnum<'a> {
     Type(&'a mut Type),
 
@@ -24,6 +27,10 @@ pub enum ASTNodeEnum<'a> {
     Module(&'a mut Module),
 
     Statement(&'a mut Statement),
+    VarAssign(&'a mut VarAssign),
+    FnCall(&'a mut FnCall),
+    Command(&'a mut Command),
+    Expression(&'a mut Expression),
 }
 
 pub type GenericVisitApplyResult<K, V> = Result<(bool, Option<K>), V>;
@@ -35,7 +42,7 @@ pub trait Visitor<K, V> {
     The apply method can be overridden to have different behaviors when visiting a node.
      */
     fn apply(&mut self, _ast_node: &mut ASTNodeEnum) -> GenericVisitApplyResult<K, V> {
-        return Ok((true, None));
+        Ok((true, None))
     }
 }
 
@@ -125,7 +132,7 @@ impl<T: Visitor<K, V>, K, V> Visitable<T, K, V> for Module {
     fn visit(&mut self, visitor: &mut T) -> Result<Option<K>, V> {
         let (visit_result, res) = visitor.apply(&mut ASTNodeEnum::Module(self))?;
         if visit_result {
-            // skip the uses because name resolution will get rid of it instantly
+            // the uses are not visited, the passes that need them read them from the module directly
             for definition in self.definitions.iter_mut().flatten() {
                 definition.visit(visitor)?;
             }
@@ -158,10 +165,94 @@ impl<T: Visitor<K, V>, K, V> Visitable<T, K, V> for Statement {
         if visit_result {
             match self {
                 Statement::Module(x) => x.visit(visitor)?,
-                _ => None,
-                // Statement::VarAssign(x) => x.visit(visitor)?,
-                // Statement::FnCall(x) => x.visit(visitor)?,
-                // Statement::Return => {}
+                Statement::VarAssign(x) => x.visit(visitor)?,
+                Statement::FnCall(x) => x.visit(visitor)?,
+                Statement::Command(x) => x.visit(visitor)?,
+                Statement::Assert(x) => match &mut x.kind {
+                    AssertKind::True(condition) => condition.visit(visitor)?,
+                    AssertKind::Eq(lhs, rhs) => {
+                        lhs.visit(visitor)?;
+                        rhs.visit(visitor)?
+                    }
+                },
+                Statement::Return(Some(x)) => x.visit(visitor)?,
+                Statement::Return(None) => None,
+                Statement::If(x) => {
+                    x.condition.visit(visitor)?;
+                    x.body.visit(visitor)?;
+                    match &mut x.else_body {
+                        Some(else_body) => else_body.visit(visitor)?,
+                        None => None,
+                    }
+                }
+                Statement::While(x) => {
+                    x.condition.visit(visitor)?;
+                    x.body.visit(visitor)?
+                }
+            };
+        }
+        Ok(res)
+    }
+}
+
+impl<T: Visitor<K, V>, K, V> Visitable<T, K, V> for VarAssign {
+    fn visit(&mut self, visitor: &mut T) -> Result<Option<K>, V> {
+        let (visit_result, res) = visitor.apply(&mut ASTNodeEnum::VarAssign(self))?;
+        if visit_result {
+            self.name.visit(visitor)?;
+            self.expr.visit(visitor)?;
+        }
+        Ok(res)
+    }
+}
+
+impl<T: Visitor<K, V>, K, V> Visitable<T, K, V> for FnCall {
+    fn visit(&mut self, visitor: &mut T) -> Result<Option<K>, V> {
+        let (visit_result, res) = visitor.apply(&mut ASTNodeEnum::FnCall(self))?;
+        if visit_result {
+            self.name.visit(visitor)?;
+            for arg in self.args.iter_mut() {
+                arg.visit(visitor)?;
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl<T: Visitor<K, V>, K, V> Visitable<T, K, V> for Command {
+    fn visit(&mut self, visitor: &mut T) -> Result<Option<K>, V> {
+        let (visit_result, res) = visitor.apply(&mut ASTNodeEnum::Command(self))?;
+        if visit_result {
+            for part in self.parts.iter_mut() {
+                match part {
+                    CommandPart::Text(_) => {}
+                    CommandPart::Value(x) | CommandPart::Location(x) => {
+                        x.visit(visitor)?;
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl<T: Visitor<K, V>, K, V> Visitable<T, K, V> for Expression {
+    fn visit(&mut self, visitor: &mut T) -> Result<Option<K>, V> {
+        let (visit_result, res) = visitor.apply(&mut ASTNodeEnum::Expression(self))?;
+        if visit_result {
+            match self {
+                Expression::Literal(_) => None,
+                Expression::Var(x) => x.visit(visitor)?,
+                Expression::Binary(lhs, _, rhs) => {
+                    lhs.visit(visitor)?;
+                    rhs.visit(visitor)?
+                }
+                Expression::Unary(_, x) => x.visit(visitor)?,
+                Expression::Cast(x, ty) => {
+                    x.visit(visitor)?;
+                    ty.visit(visitor)?
+                }
+                Expression::FnCall(x) => x.visit(visitor)?,
             };
         }
         Ok(res)
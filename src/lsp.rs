@@ -0,0 +1,658 @@
+mod convert;
+mod transport;
+
+use crate::completion::{complete, CompletionKind};
+use crate::file_system::concrete::overlay_fs::OverlayFs;
+use crate::file_system::FileSystem;
+use crate::front::ast_types::RawNameRoot;
+use crate::front::diagnostics::{diagnose, diagnose_name, Diagnostic};
+use crate::front::FrontError;
+use crate::lsp::convert::{offset_to_position, path_to_uri, position_to_offset, uri_to_path};
+use crate::lsp::transport::{read_message, write_message};
+use crate::modules::{ModuleBuildError, ModuleBuilder, ModuleId};
+use crate::symbol_index::{Location, SymbolIndex};
+use camino::Utf8PathBuf;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{BufRead, Read, Write};
+
+#[derive(Debug)]
+pub enum LspError {
+    Io(std::io::Error),
+    InvalidMessage(String),
+}
+
+pub type LspResult<T> = Result<T, LspError>;
+
+impl fmt::Display for LspError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LspError::Io(error) => write!(f, "{}", error),
+            LspError::InvalidMessage(message) => write!(f, "invalid message: {}", message),
+        }
+    }
+}
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// the errors of a module that can be shown in its file
+enum ModuleError {
+    Front(FrontError),
+    PrivateImport(RawNameRoot),
+}
+
+// serves a single client until it exits
+pub fn serve<T: FileSystem>(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    file_system: T,
+) -> LspResult<()> {
+    let mut file_system = OverlayFs::new(file_system);
+    let mut server = Server::new(&mut file_system);
+
+    while let Some(message) = read_message(input)? {
+        for response in server.handle(&message) {
+            write_message(output, &response)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+    Ok(())
+}
+
+struct Server<'p, T: FileSystem> {
+    module_builder: ModuleBuilder<'p, OverlayFs<T>>,
+    has_package: bool,
+
+    uris: HashMap<Utf8PathBuf, String>, // the uris of open documents, as sent by the client
+    published: Vec<Utf8PathBuf>, // files with diagnostics, which have to be cleared once fixed
+    exited: bool,
+}
+
+impl<'p, T: FileSystem> Server<'p, T> {
+    fn new(file_system: &'p mut OverlayFs<T>) -> Self {
+        Server {
+            module_builder: ModuleBuilder::new(file_system, None),
+            has_package: false,
+            uris: HashMap::new(),
+            published: vec![],
+            exited: false,
+        }
+    }
+
+    // handles a message from the client, returning the messages to send back
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // responses to requests of the server
+            return vec![];
+        };
+        let id = message.get("id");
+        let params = message.get("params").unwrap_or(&Value::Null);
+
+        let result = match method {
+            "initialize" => {
+                // every workspace folder is a package
+                let folders = params["workspaceFolders"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|folder| folder["uri"].as_str())
+                    .chain(params["rootUri"].as_str())
+                    .filter_map(uri_to_path)
+                    .collect::<Vec<_>>();
+                for folder in folders {
+                    self.set_package(folder);
+                }
+                json!({
+                    "capabilities": {
+                        "textDocumentSync": 1,
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "hoverProvider": true,
+                        "completionProvider": { "triggerCharacters": ["."] },
+                    },
+                    "serverInfo": { "name": "blastfurnace" },
+                })
+            }
+            "textDocument/definition" => self
+                .document_offset(params)
+                .and_then(|(path, offset)| {
+                    let index = SymbolIndex::new(self.module_builder.get_module_graph());
+                    index.definition(&path, offset).cloned()
+                })
+                .map_or(Value::Null, |location| self.location(&location)),
+            "textDocument/references" => {
+                let include_declaration = params["context"]["includeDeclaration"]
+                    .as_bool()
+                    .unwrap_or(true);
+                let references = self
+                    .document_offset(params)
+                    .map(|(path, offset)| {
+                        let index = SymbolIndex::new(self.module_builder.get_module_graph());
+                        index.references(&path, offset, include_declaration)
+                    })
+                    .unwrap_or_default();
+                Value::Array(
+                    references
+                        .iter()
+                        .map(|location| self.location(location))
+                        .collect(),
+                )
+            }
+            "textDocument/hover" => self
+                .document_offset(params)
+                .and_then(|(path, offset)| {
+                    let index = SymbolIndex::new(self.module_builder.get_module_graph());
+                    let (location, description) = index.hover(&path, offset)?;
+                    let range = self.location(location)["range"].take();
+                    Some(json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!("```\n{}\n```", description),
+                        },
+                        "range": range,
+                    }))
+                })
+                .unwrap_or(Value::Null),
+            "textDocument/completion" => {
+                let completions = self
+                    .document_offset(params)
+                    .and_then(|(path, offset)| {
+                        let src = self.read(&path)?;
+                        Some(complete(
+                            self.module_builder.get_module_graph(),
+                            &path,
+                            &src,
+                            offset,
+                        ))
+                    })
+                    .unwrap_or_default();
+                Value::Array(
+                    completions
+                        .into_iter()
+                        .map(|completion| {
+                            json!({
+                                "label": completion.label,
+                                "kind": completion_item_kind(completion.kind),
+                                "detail": completion.detail,
+                            })
+                        })
+                        .collect(),
+                )
+            }
+            "shutdown" => Value::Null,
+            "exit" => {
+                self.exited = true;
+                return vec![];
+            }
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                let text = document["text"].as_str().unwrap_or_default();
+                return self.update_document(document, Some(text.to_string()));
+            }
+            "textDocument/didChange" => {
+                // the whole document is sent on every change
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                return self.update_document(&params["textDocument"], text.map(str::to_string));
+            }
+            "textDocument/didClose" => {
+                return self.update_document(&params["textDocument"], None);
+            }
+            _ => {
+                return match id {
+                    Some(id) => vec![json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": METHOD_NOT_FOUND,
+                            "message": format!("unsupported method `{}`", method),
+                        },
+                    })],
+                    None => vec![],
+                };
+            }
+        };
+
+        match id {
+            Some(id) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+            None => vec![],
+        }
+    }
+
+    // the package is the directory of the workspace, without requiring a main module
+    fn set_package(&mut self, path: Utf8PathBuf) {
+        let package_map = &self.module_builder.get_module_graph().package_map;
+        if package_map
+            .values()
+            .any(|package_path| package_path == &path)
+        {
+            return;
+        }
+        if let Some(package_name) = path.file_name().map(str::to_string) {
+            // the modules are loaded when the diagnostics are computed
+            let _ = self
+                .module_builder
+                .add_fs_package(&package_name, &path, false);
+            self.has_package = true;
+        }
+    }
+
+    // replaces the document with its unsaved content, or with the saved file if there is none
+    fn update_document(&mut self, document: &Value, text: Option<String>) -> Vec<Value> {
+        let Some(uri) = document["uri"].as_str() else {
+            return vec![];
+        };
+        let Some(path) = uri_to_path(uri) else {
+            return vec![];
+        };
+
+        if !self.has_package {
+            if let Some(parent) = path.parent() {
+                self.set_package(parent.to_path_buf());
+            }
+        }
+
+        let file_system = self.module_builder.get_file_system();
+        match text {
+            Some(text) => {
+                file_system.set_buffer(path.clone(), text);
+                self.uris.insert(path, uri.to_string());
+            }
+            None => {
+                file_system.remove_buffer(&path);
+                self.uris.remove(&path);
+            }
+        }
+
+        self.publish_diagnostics()
+    }
+
+    fn publish_diagnostics(&mut self) -> Vec<Value> {
+        // the modules that depend on a modified module are checked again as well
+        let errors = match self.module_builder.reload_module_bodies() {
+            Ok(_) => vec![],
+            Err(ModuleBuildError::InvalidModules(errors)) => errors
+                .into_iter()
+                .map(|(id, error)| (id, ModuleError::Front(error)))
+                .collect(),
+            Err(ModuleBuildError::PrivateImport(id, name)) => {
+                vec![(id, ModuleError::PrivateImport(name.item_name))]
+            }
+            // the other errors do not belong to a file
+            Err(_) => vec![],
+        };
+
+        let mut diagnostics: BTreeMap<Utf8PathBuf, Vec<Diagnostic>> = self
+            .published
+            .drain(..)
+            .map(|path| (path, vec![]))
+            .collect();
+        for (id, error) in errors {
+            let Some(path) = self.module_path(&id) else {
+                continue;
+            };
+            let Some(src) = self.read(&path) else {
+                continue;
+            };
+            let entry = diagnostics.entry(path).or_default();
+            match error {
+                ModuleError::Front(error) => entry.extend(diagnose(&src, &error)),
+                ModuleError::PrivateImport(name) => {
+                    entry.push(diagnose_name(&src, &name, format!("`{}` is private", name)))
+                }
+            }
+        }
+
+        let mut notifications = vec![];
+        for (path, diagnostics) in diagnostics {
+            let src = self.read(&path).unwrap_or_default();
+            let diagnostics = diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    json!({
+                        "range": {
+                            "start": offset_to_position(&src, diagnostic.lo),
+                            "end": offset_to_position(&src, diagnostic.hi),
+                        },
+                        "severity": 1,
+                        "source": "blastfurnace",
+                        "message": diagnostic.message,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            if !diagnostics.is_empty() {
+                self.published.push(path.clone());
+            }
+            notifications.push(json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {
+                    "uri": self.uri(&path),
+                    "diagnostics": diagnostics,
+                },
+            }));
+        }
+        notifications
+    }
+
+    // the file and byte offset of a position in a document
+    fn document_offset(&mut self, params: &Value) -> Option<(Utf8PathBuf, usize)> {
+        let path = uri_to_path(params["textDocument"]["uri"].as_str()?)?;
+        let src = self.read(&path)?;
+        let offset = position_to_offset(&src, &params["position"])?;
+        Some((path, offset))
+    }
+
+    fn location(&mut self, location: &Location) -> Value {
+        let src = self.read(&location.path).unwrap_or_default();
+        json!({
+            "uri": self.uri(&location.path),
+            "range": {
+                "start": offset_to_position(&src, location.lo),
+                "end": offset_to_position(&src, location.hi),
+            },
+        })
+    }
+
+    fn uri(&self, path: &Utf8PathBuf) -> String {
+        self.uris
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| path_to_uri(path))
+    }
+
+    // the file a module is defined in
+    fn module_path(&self, id: &ModuleId) -> Option<Utf8PathBuf> {
+        let module_graph = self.module_builder.get_module_graph();
+        let node = module_graph.nodes.get(id)?;
+        Some(module_graph.package_map[&node.package_name].join(&node.rel_path))
+    }
+
+    fn read(&mut self, path: &Utf8PathBuf) -> Option<String> {
+        let mut src = String::new();
+        self.module_builder
+            .get_file_system()
+            .get_reader(path)
+            .ok()?
+            .read_to_string(&mut src)
+            .ok()?;
+        Some(src)
+    }
+}
+
+// the numbers of the kinds in the protocol
+fn completion_item_kind(kind: CompletionKind) -> u64 {
+    match kind {
+        CompletionKind::Function => 3,
+        CompletionKind::Field => 5,
+        CompletionKind::Variable => 6,
+        CompletionKind::Module => 9,
+        CompletionKind::Struct => 22,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use std::io::Cursor;
+
+    // runs the server with the messages of a client, returning the messages sent back
+    fn run_client(file_system: MockFileSystem, messages: &[Value]) -> Vec<Value> {
+        let mut input = vec![];
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+
+        let mut output = vec![];
+        serve(&mut Cursor::new(input), &mut output, file_system).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut responses = vec![];
+        while let Some(response) = read_message(&mut output).unwrap() {
+            responses.push(response);
+        }
+        responses
+    }
+
+    fn did_change(uri: &str, text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": { "uri": uri, "version": 2 },
+                "contentChanges": [{ "text": text }],
+            },
+        })
+    }
+
+    fn diagnostics(notification: &Value) -> (&str, Vec<(u64, u64, &str)>) {
+        assert_eq!(notification["method"], "textDocument/publishDiagnostics");
+        let params = &notification["params"];
+        let diagnostics = params["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|diagnostic| {
+                let start = &diagnostic["range"]["start"];
+                (
+                    start["line"].as_u64().unwrap(),
+                    start["character"].as_u64().unwrap(),
+                    diagnostic["message"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        (params["uri"].as_str().unwrap(), diagnostics)
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("/pkg/main.ing"),
+            "use root::util::*;\nfn main() {\n    helper();\n}",
+        );
+        mock_fs.insert_file(Utf8PathBuf::from("/pkg/util.ing"), "pub fn helper() {}");
+
+        let responses = run_client(
+            mock_fs,
+            &[
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "initialize",
+                    "params": { "rootUri": "file:///pkg", "capabilities": {} },
+                }),
+                json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": {
+                            "uri": "file:///pkg/util.ing",
+                            "languageId": "ing",
+                            "version": 1,
+                            "text": "pub fn helper() {}",
+                        },
+                    },
+                }),
+                // errors in the edited file, and in the files depending on it
+                did_change("file:///pkg/util.ing", "pub fn renamed() {}\nfn x( {}"),
+                did_change("file:///pkg/util.ing", "pub fn renamed() {}"),
+                did_change("file:///pkg/util.ing", "pub fn helper() {}"),
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/rename", "params": {} }),
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+                json!({ "jsonrpc": "2.0", "method": "exit" }),
+            ],
+        );
+
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["textDocumentSync"],
+            1
+        );
+
+        // nothing is published when the file is opened without errors
+        let main_error = ("file:///pkg/main.ing", vec![(2, 4, "Cannot find `helper`")]);
+        assert_eq!(diagnostics(&responses[1]), main_error);
+        assert_eq!(
+            diagnostics(&responses[2]),
+            ("file:///pkg/util.ing", vec![(1, 7, "Expected ident")])
+        );
+        assert_eq!(diagnostics(&responses[3]), main_error);
+        assert_eq!(diagnostics(&responses[4]), ("file:///pkg/util.ing", vec![]));
+        assert_eq!(diagnostics(&responses[5]), ("file:///pkg/main.ing", vec![]));
+
+        assert_eq!(responses[6]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(
+            responses[7],
+            json!({ "jsonrpc": "2.0", "id": 3, "result": null })
+        );
+        assert_eq!(responses.len(), 8);
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("/pkg/main.ing"),
+            "use package_b::util::helper;\nfn main() {\n    helper();\n}",
+        );
+        mock_fs.insert_file(
+            Utf8PathBuf::from("/package_b/util.ing"),
+            "pub fn helper() {}",
+        );
+
+        let position = |uri: &str, line: u64, character: u64| {
+            json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            })
+        };
+        let responses = run_client(
+            mock_fs,
+            &[
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "initialize",
+                    "params": {
+                        "rootUri": "file:///pkg",
+                        "workspaceFolders": [{ "uri": "file:///package_b", "name": "package_b" }],
+                    },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": {
+                            "uri": "file:///pkg/main.ing",
+                            "text": "use package_b::util::helper;\nfn main() {\n    helper();\n}",
+                        },
+                    },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 2,
+                    "method": "textDocument/definition",
+                    "params": position("file:///pkg/main.ing", 2, 6),
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 3,
+                    "method": "textDocument/references",
+                    "params": position("file:///package_b/util.ing", 0, 8),
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 4,
+                    "method": "textDocument/definition",
+                    "params": position("file:///pkg/main.ing", 1, 0),
+                }),
+            ],
+        );
+
+        let range = |line: u64, start: u64, end: u64| {
+            json!({
+                "start": { "line": line, "character": start },
+                "end": { "line": line, "character": end },
+            })
+        };
+        assert_eq!(
+            responses[1]["result"],
+            json!({ "uri": "file:///package_b/util.ing", "range": range(0, 7, 13) })
+        );
+        assert_eq!(
+            responses[2]["result"],
+            json!([
+                { "uri": "file:///package_b/util.ing", "range": range(0, 7, 13) },
+                { "uri": "file:///pkg/main.ing", "range": range(2, 4, 10) },
+            ])
+        );
+        assert_eq!(responses[3]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_hover_and_completion() {
+        let main = "fn helper(a: int) -> int {\n    return a;\n}\nfn main() {\n}";
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(Utf8PathBuf::from("/pkg/main.ing"), main);
+
+        let position = |line: u64, character: u64| {
+            json!({
+                "textDocument": { "uri": "file:///pkg/main.ing" },
+                "position": { "line": line, "character": character },
+            })
+        };
+        let responses = run_client(
+            mock_fs,
+            &[
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "initialize",
+                    "params": { "rootUri": "file:///pkg" },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": { "textDocument": { "uri": "file:///pkg/main.ing", "text": main } },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 2,
+                    "method": "textDocument/hover",
+                    "params": position(0, 4),
+                }),
+                did_change(
+                    "file:///pkg/main.ing",
+                    &main.replace("{\n}", "{\n    he\n}"),
+                ),
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 3,
+                    "method": "textDocument/completion",
+                    "params": position(4, 6),
+                }),
+            ],
+        );
+
+        assert_eq!(
+            responses[1]["result"]["contents"]["value"],
+            "```\nfn helper(a: int) -> int\n```"
+        );
+        // the unfinished name is an error, but the rest of the file can still be completed
+        assert_eq!(diagnostics(&responses[2]).0, "file:///pkg/main.ing");
+        assert_eq!(
+            responses[3]["result"],
+            json!([{ "label": "helper", "kind": 3, "detail": "fn helper(a: int) -> int" }])
+        );
+    }
+}
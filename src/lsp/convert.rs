@@ -0,0 +1,94 @@
+use camino::Utf8PathBuf;
+use serde_json::{json, Value};
+
+// positions are zero based lines, and columns counted in UTF-16 code units
+pub fn offset_to_position(src: &str, offset: usize) -> Value {
+    let mut line = 0;
+    let mut character = 0;
+
+    for (index, ch) in src.char_indices() {
+        if index >= offset {
+            break;
+        }
+        if ch == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += ch.len_utf16();
+        }
+    }
+
+    json!({ "line": line, "character": character })
+}
+
+pub fn position_to_offset(src: &str, position: &Value) -> Option<usize> {
+    let line = position.get("line")?.as_u64()? as usize;
+    let character = position.get("character")?.as_u64()? as usize;
+
+    let line_start = if line == 0 {
+        0
+    } else {
+        src.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+
+    let mut units = 0;
+    for (index, ch) in src[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return Some(line_start + index);
+        }
+        units += ch.len_utf16();
+    }
+    Some(src.len())
+}
+
+pub fn uri_to_path(uri: &str) -> Option<Utf8PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    // percent-encoded bytes, like `%20`
+    let mut bytes = vec![];
+    let mut chars = path.bytes();
+    while let Some(byte) = chars.next() {
+        if byte == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok().map(Utf8PathBuf::from)
+}
+
+pub fn path_to_uri(path: &Utf8PathBuf) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.as_str().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions() {
+        let src = "fn a() {}\nlet 😎 = x;";
+        let offset = src.find('=').unwrap();
+
+        let position = offset_to_position(src, offset);
+        assert_eq!(position, json!({ "line": 1, "character": 7 }));
+        assert_eq!(position_to_offset(src, &position), Some(offset));
+    }
+
+    #[test]
+    fn test_uris() {
+        let path = Utf8PathBuf::from("/home/my package/main.ing");
+        assert_eq!(path_to_uri(&path), "file:///home/my%20package/main.ing");
+        assert_eq!(uri_to_path(&path_to_uri(&path)), Some(path));
+    }
+}
@@ -0,0 +1,46 @@
+use crate::lsp::{LspError, LspResult};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+// reads a message framed by a `Content-Length` header, or nothing if the input ended
+pub fn read_message(input: &mut impl BufRead) -> LspResult<Option<Value>> {
+    let mut content_length = None;
+
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(LspError::Io)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let content_length = content_length
+        .ok_or_else(|| LspError::InvalidMessage("missing Content-Length".to_string()))?;
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content).map_err(LspError::Io)?;
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| LspError::InvalidMessage(e.to_string()))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> LspResult<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )
+    .map_err(LspError::Io)?;
+    output.flush().map_err(LspError::Io)
+}
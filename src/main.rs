@@ -1,8 +1,22 @@
+mod back;
+mod build;
+mod cli;
+mod completion;
+mod doc;
 mod file_system;
 mod front;
+mod lsp;
 mod middle;
 mod modules;
+mod simulator;
+mod symbol_index;
+mod testing;
 
 fn main() {
-    println!("Hello, world!");
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    if let Err(message) = cli::run(&args) {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
 }
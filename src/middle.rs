@@ -1,20 +1,524 @@
+use crate::front::ast_types::{Attribute, BinOp, ResolvedName, Type, UnOp};
 use crate::middle::global_definition_table::GlobalDefinitionTable;
-use crate::middle::types::{IRFunction, IRInstruction, IRModule};
+use crate::middle::lowering::{collect_nested_functions, FunctionLowering, LocalFunctions};
+use crate::middle::passes::PassManager;
+use crate::middle::types::IRModule;
 use crate::modules::ModuleId;
 
+pub mod call_graph;
+pub mod constant_folding;
+pub mod dead_code;
 pub mod global_definition_table;
+pub mod inlining;
+pub mod ir_text;
+pub mod liveness;
+mod lowering;
+pub mod passes;
+pub mod register_allocation;
+pub mod stack_frames;
 pub mod types;
 
+#[derive(Debug, PartialEq)]
+pub enum IRGenError {
+    UnknownModule(ModuleId),
+    UnknownDefinition(ResolvedName),
+    TypeMismatch(Box<Type>, Box<Type>), // expected, found
+    InvalidBinaryOperation(BinOp, Type),
+    InvalidUnaryOperation(UnOp, Type),
+    InvalidCast(Box<Type>, Box<Type>),
+    ArgumentCount(ResolvedName, usize, usize), // expected, found
+    FloatOutOfRange(f64),
+    UnsupportedType(Type),
+}
+
+pub type IRGenResult<T> = Result<T, IRGenError>;
+
+pub struct IRGenOptions {
+    // floats are stored as fixed-point numbers, multiplied by this scale
+    pub float_scale: i32,
+    // whether `#[test]` functions are generated, they are left out of regular builds
+    pub tests: bool,
+    // the passes run on the IR of the whole program, once every module is generated
+    pub passes: PassManager,
+}
+
+impl Default for IRGenOptions {
+    fn default() -> Self {
+        IRGenOptions {
+            float_scale: 1000,
+            tests: false,
+            passes: PassManager::default(),
+        }
+    }
+}
+
 pub fn generate_ir(
     module_id: &ModuleId,
     global_definition_table: &GlobalDefinitionTable,
-) -> IRModule {
-    // TODO: This is a placeholder implementation that just prints "Hello, world!".
-    return IRModule {
+    options: &IRGenOptions,
+) -> IRGenResult<IRModule> {
+    let definition_table = global_definition_table
+        .definition_tables
+        .get(module_id)
+        .ok_or_else(|| IRGenError::UnknownModule(module_id.clone()))?;
+
+    let fn_defs = definition_table
+        .fn_map
+        .values()
+        .filter(|fn_def| options.tests || !fn_def.attributes.contains(&Attribute::Test))
+        .collect::<Vec<_>>();
+
+    let mut local_functions = LocalFunctions::new();
+    for fn_def in fn_defs.iter() {
+        collect_nested_functions(&fn_def.body, &mut local_functions);
+    }
+
+    let mut functions = vec![];
+    for fn_def in fn_defs
+        .iter()
+        .copied()
+        .chain(local_functions.values().copied())
+    {
+        functions.push(
+            FunctionLowering::new(global_definition_table, &local_functions, options)
+                .lower_fn(fn_def)?,
+        );
+    }
+    // keep the output deterministic
+    functions.sort_by(|a, b| a.name.item_name.cmp(&b.name.item_name));
+
+    let mut statics = definition_table
+        .static_var_map
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    statics.sort();
+
+    Ok(IRModule {
         id: module_id.clone(),
-        functions: vec![IRFunction {
-            name: "main".to_string(),
-            instructions: vec![IRInstruction::MCommand("say Hello, world!".to_string())],
-        }],
+        statics,
+        functions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front::ast_types::FullItemPath;
+    use crate::front::parse_file;
+    use crate::middle::constant_folding::{evaluate_comparison, evaluate_operation};
+    use crate::middle::ir_text::{parse_modules, print_module};
+    use crate::middle::passes::{FoldConstants, OptLevel, RemoveDeadStores};
+    use crate::middle::types::{
+        IRCommandPart, IRFunction, IRInstruction, Score, Storage, Terminator,
     };
+    use std::collections::HashMap;
+
+    fn lower(src: &str, options: &IRGenOptions) -> IRGenResult<IRModule> {
+        let module_path = FullItemPath::new("package_a".to_string(), vec!["main".to_string()]);
+        let (_, _, definition_table) = parse_file(module_path, src);
+
+        let mut global_definition_table = GlobalDefinitionTable::new();
+        global_definition_table
+            .add_definition_table("package_a::main".to_string(), &definition_table);
+        generate_ir(
+            &"package_a::main".to_string(),
+            &global_definition_table,
+            options,
+        )
+    }
+
+    // evaluates a function without calls and returns its scores and storage
+    fn run(function: &IRFunction) -> (HashMap<Score, i32>, HashMap<Storage, String>) {
+        let mut scores: HashMap<Score, i32> = HashMap::new();
+        let mut storage: HashMap<Storage, String> = HashMap::new();
+        let mut block = 0;
+        loop {
+            for instruction in function.blocks[block].instructions.iter() {
+                match instruction {
+                    IRInstruction::ScoreSet(score, value) => {
+                        scores.insert(score.clone(), *value);
+                    }
+                    IRInstruction::ScoreOperation(target, operation, source) => {
+                        let a = scores.get(target).copied().unwrap_or(0);
+                        let b = scores[source];
+                        scores.insert(target.clone(), evaluate_operation(*operation, a, b));
+                    }
+                    IRInstruction::ScoreCompare(target, lhs, operation, rhs) => {
+                        let value = evaluate_comparison(*operation, scores[lhs], scores[rhs]);
+                        scores.insert(target.clone(), value as i32);
+                    }
+                    IRInstruction::StorageSetString(target, value) => {
+                        storage.insert(target.clone(), value.clone());
+                    }
+                    IRInstruction::StorageCopy(target, source) => {
+                        storage.insert(target.clone(), storage[source].clone());
+                    }
+                    _ => panic!("Unsupported instruction"),
+                }
+            }
+            block = match &function.blocks[block].terminator {
+                Terminator::Jump(target) => *target,
+                Terminator::Branch(condition, then_block, else_block) => {
+                    if scores[condition] != 0 {
+                        *then_block
+                    } else {
+                        *else_block
+                    }
+                }
+                Terminator::Return => break,
+                Terminator::TailCall(_) => panic!("Unsupported terminator"),
+            };
+        }
+        (scores, storage)
+    }
+
+    // evaluates a function without calls and returns its return value
+    fn evaluate(function: &IRFunction) -> i32 {
+        run(function).0[&Score::Return]
+    }
+
+    fn evaluate_expression(ty: &str, expr: &str, options: &IRGenOptions) -> i32 {
+        let src = format!("fn f() -> {} {{ return {}; }}", ty, expr);
+        let mut module = lower(&src, options).unwrap();
+        let value = evaluate(&module.functions[0]);
+
+        // folding the constants gives the same result as running the operations
+        let mut passes = PassManager::new(OptLevel::O0);
+        passes.add(FoldConstants);
+        passes.add(RemoveDeadStores);
+        passes.run(std::slice::from_mut(&mut module), None).unwrap();
+        assert_eq!(evaluate(&module.functions[0]), value);
+        value
+    }
+
+    #[test]
+    fn test_int_arithmetic() {
+        let options = IRGenOptions::default();
+        assert_eq!(evaluate_expression("int", "1 + 2 * 3", &options), 7);
+        assert_eq!(evaluate_expression("int", "(1 + 2) * 3", &options), 9);
+        assert_eq!(evaluate_expression("int", "10 - 4 - 3", &options), 3);
+        assert_eq!(evaluate_expression("int", "-7 / 2", &options), -4);
+        assert_eq!(evaluate_expression("int", "-7 % 2", &options), 1);
+    }
+
+    #[test]
+    fn test_float_arithmetic() {
+        let options = IRGenOptions::default();
+        assert_eq!(evaluate_expression("float", "1.5", &options), 1500);
+        assert_eq!(evaluate_expression("float", "1.5 + 0.25", &options), 1750);
+        assert_eq!(evaluate_expression("float", "2.5 * -1.25", &options), -3125);
+        assert_eq!(evaluate_expression("float", "7.5 / 2.0", &options), 3750);
+        assert_eq!(evaluate_expression("float", "-7.5 / 2.0", &options), -3750);
+        assert_eq!(evaluate_expression("float", "1.0 / 3.0", &options), 333);
+    }
+
+    #[test]
+    fn test_float_mul_div_no_intermediate_overflow() {
+        let options = IRGenOptions::default();
+        // the raw product 40000000 * 500 does not fit in an i32
+        assert_eq!(
+            evaluate_expression("float", "40000.0 * 0.5", &options),
+            20000000
+        );
+        // the raw dividend 40000000 * 1000 does not fit in an i32
+        assert_eq!(
+            evaluate_expression("float", "40000.0 / 2.0", &options),
+            20000000
+        );
+    }
+
+    #[test]
+    fn test_float_casts() {
+        let options = IRGenOptions::default();
+        assert_eq!(evaluate_expression("int", "7.9 as int", &options), 7);
+        assert_eq!(evaluate_expression("int", "-7.9 as int", &options), -8);
+        assert_eq!(evaluate_expression("float", "3 as float", &options), 3000);
+        assert_eq!(
+            evaluate_expression("float", "(7 as float) / 2.0", &options),
+            3500
+        );
+    }
+
+    #[test]
+    fn test_float_scale() {
+        let options = IRGenOptions {
+            float_scale: 100,
+            ..IRGenOptions::default()
+        };
+        assert_eq!(evaluate_expression("float", "1.5", &options), 150);
+        assert_eq!(evaluate_expression("float", "1.5 * 1.5", &options), 225);
+        assert_eq!(evaluate_expression("int", "2.99 as int", &options), 2);
+    }
+
+    #[test]
+    fn test_float_variables() {
+        let src = r#"
+        static gravity: float;
+        fn f() -> float {
+            gravity = 9.81;
+            let t: float = 2.0;
+            let steps: int = 3;
+            return gravity * t * t / 2.0 + steps as float;
+        }
+        "#;
+        let module = lower(src, &IRGenOptions::default()).unwrap();
+        assert_eq!(evaluate(&module.functions[0]), 22620);
+    }
+
+    #[test]
+    fn test_implicit_conversion_error() {
+        let src = "fn f() -> float { return 1 + 1.0; }";
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::TypeMismatch(
+                Box::new(Type::Int),
+                Box::new(Type::Float)
+            ))
+        );
+
+        let src = "fn f() -> float { let a: int = 1; return a; }";
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::TypeMismatch(
+                Box::new(Type::Float),
+                Box::new(Type::Int)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_fn_call() {
+        let src = r#"
+        fn f(a: float, b: int) -> float {
+            return a * b as float;
+        }
+        fn g() {
+            f(1.5, 2);
+        }
+        "#;
+        let module = lower(src, &IRGenOptions::default()).unwrap();
+        let g = &module.functions[1];
+        assert_eq!(
+            g.blocks[0].instructions.last(),
+            Some(&IRInstruction::Call(ResolvedName::new(
+                "package_a::main".to_string(),
+                "0:0:f".to_string()
+            )))
+        );
+
+        let src = "fn f(a: float) {} fn g() { f(1); }";
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::TypeMismatch(
+                Box::new(Type::Float),
+                Box::new(Type::Int)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_bool_expressions() {
+        let options = IRGenOptions::default();
+        assert_eq!(evaluate_expression("bool", "true", &options), 1);
+        assert_eq!(evaluate_expression("bool", "!true", &options), 0);
+        assert_eq!(
+            evaluate_expression("bool", "1 < 2 && 2.5 >= 2.5", &options),
+            1
+        );
+        assert_eq!(evaluate_expression("bool", "1 == 2 || false", &options), 0);
+        assert_eq!(evaluate_expression("bool", "true != false", &options), 1);
+        assert_eq!(evaluate_expression("bool", "-3 as bool", &options), 1);
+        assert_eq!(
+            evaluate_expression("int", "(1 > 2) as int + 1", &options),
+            1
+        );
+    }
+
+    #[test]
+    fn test_bool_type_errors() {
+        let src = "fn f() -> bool { return 1; }";
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::TypeMismatch(
+                Box::new(Type::Bool),
+                Box::new(Type::Int)
+            ))
+        );
+
+        let src = "fn f() -> bool { return true + false; }";
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::InvalidBinaryOperation(BinOp::Add, Type::Bool))
+        );
+
+        let src = "fn f() -> bool { return true < false; }";
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::InvalidBinaryOperation(BinOp::Lt, Type::Bool))
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        let src = r#"
+        static greeting: string;
+        fn f() -> string {
+            greeting = "hello \"world\"";
+            let copy: string = greeting;
+            return copy;
+        }
+        "#;
+        let module = lower(src, &IRGenOptions::default()).unwrap();
+        let (_, storage) = run(&module.functions[0]);
+        assert_eq!(storage[&Storage::Return], "hello \"world\"");
+
+        let src = r#"fn f() -> string { return "a" + "b"; }"#;
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::InvalidBinaryOperation(BinOp::Add, Type::String))
+        );
+
+        let src = r#"fn f() -> int { return "1" as int; }"#;
+        assert_eq!(
+            lower(src, &IRGenOptions::default()),
+            Err(IRGenError::InvalidCast(
+                Box::new(Type::String),
+                Box::new(Type::Int)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_command_interpolation() {
+        let src = r#"
+        struct point {
+            x: int,
+        }
+        static p: point;
+        fn f(y: int, speed: float, name: string) {
+            cmd!("say hi");
+            cmd!("tp @s ~ ~{y} ~{speed}");
+            cmd!("execute store result score {&y} run data get storage {p}.x");
+            cmd!("tellraw @a {{\"text\":\"{name}\"}}");
+        }
+        "#;
+        let module = lower(src, &IRGenOptions::default()).unwrap();
+
+        let var = |name: &str| ResolvedName::new("package_a::main".to_string(), name.to_string());
+        assert_eq!(
+            module.functions[0].blocks[0].instructions,
+            vec![
+                IRInstruction::MCommand("say hi".to_string()),
+                IRInstruction::InterpolatedCommand(vec![
+                    IRCommandPart::Text("tp @s ~ ~".to_string()),
+                    IRCommandPart::ScoreValue(Score::Var(var("1:0:y")), 1),
+                    IRCommandPart::Text(" ~".to_string()),
+                    IRCommandPart::ScoreValue(Score::Var(var("1:0:speed")), 1000),
+                ]),
+                IRInstruction::InterpolatedCommand(vec![
+                    IRCommandPart::Text("execute store result score ".to_string()),
+                    IRCommandPart::ScoreLocation(Score::Var(var("1:0:y"))),
+                    IRCommandPart::Text(" run data get storage ".to_string()),
+                    IRCommandPart::StorageLocation(Storage::Var(var("0:0:p"))),
+                    IRCommandPart::Text(".x".to_string()),
+                ]),
+                IRInstruction::InterpolatedCommand(vec![
+                    IRCommandPart::Text("tellraw @a {\"text\":\"".to_string()),
+                    IRCommandPart::StorageValue(Storage::Var(var("1:0:name"))),
+                    IRCommandPart::Text("\"}".to_string()),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ir_text_fixtures() {
+        let src = "fn f(x: int) -> bool { let y: int = x * 2; return y > 3; }";
+        let expected = r#"module package_a::main
+
+fn package_a::main+0:0:f(package_a::main+1:0:x) {
+    locals package_a::main+1:0:y
+b0:
+    r0 = 2
+    r1 = package_a::main+1:0:x
+    r1 *= r0
+    package_a::main+1:0:y = r1
+    r2 = 3
+    r3 = package_a::main+1:0:y > r2
+    ret = r3
+    return
+}
+"#;
+        let module = lower(src, &IRGenOptions::default()).unwrap();
+        assert_eq!(print_module(&module), expected);
+
+        // the IR of a test can be written as text, without a source to lower
+        let fixture = r#"
+        module pkg::main
+        fn pkg::main+0:0:f() {
+        b0:
+            r0 = 7
+            r1 = -2
+            r0 /= r1
+            ret = r0
+            ret max= r1
+            r2 = ret < r1
+            branch r2 b1 b2
+        b1:
+            ret = 0
+            return
+        b2:
+            return
+        }
+        "#;
+        let modules = parse_modules(fixture).unwrap();
+        assert_eq!(evaluate(&modules[0].functions[0]), -2);
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let src = r#"
+        fn f() -> int {
+            let i: int = 0;
+            let sum: int = 0;
+            while i < 10 {
+                i = i + 1;
+                if i % 3 == 0 {
+                    sum = sum + 10;
+                } else if i % 2 == 0 {
+                    sum = sum + 1;
+                } else if i > 6 {
+                    return sum;
+                }
+            }
+            return sum;
+            sum = 5;
+        }
+        fn g() -> int {
+            return f();
+        }
+        "#;
+        let module = lower(src, &IRGenOptions::default()).unwrap();
+
+        // the code after the last return is removed, since nothing jumps to it
+        let f = &module.functions[0];
+        assert!(f.blocks.iter().all(|block| !block
+            .instructions
+            .iter()
+            .any(|instruction| matches!(instruction, IRInstruction::ScoreSet(_, 5)))));
+
+        // returns once i is 7
+        assert_eq!(evaluate(f), 22);
+
+        let g = &module.functions[1];
+        assert_eq!(g.blocks.len(), 1);
+        assert_eq!(
+            g.blocks[0].terminator,
+            Terminator::TailCall(ResolvedName::new(
+                "package_a::main".to_string(),
+                "0:0:f".to_string()
+            ))
+        );
+    }
 }
@@ -0,0 +1,208 @@
+use crate::front::ast_types::ResolvedName;
+use crate::middle::types::{IRFunction, IRInstruction, Terminator};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Call {
+    pub callee: usize,
+    pub is_tail_call: bool, // the caller returns right after, and keeps nothing
+}
+
+/* The calls between the functions of the whole program, where functions are given by their index.
+
+Functions that may call each other recursively are in the same strongly connected component, which are found with
+Tarjan's algorithm. A function that does not call itself is in a component of its own. The components are ordered so
+that every component comes before the components it calls.
+ */
+pub struct CallGraph {
+    pub calls: Vec<Vec<Call>>,
+    pub components: Vec<Vec<usize>>,
+    pub component_of: Vec<usize>,
+    indices: HashMap<ResolvedName, usize>,
+}
+
+struct Tarjan<'a> {
+    calls: &'a [Vec<Call>],
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.low_link[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+
+        for call in self.calls[node].iter() {
+            let next = call.callee;
+            match self.index[next] {
+                None => {
+                    self.visit(next);
+                    self.low_link[node] = self.low_link[node].min(self.low_link[next]);
+                }
+                Some(index) if self.on_stack[next] => {
+                    self.low_link[node] = self.low_link[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_link[node]) == self.index[node] {
+            let mut component = vec![];
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            component.sort();
+            self.components.push(component);
+        }
+    }
+}
+
+impl CallGraph {
+    pub fn new(functions: &[&IRFunction]) -> CallGraph {
+        let indices = functions
+            .iter()
+            .enumerate()
+            .map(|(index, function)| (function.name.clone(), index))
+            .collect::<HashMap<_, _>>();
+
+        // calls to functions outside of the program are left out
+        let calls = functions
+            .iter()
+            .map(|function| {
+                let mut calls = vec![];
+                for block in function.blocks.iter() {
+                    for instruction in block.instructions.iter() {
+                        if let IRInstruction::Call(name) = instruction {
+                            calls.extend(indices.get(name).map(|callee| Call {
+                                callee: *callee,
+                                is_tail_call: false,
+                            }));
+                        }
+                    }
+                    if let Terminator::TailCall(name) = &block.terminator {
+                        calls.extend(indices.get(name).map(|callee| Call {
+                            callee: *callee,
+                            is_tail_call: true,
+                        }));
+                    }
+                }
+                calls
+            })
+            .collect::<Vec<_>>();
+
+        let mut tarjan = Tarjan {
+            calls: &calls,
+            index: vec![None; functions.len()],
+            low_link: vec![0; functions.len()],
+            on_stack: vec![false; functions.len()],
+            stack: vec![],
+            next_index: 0,
+            components: vec![],
+        };
+        for node in 0..functions.len() {
+            if tarjan.index[node].is_none() {
+                tarjan.visit(node);
+            }
+        }
+        // Tarjan's algorithm finds the callees first
+        let mut components = tarjan.components;
+        components.reverse();
+
+        let mut component_of = vec![0; functions.len()];
+        for (component, members) in components.iter().enumerate() {
+            for member in members.iter() {
+                component_of[*member] = component;
+            }
+        }
+
+        CallGraph {
+            calls,
+            components,
+            component_of,
+            indices,
+        }
+    }
+
+    pub fn index(&self, name: &ResolvedName) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    // whether a call from the caller may end up calling the caller again
+    pub fn is_recursive_call(&self, caller: usize, callee: usize) -> bool {
+        self.component_of[caller] == self.component_of[callee]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_text::parse_modules;
+
+    #[test]
+    fn test_components() {
+        let src = r#"
+        module pkg::main
+        fn pkg::main+0:0:main() {
+        b0:
+            call pkg::main+0:0:even
+            call pkg::main+0:0:fact
+            return
+        }
+        fn pkg::main+0:0:even() {
+        b0:
+            call pkg::util+0:0:odd
+            return
+        }
+        fn pkg::main+0:0:fact() {
+        b0:
+            tail_call pkg::main+0:0:fact
+        }
+        module pkg::util
+        fn pkg::util+0:0:odd() {
+        b0:
+            call pkg::main+0:0:even
+            call pkg::util+0:0:leaf
+            return
+        }
+        fn pkg::util+0:0:leaf() {
+        b0:
+            return
+        }
+        "#;
+        let modules = parse_modules(src).unwrap();
+        let functions = modules
+            .iter()
+            .flat_map(|module| module.functions.iter())
+            .collect::<Vec<_>>();
+        let call_graph = CallGraph::new(&functions);
+
+        // main, even and odd, fact, leaf
+        assert_eq!(
+            call_graph.components,
+            vec![vec![0], vec![2], vec![1, 3], vec![4]]
+        );
+        assert!(call_graph.is_recursive_call(1, 3));
+        assert!(call_graph.is_recursive_call(2, 2));
+        assert!(!call_graph.is_recursive_call(0, 1));
+        assert!(!call_graph.is_recursive_call(3, 4));
+        assert_eq!(
+            call_graph.calls[2],
+            vec![Call {
+                callee: 2,
+                is_tail_call: true
+            }]
+        );
+    }
+}
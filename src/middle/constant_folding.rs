@@ -0,0 +1,386 @@
+use crate::front::ast_types::ResolvedName;
+use crate::middle::liveness::successors;
+use crate::middle::lowering::remove_unreachable_blocks;
+use crate::middle::types::{
+    BasicBlock, BlockId, CompareOperation, IRCommandPart, IRFunction, IRInstruction, Location,
+    Score, ScoreOperation, Terminator,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+// the scores whose values are known at some point of a function
+type Constants = BTreeMap<Score, i32>;
+
+// division rounding towards negative infinity, like the scoreboard
+fn floor_div(a: i32, b: i32) -> i32 {
+    let quotient = a.wrapping_div(b);
+    if a.wrapping_rem(b) != 0 && ((a < 0) != (b < 0)) {
+        quotient.wrapping_sub(1)
+    } else {
+        quotient
+    }
+}
+
+// the result of `scoreboard players operation`, which wraps around and leaves the score unchanged when dividing by 0
+pub fn evaluate_operation(operation: ScoreOperation, a: i32, b: i32) -> i32 {
+    match operation {
+        ScoreOperation::Assign => b,
+        ScoreOperation::Add => a.wrapping_add(b),
+        ScoreOperation::Sub => a.wrapping_sub(b),
+        ScoreOperation::Mul => a.wrapping_mul(b),
+        ScoreOperation::Div | ScoreOperation::Mod if b == 0 => a,
+        ScoreOperation::Div => floor_div(a, b),
+        ScoreOperation::Mod => a.wrapping_sub(b.wrapping_mul(floor_div(a, b))),
+        ScoreOperation::Min => a.min(b),
+        ScoreOperation::Max => a.max(b),
+    }
+}
+
+pub fn evaluate_comparison(operation: CompareOperation, a: i32, b: i32) -> bool {
+    match operation {
+        CompareOperation::Eq => a == b,
+        CompareOperation::Ne => a != b,
+        CompareOperation::Lt => a < b,
+        CompareOperation::Le => a <= b,
+        CompareOperation::Gt => a > b,
+        CompareOperation::Ge => a >= b,
+    }
+}
+
+/* Folds the operations on scores whose values are known when the function is compiled, and removes the branches on
+conditions that are.
+
+The values are found with a forward analysis over the blocks, which only follows the branches that may be taken,
+so a loop that is never entered does not hide the values from before it. A value is known after a block when it is
+the same at the end of every block that may jump to it. Variables of the function keep their values across calls,
+but any other variable (e.g. a static) may be changed by a call or a command, as may the return value.
+
+An operation with known operands becomes a single `scoreboard players set`, and one that does not change its target
+(e.g. adding 0, or setting a score to the value it already has) is removed. Branches on known conditions become
+jumps, after which a block that is only jumped to from one other block is merged into it.
+ */
+pub fn fold_constants(function: &mut IRFunction) {
+    let variables = function
+        .params
+        .iter()
+        .chain(function.locals.iter())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    // until no branch is left to remove
+    while fold_blocks(function, &variables) {}
+}
+
+// folds the instructions and branches of every block, and returns whether any branch was removed
+fn fold_blocks(function: &mut IRFunction, variables: &BTreeSet<ResolvedName>) -> bool {
+    let blocks = &mut function.blocks;
+
+    // the known values at the start of each block, or none for the blocks that cannot be reached
+    let mut entry_constants: Vec<Option<Constants>> = vec![None; blocks.len()];
+    entry_constants[0] = Some(Constants::new());
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for id in 0..blocks.len() {
+            let Some(mut constants) = entry_constants[id].clone() else {
+                continue;
+            };
+            for instruction in blocks[id].instructions.iter() {
+                fold_instruction(instruction, &mut constants, variables);
+            }
+
+            for successor in taken_successors(&blocks[id].terminator, &constants) {
+                let merged = match &entry_constants[successor] {
+                    // the entry of the function may be reached without any known value
+                    _ if successor == 0 => Constants::new(),
+                    None => constants.clone(),
+                    Some(known) => known
+                        .iter()
+                        .filter(|(score, value)| constants.get(score) == Some(value))
+                        .map(|(score, value)| (score.clone(), *value))
+                        .collect(),
+                };
+                if entry_constants[successor].as_ref() != Some(&merged) {
+                    entry_constants[successor] = Some(merged);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut removed_branch = false;
+    for (block, constants) in blocks.iter_mut().zip(entry_constants) {
+        let Some(mut constants) = constants else {
+            continue;
+        };
+        block.instructions = block
+            .instructions
+            .iter()
+            .filter_map(|instruction| fold_instruction(instruction, &mut constants, variables))
+            .collect();
+
+        if let Terminator::Branch(condition, then_block, else_block) = &block.terminator {
+            if let Some(value) = constants.get(condition) {
+                let target = if *value != 0 {
+                    *then_block
+                } else {
+                    *else_block
+                };
+                block.terminator = Terminator::Jump(target);
+                removed_branch = true;
+            }
+        }
+    }
+
+    function.blocks = merge_blocks(remove_unreachable_blocks(std::mem::take(blocks)));
+    removed_branch
+}
+
+fn taken_successors(terminator: &Terminator, constants: &Constants) -> Vec<BlockId> {
+    match terminator {
+        Terminator::Branch(condition, then_block, else_block) => match constants.get(condition) {
+            Some(0) => vec![*else_block],
+            Some(_) => vec![*then_block],
+            None => vec![*then_block, *else_block],
+        },
+        terminator => successors(terminator),
+    }
+}
+
+// the scores that a call or a command may change
+fn forget_non_variables(constants: &mut Constants, variables: &BTreeSet<ResolvedName>) {
+    constants.retain(|score, _| match score {
+        Score::Var(name) => variables.contains(name),
+        Score::Reg(_) => true,
+        Score::Return => false,
+    });
+}
+
+// sets a score to a known value, which does nothing if it already has that value
+fn set_score(target: &Score, value: i32, constants: &mut Constants) -> Option<IRInstruction> {
+    if constants.insert(target.clone(), value) == Some(value) {
+        None
+    } else {
+        Some(IRInstruction::ScoreSet(target.clone(), value))
+    }
+}
+
+/* Updates the known values after an instruction, and returns the instruction to run instead of it, if any. */
+fn fold_instruction(
+    instruction: &IRInstruction,
+    constants: &mut Constants,
+    variables: &BTreeSet<ResolvedName>,
+) -> Option<IRInstruction> {
+    match instruction {
+        IRInstruction::ScoreSet(target, value) => set_score(target, *value, constants),
+        IRInstruction::ScoreOperation(target, operation, source) => {
+            let a = constants.get(target).copied();
+            let b = constants.get(source).copied();
+            let value = match (operation, a, b) {
+                (ScoreOperation::Assign, _, Some(b)) => Some(b),
+                (_, Some(a), Some(b)) => Some(evaluate_operation(*operation, a, b)),
+                // the result is the same for any value of the target
+                (ScoreOperation::Mul, _, Some(0)) => Some(0),
+                (ScoreOperation::Mod, _, Some(1 | -1)) => Some(0),
+                _ => None,
+            };
+            if let Some(value) = value {
+                return set_score(target, value, constants);
+            }
+
+            let is_unchanged = match (operation, b) {
+                (ScoreOperation::Assign | ScoreOperation::Min | ScoreOperation::Max, _)
+                    if target == source =>
+                {
+                    true
+                }
+                (ScoreOperation::Add | ScoreOperation::Sub, Some(0)) => true,
+                (ScoreOperation::Mul, Some(1)) => true,
+                (ScoreOperation::Div, Some(0 | 1)) | (ScoreOperation::Mod, Some(0)) => true,
+                (ScoreOperation::Min, Some(i32::MAX)) | (ScoreOperation::Max, Some(i32::MIN)) => {
+                    true
+                }
+                _ => false,
+            };
+            if is_unchanged {
+                None
+            } else {
+                constants.remove(target);
+                Some(instruction.clone())
+            }
+        }
+        IRInstruction::ScoreCompare(target, lhs, operation, rhs) => {
+            let result = match (constants.get(lhs), constants.get(rhs)) {
+                (Some(a), Some(b)) => Some(evaluate_comparison(*operation, *a, *b)),
+                // a score compared with itself
+                _ if lhs == rhs => Some(evaluate_comparison(*operation, 0, 0)),
+                _ => None,
+            };
+            match result {
+                Some(result) => set_score(target, result as i32, constants),
+                None => {
+                    constants.remove(target);
+                    Some(instruction.clone())
+                }
+            }
+        }
+        IRInstruction::StorageSetString(..) | IRInstruction::StorageCopy(..) => {
+            Some(instruction.clone())
+        }
+        IRInstruction::MCommand(_) | IRInstruction::Call(_) => {
+            forget_non_variables(constants, variables);
+            Some(instruction.clone())
+        }
+        IRInstruction::InterpolatedCommand(parts) => {
+            // known values are written into the command, so that it needs no macro
+            let mut folded: Vec<IRCommandPart> = vec![];
+            for part in parts.iter() {
+                let part = match part {
+                    IRCommandPart::ScoreValue(score, 1) if constants.contains_key(score) => {
+                        IRCommandPart::Text(constants[score].to_string())
+                    }
+                    part => part.clone(),
+                };
+                match (folded.last_mut(), part) {
+                    (Some(IRCommandPart::Text(text)), IRCommandPart::Text(next)) => {
+                        text.push_str(&next)
+                    }
+                    (_, part) => folded.push(part),
+                }
+            }
+
+            for part in folded.iter() {
+                if let IRCommandPart::ScoreLocation(score) = part {
+                    constants.remove(score);
+                }
+            }
+            forget_non_variables(constants, variables);
+
+            match &folded[..] {
+                [IRCommandPart::Text(text)] => Some(IRInstruction::MCommand(text.clone())),
+                _ => Some(IRInstruction::InterpolatedCommand(folded)),
+            }
+        }
+        IRInstruction::Assert(assertion) => match constants.get(&assertion.condition) {
+            Some(value) if *value != 0 => None,
+            _ => Some(instruction.clone()),
+        },
+        IRInstruction::PushFrame(_) => Some(instruction.clone()),
+        IRInstruction::PopFrame(locations) => {
+            for location in locations.iter() {
+                if let Location::Score(score) = location {
+                    constants.remove(score);
+                }
+            }
+            Some(instruction.clone())
+        }
+    }
+}
+
+// merges every block into the block before it, when that is the only block jumping to it
+pub fn merge_blocks(mut blocks: Vec<BasicBlock>) -> Vec<BasicBlock> {
+    let mut predecessors = vec![0; blocks.len()];
+    for block in blocks.iter() {
+        for successor in successors(&block.terminator) {
+            predecessors[successor] += 1;
+        }
+    }
+
+    for id in 0..blocks.len() {
+        while let Terminator::Jump(target) = blocks[id].terminator {
+            if target == id || target == 0 || predecessors[target] != 1 {
+                break;
+            }
+            // the merged block cannot be reached anymore
+            let merged = std::mem::replace(
+                &mut blocks[target],
+                BasicBlock {
+                    instructions: vec![],
+                    terminator: Terminator::Return,
+                },
+            );
+            blocks[id].instructions.extend(merged.instructions);
+            blocks[id].terminator = merged.terminator;
+        }
+    }
+    remove_unreachable_blocks(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_text::{parse_modules, print_function};
+
+    #[test]
+    fn test_evaluate_operation() {
+        assert_eq!(
+            evaluate_operation(ScoreOperation::Add, i32::MAX, 1),
+            i32::MIN
+        );
+        assert_eq!(evaluate_operation(ScoreOperation::Mul, 65536, 65536), 0);
+        assert_eq!(evaluate_operation(ScoreOperation::Div, -7, 2), -4);
+        assert_eq!(evaluate_operation(ScoreOperation::Mod, -7, 2), 1);
+        assert_eq!(evaluate_operation(ScoreOperation::Mod, 7, -2), -1);
+        assert_eq!(
+            evaluate_operation(ScoreOperation::Div, i32::MIN, -1),
+            i32::MIN
+        );
+        assert_eq!(evaluate_operation(ScoreOperation::Mod, i32::MIN, -1), 0);
+        assert_eq!(evaluate_operation(ScoreOperation::Div, 5, 0), 5);
+        assert_eq!(evaluate_operation(ScoreOperation::Mod, 5, 0), 5);
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let src = r#"
+        module pkg::main
+        fn pkg::main+0:0:f(pkg::main+1:0:x) {
+            locals pkg::main+1:0:y
+        b0:
+            r0 = 6
+            r1 = 7
+            r0 *= r1
+            pkg::main+1:0:y = r0
+            r2 = 0
+            pkg::main+1:0:x += r2
+            pkg::main+0:0:s = r0
+            call pkg::main+0:0:g
+            r3 = pkg::main+1:0:y == pkg::main+0:0:s
+            interpolate "say " score(pkg::main+1:0:y, 1) " " score(r3, 1)
+            r4 = pkg::main+1:0:y > r1
+            branch r4 b1 b2
+        b1:
+            r5 = pkg::main+1:0:y
+            jump b3
+        b2:
+            r5 = 0
+            jump b3
+        b3:
+            ret = r5
+            return
+        }
+        "#;
+        // s may be changed by the call, while y keeps its value
+        let expected = r#"fn pkg::main+0:0:f(pkg::main+1:0:x) {
+    locals pkg::main+1:0:y
+b0:
+    r0 = 6
+    r1 = 7
+    r0 = 42
+    pkg::main+1:0:y = 42
+    r2 = 0
+    pkg::main+0:0:s = 42
+    call pkg::main+0:0:g
+    r3 = pkg::main+1:0:y == pkg::main+0:0:s
+    interpolate "say 42 " score(r3, 1)
+    r4 = 1
+    r5 = 42
+    ret = 42
+    return
+}
+"#;
+        let mut modules = parse_modules(src).unwrap();
+        let function = &mut modules[0].functions[0];
+        fold_constants(function);
+        assert_eq!(print_function(function), expected);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::front::ast_types::{Attribute, ResolvedName};
+use crate::middle::call_graph::CallGraph;
+use crate::middle::liveness::{terminator_uses, uses_and_defs, Liveness};
+use crate::middle::types::{IRFunction, IRInstruction, IRModule, Location, Score, Storage};
+use std::collections::BTreeSet;
+
+// the functions and statics left out of the program, by their names
+#[derive(Debug, PartialEq, Default)]
+pub struct RemovedItems {
+    pub functions: Vec<ResolvedName>,
+    pub statics: Vec<ResolvedName>,
+}
+
+// whether the game (or the test runner) may run the function without it being called by another one
+pub fn is_entry_point(function: &IRFunction, main: Option<&ResolvedName>) -> bool {
+    Some(&function.name) == main
+        || function.attributes.iter().any(|attribute| {
+            matches!(
+                attribute,
+                Attribute::Load | Attribute::Tick | Attribute::Test | Attribute::Export(_)
+            )
+        })
+}
+
+// the variables read or written by the function
+fn variables(function: &IRFunction) -> BTreeSet<ResolvedName> {
+    let mut locations = vec![];
+    for block in function.blocks.iter() {
+        for instruction in block.instructions.iter() {
+            let (uses, defs) = uses_and_defs(instruction);
+            locations.extend(uses);
+            locations.extend(defs);
+        }
+        locations.extend(terminator_uses(&block.terminator));
+    }
+
+    locations
+        .into_iter()
+        .filter_map(|location| match location {
+            Location::Score(Score::Var(name)) | Location::Storage(Storage::Var(name)) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/* Removes the functions that cannot be reached from an entry point, i.e. `main`, the `#[load]`, `#[tick]` and
+`#[test]` functions and the exported ones, along with the statics that no remaining function uses. A library
+package only adds the functions that are actually called to the datapack.
+
+Functions are only reached through calls, since a command cannot call a function that is not exported by its name.
+ */
+pub fn remove_unreachable_items(
+    modules: &mut [IRModule],
+    main: Option<&ResolvedName>,
+) -> RemovedItems {
+    let functions = modules
+        .iter()
+        .flat_map(|module| module.functions.iter())
+        .collect::<Vec<_>>();
+    let call_graph = CallGraph::new(&functions);
+
+    let mut reachable = vec![false; functions.len()];
+    let mut stack = (0..functions.len())
+        .filter(|index| is_entry_point(functions[*index], main))
+        .collect::<Vec<_>>();
+    while let Some(index) = stack.pop() {
+        if !reachable[index] {
+            reachable[index] = true;
+            stack.extend(call_graph.calls[index].iter().map(|call| call.callee));
+        }
+    }
+
+    let used_variables = functions
+        .iter()
+        .zip(reachable.iter())
+        .filter(|(_, is_reachable)| **is_reachable)
+        .flat_map(|(function, _)| variables(function))
+        .collect::<BTreeSet<_>>();
+
+    let mut removed = RemovedItems::default();
+    let mut reachable = reachable.into_iter();
+    for module in modules.iter_mut() {
+        let (kept, unreachable) = std::mem::take(&mut module.functions)
+            .into_iter()
+            .partition::<Vec<_>, _>(|_| reachable.next().unwrap());
+        module.functions = kept;
+        removed
+            .functions
+            .extend(unreachable.into_iter().map(|function| function.name));
+
+        let (kept, unused) = std::mem::take(&mut module.statics)
+            .into_iter()
+            .partition::<Vec<_>, _>(|name| used_variables.contains(name));
+        module.statics = kept;
+        removed.statics.extend(unused);
+    }
+    removed
+}
+
+fn is_register(location: &Location) -> bool {
+    matches!(
+        location,
+        Location::Score(Score::Reg(_)) | Location::Storage(Storage::Reg(_))
+    )
+}
+
+/* Removes the instructions that only write to registers that are never read afterwards, e.g. the operands of an
+operation that was folded into a constant. Variables are kept, since a function writes the parameters of the
+functions it calls, which may include its own.
+ */
+pub fn remove_dead_stores(function: &mut IRFunction) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let liveness = Liveness::new(function, is_register);
+
+        for (block, live_after) in function.blocks.iter_mut().zip(liveness.live_after) {
+            let count = block.instructions.len();
+            let mut live_after = live_after.into_iter();
+            block.instructions.retain(|instruction| {
+                let live = live_after.next().unwrap();
+                let is_pure = matches!(
+                    instruction,
+                    IRInstruction::ScoreSet(..)
+                        | IRInstruction::ScoreOperation(..)
+                        | IRInstruction::ScoreCompare(..)
+                        | IRInstruction::StorageSetString(..)
+                        | IRInstruction::StorageCopy(..)
+                );
+                let (_, defs) = uses_and_defs(instruction);
+                !is_pure
+                    || defs
+                        .iter()
+                        .any(|def| !is_register(def) || live.contains(def))
+            });
+            changed |= block.instructions.len() != count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_text::{parse_modules, print_function};
+
+    #[test]
+    fn test_remove_unreachable_items() {
+        let src = r#"
+        module pkg::main
+        static pkg::main+0:0:count
+        static pkg::main+0:0:unused
+        fn pkg::main+0:0:main() {
+        b0:
+            call pkg::lib+0:0:used
+            return
+        }
+        fn pkg::main+0:0:helper() {
+        b0:
+            pkg::main+0:0:unused = r0
+            return
+        }
+        #[tick]
+        fn pkg::main+0:0:on_tick() {
+        b0:
+            r0 = pkg::main+0:0:count
+            tail_call pkg::lib+0:0:tail
+        }
+        module pkg::lib
+        static pkg::lib+0:0:table
+        fn pkg::lib+0:0:used() {
+        b0:
+            call pkg::lib+0:0:used
+            return
+        }
+        fn pkg::lib+0:0:tail() {
+        b0:
+            return
+        }
+        fn pkg::lib+0:0:unused() {
+        b0:
+            call pkg::main+0:0:helper
+            return
+        }
+        "#;
+        let mut modules = parse_modules(src).unwrap();
+        let main = modules[0].functions[0].name.clone();
+        let removed = remove_unreachable_items(&mut modules, Some(&main));
+
+        let names = |names: &[ResolvedName]| {
+            names
+                .iter()
+                .map(|name| name.item_name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&removed.functions), ["0:0:helper", "0:0:unused"]);
+        assert_eq!(names(&removed.statics), ["0:0:unused", "0:0:table"]);
+        assert_eq!(names(&modules[0].statics), ["0:0:count"]);
+        assert_eq!(modules[0].functions.len(), 2);
+        assert_eq!(modules[1].functions.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_dead_stores() {
+        let src = r#"
+        module pkg::main
+        fn pkg::main+0:0:f() {
+        b0:
+            r0 = 6
+            r1 = 7
+            r0 = 42
+            r2 = r1
+            storage r3 = "unused"
+            pkg::main+0:0:s = r0
+            command "say hi"
+            r4 = 1
+            branch r4 b1 b1
+        b1:
+            ret = r0
+            return
+        }
+        "#;
+        let expected = r#"fn pkg::main+0:0:f() {
+b0:
+    r0 = 42
+    pkg::main+0:0:s = r0
+    command "say hi"
+    r4 = 1
+    branch r4 b1 b1
+b1:
+    ret = r0
+    return
+}
+"#;
+        let mut modules = parse_modules(src).unwrap();
+        let function = &mut modules[0].functions[0];
+        remove_dead_stores(function);
+        assert_eq!(print_function(function), expected);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::front::ast_types::{ResolvedName, StaticVarDef};
+use crate::front::ast_types::{Definition, FnDef, ResolvedName, StaticVarDef, StructDef};
 use crate::front::definition_table::DefinitionTable;
 use crate::modules::ModuleId;
 use std::collections::HashMap;
@@ -22,27 +22,56 @@ impl<'a> GlobalDefinitionTable<'a> {
         self.definition_tables.insert(module_id, definition_table);
     }
 
-    pub fn get_static_var_definition(&self, name: &ResolvedName) -> Option<&'a StaticVarDef> {
-        self.definition_tables
-            .get(&name.module_id)
-            .and_then(|definition_table| definition_table.static_var_map.get(name))
+    /* Imported names are resolved to the path they were imported from (e.g. `package_a::module_a` + `struct_a`),
+    while definitions are stored under the name given to them by the scope table (e.g. `0:0:struct_a`).
+
+    This maps an imported name to the name of the top level definition it refers to. Names that are already
+    canonical, or that cannot be found, are returned unchanged.
+     */
+    pub fn canonical_name(&self, name: &ResolvedName) -> ResolvedName {
+        let Some(definition_table) = self.definition_tables.get(&name.module_id) else {
+            return name.clone();
+        };
+        if definition_table.contains(name) {
+            return name.clone();
+        }
+        definition_table
+            .find_by_raw_name(&name.item_name)
+            .unwrap_or_else(|| name.clone())
     }
 
-    pub fn get_var_definition(&self, name: &ResolvedName) -> Option<&'a StaticVarDef> {
+    // any definition with the name, including the ones inside of functions and the ones that were imported
+    pub fn get_definition(&self, name: &ResolvedName) -> Option<Definition> {
+        let name = self.canonical_name(name);
+        let definition_table = self.definition_tables.get(&name.module_id)?;
+        if let Some(def) = definition_table.static_var_map.get(&name) {
+            Some(Definition::StaticVarDef(def.clone()))
+        } else if let Some(def) = definition_table.var_map.get(&name) {
+            Some(Definition::VarDef(def.clone()))
+        } else if let Some(def) = definition_table.struct_map.get(&name) {
+            Some(Definition::StructDef(def.clone()))
+        } else if let Some(def) = definition_table.fn_map.get(&name) {
+            Some(Definition::FnDef(def.clone()))
+        } else {
+            definition_table.find_local(&name)
+        }
+    }
+
+    pub fn get_static_var_definition(&self, name: &ResolvedName) -> Option<&'a StaticVarDef> {
         self.definition_tables
             .get(&name.module_id)
             .and_then(|definition_table| definition_table.static_var_map.get(name))
     }
 
-    pub fn get_struct_definition(&self, name: &ResolvedName) -> Option<&'a StaticVarDef> {
+    pub fn get_struct_definition(&self, name: &ResolvedName) -> Option<&'a StructDef> {
         self.definition_tables
             .get(&name.module_id)
-            .and_then(|definition_table| definition_table.static_var_map.get(name))
+            .and_then(|definition_table| definition_table.struct_map.get(name))
     }
 
-    pub fn get_fn_definition(&self, name: &ResolvedName) -> Option<&'a StaticVarDef> {
+    pub fn get_fn_definition(&self, name: &ResolvedName) -> Option<&'a FnDef> {
         self.definition_tables
             .get(&name.module_id)
-            .and_then(|definition_table| definition_table.static_var_map.get(name))
+            .and_then(|definition_table| definition_table.fn_map.get(name))
     }
 }
@@ -0,0 +1,395 @@
+use crate::front::ast_types::{Attribute, InlineHint, ResolvedName};
+use crate::middle::call_graph::CallGraph;
+use crate::middle::constant_folding::merge_blocks;
+use crate::middle::dead_code::is_entry_point;
+use crate::middle::types::{
+    BasicBlock, BlockId, IRFunction, IRInstruction, IRModule, Register, Score, Storage, Terminator,
+};
+use std::cell::Cell;
+
+fn inline_hint(function: &IRFunction) -> Option<InlineHint> {
+    function
+        .attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::Inline(hint) => Some(*hint),
+            _ => None,
+        })
+}
+
+// the functions of the program called by the function, once for every call
+fn callees(function: &IRFunction, call_graph: &CallGraph) -> Vec<usize> {
+    let mut callees = vec![];
+    for block in function.blocks.iter() {
+        for instruction in block.instructions.iter() {
+            if let IRInstruction::Call(name) = instruction {
+                callees.extend(call_graph.index(name));
+            }
+        }
+        if let Terminator::TailCall(name) = &block.terminator {
+            callees.extend(call_graph.index(name));
+        }
+    }
+    callees
+}
+
+fn size(function: &IRFunction) -> usize {
+    function
+        .blocks
+        .iter()
+        .map(|block| block.instructions.len())
+        .sum()
+}
+
+// the first register that the function does not use
+fn next_register(function: &mut IRFunction) -> Register {
+    // scores and storage are numbered together
+    let next = Cell::new(0);
+    let mut score = |score: &mut Score| {
+        if let Score::Reg(register) = score {
+            next.set(next.get().max(*register + 1));
+        }
+    };
+    let mut storage = |storage: &mut Storage| {
+        if let Storage::Reg(register) = storage {
+            next.set(next.get().max(*register + 1));
+        }
+    };
+    for block in function.blocks.iter_mut() {
+        for instruction in block.instructions.iter_mut() {
+            instruction.for_each_location_mut(&mut score, &mut storage);
+        }
+        if let Terminator::Branch(condition, _, _) = &mut block.terminator {
+            score(condition);
+        }
+    }
+    next.get()
+}
+
+/* Adds the blocks of the callee after the blocks of the caller, and returns the first of them. Its registers are
+renumbered after those of the caller, and when it returns it continues at the given block of the caller, if any.
+ */
+fn append_callee(
+    caller: &mut IRFunction,
+    callee: &IRFunction,
+    continuation: Option<BlockId>,
+) -> BlockId {
+    let register_offset = next_register(caller);
+    let block_offset = caller.blocks.len();
+    let mut score = |score: &mut Score| {
+        if let Score::Reg(register) = score {
+            *register += register_offset;
+        }
+    };
+    let mut storage = |storage: &mut Storage| {
+        if let Storage::Reg(register) = storage {
+            *register += register_offset;
+        }
+    };
+
+    for block in callee.blocks.iter() {
+        let mut block = block.clone();
+        for instruction in block.instructions.iter_mut() {
+            instruction.for_each_location_mut(&mut score, &mut storage);
+        }
+        block.terminator = match (block.terminator, continuation) {
+            (Terminator::Jump(target), _) => Terminator::Jump(target + block_offset),
+            (Terminator::Branch(mut condition, then_block, else_block), _) => {
+                score(&mut condition);
+                Terminator::Branch(
+                    condition,
+                    then_block + block_offset,
+                    else_block + block_offset,
+                )
+            }
+            (Terminator::Return, Some(continuation)) => Terminator::Jump(continuation),
+            (Terminator::TailCall(name), Some(continuation)) => {
+                block.instructions.push(IRInstruction::Call(name));
+                Terminator::Jump(continuation)
+            }
+            (terminator, None) => terminator,
+        };
+        caller.blocks.push(block);
+    }
+    block_offset
+}
+
+/* Replaces calls to small functions with their bodies, since every `function` command takes time in the game.
+
+A call is inlined if the callee is not recursive, and it either has at most `threshold` instructions, or it is only
+called once in the whole program and is not an entry point, so that it is not needed anymore afterwards. The
+`#[inline(always)]` and `#[inline(never)]` attributes override this, but recursive functions are never inlined, as
+are functions with assertions, which return from the function they are in when they fail.
+
+The inlined body keeps the variables of the callee, so it reads the arguments that were written to its parameters
+before the call, and the caller reads the return value after it, just like with the call. Functions are handled
+callees first, so that a function is inlined with the calls in it already inlined.
+ */
+pub fn inline_functions(modules: &mut [IRModule], main: Option<&ResolvedName>, threshold: usize) {
+    let (call_graph, mut functions) = {
+        let functions = modules
+            .iter()
+            .flat_map(|module| module.functions.iter())
+            .collect::<Vec<_>>();
+        let cloned = functions.iter().map(|function| (*function).clone());
+        (CallGraph::new(&functions), cloned.collect::<Vec<_>>())
+    };
+
+    let is_recursive = (0..functions.len())
+        .map(|index| {
+            call_graph.components[call_graph.component_of[index]].len() > 1
+                || call_graph.calls[index]
+                    .iter()
+                    .any(|call| call.callee == index)
+        })
+        .collect::<Vec<_>>();
+    let has_assertions = functions
+        .iter()
+        .map(|function| {
+            function.blocks.iter().any(|block| {
+                block
+                    .instructions
+                    .iter()
+                    .any(|instruction| matches!(instruction, IRInstruction::Assert(_)))
+            })
+        })
+        .collect::<Vec<_>>();
+    let is_entry_point = functions
+        .iter()
+        .map(|function| is_entry_point(function, main))
+        .collect::<Vec<_>>();
+    let mut call_counts = vec![0; functions.len()];
+    for function in functions.iter() {
+        for callee in callees(function, &call_graph) {
+            call_counts[callee] += 1;
+        }
+    }
+
+    for component in call_graph.components.iter().rev() {
+        for caller in component.iter().copied() {
+            // the function that a call would inline, if any
+            let inlined = |name: &ResolvedName, functions: &[IRFunction], call_counts: &[usize]| {
+                let callee = call_graph.index(name)?;
+                if callee == caller || is_recursive[callee] || has_assertions[callee] {
+                    return None;
+                }
+                let is_inlined = match inline_hint(&functions[callee]) {
+                    Some(InlineHint::Always) => true,
+                    Some(InlineHint::Never) => false,
+                    None => {
+                        size(&functions[callee]) <= threshold
+                            || (call_counts[callee] == 1 && !is_entry_point[callee])
+                    }
+                };
+                is_inlined.then_some(callee)
+            };
+
+            // blocks added by inlining are visited as well
+            let mut id = 0;
+            while id < functions[caller].blocks.len() {
+                let block = &functions[caller].blocks[id];
+                let call =
+                    block
+                        .instructions
+                        .iter()
+                        .enumerate()
+                        .find_map(|(index, instruction)| match instruction {
+                            IRInstruction::Call(name) => inlined(name, &functions, &call_counts)
+                                .map(|callee| (index, callee)),
+                            _ => None,
+                        });
+                let tail_call = match &block.terminator {
+                    Terminator::TailCall(name) => inlined(name, &functions, &call_counts),
+                    _ => None,
+                };
+
+                let callee = match (call, tail_call) {
+                    // the rest of the block continues in a new block after the inlined body
+                    (Some((index, callee)), _) => {
+                        let callee_function = functions[callee].clone();
+                        let function = &mut functions[caller];
+                        let block = &mut function.blocks[id];
+                        let rest = BasicBlock {
+                            instructions: block.instructions.split_off(index + 1),
+                            terminator: std::mem::replace(
+                                &mut block.terminator,
+                                Terminator::Return,
+                            ),
+                        };
+                        block.instructions.pop();
+                        function.blocks.push(rest);
+                        let continuation = function.blocks.len() - 1;
+                        let entry = append_callee(function, &callee_function, Some(continuation));
+                        function.blocks[id].terminator = Terminator::Jump(entry);
+                        callee
+                    }
+                    (None, Some(callee)) => {
+                        let callee_function = functions[callee].clone();
+                        let function = &mut functions[caller];
+                        let entry = append_callee(function, &callee_function, None);
+                        function.blocks[id].terminator = Terminator::Jump(entry);
+                        id += 1;
+                        callee
+                    }
+                    (None, None) => {
+                        id += 1;
+                        continue;
+                    }
+                };
+
+                // the calls in the inlined body are made from the caller now
+                call_counts[callee] -= 1;
+                for callee in callees(&functions[callee], &call_graph) {
+                    call_counts[callee] += 1;
+                }
+            }
+
+            let function = &mut functions[caller];
+            function.blocks = merge_blocks(std::mem::take(&mut function.blocks));
+        }
+    }
+
+    let mut functions = functions.into_iter();
+    for module in modules.iter_mut() {
+        for function in module.functions.iter_mut() {
+            *function = functions.next().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_text::{parse_modules, print_modules};
+
+    #[test]
+    fn test_inline_functions() {
+        let src = r#"
+        module pkg::main
+        fn pkg::main+0:0:main() {
+        b0:
+            r0 = 1
+            pkg::main+0:0:add+1:0:x = r0
+            call pkg::main+0:0:add
+            r1 = ret
+            call pkg::main+0:0:big
+            call pkg::main+0:0:fact
+            call pkg::main+0:0:never
+            tail_call pkg::main+0:0:once
+        }
+        fn pkg::main+0:0:add() {
+        b0:
+            r0 = 2
+            r0 += pkg::main+0:0:add+1:0:x
+            branch r0 b1 b2
+        b1:
+            ret = r0
+            return
+        b2:
+            tail_call pkg::main+0:0:fact
+        }
+        fn pkg::main+0:0:big() {
+        b0:
+            command "a"
+            command "b"
+            command "c"
+            return
+        }
+        fn pkg::main+0:0:other() {
+        b0:
+            call pkg::main+0:0:big
+            return
+        }
+        fn pkg::main+0:0:fact() {
+        b0:
+            call pkg::main+0:0:fact
+            return
+        }
+        fn pkg::main+0:0:once() {
+        b0:
+            r0 = 3
+            ret = r0
+            return
+        }
+        #[inline(never)]
+        fn pkg::main+0:0:never() {
+        b0:
+            return
+        }
+        "#;
+        /* add is small, and its return and tail call continue in the rest of main. big is too large and called twice,
+        fact is recursive, never is not inlined although it is empty, and once is only called once.
+         */
+        let expected = r#"
+        module pkg::main
+        fn pkg::main+0:0:main() {
+        b0:
+            r0 = 1
+            pkg::main+0:0:add+1:0:x = r0
+            r2 = 2
+            r2 += pkg::main+0:0:add+1:0:x
+            branch r2 b2 b3
+        b1:
+            r1 = ret
+            call pkg::main+0:0:big
+            call pkg::main+0:0:fact
+            call pkg::main+0:0:never
+            r3 = 3
+            ret = r3
+            return
+        b2:
+            ret = r2
+            jump b1
+        b3:
+            call pkg::main+0:0:fact
+            jump b1
+        }
+        fn pkg::main+0:0:add() {
+        b0:
+            r0 = 2
+            r0 += pkg::main+0:0:add+1:0:x
+            branch r0 b1 b2
+        b1:
+            ret = r0
+            return
+        b2:
+            tail_call pkg::main+0:0:fact
+        }
+        fn pkg::main+0:0:big() {
+        b0:
+            command "a"
+            command "b"
+            command "c"
+            return
+        }
+        fn pkg::main+0:0:other() {
+        b0:
+            call pkg::main+0:0:big
+            return
+        }
+        fn pkg::main+0:0:fact() {
+        b0:
+            call pkg::main+0:0:fact
+            return
+        }
+        fn pkg::main+0:0:once() {
+        b0:
+            r0 = 3
+            ret = r0
+            return
+        }
+        #[inline(never)]
+        fn pkg::main+0:0:never() {
+        b0:
+            return
+        }
+        "#;
+
+        let mut modules = parse_modules(src).unwrap();
+        inline_functions(&mut modules, None, 2);
+        assert_eq!(
+            print_modules(&modules),
+            print_modules(&parse_modules(expected).unwrap())
+        );
+    }
+}
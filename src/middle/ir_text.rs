@@ -0,0 +1,340 @@
+use crate::front::ast_types::{Attribute, ResolvedName};
+use crate::middle::types::{
+    CompareOperation, IRCommandPart, IRFunction, IRInstruction, IRModule, Location, Score,
+    ScoreOperation, Storage, Terminator,
+};
+
+// the IR is only parsed to write the IR of tests as text
+#[cfg(test)]
+mod parser;
+#[cfg(test)]
+pub use parser::{parse_modules, IRParseError};
+
+/* A textual form of the IR, so that the output of the middle end can be read and diffed, and tests can be written
+without building the IR by hand. Printing a module and parsing it again gives the same module.
+
+    module pkg::main
+    static pkg::main+0:0:count
+
+    #[load]
+    fn pkg::main+0:0:f(pkg::main+1:0:x) {
+        locals pkg::main+1:0:y
+    b0:
+        r0 = 5
+        pkg::main+1:0:x += r0
+        r1 = r0 < pkg::main+1:0:x
+        branch r1 b1 b2
+    b1:
+        storage r2 = "text"
+        interpolate "say " score(r0, 1) " " storage(r2)
+        jump b2
+    b2:
+        tail_call pkg::main+0:0:g
+    }
+
+The static variables of a module are listed right after it. Each instruction is on a line of its own, and every
+block is labeled with its index and ends with its terminator, which is one of `jump`, `branch`, `return` and
+`tail_call`. Names are written as `<module>+<item>`, registers as `r<n>` and the return value as `ret`, and whether a
+name refers to a score or to storage follows from the instruction. The parameters of a function are listed in its
+header, and its other variables on a `locals` line before the first block. Lines starting with `//` are comments.
+ */
+
+fn quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap()
+}
+
+fn name(name: &ResolvedName) -> String {
+    format!("{}+{}", name.module_id, name.item_name)
+}
+
+fn score(score: &Score) -> String {
+    match score {
+        Score::Var(var) => name(var),
+        Score::Reg(register) => format!("r{}", register),
+        Score::Return => "ret".to_string(),
+    }
+}
+
+fn storage(storage: &Storage) -> String {
+    match storage {
+        Storage::Var(var) => name(var),
+        Storage::Reg(register) => format!("r{}", register),
+        Storage::Return => "ret".to_string(),
+    }
+}
+
+fn score_operation(operation: ScoreOperation) -> &'static str {
+    match operation {
+        ScoreOperation::Assign => "=",
+        ScoreOperation::Add => "+=",
+        ScoreOperation::Sub => "-=",
+        ScoreOperation::Mul => "*=",
+        ScoreOperation::Div => "/=",
+        ScoreOperation::Mod => "%=",
+        ScoreOperation::Min => "min=",
+        ScoreOperation::Max => "max=",
+    }
+}
+
+fn compare_operation(operation: CompareOperation) -> &'static str {
+    match operation {
+        CompareOperation::Eq => "==",
+        CompareOperation::Ne => "!=",
+        CompareOperation::Lt => "<",
+        CompareOperation::Le => "<=",
+        CompareOperation::Gt => ">",
+        CompareOperation::Ge => ">=",
+    }
+}
+
+fn print_locations(locations: &[Location]) -> String {
+    locations
+        .iter()
+        .map(|location| match location {
+            Location::Score(s) => format!("score({})", score(s)),
+            Location::Storage(s) => format!("storage({})", storage(s)),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn print_instruction(instruction: &IRInstruction) -> String {
+    match instruction {
+        IRInstruction::MCommand(command) => format!("command {}", quote(command)),
+        IRInstruction::InterpolatedCommand(parts) => {
+            let parts = parts
+                .iter()
+                .map(|part| match part {
+                    IRCommandPart::Text(text) => quote(text),
+                    IRCommandPart::ScoreValue(s, scale) => {
+                        format!("score({}, {})", score(s), scale)
+                    }
+                    IRCommandPart::StorageValue(s) => format!("storage({})", storage(s)),
+                    IRCommandPart::ScoreLocation(s) => format!("&score({})", score(s)),
+                    IRCommandPart::StorageLocation(s) => format!("&storage({})", storage(s)),
+                })
+                .collect::<Vec<_>>();
+            format!("interpolate {}", parts.join(" "))
+        }
+        IRInstruction::ScoreSet(target, value) => format!("{} = {}", score(target), value),
+        IRInstruction::ScoreOperation(target, operation, source) => format!(
+            "{} {} {}",
+            score(target),
+            score_operation(*operation),
+            score(source)
+        ),
+        IRInstruction::ScoreCompare(target, lhs, operation, rhs) => format!(
+            "{} = {} {} {}",
+            score(target),
+            score(lhs),
+            compare_operation(*operation),
+            score(rhs)
+        ),
+        IRInstruction::StorageSetString(target, value) => {
+            format!("storage {} = {}", storage(target), quote(value))
+        }
+        IRInstruction::StorageCopy(target, source) => {
+            format!("storage {} = {}", storage(target), storage(source))
+        }
+        IRInstruction::Call(function) => format!("call {}", name(function)),
+        IRInstruction::Assert(assertion) => {
+            let mut line = format!(
+                "assert {} at {} {} {}",
+                score(&assertion.condition),
+                assertion.module_id,
+                assertion.offset,
+                quote(&assertion.message)
+            );
+            if let Some((lhs, rhs, scale)) = &assertion.values {
+                line.push_str(&format!(" values {} {} {}", score(lhs), score(rhs), scale));
+            }
+            line
+        }
+        IRInstruction::PushFrame(locations) => format!("push_frame {}", print_locations(locations)),
+        IRInstruction::PopFrame(locations) => format!("pop_frame {}", print_locations(locations)),
+    }
+}
+
+pub fn print_terminator(terminator: &Terminator) -> String {
+    match terminator {
+        Terminator::Jump(target) => format!("jump b{}", target),
+        Terminator::Branch(condition, then_block, else_block) => format!(
+            "branch {} b{} b{}",
+            score(condition),
+            then_block,
+            else_block
+        ),
+        Terminator::Return => "return".to_string(),
+        Terminator::TailCall(function) => format!("tail_call {}", name(function)),
+    }
+}
+
+fn print_attribute(attribute: &Attribute) -> String {
+    match attribute {
+        Attribute::Load => "#[load]".to_string(),
+        Attribute::Tick => "#[tick]".to_string(),
+        Attribute::Test => "#[test]".to_string(),
+        Attribute::Export(location) => format!("#[export({})]", quote(location)),
+        Attribute::Inline(hint) => format!("#[inline({})]", hint),
+    }
+}
+
+pub fn print_function(function: &IRFunction) -> String {
+    let mut text = String::new();
+    for attribute in function.attributes.iter() {
+        text.push_str(&print_attribute(attribute));
+        text.push('\n');
+    }
+    let params = function.params.iter().map(name).collect::<Vec<_>>();
+    text.push_str(&format!(
+        "fn {}({}) {{\n",
+        name(&function.name),
+        params.join(", ")
+    ));
+    if !function.locals.is_empty() {
+        let locals = function.locals.iter().map(name).collect::<Vec<_>>();
+        text.push_str(&format!("    locals {}\n", locals.join(" ")));
+    }
+    for (id, block) in function.blocks.iter().enumerate() {
+        text.push_str(&format!("b{}:\n", id));
+        for instruction in block.instructions.iter() {
+            text.push_str(&format!("    {}\n", print_instruction(instruction)));
+        }
+        text.push_str(&format!("    {}\n", print_terminator(&block.terminator)));
+    }
+    text.push_str("}\n");
+    text
+}
+
+pub fn print_module(module: &IRModule) -> String {
+    let mut text = format!("module {}\n", module.id);
+    for name in module.statics.iter() {
+        text.push_str(&format!("static {}\n", self::name(name)));
+    }
+    for function in module.functions.iter() {
+        text.push('\n');
+        text.push_str(&print_function(function));
+    }
+    text
+}
+
+// the modules one after another, separated by blank lines
+pub fn print_modules(modules: &[IRModule]) -> String {
+    modules
+        .iter()
+        .map(print_module)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = r#"module pkg::main
+static pkg::main+0:0:name
+static pkg::main+0:0:p
+
+#[load]
+#[export("map:start")]
+#[inline(never)]
+fn pkg::main+0:0:f(pkg::main+1:0:x, pkg::main+1:0:z) {
+    locals pkg::main+1:0:y
+b0:
+    r0 = -5
+    pkg::main+1:0:x = r0
+    r0 min= ret
+    r1 = r0 <= pkg::main+1:0:x
+    storage r2 = "say \"hi\"\n"
+    storage ret = pkg::main+0:0:name
+    command "say (a, b) & c"
+    interpolate "tp @s ~ " score(r0, 1000) " " &score(r1) storage(r2) &storage(pkg::main+0:0:p) ".x"
+    call pkg::util+0:0:g
+    assert r1 at pkg::main 42 "assert!(x < 1)"
+    assert r1 at pkg::main 57 "assert_eq!(a, b)" values r0 ret 1
+    push_frame score(r0) storage(r2)
+    pop_frame score(r0) storage(pkg::main+1:0:y)
+    branch r1 b1 b2
+b1:
+    jump b2
+b2:
+    tail_call pkg::util+0:0:g
+}
+
+fn pkg::main+1:0:empty() {
+b0:
+    return
+}
+
+module pkg::util
+
+#[test]
+fn pkg::util+0:0:g() {
+b0:
+    return
+}
+"#;
+
+    #[test]
+    fn test_round_trip() {
+        let modules = parse_modules(SRC).unwrap();
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0].functions[0].blocks.len(), 3);
+        assert_eq!(modules[0].functions[0].blocks[0].instructions.len(), 13);
+        assert_eq!(
+            modules[0].functions[0].blocks[0].instructions[5],
+            IRInstruction::StorageCopy(
+                Storage::Return,
+                Storage::Var(ResolvedName::new(
+                    "pkg::main".to_string(),
+                    "0:0:name".to_string()
+                ))
+            )
+        );
+        assert_eq!(print_modules(&modules), SRC);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |src: &str| parse_modules(src).unwrap_err();
+        assert_eq!(error("fn pkg+f() {\n}").line, 1);
+        assert_eq!(
+            error("module pkg\n// comment\nfn pkg+f() {\nb0:\n    r0 ?= r1\n}"),
+            IRParseError {
+                line: 5,
+                message: "Unknown operation ?=".to_string(),
+            }
+        );
+        assert_eq!(
+            error("module pkg\nfn pkg+f() {\nb0:\n    call f\n}").line,
+            4
+        );
+        assert_eq!(error("module pkg\nfn pkg+f() {\nb0:\n    return").line, 4);
+
+        assert_eq!(
+            error("module pkg\nfn pkg+f {\nb0:\n    return\n}").message,
+            "Expected the parameters"
+        );
+
+        // blocks are labeled in order, and end with a terminator
+        assert_eq!(
+            error("module pkg\nfn pkg+f() {\n    return\n}").message,
+            "Expected a block label"
+        );
+        assert_eq!(
+            error("module pkg\nfn pkg+f() {\nb0:\n    command \"a\"\nb1:\n    return\n}"),
+            IRParseError {
+                line: 5,
+                message: "Expected a terminator".to_string(),
+            }
+        );
+        assert_eq!(
+            error("module pkg\nfn pkg+f() {\nb1:\n    return\n}").message,
+            "Expected block b0"
+        );
+        assert_eq!(
+            error("module pkg\nfn pkg+f() {\nb0:\n    jump b1\n}").message,
+            "Unknown block b1"
+        );
+    }
+}
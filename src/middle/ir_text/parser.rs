@@ -0,0 +1,460 @@
+use crate::front::ast_types::{Attribute, InlineHint, ResolvedName};
+use crate::middle::types::{
+    Assertion, BasicBlock, CompareOperation, IRCommandPart, IRFunction, IRInstruction, IRModule,
+    Location, Score, ScoreOperation, Storage, Terminator,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct IRParseError {
+    pub line: usize, // starting from 1
+    pub message: String,
+}
+
+pub type IRParseResult<T> = Result<T, IRParseError>;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    String(String),
+    Symbol(char), // `(`, `)`, `,` or `&`
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, ch)) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => {}
+            '(' | ')' | ',' | '&' => tokens.push(Token::Symbol(ch)),
+            '"' => {
+                // the string ends at the first quote that is not escaped
+                let mut escaped = false;
+                let end = loop {
+                    match chars.next() {
+                        Some((end, '"')) if !escaped => break end,
+                        Some((_, ch)) => escaped = ch == '\\' && !escaped,
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                };
+                let text = serde_json::from_str(&line[start..=end])
+                    .map_err(|_| format!("Invalid string {}", &line[start..=end]))?;
+                tokens.push(Token::String(text));
+            }
+            _ => {
+                let mut end = start + ch.len_utf8();
+                while let Some((index, ch)) = chars.peek() {
+                    if ch.is_whitespace() || matches!(ch, '(' | ')' | ',' | '&' | '"') {
+                        break;
+                    }
+                    end = index + ch.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(line[start..end].to_string()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// the tokens of a single line
+struct Tokens {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Tokens {
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.index)
+            .cloned()
+            .ok_or("Unexpected end of line")?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn is_done(&self) -> bool {
+        self.index >= self.tokens.len()
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Word(word) => Ok(word),
+            token => Err(format!("Expected a word, found {:?}", token)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::String(text) => Ok(text),
+            token => Err(format!("Expected a string, found {:?}", token)),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(format!("Expected {:?}, found {:?}", expected, token))
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| format!("Expected a number, found {}", word))
+    }
+
+    fn end(&self) -> Result<(), String> {
+        match self.tokens.get(self.index) {
+            None => Ok(()),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+        }
+    }
+}
+
+fn parse_name(word: &str) -> Result<ResolvedName, String> {
+    word.split_once('+')
+        .map(|(module_id, item_name)| {
+            ResolvedName::new(module_id.to_string(), item_name.to_string())
+        })
+        .ok_or_else(|| format!("Expected a name like module+item, found {}", word))
+}
+
+// a register, the return value or a name
+fn parse_score(word: &str) -> Result<Score, String> {
+    if word == "ret" {
+        return Ok(Score::Return);
+    }
+    if let Some(register) = word.strip_prefix('r').and_then(|n| n.parse().ok()) {
+        return Ok(Score::Reg(register));
+    }
+    Ok(Score::Var(parse_name(word)?))
+}
+
+// written the same way as scores
+fn parse_storage(word: &str) -> Result<Storage, String> {
+    Ok(match parse_score(word)? {
+        Score::Var(name) => Storage::Var(name),
+        Score::Reg(register) => Storage::Reg(register),
+        Score::Return => Storage::Return,
+    })
+}
+
+fn parse_part(tokens: &mut Tokens) -> Result<IRCommandPart, String> {
+    let is_location = match tokens.next()? {
+        Token::String(text) => return Ok(IRCommandPart::Text(text)),
+        Token::Symbol('&') => true,
+        _ => {
+            tokens.index -= 1;
+            false
+        }
+    };
+
+    let kind = tokens.word()?;
+    tokens.expect(Token::Symbol('('))?;
+    let location = tokens.word()?;
+    let part = match (kind.as_str(), is_location) {
+        ("score", true) => IRCommandPart::ScoreLocation(parse_score(&location)?),
+        ("storage", true) => IRCommandPart::StorageLocation(parse_storage(&location)?),
+        ("score", false) => {
+            tokens.expect(Token::Symbol(','))?;
+            IRCommandPart::ScoreValue(parse_score(&location)?, tokens.number()?)
+        }
+        ("storage", false) => IRCommandPart::StorageValue(parse_storage(&location)?),
+        _ => return Err(format!("Expected `score` or `storage`, found {}", kind)),
+    };
+    tokens.expect(Token::Symbol(')'))?;
+    Ok(part)
+}
+
+// a line inside a block
+enum Line {
+    Instruction(IRInstruction),
+    Terminator(Terminator),
+}
+
+fn parse_block_id(word: &str) -> Result<usize, String> {
+    word.strip_prefix('b')
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| format!("Expected a block like b0, found {}", word))
+}
+
+fn parse_line(line: &str) -> Result<Line, String> {
+    let mut tokens = Tokens {
+        tokens: tokenize(line)?,
+        index: 0,
+    };
+
+    let first = tokens.word()?;
+    let terminator = match first.as_str() {
+        "jump" => Some(Terminator::Jump(parse_block_id(&tokens.word()?)?)),
+        "branch" => Some(Terminator::Branch(
+            parse_score(&tokens.word()?)?,
+            parse_block_id(&tokens.word()?)?,
+            parse_block_id(&tokens.word()?)?,
+        )),
+        "return" => Some(Terminator::Return),
+        "tail_call" => Some(Terminator::TailCall(parse_name(&tokens.word()?)?)),
+        _ => None,
+    };
+    if let Some(terminator) = terminator {
+        tokens.end()?;
+        return Ok(Line::Terminator(terminator));
+    }
+
+    let instruction = match first.as_str() {
+        "command" => IRInstruction::MCommand(tokens.string()?),
+        "interpolate" => {
+            let mut parts = vec![];
+            while !tokens.is_done() {
+                parts.push(parse_part(&mut tokens)?);
+            }
+            IRInstruction::InterpolatedCommand(parts)
+        }
+        "storage" => {
+            let target = parse_storage(&tokens.word()?)?;
+            tokens.expect(Token::Word("=".to_string()))?;
+            match tokens.next()? {
+                Token::String(text) => IRInstruction::StorageSetString(target, text),
+                Token::Word(source) => IRInstruction::StorageCopy(target, parse_storage(&source)?),
+                token => return Err(format!("Unexpected {:?}", token)),
+            }
+        }
+        "call" => IRInstruction::Call(parse_name(&tokens.word()?)?),
+        "push_frame" | "pop_frame" => {
+            let mut locations = vec![];
+            while !tokens.is_done() {
+                let kind = tokens.word()?;
+                tokens.expect(Token::Symbol('('))?;
+                let location = tokens.word()?;
+                tokens.expect(Token::Symbol(')'))?;
+                locations.push(match kind.as_str() {
+                    "score" => Location::Score(parse_score(&location)?),
+                    "storage" => Location::Storage(parse_storage(&location)?),
+                    _ => return Err(format!("Expected `score` or `storage`, found {}", kind)),
+                });
+            }
+            if first == "push_frame" {
+                IRInstruction::PushFrame(locations)
+            } else {
+                IRInstruction::PopFrame(locations)
+            }
+        }
+        "assert" => {
+            let condition = parse_score(&tokens.word()?)?;
+            tokens.expect(Token::Word("at".to_string()))?;
+            let module_id = tokens.word()?;
+            let offset = tokens.number()?;
+            let message = tokens.string()?;
+            let values = if tokens.is_done() {
+                None
+            } else {
+                tokens.expect(Token::Word("values".to_string()))?;
+                Some((
+                    parse_score(&tokens.word()?)?,
+                    parse_score(&tokens.word()?)?,
+                    tokens.number()?,
+                ))
+            };
+            IRInstruction::Assert(Assertion {
+                condition,
+                values,
+                module_id,
+                offset,
+                message,
+            })
+        }
+        target => {
+            let target = parse_score(target)?;
+            let operation = match tokens.word()?.as_str() {
+                "=" => ScoreOperation::Assign,
+                "+=" => ScoreOperation::Add,
+                "-=" => ScoreOperation::Sub,
+                "*=" => ScoreOperation::Mul,
+                "/=" => ScoreOperation::Div,
+                "%=" => ScoreOperation::Mod,
+                "min=" => ScoreOperation::Min,
+                "max=" => ScoreOperation::Max,
+                operation => return Err(format!("Unknown operation {}", operation)),
+            };
+            let source = tokens.word()?;
+
+            if operation != ScoreOperation::Assign {
+                IRInstruction::ScoreOperation(target, operation, parse_score(&source)?)
+            } else if let Ok(value) = source.parse() {
+                IRInstruction::ScoreSet(target, value)
+            } else if tokens.is_done() {
+                IRInstruction::ScoreOperation(target, operation, parse_score(&source)?)
+            } else {
+                let operation = match tokens.word()?.as_str() {
+                    "==" => CompareOperation::Eq,
+                    "!=" => CompareOperation::Ne,
+                    "<" => CompareOperation::Lt,
+                    "<=" => CompareOperation::Le,
+                    ">" => CompareOperation::Gt,
+                    ">=" => CompareOperation::Ge,
+                    operation => return Err(format!("Unknown comparison {}", operation)),
+                };
+                IRInstruction::ScoreCompare(
+                    target,
+                    parse_score(&source)?,
+                    operation,
+                    parse_score(&tokens.word()?)?,
+                )
+            }
+        }
+    };
+    tokens.end()?;
+    Ok(Line::Instruction(instruction))
+}
+
+fn parse_attribute(line: &str) -> Result<Attribute, String> {
+    let inner = line
+        .strip_prefix("#[")
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(|| format!("Invalid attribute {}", line))?;
+    Ok(match inner {
+        "load" => Attribute::Load,
+        "tick" => Attribute::Tick,
+        "test" => Attribute::Test,
+        "inline(always)" => Attribute::Inline(InlineHint::Always),
+        "inline(never)" => Attribute::Inline(InlineHint::Never),
+        _ => {
+            let location = inner
+                .strip_prefix("export(")
+                .and_then(|inner| inner.strip_suffix(')'))
+                .ok_or_else(|| format!("Unknown attribute {}", inner))?;
+            Attribute::Export(
+                serde_json::from_str(location)
+                    .map_err(|_| format!("Invalid string {}", location))?,
+            )
+        }
+    })
+}
+
+// parses modules written by `print_modules`
+pub fn parse_modules(src: &str) -> IRParseResult<Vec<IRModule>> {
+    let mut modules: Vec<IRModule> = vec![];
+    let mut attributes = vec![];
+    let mut function: Option<IRFunction> = None;
+    let mut terminated = true; // whether the last block of the function has its terminator
+
+    for (index, line) in src.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| IRParseError {
+            line: index + 1,
+            message,
+        };
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(current) = function.as_mut() {
+            let is_label = line.ends_with(':');
+            if (line == "}" || is_label) && !terminated {
+                return Err(error("Expected a terminator".to_string()));
+            }
+
+            if line == "}" {
+                let count = current.blocks.len();
+                let target = current
+                    .blocks
+                    .iter()
+                    .flat_map(|block| match &block.terminator {
+                        Terminator::Jump(target) => vec![*target],
+                        Terminator::Branch(_, then_block, else_block) => {
+                            vec![*then_block, *else_block]
+                        }
+                        Terminator::Return | Terminator::TailCall(_) => vec![],
+                    })
+                    .find(|target| *target >= count);
+                if let Some(target) = target {
+                    return Err(error(format!("Unknown block b{}", target)));
+                }
+                modules
+                    .last_mut()
+                    .unwrap()
+                    .functions
+                    .push(function.take().unwrap());
+            } else if is_label {
+                let id = parse_block_id(line.strip_suffix(':').unwrap()).map_err(error)?;
+                if id != current.blocks.len() {
+                    return Err(error(format!("Expected block b{}", current.blocks.len())));
+                }
+                current.blocks.push(BasicBlock {
+                    instructions: vec![],
+                    terminator: Terminator::Return,
+                });
+                terminated = false;
+            } else if let (Some(locals), true) =
+                (line.strip_prefix("locals "), current.blocks.is_empty())
+            {
+                current.locals = locals
+                    .split_whitespace()
+                    .map(parse_name)
+                    .collect::<Result<_, _>>()
+                    .map_err(error)?;
+            } else if terminated {
+                return Err(error("Expected a block label".to_string()));
+            } else {
+                let block = current.blocks.last_mut().unwrap();
+                match parse_line(line).map_err(error)? {
+                    Line::Instruction(instruction) => block.instructions.push(instruction),
+                    Line::Terminator(terminator) => {
+                        block.terminator = terminator;
+                        terminated = true;
+                    }
+                }
+            }
+        } else if let Some(id) = line.strip_prefix("module ") {
+            modules.push(IRModule {
+                id: id.trim().to_string(),
+                statics: vec![],
+                functions: vec![],
+            });
+        } else if modules.is_empty() {
+            return Err(error("Expected a module".to_string()));
+        } else if let Some(name) = line.strip_prefix("static ") {
+            let name = parse_name(name.trim()).map_err(error)?;
+            modules.last_mut().unwrap().statics.push(name);
+        } else if line.starts_with("#[") {
+            attributes.push(parse_attribute(line).map_err(error)?);
+        } else if let Some(header) = line.strip_prefix("fn ") {
+            let signature = header
+                .strip_suffix('{')
+                .ok_or_else(|| error("Expected `{`".to_string()))?;
+            let (name, params) = signature
+                .trim()
+                .strip_suffix(')')
+                .and_then(|signature| signature.split_once('('))
+                .ok_or_else(|| error("Expected the parameters".to_string()))?;
+            let params = params
+                .split(',')
+                .map(str::trim)
+                .filter(|param| !param.is_empty())
+                .map(parse_name)
+                .collect::<Result<_, _>>()
+                .map_err(error)?;
+            function = Some(IRFunction {
+                name: parse_name(name).map_err(error)?,
+                attributes: std::mem::take(&mut attributes),
+                params,
+                locals: vec![],
+                blocks: vec![],
+            });
+        } else {
+            return Err(error(format!("Unexpected {}", line)));
+        }
+    }
+
+    if function.is_some() {
+        return Err(IRParseError {
+            line: src.lines().count(),
+            message: "Expected `}`".to_string(),
+        });
+    }
+    Ok(modules)
+}
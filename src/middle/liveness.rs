@@ -0,0 +1,145 @@
+use crate::middle::types::{
+    BlockId, IRCommandPart, IRFunction, IRInstruction, Location, Score, ScoreOperation, Storage,
+    Terminator,
+};
+use std::collections::BTreeSet;
+
+pub fn successors(terminator: &Terminator) -> Vec<BlockId> {
+    match terminator {
+        Terminator::Jump(target) => vec![*target],
+        Terminator::Branch(_, then_block, else_block) => vec![*then_block, *else_block],
+        Terminator::Return | Terminator::TailCall(_) => vec![],
+    }
+}
+
+// the locations read and written by an instruction, where a call only writes the return value
+pub fn uses_and_defs(instruction: &IRInstruction) -> (Vec<Location>, Vec<Location>) {
+    let score = |score: &Score| Location::Score(score.clone());
+    let storage = |storage: &Storage| Location::Storage(storage.clone());
+
+    match instruction {
+        IRInstruction::MCommand(_) => (vec![], vec![]),
+        IRInstruction::InterpolatedCommand(parts) => {
+            let mut uses = vec![];
+            let mut defs = vec![];
+            for part in parts.iter() {
+                match part {
+                    IRCommandPart::Text(_) => {}
+                    IRCommandPart::ScoreValue(s, _) => uses.push(score(s)),
+                    IRCommandPart::StorageValue(s) => uses.push(storage(s)),
+                    // the command may write to the location as well as read from it
+                    IRCommandPart::ScoreLocation(s) => {
+                        uses.push(score(s));
+                        defs.push(score(s));
+                    }
+                    IRCommandPart::StorageLocation(s) => {
+                        uses.push(storage(s));
+                        defs.push(storage(s));
+                    }
+                }
+            }
+            (uses, defs)
+        }
+        IRInstruction::ScoreSet(target, _) => (vec![], vec![score(target)]),
+        IRInstruction::ScoreOperation(target, ScoreOperation::Assign, source) => {
+            (vec![score(source)], vec![score(target)])
+        }
+        IRInstruction::ScoreOperation(target, _, source) => {
+            (vec![score(target), score(source)], vec![score(target)])
+        }
+        IRInstruction::ScoreCompare(target, lhs, _, rhs) => {
+            (vec![score(lhs), score(rhs)], vec![score(target)])
+        }
+        IRInstruction::StorageSetString(target, _) => (vec![], vec![storage(target)]),
+        IRInstruction::StorageCopy(target, source) => {
+            (vec![storage(source)], vec![storage(target)])
+        }
+        IRInstruction::Call(_) => (
+            vec![],
+            vec![score(&Score::Return), storage(&Storage::Return)],
+        ),
+        IRInstruction::Assert(assertion) => {
+            let mut uses = vec![score(&assertion.condition)];
+            if let Some((lhs, rhs, _)) = &assertion.values {
+                uses.extend([score(lhs), score(rhs)]);
+            }
+            (uses, vec![])
+        }
+        IRInstruction::PushFrame(locations) => (locations.clone(), vec![]),
+        IRInstruction::PopFrame(locations) => (vec![], locations.clone()),
+    }
+}
+
+// the return value is read by the caller
+pub fn terminator_uses(terminator: &Terminator) -> Vec<Location> {
+    match terminator {
+        Terminator::Branch(condition, _, _) => vec![Location::Score(condition.clone())],
+        Terminator::Return => vec![
+            Location::Score(Score::Return),
+            Location::Storage(Storage::Return),
+        ],
+        Terminator::Jump(_) | Terminator::TailCall(_) => vec![],
+    }
+}
+
+/* The locations whose values are still needed at each point of a function, i.e. that may be read before they are
+written again. Only the locations accepted by the filter are tracked.
+ */
+pub struct Liveness {
+    pub live_in: Vec<BTreeSet<Location>>, // at the start of each block
+    pub live_after: Vec<Vec<BTreeSet<Location>>>, // after each instruction of each block
+}
+
+impl Liveness {
+    pub fn new(function: &IRFunction, filter: impl Fn(&Location) -> bool) -> Liveness {
+        let blocks = &function.blocks;
+        let tracked = |locations: Vec<Location>| {
+            locations
+                .into_iter()
+                .filter(|location| filter(location))
+                .collect::<Vec<_>>()
+        };
+
+        // goes backwards through the block, from the locations needed at its end
+        let walk = |block: BlockId, live_in: &Vec<BTreeSet<Location>>| {
+            let mut live = successors(&blocks[block].terminator)
+                .iter()
+                .flat_map(|successor| live_in[*successor].iter().cloned())
+                .collect::<BTreeSet<_>>();
+            live.extend(tracked(terminator_uses(&blocks[block].terminator)));
+
+            let mut live_after = vec![BTreeSet::new(); blocks[block].instructions.len()];
+            for (index, instruction) in blocks[block].instructions.iter().enumerate().rev() {
+                live_after[index] = live.clone();
+                let (uses, defs) = uses_and_defs(instruction);
+                for def in tracked(defs) {
+                    live.remove(&def);
+                }
+                live.extend(tracked(uses));
+            }
+            (live, live_after)
+        };
+
+        // until nothing changes
+        let mut live_in = vec![BTreeSet::new(); blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in (0..blocks.len()).rev() {
+                let (live, _) = walk(block, &live_in);
+                if live != live_in[block] {
+                    live_in[block] = live;
+                    changed = true;
+                }
+            }
+        }
+
+        let live_after = (0..blocks.len())
+            .map(|block| walk(block, &live_in).1)
+            .collect();
+        Liveness {
+            live_in,
+            live_after,
+        }
+    }
+}
@@ -0,0 +1,786 @@
+use crate::front::ast_types::{
+    Assert, AssertKind, BinOp, Command, CommandPart, Definition, Expression, FnCall, FnDef, If,
+    Literal, Module, ResolvedName, Statement, Type, UnOp, VarAssign, While,
+};
+use crate::front::formatter::format_assert;
+use crate::middle::global_definition_table::GlobalDefinitionTable;
+use crate::middle::types::{
+    Assertion, BasicBlock, BlockId, CompareOperation, IRCommandPart, IRFunction, IRInstruction,
+    Register, Score, ScoreOperation, Storage, Terminator,
+};
+use crate::middle::{IRGenError, IRGenOptions, IRGenResult};
+use crate::modules::ModuleId;
+use std::collections::HashMap;
+
+// function definitions that are not visible through the global definition table (e.g. nested functions)
+pub type LocalFunctions<'a> = HashMap<ResolvedName, &'a FnDef>;
+
+// collects every function definition nested inside the given module, including the ones in nested scopes
+pub fn collect_nested_functions<'a>(module: &'a Module, functions: &mut LocalFunctions<'a>) {
+    for definition in module.definitions.iter().flatten() {
+        if let Definition::FnDef(fn_def) = definition {
+            functions.insert(fn_def.name.resolved.clone().unwrap(), fn_def);
+            collect_nested_functions(&fn_def.body, functions);
+        }
+    }
+    for block in module.statements.iter().flat_map(Statement::blocks) {
+        collect_nested_functions(block, functions);
+    }
+}
+
+fn collect_local_types(module: &Module, local_types: &mut HashMap<ResolvedName, Type>) {
+    for definition in module.definitions.iter().flatten() {
+        if let Definition::VarDef(var_def) = definition {
+            local_types.insert(var_def.name.resolved.clone().unwrap(), var_def.ty.clone());
+        }
+    }
+    for block in module.statements.iter().flat_map(Statement::blocks) {
+        collect_local_types(block, local_types);
+    }
+}
+
+/* Lowers the body of a single function to scoreboard instructions.
+
+Ints are stored as is. Floats are stored in fixed-point, as the value multiplied by the float scale. Adding, subtracting
+and taking the modulo of two fixed-point numbers works the same as with ints, but multiplication and division have to
+be rescaled.
+
+Control flow splits the function into basic blocks. Instructions are added to the current block, until it is ended by
+a terminator and lowering continues in another block. The code after a `return` is put in a new block that nothing
+jumps to, and such blocks are removed once the whole function is lowered.
+ */
+pub struct FunctionLowering<'a, 'b> {
+    global_definition_table: &'b GlobalDefinitionTable<'a>,
+    local_functions: &'b LocalFunctions<'b>,
+    options: &'b IRGenOptions,
+
+    module_id: ModuleId,
+    local_types: HashMap<ResolvedName, Type>,
+    return_type: Type,
+
+    blocks: Vec<BasicBlock>,
+    current_block: BlockId,
+    next_register: Register,
+}
+
+impl<'a, 'b> FunctionLowering<'a, 'b> {
+    pub fn new(
+        global_definition_table: &'b GlobalDefinitionTable<'a>,
+        local_functions: &'b LocalFunctions<'b>,
+        options: &'b IRGenOptions,
+    ) -> FunctionLowering<'a, 'b> {
+        FunctionLowering {
+            global_definition_table,
+            local_functions,
+            options,
+            module_id: ModuleId::new(),
+            local_types: HashMap::new(),
+            return_type: Type::Void,
+            blocks: vec![BasicBlock {
+                instructions: vec![],
+                terminator: Terminator::Return,
+            }],
+            current_block: 0,
+            next_register: 0,
+        }
+    }
+
+    pub fn lower_fn(mut self, fn_def: &FnDef) -> IRGenResult<IRFunction> {
+        for arg in fn_def.args.iter() {
+            self.local_types
+                .insert(arg.name.resolved.clone().unwrap(), arg.ty.clone());
+        }
+        collect_local_types(&fn_def.body, &mut self.local_types);
+        self.return_type = fn_def.return_type.clone();
+        self.module_id = fn_def.name.resolved.as_ref().unwrap().module_id.clone();
+
+        self.lower_module(&fn_def.body)?;
+
+        let params = fn_def
+            .args
+            .iter()
+            .map(|arg| arg.name.resolved.clone().unwrap())
+            .collect::<Vec<_>>();
+        let mut locals = self
+            .local_types
+            .into_keys()
+            .filter(|name| !params.contains(name))
+            .collect::<Vec<_>>();
+        locals.sort();
+
+        Ok(IRFunction {
+            name: fn_def.name.resolved.clone().unwrap(),
+            attributes: fn_def.attributes.clone(),
+            params,
+            locals,
+            blocks: remove_unreachable_blocks(self.blocks),
+        })
+    }
+
+    fn new_register(&mut self) -> Score {
+        let register = self.next_register;
+        self.next_register += 1;
+        Score::Reg(register)
+    }
+
+    fn emit(&mut self, instruction: IRInstruction) {
+        self.blocks[self.current_block]
+            .instructions
+            .push(instruction);
+    }
+
+    // blocks return unless they are terminated otherwise
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock {
+            instructions: vec![],
+            terminator: Terminator::Return,
+        });
+        self.blocks.len() - 1
+    }
+
+    // ends the current block, and continues lowering in the given one
+    fn terminate(&mut self, terminator: Terminator, next: BlockId) {
+        self.blocks[self.current_block].terminator = terminator;
+        self.current_block = next;
+    }
+
+    fn emit_operation(&mut self, target: &Score, operation: ScoreOperation, source: &Score) {
+        self.emit(IRInstruction::ScoreOperation(
+            target.clone(),
+            operation,
+            source.clone(),
+        ));
+    }
+
+    // creates a new register holding the given constant
+    fn constant(&mut self, value: i32) -> Score {
+        let register = self.new_register();
+        self.emit(IRInstruction::ScoreSet(register.clone(), value));
+        register
+    }
+
+    // creates a new register holding a copy of the given score
+    fn copy(&mut self, source: &Score) -> Score {
+        let register = self.new_register();
+        self.emit_operation(&register, ScoreOperation::Assign, source);
+        register
+    }
+
+    fn lower_module(&mut self, module: &Module) -> IRGenResult<()> {
+        for statement in module.statements.iter() {
+            self.lower_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn lower_statement(&mut self, statement: &Statement) -> IRGenResult<()> {
+        match statement {
+            Statement::VarAssign(var_assign) => self.lower_var_assign(var_assign),
+            Statement::FnCall(fn_call) => self.lower_fn_call(fn_call).map(|_| ()),
+            Statement::Command(command) => self.lower_command(command),
+            Statement::Assert(assert) => self.lower_assert(assert),
+            Statement::Return(expr) => self.lower_return(expr.as_ref()),
+            Statement::If(if_statement) => self.lower_if(if_statement),
+            Statement::While(while_statement) => self.lower_while(while_statement),
+            Statement::Module(module) => self.lower_module(module),
+        }
+    }
+
+    fn lower_return(&mut self, expr: Option<&Expression>) -> IRGenResult<()> {
+        let (terminator, ty) = match expr {
+            // the callee already leaves its return value where the caller returns it from
+            Some(Expression::FnCall(fn_call)) => {
+                let (fn_name, ty) = self.lower_call_arguments(fn_call)?;
+                (Terminator::TailCall(fn_name), ty)
+            }
+            Some(expr) => {
+                let (value, ty) = self.lower_expression(expr)?;
+                let return_value = return_value(&ty)?;
+                self.assign(&return_value, &value);
+                (Terminator::Return, ty)
+            }
+            None => (Terminator::Return, Type::Void),
+        };
+        self.expect_type(&self.return_type, &ty)?;
+
+        let unreachable = self.new_block();
+        self.terminate(terminator, unreachable);
+        Ok(())
+    }
+
+    // a bool expression, evaluated in the current block
+    fn lower_condition(&mut self, condition: &Expression) -> IRGenResult<Score> {
+        let (value, ty) = self.lower_expression(condition)?;
+        self.expect_type(&Type::Bool, &ty)?;
+        let Value::Score(condition) = value else {
+            unreachable!("Bools are stored in scores")
+        };
+        Ok(condition)
+    }
+
+    fn lower_if(&mut self, if_statement: &If) -> IRGenResult<()> {
+        let condition = self.lower_condition(&if_statement.condition)?;
+        let then_block = self.new_block();
+        let else_block = if_statement.else_body.as_ref().map(|_| self.new_block());
+        let end_block = self.new_block();
+
+        self.terminate(
+            Terminator::Branch(condition, then_block, else_block.unwrap_or(end_block)),
+            then_block,
+        );
+        self.lower_module(&if_statement.body)?;
+        self.terminate(Terminator::Jump(end_block), end_block);
+
+        if let (Some(else_block), Some(else_body)) = (else_block, &if_statement.else_body) {
+            self.current_block = else_block;
+            self.lower_statement(else_body)?;
+            self.terminate(Terminator::Jump(end_block), end_block);
+        }
+        Ok(())
+    }
+
+    fn lower_while(&mut self, while_statement: &While) -> IRGenResult<()> {
+        let header_block = self.new_block();
+        let body_block = self.new_block();
+        let end_block = self.new_block();
+
+        self.terminate(Terminator::Jump(header_block), header_block);
+        let condition = self.lower_condition(&while_statement.condition)?;
+        self.terminate(
+            Terminator::Branch(condition, body_block, end_block),
+            body_block,
+        );
+        self.lower_module(&while_statement.body)?;
+        self.terminate(Terminator::Jump(header_block), end_block);
+        Ok(())
+    }
+
+    fn lower_var_assign(&mut self, var_assign: &VarAssign) -> IRGenResult<()> {
+        let name = self.var_name(var_assign.name.resolved.as_ref().unwrap());
+        let var_ty = self.var_type(&name)?;
+        let var_value = var_value(name, &var_ty)?;
+
+        let (value, ty) = self.lower_expression(&var_assign.expr)?;
+        self.expect_type(&var_ty, &ty)?;
+
+        self.assign(&var_value, &value);
+        Ok(())
+    }
+
+    fn expect_type(&self, expected: &Type, found: &Type) -> IRGenResult<()> {
+        let same = match (expected, found) {
+            // the same struct may be referred to by different (e.g. imported) names
+            (Type::Struct(expected), Type::Struct(found)) => {
+                self.global_definition_table
+                    .canonical_name(expected.resolved.as_ref().unwrap())
+                    == self
+                        .global_definition_table
+                        .canonical_name(found.resolved.as_ref().unwrap())
+            }
+            _ => expected == found,
+        };
+
+        if same {
+            Ok(())
+        } else {
+            Err(IRGenError::TypeMismatch(
+                Box::new(expected.clone()),
+                Box::new(found.clone()),
+            ))
+        }
+    }
+
+    fn lower_command(&mut self, command: &Command) -> IRGenResult<()> {
+        let mut parts = vec![];
+        for part in command.parts.iter() {
+            parts.push(match part {
+                CommandPart::Text(text) => IRCommandPart::Text(text.clone()),
+                CommandPart::Value(var) | CommandPart::Location(var) => {
+                    let name = self.var_name(var.resolved.as_ref().unwrap());
+                    let ty = self.var_type(&name)?;
+                    let is_location = matches!(part, CommandPart::Location(_));
+
+                    match (var_value(name, &ty)?, &ty) {
+                        (Value::Score(score), _) if is_location => {
+                            IRCommandPart::ScoreLocation(score)
+                        }
+                        (Value::Score(score), Type::Float) => {
+                            IRCommandPart::ScoreValue(score, self.options.float_scale)
+                        }
+                        (Value::Score(score), _) => IRCommandPart::ScoreValue(score, 1),
+                        // structs have no textual value, so they are always referred to by their storage path
+                        (Value::Storage(storage), Type::Struct(_)) => {
+                            IRCommandPart::StorageLocation(storage)
+                        }
+                        (Value::Storage(storage), _) if is_location => {
+                            IRCommandPart::StorageLocation(storage)
+                        }
+                        (Value::Storage(storage), _) => IRCommandPart::StorageValue(storage),
+                        (Value::Void, _) => unreachable!("Variables cannot be void"),
+                    }
+                }
+            });
+        }
+
+        if let [IRCommandPart::Text(text)] = &parts[..] {
+            self.emit(IRInstruction::MCommand(text.clone()));
+        } else if !parts.is_empty() {
+            self.emit(IRInstruction::InterpolatedCommand(parts));
+        }
+        Ok(())
+    }
+
+    fn lower_assert(&mut self, assert: &Assert) -> IRGenResult<()> {
+        let (condition, values) = match &assert.kind {
+            AssertKind::True(condition) => (self.lower_condition(condition)?, None),
+            AssertKind::Eq(lhs, rhs) => {
+                let (lhs, lhs_ty) = self.lower_expression(lhs)?;
+                let (rhs, rhs_ty) = self.lower_expression(rhs)?;
+                self.expect_type(&lhs_ty, &rhs_ty)?;
+                let (Value::Score(lhs), Value::Score(rhs)) = (lhs, rhs) else {
+                    return Err(IRGenError::InvalidBinaryOperation(BinOp::Eq, lhs_ty));
+                };
+                let (condition, _) = self.lower_binary(&lhs, BinOp::Eq, &rhs, &lhs_ty)?;
+                let scale = match lhs_ty {
+                    Type::Float => self.options.float_scale,
+                    _ => 1,
+                };
+                (condition, Some((lhs, rhs, scale)))
+            }
+        };
+
+        self.emit(IRInstruction::Assert(Assertion {
+            condition,
+            values,
+            module_id: self.module_id.clone(),
+            offset: assert.span.map_or(0, |span| span.lo),
+            message: format_assert(assert),
+        }));
+        Ok(())
+    }
+
+    fn var_name(&self, name: &ResolvedName) -> ResolvedName {
+        if self.local_types.contains_key(name) {
+            name.clone()
+        } else {
+            self.global_definition_table.canonical_name(name)
+        }
+    }
+
+    fn var_type(&self, name: &ResolvedName) -> IRGenResult<Type> {
+        if let Some(ty) = self.local_types.get(name) {
+            return Ok(ty.clone());
+        }
+        self.global_definition_table
+            .get_static_var_definition(name)
+            .map(|static_var_def| static_var_def.ty.clone())
+            .ok_or_else(|| IRGenError::UnknownDefinition(name.clone()))
+    }
+
+    fn fn_definition(&self, name: &ResolvedName) -> IRGenResult<&'b FnDef>
+    where
+        'a: 'b,
+    {
+        if let Some(fn_def) = self.local_functions.get(name) {
+            return Ok(fn_def);
+        }
+        self.global_definition_table
+            .get_fn_definition(&self.global_definition_table.canonical_name(name))
+            .ok_or_else(|| IRGenError::UnknownDefinition(name.clone()))
+    }
+
+    fn lower_expression(&mut self, expr: &Expression) -> IRGenResult<(Value, Type)> {
+        match expr {
+            Expression::Literal(literal) => self.lower_literal(literal),
+            Expression::Var(var) => {
+                let name = self.var_name(var.resolved.as_ref().unwrap());
+                let ty = self.var_type(&name)?;
+                Ok((var_value(name, &ty)?, ty))
+            }
+            Expression::Binary(lhs, op @ (BinOp::And | BinOp::Or), rhs) => {
+                self.lower_logical(lhs, *op, rhs)
+            }
+            Expression::Binary(lhs, op, rhs) => {
+                let (mut lhs, lhs_ty) = self.lower_expression(lhs)?;
+                // a call in the rhs may change the variable read by the lhs, which is evaluated first
+                if contains_call(rhs) {
+                    if let Value::Score(Score::Var(_)) | Value::Storage(Storage::Var(_)) = lhs {
+                        lhs = self.copy_value(&lhs);
+                    }
+                }
+                let (rhs, rhs_ty) = self.lower_expression(rhs)?;
+                self.expect_type(&lhs_ty, &rhs_ty)?;
+                match (lhs, rhs) {
+                    (Value::Score(lhs), Value::Score(rhs)) => {
+                        let (score, ty) = self.lower_binary(&lhs, *op, &rhs, &lhs_ty)?;
+                        Ok((Value::Score(score), ty))
+                    }
+                    _ => Err(IRGenError::InvalidBinaryOperation(*op, lhs_ty)),
+                }
+            }
+            Expression::Unary(op, expr) => {
+                let (value, ty) = self.lower_expression(expr)?;
+                let score = match (op, &ty, value) {
+                    (UnOp::Neg, Type::Int | Type::Float, Value::Score(score)) => {
+                        let result = self.constant(0);
+                        self.emit_operation(&result, ScoreOperation::Sub, &score);
+                        result
+                    }
+                    (UnOp::Not, Type::Bool, Value::Score(score)) => {
+                        let result = self.constant(1);
+                        self.emit_operation(&result, ScoreOperation::Sub, &score);
+                        result
+                    }
+                    _ => return Err(IRGenError::InvalidUnaryOperation(*op, ty)),
+                };
+                Ok((Value::Score(score), ty))
+            }
+            Expression::Cast(expr, target_ty) => {
+                let (value, ty) = self.lower_expression(expr)?;
+                let value = self.lower_cast(value, &ty, target_ty)?;
+                Ok((value, target_ty.clone()))
+            }
+            Expression::FnCall(fn_call) => {
+                let (value, ty) = self.lower_fn_call(fn_call)?;
+                // the return value is overwritten by the next call, so it has to be copied out
+                Ok((self.copy_value(&value), ty))
+            }
+        }
+    }
+
+    fn lower_literal(&mut self, literal: &Literal) -> IRGenResult<(Value, Type)> {
+        Ok(match literal {
+            Literal::Int(value) => (Value::Score(self.constant(*value)), Type::Int),
+            Literal::Float(value) => {
+                let scaled = (value * self.options.float_scale as f64).round();
+                if scaled < i32::MIN as f64 || scaled > i32::MAX as f64 {
+                    return Err(IRGenError::FloatOutOfRange(*value));
+                }
+                (Value::Score(self.constant(scaled as i32)), Type::Float)
+            }
+            // bools are stored as 0 or 1
+            Literal::Bool(value) => (Value::Score(self.constant(*value as i32)), Type::Bool),
+            Literal::String(value) => {
+                let register = self.new_storage_register();
+                self.emit(IRInstruction::StorageSetString(
+                    register.clone(),
+                    value.clone(),
+                ));
+                (Value::Storage(register), Type::String)
+            }
+        })
+    }
+
+    // lowers a binary operation on two scores of the same type, returning the result and its type
+    fn lower_binary(
+        &mut self,
+        lhs: &Score,
+        op: BinOp,
+        rhs: &Score,
+        ty: &Type,
+    ) -> IRGenResult<(Score, Type)> {
+        let arithmetic = match op {
+            BinOp::Add => Some(ScoreOperation::Add),
+            BinOp::Sub => Some(ScoreOperation::Sub),
+            BinOp::Mul => Some(ScoreOperation::Mul),
+            BinOp::Div => Some(ScoreOperation::Div),
+            BinOp::Mod => Some(ScoreOperation::Mod),
+            _ => None,
+        };
+        let comparison = match op {
+            BinOp::Eq => Some(CompareOperation::Eq),
+            BinOp::Ne => Some(CompareOperation::Ne),
+            BinOp::Lt => Some(CompareOperation::Lt),
+            BinOp::Le => Some(CompareOperation::Le),
+            BinOp::Gt => Some(CompareOperation::Gt),
+            BinOp::Ge => Some(CompareOperation::Ge),
+            _ => None,
+        };
+
+        match (ty, op) {
+            (Type::Float, BinOp::Mul) => Ok((self.lower_fixed_point_mul(lhs, rhs), Type::Float)),
+            (Type::Float, BinOp::Div) => Ok((self.lower_fixed_point_div(lhs, rhs), Type::Float)),
+            (Type::Int | Type::Float, _) if arithmetic.is_some() => {
+                let result = self.copy(lhs);
+                self.emit_operation(&result, arithmetic.unwrap(), rhs);
+                Ok((result, ty.clone()))
+            }
+            // fixed-point numbers with the same scale compare the same way as their raw values
+            (Type::Int | Type::Float, _) | (Type::Bool, BinOp::Eq | BinOp::Ne)
+                if comparison.is_some() =>
+            {
+                Ok((self.compare(lhs, comparison.unwrap(), rhs), Type::Bool))
+            }
+            _ => Err(IRGenError::InvalidBinaryOperation(op, ty.clone())),
+        }
+    }
+
+    // `&&` only evaluates the rhs if the lhs is true, and `||` only if it is false
+    fn lower_logical(
+        &mut self,
+        lhs: &Expression,
+        op: BinOp,
+        rhs: &Expression,
+    ) -> IRGenResult<(Value, Type)> {
+        let (lhs, ty) = self.lower_expression(lhs)?;
+        let (Value::Score(lhs), Type::Bool) = (lhs, &ty) else {
+            return Err(IRGenError::InvalidBinaryOperation(op, ty));
+        };
+        // the result is the lhs, unless the rhs is evaluated
+        let result = self.copy(&lhs);
+        let rhs_block = self.new_block();
+        let end_block = self.new_block();
+        let branch = match op {
+            BinOp::And => Terminator::Branch(result.clone(), rhs_block, end_block),
+            _ => Terminator::Branch(result.clone(), end_block, rhs_block),
+        };
+        self.terminate(branch, rhs_block);
+
+        let rhs = self.lower_condition(rhs)?;
+        self.emit_operation(&result, ScoreOperation::Assign, &rhs);
+        self.terminate(Terminator::Jump(end_block), end_block);
+        Ok((Value::Score(result), Type::Bool))
+    }
+
+    fn compare(&mut self, lhs: &Score, operation: CompareOperation, rhs: &Score) -> Score {
+        let result = self.new_register();
+        self.emit(IRInstruction::ScoreCompare(
+            result.clone(),
+            lhs.clone(),
+            operation,
+            rhs.clone(),
+        ));
+        result
+    }
+
+    /* lhs * rhs / scale would overflow as soon as the product exceeds i32, so lhs is split into
+    lhs = q * scale + r, with 0 <= r < scale. Then lhs * rhs / scale = q * rhs + r * rhs / scale.
+
+    Since the scoreboard uses floor division and modulo, this gives the same result as flooring the exact product,
+    and only overflows if |rhs| >= i32::MAX / scale or the result itself does not fit.
+     */
+    fn lower_fixed_point_mul(&mut self, lhs: &Score, rhs: &Score) -> Score {
+        let scale = self.constant(self.options.float_scale);
+
+        let quotient = self.copy(lhs);
+        self.emit_operation(&quotient, ScoreOperation::Div, &scale);
+        let remainder = self.copy(lhs);
+        self.emit_operation(&remainder, ScoreOperation::Mod, &scale);
+
+        self.emit_operation(&quotient, ScoreOperation::Mul, rhs);
+        self.emit_operation(&remainder, ScoreOperation::Mul, rhs);
+        self.emit_operation(&remainder, ScoreOperation::Div, &scale);
+        self.emit_operation(&quotient, ScoreOperation::Add, &remainder);
+        quotient
+    }
+
+    /* lhs * scale / rhs would overflow for |lhs| >= i32::MAX / scale, so lhs is split into
+    lhs = q * rhs + r, with |r| < |rhs|. Then lhs * scale / rhs = q * scale + r * scale / rhs.
+
+    This only overflows if |rhs| >= i32::MAX / scale or the result itself does not fit.
+     */
+    fn lower_fixed_point_div(&mut self, lhs: &Score, rhs: &Score) -> Score {
+        let scale = self.constant(self.options.float_scale);
+
+        let quotient = self.copy(lhs);
+        self.emit_operation(&quotient, ScoreOperation::Div, rhs);
+        let remainder = self.copy(lhs);
+        self.emit_operation(&remainder, ScoreOperation::Mod, rhs);
+
+        self.emit_operation(&quotient, ScoreOperation::Mul, &scale);
+        self.emit_operation(&remainder, ScoreOperation::Mul, &scale);
+        self.emit_operation(&remainder, ScoreOperation::Div, rhs);
+        self.emit_operation(&quotient, ScoreOperation::Add, &remainder);
+        quotient
+    }
+
+    fn lower_cast(&mut self, value: Value, from: &Type, to: &Type) -> IRGenResult<Value> {
+        if from == to {
+            return Ok(value);
+        }
+        let Value::Score(score) = value else {
+            return Err(IRGenError::InvalidCast(
+                Box::new(from.clone()),
+                Box::new(to.clone()),
+            ));
+        };
+
+        Ok(Value::Score(match (from, to) {
+            (Type::Int, Type::Float) => {
+                let scale = self.constant(self.options.float_scale);
+                let result = self.copy(&score);
+                self.emit_operation(&result, ScoreOperation::Mul, &scale);
+                result
+            }
+            // rounds towards negative infinity, like the scoreboard
+            (Type::Float, Type::Int) => {
+                let scale = self.constant(self.options.float_scale);
+                let result = self.copy(&score);
+                self.emit_operation(&result, ScoreOperation::Div, &scale);
+                result
+            }
+            (Type::Bool, Type::Int) => score,
+            (Type::Int, Type::Bool) => {
+                let zero = self.constant(0);
+                self.compare(&score, CompareOperation::Ne, &zero)
+            }
+            _ => {
+                return Err(IRGenError::InvalidCast(
+                    Box::new(from.clone()),
+                    Box::new(to.clone()),
+                ))
+            }
+        }))
+    }
+
+    fn lower_fn_call(&mut self, fn_call: &FnCall) -> IRGenResult<(Value, Type)> {
+        let (fn_name, ty) = self.lower_call_arguments(fn_call)?;
+        self.emit(IRInstruction::Call(fn_name));
+        Ok((return_value(&ty)?, ty))
+    }
+
+    // checks the call and writes its arguments to the parameters, returning the function and its return type
+    fn lower_call_arguments(&mut self, fn_call: &FnCall) -> IRGenResult<(ResolvedName, Type)> {
+        let fn_def = self.fn_definition(fn_call.name.resolved.as_ref().unwrap())?;
+        let fn_name = fn_def.name.resolved.clone().unwrap();
+
+        if fn_def.args.len() != fn_call.args.len() {
+            return Err(IRGenError::ArgumentCount(
+                fn_name,
+                fn_def.args.len(),
+                fn_call.args.len(),
+            ));
+        }
+
+        // every argument is evaluated before any parameter is written, since the arguments may read the parameters
+        let mut arg_values = vec![];
+        for (arg, param) in fn_call.args.iter().zip(fn_def.args.iter()) {
+            let (value, ty) = self.lower_expression(arg)?;
+            self.expect_type(&param.ty, &ty)?;
+            arg_values.push(match value {
+                Value::Score(Score::Reg(_)) | Value::Storage(Storage::Reg(_)) => value,
+                _ => self.copy_value(&value),
+            });
+        }
+        for (value, param) in arg_values.iter().zip(fn_def.args.iter()) {
+            let param_value = var_value(param.name.resolved.clone().unwrap(), &param.ty)?;
+            self.assign(&param_value, value);
+        }
+        Ok((fn_name, fn_def.return_type.clone()))
+    }
+
+    fn new_storage_register(&mut self) -> Storage {
+        let register = self.next_register;
+        self.next_register += 1;
+        Storage::Reg(register)
+    }
+
+    // creates a new register holding a copy of the given value
+    fn copy_value(&mut self, value: &Value) -> Value {
+        match value {
+            Value::Score(score) => Value::Score(self.copy(score)),
+            Value::Storage(storage) => {
+                let register = self.new_storage_register();
+                self.emit(IRInstruction::StorageCopy(
+                    register.clone(),
+                    storage.clone(),
+                ));
+                Value::Storage(register)
+            }
+            Value::Void => Value::Void,
+        }
+    }
+
+    fn assign(&mut self, target: &Value, source: &Value) {
+        match (target, source) {
+            (Value::Score(target), Value::Score(source)) => {
+                self.emit_operation(target, ScoreOperation::Assign, source)
+            }
+            (Value::Storage(target), Value::Storage(source)) => {
+                self.emit(IRInstruction::StorageCopy(target.clone(), source.clone()))
+            }
+            (Value::Void, Value::Void) => {}
+            _ => unreachable!("Values of the same type are always stored the same way"),
+        }
+    }
+}
+
+// whether evaluating the expression calls a function, which may have side effects
+fn contains_call(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(_) | Expression::Var(_) => false,
+        Expression::Binary(lhs, _, rhs) => contains_call(lhs) || contains_call(rhs),
+        Expression::Unary(_, expr) | Expression::Cast(expr, _) => contains_call(expr),
+        Expression::FnCall(_) => true,
+    }
+}
+
+// removes the blocks that cannot be reached from the first one, keeping the others in order
+pub fn remove_unreachable_blocks(blocks: Vec<BasicBlock>) -> Vec<BasicBlock> {
+    let mut reachable = vec![false; blocks.len()];
+    let mut stack = vec![0];
+    while let Some(block) = stack.pop() {
+        if reachable[block] {
+            continue;
+        }
+        reachable[block] = true;
+        match &blocks[block].terminator {
+            Terminator::Jump(target) => stack.push(*target),
+            Terminator::Branch(_, then_block, else_block) => {
+                stack.push(*then_block);
+                stack.push(*else_block);
+            }
+            Terminator::Return | Terminator::TailCall(_) => {}
+        }
+    }
+
+    let mut new_ids = vec![0; blocks.len()];
+    let mut next_id = 0;
+    for (block, is_reachable) in reachable.iter().enumerate() {
+        if *is_reachable {
+            new_ids[block] = next_id;
+            next_id += 1;
+        }
+    }
+
+    blocks
+        .into_iter()
+        .zip(reachable)
+        .filter(|(_, is_reachable)| *is_reachable)
+        .map(|(mut block, _)| {
+            block.terminator = match block.terminator {
+                Terminator::Jump(target) => Terminator::Jump(new_ids[target]),
+                Terminator::Branch(condition, then_block, else_block) => {
+                    Terminator::Branch(condition, new_ids[then_block], new_ids[else_block])
+                }
+                terminator => terminator,
+            };
+            block
+        })
+        .collect()
+}
+
+// where the value of an expression is stored
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Score(Score),
+    Storage(Storage),
+    Void,
+}
+
+// ints, floats and bools fit in a single score, strings and structs are kept in data storage
+fn var_value(name: ResolvedName, ty: &Type) -> IRGenResult<Value> {
+    match ty {
+        Type::Int | Type::Float | Type::Bool => Ok(Value::Score(Score::Var(name))),
+        Type::String | Type::Struct(_) => Ok(Value::Storage(Storage::Var(name))),
+        Type::Void => Err(IRGenError::UnsupportedType(ty.clone())),
+    }
+}
+
+fn return_value(ty: &Type) -> IRGenResult<Value> {
+    match ty {
+        Type::Int | Type::Float | Type::Bool => Ok(Value::Score(Score::Return)),
+        Type::String | Type::Struct(_) => Ok(Value::Storage(Storage::Return)),
+        Type::Void => Ok(Value::Void),
+    }
+}
@@ -0,0 +1,438 @@
+use crate::front::ast_types::ResolvedName;
+use crate::middle::constant_folding::fold_constants;
+use crate::middle::dead_code::{remove_dead_stores, remove_unreachable_items, RemovedItems};
+use crate::middle::inlining::inline_functions;
+use crate::middle::ir_text::print_modules;
+use crate::middle::liveness::successors;
+use crate::middle::types::{IRFunction, IRInstruction, IRModule, Terminator};
+use camino::Utf8PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+
+// functions with at most this many instructions are inlined at `-O2`
+const INLINE_THRESHOLD: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub enum PassError {
+    // the pass, the invariant it declared to preserve, and the IR dumped until then (including after the pass)
+    InvariantBroken(String, Invariant, Dumps),
+}
+
+pub type PassResult<T> = Result<T, PassError>;
+
+// properties of the IR that a pass may keep, checked after every pass that declares them
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Invariant {
+    ReachableBlocks, // every block of a function can be reached from its first block
+    Items,           // no function or static is added to or removed from the program
+    Calls,           // every function calls the same functions as before
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OptLevel {
+    O0, // no passes
+    O1, // optimizations within functions, and leaving out unreachable items
+    O2, // inlining as well
+}
+
+// what the passes can use besides the IR, and what they report
+pub struct PassContext<'a> {
+    pub main: Option<&'a ResolvedName>, // an entry point, like the functions marked as one
+    pub removed: RemovedItems,          // the items that passes left out of the program
+}
+
+/* A transformation of the IR of the whole program. Besides the passes of the optimization levels, other ones can be
+added to a `PassManager`, e.g. to check or rewrite the IR for a particular project.
+ */
+pub trait Pass {
+    // a short name in kebab case, used in errors and for the dumped IR
+    fn name(&self) -> &str;
+    // the invariants that still hold after the pass, if they held before it
+    fn preserves(&self) -> &[Invariant];
+    fn run(&self, modules: &mut [IRModule], context: &mut PassContext);
+}
+
+// the functions of every module, for passes that work on one function at a time
+pub fn functions_mut(modules: &mut [IRModule]) -> impl Iterator<Item = &mut IRFunction> {
+    modules
+        .iter_mut()
+        .flat_map(|module| module.functions.iter_mut())
+}
+
+pub struct FoldConstants;
+
+impl Pass for FoldConstants {
+    fn name(&self) -> &str {
+        "fold-constants"
+    }
+
+    // branches that are never taken are removed, with their calls
+    fn preserves(&self) -> &[Invariant] {
+        &[Invariant::ReachableBlocks, Invariant::Items]
+    }
+
+    fn run(&self, modules: &mut [IRModule], _: &mut PassContext) {
+        for function in functions_mut(modules) {
+            fold_constants(function);
+        }
+    }
+}
+
+pub struct RemoveDeadStores;
+
+impl Pass for RemoveDeadStores {
+    fn name(&self) -> &str {
+        "remove-dead-stores"
+    }
+
+    fn preserves(&self) -> &[Invariant] {
+        &[
+            Invariant::ReachableBlocks,
+            Invariant::Items,
+            Invariant::Calls,
+        ]
+    }
+
+    fn run(&self, modules: &mut [IRModule], _: &mut PassContext) {
+        for function in functions_mut(modules) {
+            remove_dead_stores(function);
+        }
+    }
+}
+
+pub struct Inline {
+    pub threshold: usize, // see `inline_functions`
+}
+
+impl Pass for Inline {
+    fn name(&self) -> &str {
+        "inline"
+    }
+
+    // the inlined functions are kept, they are left out later if they are not called anymore
+    fn preserves(&self) -> &[Invariant] {
+        &[Invariant::ReachableBlocks, Invariant::Items]
+    }
+
+    fn run(&self, modules: &mut [IRModule], context: &mut PassContext) {
+        inline_functions(modules, context.main, self.threshold);
+    }
+}
+
+pub struct RemoveUnreachable;
+
+impl Pass for RemoveUnreachable {
+    fn name(&self) -> &str {
+        "remove-unreachable"
+    }
+
+    fn preserves(&self) -> &[Invariant] {
+        &[Invariant::ReachableBlocks, Invariant::Calls]
+    }
+
+    fn run(&self, modules: &mut [IRModule], context: &mut PassContext) {
+        let removed = remove_unreachable_items(modules, context.main);
+        context.removed.functions.extend(removed.functions);
+        context.removed.statics.extend(removed.statics);
+    }
+}
+
+// the IR before the passes and after each of them, by file name
+pub type Dumps = BTreeMap<Utf8PathBuf, String>;
+
+// the result of running the passes
+#[derive(Debug, PartialEq, Default)]
+pub struct PassOutput {
+    pub removed: RemovedItems,
+    pub dumps: Dumps, // empty unless the IR is dumped
+}
+
+// runs passes on the IR of the whole program, in the order they were added
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    pub dump_ir: Option<Utf8PathBuf>, // the directory the IR is dumped to, if any
+}
+
+impl PassManager {
+    // the passes of the optimization level
+    pub fn new(level: OptLevel) -> Self {
+        let mut manager = PassManager {
+            passes: vec![],
+            dump_ir: None,
+        };
+        if level == OptLevel::O0 {
+            return manager;
+        }
+
+        manager.add(FoldConstants);
+        manager.add(RemoveDeadStores);
+        if level == OptLevel::O2 {
+            // the inlined bodies may fold with the arguments of the calls
+            manager.add(Inline {
+                threshold: INLINE_THRESHOLD,
+            });
+            manager.add(FoldConstants);
+            manager.add(RemoveDeadStores);
+        }
+        manager.add(RemoveUnreachable);
+        manager
+    }
+
+    // runs the pass after the ones added before
+    pub fn add(&mut self, pass: impl Pass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    #[cfg(test)]
+    pub fn names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    pub fn run(
+        &self,
+        modules: &mut [IRModule],
+        main: Option<&ResolvedName>,
+    ) -> PassResult<PassOutput> {
+        let mut context = PassContext {
+            main,
+            removed: RemovedItems::default(),
+        };
+        let mut dumps = BTreeMap::new();
+        if self.dump_ir.is_some() {
+            dumps.insert(Utf8PathBuf::from("00-input.ir"), print_modules(modules));
+        }
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            let before = pass
+                .preserves()
+                .iter()
+                .map(|invariant| (*invariant, State::of(*invariant, modules)))
+                .collect::<Vec<_>>();
+
+            pass.run(modules, &mut context);
+            if self.dump_ir.is_some() {
+                dumps.insert(
+                    Utf8PathBuf::from(format!("{:02}-{}.ir", index + 1, pass.name())),
+                    print_modules(modules),
+                );
+            }
+
+            for (invariant, before) in before {
+                if !before.is_preserved_by(&State::of(invariant, modules)) {
+                    return Err(PassError::InvariantBroken(
+                        pass.name().to_string(),
+                        invariant,
+                        dumps,
+                    ));
+                }
+            }
+        }
+
+        Ok(PassOutput {
+            removed: context.removed,
+            dumps,
+        })
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        PassManager::new(OptLevel::O2)
+    }
+}
+
+// the part of the program an invariant is about, to compare it before and after a pass
+#[derive(PartialEq)]
+enum State {
+    ReachableBlocks(bool),
+    Items(BTreeSet<ResolvedName>, BTreeSet<ResolvedName>), // functions, statics
+    Calls(BTreeMap<ResolvedName, BTreeSet<ResolvedName>>),
+}
+
+impl State {
+    fn of(invariant: Invariant, modules: &[IRModule]) -> State {
+        let mut functions = modules.iter().flat_map(|module| module.functions.iter());
+        match invariant {
+            Invariant::ReachableBlocks => {
+                State::ReachableBlocks(functions.all(all_blocks_reachable))
+            }
+            Invariant::Items => State::Items(
+                functions.map(|function| function.name.clone()).collect(),
+                modules
+                    .iter()
+                    .flat_map(|module| module.statics.iter().cloned())
+                    .collect(),
+            ),
+            Invariant::Calls => State::Calls(
+                functions
+                    .map(|function| (function.name.clone(), called_functions(function)))
+                    .collect(),
+            ),
+        }
+    }
+
+    // invariants that did not hold before the pass cannot be broken by it
+    fn is_preserved_by(&self, after: &State) -> bool {
+        match (self, after) {
+            (State::ReachableBlocks(before), State::ReachableBlocks(after)) => !before || *after,
+            (State::Items(..), State::Items(..)) => self == after,
+            // functions that were left out have no calls to compare
+            (State::Calls(before), State::Calls(after)) => after
+                .iter()
+                .all(|(name, calls)| before.get(name).is_none_or(|before| before == calls)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn all_blocks_reachable(function: &IRFunction) -> bool {
+    let mut reached = vec![false; function.blocks.len()];
+    let mut stack = vec![0];
+    while let Some(id) = stack.pop() {
+        if id < reached.len() && !reached[id] {
+            reached[id] = true;
+            stack.extend(successors(&function.blocks[id].terminator));
+        }
+    }
+    reached.into_iter().all(|reached| reached)
+}
+
+fn called_functions(function: &IRFunction) -> BTreeSet<ResolvedName> {
+    let mut called = BTreeSet::new();
+    for block in function.blocks.iter() {
+        for instruction in block.instructions.iter() {
+            if let IRInstruction::Call(name) = instruction {
+                called.insert(name.clone());
+            }
+        }
+        if let Terminator::TailCall(name) = &block.terminator {
+            called.insert(name.clone());
+        }
+    }
+    called
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_text::parse_modules;
+    use camino::Utf8Path;
+
+    const SRC: &str = r#"
+    module pkg::main
+    static pkg::main+0:0:unused
+    fn pkg::main+0:0:main() {
+    b0:
+        r0 = 1
+        r1 = 2
+        r0 += r1
+        pkg::main+0:0:x = r0
+        return
+    }
+    fn pkg::main+0:0:helper() {
+    b0:
+        return
+    }
+    "#;
+
+    // removes every function but the first, although it declares to keep them
+    struct KeepFirst;
+
+    impl Pass for KeepFirst {
+        fn name(&self) -> &str {
+            "keep-first"
+        }
+
+        fn preserves(&self) -> &[Invariant] {
+            &[Invariant::Calls, Invariant::Items]
+        }
+
+        fn run(&self, modules: &mut [IRModule], _: &mut PassContext) {
+            modules[0].functions.truncate(1);
+        }
+    }
+
+    #[test]
+    fn test_opt_levels() {
+        assert!(PassManager::new(OptLevel::O0).names().is_empty());
+        assert_eq!(
+            PassManager::new(OptLevel::O1).names(),
+            ["fold-constants", "remove-dead-stores", "remove-unreachable"]
+        );
+        assert_eq!(
+            PassManager::new(OptLevel::O2).names(),
+            [
+                "fold-constants",
+                "remove-dead-stores",
+                "inline",
+                "fold-constants",
+                "remove-dead-stores",
+                "remove-unreachable"
+            ]
+        );
+
+        let main = ResolvedName::new("pkg::main".to_string(), "0:0:main".to_string());
+        let mut modules = parse_modules(SRC).unwrap();
+        let output = PassManager::new(OptLevel::O1)
+            .run(&mut modules, Some(&main))
+            .unwrap();
+        assert_eq!(
+            print_modules(&modules),
+            print_modules(
+                &parse_modules(
+                    r#"
+                    module pkg::main
+                    fn pkg::main+0:0:main() {
+                    b0:
+                        pkg::main+0:0:x = 3
+                        return
+                    }
+                    "#
+                )
+                .unwrap()
+            )
+        );
+        assert_eq!(output.removed.functions.len(), 1);
+        assert_eq!(output.removed.statics.len(), 1);
+        assert!(output.dumps.is_empty());
+    }
+
+    #[test]
+    fn test_custom_passes() {
+        let mut passes = PassManager::new(OptLevel::O0);
+        passes.add(FoldConstants);
+        passes.add(KeepFirst);
+        passes.dump_ir = Some(Utf8PathBuf::from("passes"));
+
+        // the IR is dumped after every pass up to the one that breaks an invariant it declared, and kept with the error
+        let mut modules = parse_modules(SRC).unwrap();
+        let Err(PassError::InvariantBroken(pass, invariant, dumps)) =
+            passes.run(&mut modules, None)
+        else {
+            panic!("The invariant is not checked");
+        };
+        assert_eq!((pass.as_str(), invariant), ("keep-first", Invariant::Items));
+        assert_eq!(
+            dumps.keys().collect::<Vec<_>>(),
+            ["00-input.ir", "01-fold-constants.ir", "02-keep-first.ir"]
+        );
+        assert_eq!(
+            dumps[Utf8Path::new("02-keep-first.ir")],
+            print_modules(&modules)
+        );
+
+        let mut passes = PassManager::new(OptLevel::O0);
+        passes.add(KeepFirst);
+        passes.add(FoldConstants);
+        passes.dump_ir = Some(Utf8PathBuf::from("passes"));
+        let mut modules = parse_modules(SRC).unwrap();
+        modules[0].functions.truncate(1);
+        let output = passes.run(&mut modules, None).unwrap();
+        assert_eq!(
+            output.dumps.keys().collect::<Vec<_>>(),
+            ["00-input.ir", "01-keep-first.ir", "02-fold-constants.ir"]
+        );
+        let dump = |name: &str| &output.dumps[Utf8Path::new(name)];
+        assert_eq!(dump("00-input.ir"), dump("01-keep-first.ir"));
+        assert_eq!(dump("02-fold-constants.ir"), &print_modules(&modules));
+    }
+}
@@ -0,0 +1,459 @@
+use crate::front::ast_types::ResolvedName;
+use crate::middle::call_graph::CallGraph;
+use crate::middle::liveness::{uses_and_defs, Liveness};
+use crate::middle::types::{
+    BlockId, IRFunction, IRInstruction, IRModule, Location, Register, Score, ScoreOperation,
+    Storage, Terminator,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/* Maps the registers of every function to a small set of slots shared by the whole program.
+
+Within a function, two registers get the same slot unless one of them is written while the other is still needed
+later, which is found with a liveness analysis over the blocks of the function. A copy between two registers does
+not keep them apart, so that both can get the same slot and the copy can be removed.
+
+Across functions, the slots of a function start after the slots of every function that may call it, so a call never
+overwrites the registers of its callers. This is not possible for functions that call each other recursively, so
+those share their slots, and the registers that are still needed after a recursive call are saved in a stack frame
+around it. A tail call keeps nothing, so the function it calls may reuse the slots of the caller.
+ */
+pub fn allocate_registers(modules: &mut [IRModule]) {
+    let functions = modules
+        .iter()
+        .flat_map(|module| module.functions.iter())
+        .collect::<Vec<_>>();
+    let allocations = functions
+        .iter()
+        .map(|function| FunctionAllocation::new(function))
+        .collect::<Vec<_>>();
+    let call_graph = CallGraph::new(&functions);
+
+    // the components are ordered so that the callers of a function come before it
+    let mut bases = vec![Slots::default(); call_graph.components.len()];
+    for (component, members) in call_graph.components.iter().enumerate() {
+        let size = members
+            .iter()
+            .map(|member| allocations[*member].slots)
+            .fold(Slots::default(), Slots::max);
+        let end = bases[component].add(size);
+
+        for member in members.iter() {
+            for call in call_graph.calls[*member].iter() {
+                let callee_component = call_graph.component_of[call.callee];
+                if callee_component == component {
+                    continue;
+                }
+                let start = if call.is_tail_call {
+                    bases[component]
+                } else {
+                    end
+                };
+                bases[callee_component] = bases[callee_component].max(start);
+            }
+        }
+    }
+
+    for (index, function) in modules
+        .iter_mut()
+        .flat_map(|module| module.functions.iter_mut())
+        .enumerate()
+    {
+        let base = bases[call_graph.component_of[index]];
+        let is_recursive_call = |name: &_| {
+            call_graph
+                .index(name)
+                .is_some_and(|callee| call_graph.is_recursive_call(index, callee))
+        };
+        allocations[index].apply(function, base, is_recursive_call);
+    }
+}
+
+fn is_register(location: &Location) -> bool {
+    matches!(
+        location,
+        Location::Score(Score::Reg(_)) | Location::Storage(Storage::Reg(_))
+    )
+}
+
+// registers holding scores and registers holding storage are allocated separately
+fn is_storage(location: &Location) -> bool {
+    matches!(location, Location::Storage(_))
+}
+
+// a number of slots (or the first slot) for each kind of register
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+struct Slots {
+    scores: Register,
+    storage: Register,
+}
+
+impl Slots {
+    fn add(self, other: Slots) -> Slots {
+        Slots {
+            scores: self.scores + other.scores,
+            storage: self.storage + other.storage,
+        }
+    }
+
+    fn max(self, other: Slots) -> Slots {
+        Slots {
+            scores: self.scores.max(other.scores),
+            storage: self.storage.max(other.storage),
+        }
+    }
+}
+
+// the source of a copy from one register to another
+fn copy_source(instruction: &IRInstruction) -> Option<Location> {
+    match instruction {
+        IRInstruction::ScoreOperation(
+            Score::Reg(_),
+            ScoreOperation::Assign,
+            Score::Reg(source),
+        ) => Some(Location::Score(Score::Reg(*source))),
+        IRInstruction::StorageCopy(Storage::Reg(_), Storage::Reg(source)) => {
+            Some(Location::Storage(Storage::Reg(*source)))
+        }
+        _ => None,
+    }
+}
+
+fn rename_score(score: &mut Score, slots: &HashMap<Location, Register>) {
+    if let Some(slot) = slots.get(&Location::Score(score.clone())) {
+        *score = Score::Reg(*slot);
+    }
+}
+
+fn rename_storage(storage: &mut Storage, slots: &HashMap<Location, Register>) {
+    if let Some(slot) = slots.get(&Location::Storage(storage.clone())) {
+        *storage = Storage::Reg(*slot);
+    }
+}
+
+fn rename_location(location: &mut Location, slots: &HashMap<Location, Register>) {
+    match location {
+        Location::Score(score) => rename_score(score, slots),
+        Location::Storage(storage) => rename_storage(storage, slots),
+    }
+}
+
+fn rename_instruction(instruction: &mut IRInstruction, slots: &HashMap<Location, Register>) {
+    instruction.for_each_location_mut(&mut |score| rename_score(score, slots), &mut |storage| {
+        rename_storage(storage, slots)
+    });
+}
+
+// the slots of the registers of a single function, counted from the first slot of the function
+struct FunctionAllocation {
+    colors: BTreeMap<Location, Register>,
+    slots: Slots,
+    // the registers that are still needed after each call, by block and index of the call
+    live_after_calls: BTreeMap<(BlockId, usize), BTreeSet<Location>>,
+}
+
+impl FunctionAllocation {
+    fn new(function: &IRFunction) -> FunctionAllocation {
+        let liveness = Liveness::new(function, is_register);
+        let registers = |locations: Vec<Location>| {
+            locations
+                .into_iter()
+                .filter(is_register)
+                .collect::<Vec<_>>()
+        };
+
+        let mut interference: BTreeMap<Location, BTreeSet<Location>> = BTreeMap::new();
+        let mut copies = vec![];
+        let mut live_after_calls = BTreeMap::new();
+        let mut interfere = |a: &Location, b: &Location| {
+            interference.entry(a.clone()).or_default();
+            interference.entry(b.clone()).or_default();
+            if a != b && is_storage(a) == is_storage(b) {
+                interference.get_mut(a).unwrap().insert(b.clone());
+                interference.get_mut(b).unwrap().insert(a.clone());
+            }
+        };
+
+        // registers that are read before they are written hold their values at the same time
+        if let Some(entry) = liveness.live_in.first() {
+            for a in entry.iter() {
+                for b in entry.iter() {
+                    interfere(a, b);
+                }
+            }
+        }
+
+        for (id, block) in function.blocks.iter().enumerate() {
+            if let Terminator::Branch(Score::Reg(register), _, _) = &block.terminator {
+                let condition = Location::Score(Score::Reg(*register));
+                interfere(&condition, &condition);
+            }
+
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                let live = &liveness.live_after[id][index];
+                if let IRInstruction::Call(_) = instruction {
+                    live_after_calls.insert((id, index), live.clone());
+                }
+
+                let (uses, defs) = uses_and_defs(instruction);
+                let source = copy_source(instruction);
+                for def in registers(defs.clone()) {
+                    interfere(&def, &def);
+                    for register in live.iter() {
+                        if Some(register) != source.as_ref() {
+                            interfere(&def, register);
+                        }
+                    }
+                }
+                if let (Some(source), [target]) = (&source, &defs[..]) {
+                    copies.push((target.clone(), source.clone()));
+                }
+                for register in registers(uses) {
+                    interfere(&register, &register);
+                }
+            }
+        }
+
+        // each register gets the lowest slot that none of its neighbours has, preferring the slot of a copy
+        let mut colors = BTreeMap::new();
+        let mut slots = Slots::default();
+        for (register, neighbours) in interference.iter() {
+            let taken = neighbours
+                .iter()
+                .filter_map(|neighbour| colors.get(neighbour))
+                .collect::<BTreeSet<_>>();
+            let preferred = copies
+                .iter()
+                .filter_map(|(a, b)| match (a == register, b == register) {
+                    (true, _) => colors.get(b),
+                    (_, true) => colors.get(a),
+                    _ => None,
+                })
+                .find(|color| !taken.contains(color));
+            let color = match preferred {
+                Some(color) => *color,
+                None => (0..).find(|color| !taken.contains(color)).unwrap(),
+            };
+            colors.insert(register.clone(), color);
+
+            let count = if is_storage(register) {
+                &mut slots.storage
+            } else {
+                &mut slots.scores
+            };
+            *count = (*count).max(color + 1);
+        }
+
+        FunctionAllocation {
+            colors,
+            slots,
+            live_after_calls,
+        }
+    }
+
+    // renames the registers of the function to their slots, and saves them around the recursive calls
+    fn apply(
+        &self,
+        function: &mut IRFunction,
+        base: Slots,
+        is_recursive_call: impl Fn(&ResolvedName) -> bool,
+    ) {
+        let slots = self
+            .colors
+            .iter()
+            .map(|(register, color)| {
+                let slot = if is_storage(register) {
+                    base.storage + color
+                } else {
+                    base.scores + color
+                };
+                (register.clone(), slot)
+            })
+            .collect::<HashMap<_, _>>();
+
+        for (id, block) in function.blocks.iter_mut().enumerate() {
+            let mut instructions = vec![];
+            for (index, mut instruction) in block.instructions.drain(..).enumerate() {
+                let mut saved = match &instruction {
+                    IRInstruction::Call(name) if is_recursive_call(name) => self.live_after_calls
+                        [&(id, index)]
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    _ => vec![],
+                };
+                for location in saved.iter_mut() {
+                    rename_location(location, &slots);
+                }
+                if !saved.is_empty() {
+                    instructions.push(IRInstruction::PushFrame(saved.clone()));
+                }
+
+                rename_instruction(&mut instruction, &slots);
+                // copies between registers that got the same slot do nothing
+                match &instruction {
+                    IRInstruction::ScoreOperation(target, ScoreOperation::Assign, source)
+                    | IRInstruction::ScoreOperation(target, ScoreOperation::Min, source)
+                    | IRInstruction::ScoreOperation(target, ScoreOperation::Max, source)
+                        if target == source && matches!(target, Score::Reg(_)) => {}
+                    IRInstruction::StorageCopy(Storage::Reg(target), Storage::Reg(source))
+                        if target == source => {}
+                    _ => instructions.push(instruction),
+                }
+
+                if !saved.is_empty() {
+                    instructions.push(IRInstruction::PopFrame(saved));
+                }
+            }
+            block.instructions = instructions;
+
+            if let Terminator::Branch(condition, _, _) = &mut block.terminator {
+                rename_score(condition, &slots);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_text::{parse_modules, print_modules};
+
+    fn allocate(src: &str) -> String {
+        let mut modules = parse_modules(src).unwrap();
+        allocate_registers(&mut modules);
+        print_modules(&modules)
+    }
+
+    #[test]
+    fn test_reuse_slots() {
+        let src = r#"module pkg::main
+
+fn pkg::main+0:0:f() {
+b0:
+    r0 = 2
+    r1 = pkg::main+1:0:x
+    r1 *= r0
+    pkg::main+1:0:y = r1
+    r2 = 3
+    r3 = pkg::main+1:0:y > r2
+    r4 = r3
+    branch r4 b1 b2
+b1:
+    storage r5 = "a"
+    storage r6 = r5
+    interpolate "say " storage(r6)
+    jump b2
+b2:
+    ret = r0
+    return
+}
+"#;
+        // r0 is needed until the end, while r1, r2 and the copies of r3 can share a slot
+        let expected = r#"module pkg::main
+
+fn pkg::main+0:0:f() {
+b0:
+    r0 = 2
+    r1 = pkg::main+1:0:x
+    r1 *= r0
+    pkg::main+1:0:y = r1
+    r1 = 3
+    r1 = pkg::main+1:0:y > r1
+    branch r1 b1 b2
+b1:
+    storage r0 = "a"
+    interpolate "say " storage(r0)
+    jump b2
+b2:
+    ret = r0
+    return
+}
+"#;
+        assert_eq!(allocate(src), expected);
+    }
+
+    #[test]
+    fn test_calls() {
+        let src = r#"module pkg::main
+
+fn pkg::main+0:0:main() {
+b0:
+    r0 = 1
+    call pkg::main+0:0:f
+    ret += r0
+    tail_call pkg::main+0:0:g
+}
+
+fn pkg::main+0:0:f() {
+b0:
+    r0 = 2
+    r1 = r0
+    r1 += r0
+    ret = r1
+    tail_call pkg::main+0:0:g
+}
+
+fn pkg::main+0:0:g() {
+b0:
+    r0 = 3
+    r1 = pkg::main+0:0:n
+    r1 -= r0
+    pkg::main+0:0:n = r1
+    r2 = 0
+    r3 = r1 > r2
+    branch r3 b1 b2
+b1:
+    r4 = 1
+    call pkg::main+0:0:g
+    ret += r4
+    return
+b2:
+    return
+}
+"#;
+        /* f starts after the slot of main, which main still needs after the call. g may reuse the slots of f, since
+        f only tail calls it, and the copy in f is removed. g calls itself, so the register it needs after the call
+        is saved around it.
+         */
+        let expected = r#"module pkg::main
+
+fn pkg::main+0:0:main() {
+b0:
+    r0 = 1
+    call pkg::main+0:0:f
+    ret += r0
+    tail_call pkg::main+0:0:g
+}
+
+fn pkg::main+0:0:f() {
+b0:
+    r1 = 2
+    r1 += r1
+    ret = r1
+    tail_call pkg::main+0:0:g
+}
+
+fn pkg::main+0:0:g() {
+b0:
+    r1 = 3
+    r2 = pkg::main+0:0:n
+    r2 -= r1
+    pkg::main+0:0:n = r2
+    r1 = 0
+    r1 = r2 > r1
+    branch r1 b1 b2
+b1:
+    r1 = 1
+    push_frame score(r1)
+    call pkg::main+0:0:g
+    pop_frame score(r1)
+    ret += r1
+    return
+b2:
+    return
+}
+"#;
+        assert_eq!(allocate(src), expected);
+    }
+}
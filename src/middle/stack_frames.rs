@@ -0,0 +1,185 @@
+use crate::front::ast_types::ResolvedName;
+use crate::middle::call_graph::CallGraph;
+use crate::middle::liveness::Liveness;
+use crate::middle::types::{
+    IRFunction, IRInstruction, IRModule, Location, Score, ScoreOperation, Storage,
+};
+use std::collections::BTreeSet;
+
+/* Saves the variables of recursive functions around their recursive calls.
+
+Variables are kept in the scoreboard like those of any other function, so a function that may call itself again
+(directly or through other functions) would find them overwritten when the call returns. The variables that are still
+needed after such a call are pushed in a frame onto a stack in data storage before it, and popped back after it.
+Functions that are not recursive never pay for this.
+
+The frame is pushed before the arguments are written to the parameters of the callee, since a function that calls
+itself overwrites its own parameters there. A tail call keeps nothing, so it needs no frame.
+ */
+pub fn insert_stack_frames(modules: &mut [IRModule]) {
+    let (call_graph, params) = {
+        let functions = modules
+            .iter()
+            .flat_map(|module| module.functions.iter())
+            .collect::<Vec<_>>();
+        let params = functions
+            .iter()
+            .map(|function| function.params.clone())
+            .collect::<Vec<_>>();
+        (CallGraph::new(&functions), params)
+    };
+
+    for (index, function) in modules
+        .iter_mut()
+        .flat_map(|module| module.functions.iter_mut())
+        .enumerate()
+    {
+        let recursive_params = |name: &ResolvedName| {
+            call_graph
+                .index(name)
+                .filter(|callee| call_graph.is_recursive_call(index, *callee))
+                .map(|callee| &params[callee])
+        };
+        insert_function_frames(function, recursive_params);
+    }
+}
+
+// the variable written by an instruction that copies a value into one of the given parameters
+fn assigned_param<'a>(
+    instruction: &'a IRInstruction,
+    params: &[ResolvedName],
+) -> Option<&'a ResolvedName> {
+    let name = match instruction {
+        IRInstruction::ScoreOperation(Score::Var(name), ScoreOperation::Assign, _)
+        | IRInstruction::StorageCopy(Storage::Var(name), _) => name,
+        _ => return None,
+    };
+    params.contains(name).then_some(name)
+}
+
+fn insert_function_frames<'a>(
+    function: &mut IRFunction,
+    recursive_params: impl Fn(&ResolvedName) -> Option<&'a Vec<ResolvedName>>,
+) {
+    let variables = function
+        .params
+        .iter()
+        .chain(function.locals.iter())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    let liveness = Liveness::new(function, |location| match location {
+        Location::Score(Score::Var(name)) | Location::Storage(Storage::Var(name)) => {
+            variables.contains(name)
+        }
+        _ => false,
+    });
+
+    for (id, block) in function.blocks.iter_mut().enumerate() {
+        // the frames of each call, by the index to push it at and the index of the call
+        let mut frames = vec![];
+        for (index, instruction) in block.instructions.iter().enumerate() {
+            let IRInstruction::Call(name) = instruction else {
+                continue;
+            };
+            let Some(params) = recursive_params(name) else {
+                continue;
+            };
+            let live = liveness.live_after[id][index]
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            if live.is_empty() {
+                continue;
+            }
+
+            let mut start = index;
+            while start > 0 && assigned_param(&block.instructions[start - 1], params).is_some() {
+                start -= 1;
+            }
+            frames.push((start, index, live));
+        }
+
+        // from the last call, so that the indices of the others stay the same
+        for (start, call, live) in frames.into_iter().rev() {
+            block
+                .instructions
+                .insert(call + 1, IRInstruction::PopFrame(live.clone()));
+            block
+                .instructions
+                .insert(start, IRInstruction::PushFrame(live));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_text::{parse_modules, print_modules};
+
+    #[test]
+    fn test_recursive_calls() {
+        let src = r#"
+        module pkg::main
+        fn pkg::main+0:0:fib(pkg::main+1:0:n) {
+            locals pkg::main+1:0:a
+        b0:
+            r2 = 1
+            r0 = pkg::main+1:0:n
+            r0 -= r2
+            pkg::main+1:0:n = r0
+            call pkg::main+0:0:fib
+            pkg::main+1:0:a = ret
+            r3 = 2
+            r1 = pkg::main+1:0:n
+            r1 -= r3
+            pkg::main+1:0:n = r1
+            call pkg::main+0:0:fib
+            pkg::main+1:0:a += ret
+            call pkg::main+0:0:leaf
+            ret = pkg::main+1:0:a
+            return
+        }
+        fn pkg::main+0:0:leaf() {
+        b0:
+            return
+        }
+        "#;
+        let expected = r#"
+        module pkg::main
+        fn pkg::main+0:0:fib(pkg::main+1:0:n) {
+            locals pkg::main+1:0:a
+        b0:
+            r2 = 1
+            r0 = pkg::main+1:0:n
+            r0 -= r2
+            push_frame score(pkg::main+1:0:n)
+            pkg::main+1:0:n = r0
+            call pkg::main+0:0:fib
+            pop_frame score(pkg::main+1:0:n)
+            pkg::main+1:0:a = ret
+            r3 = 2
+            r1 = pkg::main+1:0:n
+            r1 -= r3
+            push_frame score(pkg::main+1:0:a)
+            pkg::main+1:0:n = r1
+            call pkg::main+0:0:fib
+            pop_frame score(pkg::main+1:0:a)
+            pkg::main+1:0:a += ret
+            call pkg::main+0:0:leaf
+            ret = pkg::main+1:0:a
+            return
+        }
+        fn pkg::main+0:0:leaf() {
+        b0:
+            return
+        }
+        "#;
+
+        let mut modules = parse_modules(src).unwrap();
+        insert_stack_frames(&mut modules);
+        assert_eq!(
+            print_modules(&modules),
+            print_modules(&parse_modules(expected).unwrap())
+        );
+    }
+}
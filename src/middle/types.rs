@@ -1,15 +1,175 @@
+use crate::front::ast_types::{Attribute, ResolvedName};
 use crate::modules::ModuleId;
+use serde::{Deserialize, Serialize};
 
+// temporaries created while lowering expressions, numbered per function until registers are allocated
+pub type Register = u32;
+
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
+pub enum Score {
+    Var(ResolvedName), // a variable stored in a scoreboard
+    Reg(Register),     // a temporary of the current function
+    Return,            // the return value of the last called function
+}
+
+// values that do not fit in a score (e.g. strings) are kept in data storage
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
+pub enum Storage {
+    Var(ResolvedName),
+    Reg(Register),
+    Return,
+}
+
+// where a value is kept, e.g. a value saved in a stack frame
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
+pub enum Location {
+    Score(Score),
+    Storage(Storage),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum ScoreOperation {
+    Assign,
+    Add,
+    Sub,
+    Mul,
+    Div, // floor division, like the scoreboard
+    Mod, // floor modulo, like the scoreboard
+    Min,
+    Max,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum CompareOperation {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum IRCommandPart {
+    Text(String),
+    ScoreValue(Score, i32), // the value of the score divided by the given scale
+    StorageValue(Storage),
+    ScoreLocation(Score),     // the score holder and objective
+    StorageLocation(Storage), // the storage and path
+}
+
+// a failed assertion is reported with where it is written, and its source
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Assertion {
+    pub condition: Score,                    // fails if 0
+    pub values: Option<(Score, Score, i32)>, // the two sides of `assert_eq!`, and their scale
+    pub module_id: ModuleId,
+    pub offset: usize, // in the file of the module
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum IRInstruction {
     MCommand(String),
+    InterpolatedCommand(Vec<IRCommandPart>),
+    ScoreSet(Score, i32),
+    ScoreOperation(Score, ScoreOperation, Score),
+    ScoreCompare(Score, Score, CompareOperation, Score), // sets the target to 1 if the comparison holds, 0 otherwise
+    StorageSetString(Storage, String),
+    StorageCopy(Storage, Storage),
+    Call(ResolvedName),
+    Assert(Assertion),        // records the failure and returns from the function
+    PushFrame(Vec<Location>), // saves the values in a new frame on top of the stack
+    PopFrame(Vec<Location>),  // restores the values saved in the top frame, and removes it
 }
 
-pub struct IRFunction {
-    pub name: String,
+impl IRInstruction {
+    // calls the given functions on every score and storage the instruction reads or writes, e.g. to rename them
+    pub fn for_each_location_mut(
+        &mut self,
+        score: &mut impl FnMut(&mut Score),
+        storage: &mut impl FnMut(&mut Storage),
+    ) {
+        match self {
+            IRInstruction::MCommand(_) | IRInstruction::Call(_) => {}
+            IRInstruction::InterpolatedCommand(parts) => {
+                for part in parts.iter_mut() {
+                    match part {
+                        IRCommandPart::Text(_) => {}
+                        IRCommandPart::ScoreValue(s, _) | IRCommandPart::ScoreLocation(s) => {
+                            score(s)
+                        }
+                        IRCommandPart::StorageValue(s) | IRCommandPart::StorageLocation(s) => {
+                            storage(s)
+                        }
+                    }
+                }
+            }
+            IRInstruction::ScoreSet(target, _) => score(target),
+            IRInstruction::ScoreOperation(target, _, source) => {
+                score(target);
+                score(source);
+            }
+            IRInstruction::ScoreCompare(target, lhs, _, rhs) => {
+                score(target);
+                score(lhs);
+                score(rhs);
+            }
+            IRInstruction::StorageSetString(target, _) => storage(target),
+            IRInstruction::StorageCopy(target, source) => {
+                storage(target);
+                storage(source);
+            }
+            IRInstruction::Assert(assertion) => {
+                score(&mut assertion.condition);
+                if let Some((lhs, rhs, _)) = assertion.values.as_mut() {
+                    score(lhs);
+                    score(rhs);
+                }
+            }
+            IRInstruction::PushFrame(locations) | IRInstruction::PopFrame(locations) => {
+                for location in locations.iter_mut() {
+                    match location {
+                        Location::Score(s) => score(s),
+                        Location::Storage(s) => storage(s),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the index of a block in its function
+pub type BlockId = usize;
+
+// how control leaves a basic block
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch(Score, BlockId, BlockId), // to the first block if the score is not 0, to the second otherwise
+    Return,
+    TailCall(ResolvedName), // calls the function and returns what it returns
+}
+
+// instructions that run one after another, without control flow in between
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BasicBlock {
     pub instructions: Vec<IRInstruction>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct IRFunction {
+    pub name: ResolvedName,
+    pub attributes: Vec<Attribute>,
+    pub params: Vec<ResolvedName>,
+    pub locals: Vec<ResolvedName>, // the variables defined in the body, including nested blocks
+    pub blocks: Vec<BasicBlock>,   // the function starts at the first block
 }
 
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct IRModule {
     pub id: ModuleId,
+    pub statics: Vec<ResolvedName>, // the static variables defined in the module
     pub functions: Vec<IRFunction>,
 }
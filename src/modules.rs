@@ -1,23 +1,56 @@
 use crate::file_system::FileSystem;
-use crate::modules::cache::BuildCacheLayer;
-use crate::modules::types::ModuleGraph;
+use crate::front::ast_types::{FullItemPath, ResolvedName, Visibility};
+use crate::front::definition_table::ModuleExports;
+use crate::front::{collect_exports, resolve_module, FrontError};
+use crate::modules::cache::{BuildCacheLayer, LoadedModule};
+use crate::modules::types::{ModuleCachableData, ModuleGraph};
 use crate::modules::utf8buf_utils::utf8path_buf_to_vec;
 use camino::Utf8PathBuf;
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::io::Read;
+use std::fmt;
 
 mod cache;
-mod types;
+pub mod types;
 mod utf8buf_utils;
 
 #[derive(Debug)]
-enum ModuleBuildError {
+pub enum ModuleBuildError {
     NoMainInRoot,
     FileNoLongerExists,
     FileReadError,
+    PrivateImport(ModuleId, ResolvedName), // the importing module, the imported item
+    DuplicateModule(ModuleId),             // an inline module with the same path as a file module
+    InvalidModules(Vec<(ModuleId, FrontError)>), // every module that could not be parsed or resolved
 }
 
-type ModuleBuildResult<T> = Result<T, ModuleBuildError>;
+pub type ModuleBuildResult<T> = Result<T, ModuleBuildError>;
+
+impl fmt::Display for ModuleBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModuleBuildError::NoMainInRoot => write!(f, "the package has no `main.ing`"),
+            ModuleBuildError::FileNoLongerExists => write!(f, "a source file no longer exists"),
+            ModuleBuildError::FileReadError => write!(f, "a source file cannot be read"),
+            ModuleBuildError::PrivateImport(module_id, name) => write!(
+                f,
+                "`{}` imports `{}+{}`, which is private",
+                module_id, name.module_id, name.item_name
+            ),
+            ModuleBuildError::DuplicateModule(module_id) => write!(
+                f,
+                "the module `{}` is declared inline and as a file",
+                module_id
+            ),
+            ModuleBuildError::InvalidModules(errors) => {
+                write!(f, "{} module(s) have errors", errors.len())?;
+                for (module_id, error) in errors {
+                    write!(f, "\n  {}: {:?}", module_id, error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
 pub struct ModuleBuilder<'p, T: FileSystem> {
     module_graph: ModuleGraph,
@@ -42,12 +75,13 @@ impl<'p, T: FileSystem> ModuleBuilder<'p, T> {
             .package_map
             .insert(package_name.to_string(), path.clone());
 
-        let mut queue = VecDeque::from([path.clone()]);
+        // directories to scan, with the module whose submodules they contain. `foo/` belongs to `foo.ing`
+        let mut queue = VecDeque::from([(path.clone(), None::<ModuleId>)]);
 
         let mut is_root_dir = true;
         let mut found_root_module = false;
 
-        while let Some(current_path) = queue.pop_front() {
+        while let Some((current_path, parent)) = queue.pop_front() {
             let file_paths = self
                 .build_cache
                 .file_system
@@ -55,7 +89,7 @@ impl<'p, T: FileSystem> ModuleBuilder<'p, T> {
 
             for file_path in file_paths {
                 if let Some(module_name) = file_path.file_name() {
-                    let rel_path = &create_rel_path(&file_path, &path);
+                    let rel_path = &create_rel_path(&file_path, path);
                     let id = module_id_from_local(
                         package_name,
                         &utf8path_buf_to_vec(&rel_path.with_extension("")),
@@ -66,8 +100,11 @@ impl<'p, T: FileSystem> ModuleBuilder<'p, T> {
                         found_root_module = true;
                     }
 
-                    queue.push_back(file_path.with_extension(""));
-                    self.module_graph.create_node(id, &package_name, &rel_path);
+                    if let Some(parent) = &parent {
+                        self.module_graph.add_child(parent, &id);
+                    }
+                    queue.push_back((file_path.with_extension(""), Some(id.clone())));
+                    self.module_graph.create_node(id, package_name, rel_path);
                 }
             }
             is_root_dir = false;
@@ -78,12 +115,21 @@ impl<'p, T: FileSystem> ModuleBuilder<'p, T> {
             }
         }
 
-        return Ok(());
+        Ok(())
     }
 
-    // can be multithreaded easily
-    pub fn load_module_bodies(&mut self) -> ModuleBuildResult<()> {
-        for node in self.module_graph.nodes.values_mut() {
+    // can be multithreaded easily. Returns the modules that had to be parsed again
+    pub fn load_module_bodies(&mut self) -> ModuleBuildResult<Vec<ModuleId>> {
+        // inline modules are created again from the file they are declared in
+        self.module_graph.remove_inline_nodes();
+
+        let mut changed = vec![];
+        let mut inline_nodes = vec![];
+        let mut module_exports = ModuleExports::new();
+        // invalid modules are left without a body, so the valid ones can still be loaded
+        let mut errors = vec![];
+
+        for (id, node) in self.module_graph.nodes.iter_mut() {
             let rel_path = node.rel_path.clone();
             let abs_path = self
                 .module_graph
@@ -91,12 +137,207 @@ impl<'p, T: FileSystem> ModuleBuilder<'p, T> {
                 .get(&node.package_name)
                 .unwrap()
                 .join(&rel_path);
+            let item_path = utf8path_buf_to_vec(&rel_path);
+
+            match self
+                .build_cache
+                .take_module(&node.package_name, &item_path, &abs_path)?
+            {
+                LoadedModule::Cached(modules) => {
+                    for (module_id, body) in modules {
+                        module_exports.insert(module_id.clone(), body.definitions.exports());
+                        if &module_id == id {
+                            node.body = Some(body);
+                        } else {
+                            inline_nodes.push((
+                                module_id,
+                                node.package_name.clone(),
+                                rel_path.clone(),
+                                Some(body),
+                            ));
+                        }
+                    }
+                }
+                LoadedModule::Changed(read_on, modules) => {
+                    for (inline_path, module) in modules {
+                        let module_path = FullItemPath::new(
+                            node.package_name.clone(),
+                            [item_path.clone(), inline_path].concat(),
+                        );
+                        let module_id =
+                            module_id_from_local(&module_path.package_name, &module_path.item_path);
+
+                        module_exports.insert(module_id.clone(), collect_exports(&module));
+                        if &module_id != id {
+                            inline_nodes.push((
+                                module_id.clone(),
+                                node.package_name.clone(),
+                                rel_path.clone(),
+                                None,
+                            ));
+                        }
+                        changed.push((module_id, module_path, read_on, module));
+                    }
+                }
+                LoadedModule::Invalid(error) => {
+                    // the module still exists for glob imports, even though its definitions are unknown
+                    module_exports.insert(id.clone(), vec![]);
+                    node.body = None;
+                    errors.push((id.clone(), error));
+                }
+            }
+        }
 
-            node.body = Some(self.build_cache.take_module(
-                &node.package_name,
-                &utf8path_buf_to_vec(&rel_path),
-                &abs_path,
-            )?);
+        let inline_ids = inline_nodes
+            .iter()
+            .map(|(id, ..)| id.clone())
+            .collect::<Vec<_>>();
+        for (id, package_name, rel_path, body) in inline_nodes {
+            if self.module_graph.nodes.contains_key(&id) {
+                return Err(ModuleBuildError::DuplicateModule(id));
+            }
+            self.module_graph
+                .create_node(id.clone(), &package_name, &rel_path);
+            self.module_graph.nodes.get_mut(&id).unwrap().body = body;
+        }
+        // children are added once every inline module exists, as they are not ordered
+        for id in inline_ids {
+            self.module_graph.add_child(parent_module_id(&id), &id);
+        }
+
+        // names can only be resolved once the definitions of every module are known, because of glob imports
+        let changed_ids = changed
+            .iter()
+            .map(|(id, ..)| id.clone())
+            .collect::<Vec<_>>();
+        for (id, module_path, read_on, module) in changed {
+            let (direct_deps, imports, definitions, symbols) =
+                match resolve_module(module_path, module, &module_exports) {
+                    Ok(resolved) => resolved,
+                    Err(error) => {
+                        errors.push((id, error));
+                        continue;
+                    }
+                };
+
+            // the inline modules declared directly inside of this one
+            let inline_modules = changed_ids
+                .iter()
+                .filter(|child| child.as_str() != id && parent_module_id(child) == id)
+                .cloned()
+                .collect();
+
+            self.module_graph.nodes.get_mut(&id).unwrap().body = Some(ModuleCachableData {
+                read_on,
+                direct_deps,
+                definitions,
+                object: None,
+                imports,
+                inline_modules,
+                symbols,
+            });
+        }
+
+        if !errors.is_empty() {
+            errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+            return Err(ModuleBuildError::InvalidModules(errors));
+        }
+
+        self.check_imports()?;
+        Ok(changed_ids)
+    }
+
+    // loads the packages again, keeping the modules whose files were not modified. Modules depending on a
+    // modified module are loaded again as well, as their names may no longer resolve to the same items
+    pub fn reload_module_bodies(&mut self) -> ModuleBuildResult<Vec<ModuleId>> {
+        // the root is kept if the build fails, so that the package can be loaded again once it is fixed
+        let root_package = self
+            .module_graph
+            .root
+            .as_ref()
+            .map(|root| parent_module_id(root).to_string());
+
+        let mut dirty = HashSet::new();
+        let mut bodies = HashMap::new();
+        for (id, node) in self.module_graph.nodes.drain() {
+            let abs_path = self.module_graph.package_map[&node.package_name].join(&node.rel_path);
+            let age = self.build_cache.file_system.get_file_age(&abs_path).ok();
+
+            match node.body {
+                Some(body) if Some(body.read_on) == age => {
+                    bodies.insert(id, body);
+                }
+                _ => {
+                    dirty.insert(id);
+                }
+            }
+        }
+
+        for (package_name, path) in self.module_graph.package_map.clone() {
+            let is_root_package = root_package.as_ref() == Some(&package_name);
+            self.add_fs_package(&package_name, &path, is_root_package)?;
+        }
+        // added files may now be the target of a previously unresolved import
+        dirty.extend(
+            self.module_graph
+                .nodes
+                .keys()
+                .filter(|id| !bodies.contains_key(*id))
+                .cloned(),
+        );
+
+        let mut dependents: HashMap<&ModuleId, Vec<&ModuleId>> = HashMap::new();
+        for (id, body) in bodies.iter() {
+            for dependency in body.direct_deps.iter() {
+                dependents.entry(dependency).or_default().push(id);
+            }
+        }
+
+        let mut pending = dirty.iter().cloned().collect::<Vec<_>>();
+        while let Some(id) = pending.pop() {
+            for dependent in dependents.get(&id).into_iter().flatten() {
+                if dirty.insert((*dependent).clone()) {
+                    pending.push((*dependent).clone());
+                }
+            }
+        }
+
+        bodies.retain(|id, _| !dirty.contains(id));
+        self.build_cache.restore(bodies);
+        self.load_module_bodies()
+    }
+
+    // makes sure that no module uses an item that is not visible to it
+    fn check_imports(&self) -> ModuleBuildResult<()> {
+        let nodes = &self.module_graph.nodes;
+
+        for (id, node) in nodes.iter() {
+            let Some(body) = &node.body else {
+                continue;
+            };
+
+            for name in body.imports.iter() {
+                // imports of modules, or of items that do not exist, are not a visibility problem
+                let Some(visibility) = nodes
+                    .get(&name.module_id)
+                    .and_then(|target| target.body.as_ref())
+                    .and_then(|target| target.definitions.get_visibility(&name.item_name))
+                else {
+                    continue;
+                };
+
+                let visible = match visibility {
+                    Visibility::Public => true,
+                    Visibility::Package => nodes[&name.module_id].package_name == node.package_name,
+                    // private items are visible in their module and its descendants, like in Rust
+                    Visibility::Private => {
+                        *id == name.module_id || id.starts_with(&format!("{}::", name.module_id))
+                    }
+                };
+                if !visible {
+                    return Err(ModuleBuildError::PrivateImport(id.clone(), name.clone()));
+                }
+            }
         }
 
         Ok(())
@@ -124,6 +365,10 @@ impl<'p, T: FileSystem> ModuleBuilder<'p, T> {
     pub fn get_module_graph(&self) -> &ModuleGraph {
         &self.module_graph
     }
+
+    pub fn get_file_system(&mut self) -> &mut T {
+        self.build_cache.file_system
+    }
 }
 
 fn create_rel_path(file_path: &Utf8PathBuf, package_path: &Utf8PathBuf) -> Utf8PathBuf {
@@ -133,20 +378,32 @@ fn create_rel_path(file_path: &Utf8PathBuf, package_path: &Utf8PathBuf) -> Utf8P
 // the module id contains the package name and the relative path to module file from the package root.
 pub type ModuleId = String;
 
-pub fn module_id_from_local(package_name: &str, file_path: &Vec<String>) -> ModuleId {
+pub fn module_id_from_local(package_name: &str, file_path: &[String]) -> ModuleId {
     file_path
         .iter()
         .fold(package_name.to_string(), |a, b| a + "::" + b)
 }
 
+// package_a::module_a::module_b => package_a::module_a
+fn parent_module_id(module_id: &str) -> &str {
+    module_id
+        .rsplit_once("::")
+        .map_or(module_id, |(parent, _)| parent)
+}
+
 pub type ModuleDependencies = HashSet<ModuleId>;
+pub type ModuleImports = HashSet<ResolvedName>;
 
 #[cfg(test)]
 mod tests {
     use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use crate::file_system::concrete::system_fs::SystemFs;
+    use crate::file_system::FileSystem;
     use crate::front::ast_types::{ResolvedName, Type};
-    use crate::modules::ModuleBuilder;
+    use crate::front::FrontError;
+    use crate::modules::{ModuleBuildError, ModuleBuildResult, ModuleBuilder};
     use camino::Utf8PathBuf;
+    use std::io::Write;
 
     #[test]
     fn test_module_id_from_local() {
@@ -257,6 +514,414 @@ mod tests {
         );
     }
 
+    fn build_packages(
+        package_a: &[(&str, &str)],
+        package_b: &[(&str, &str)],
+    ) -> ModuleBuildResult<()> {
+        let mut mock_fs = MockFileSystem::new();
+        for (name, content) in package_a {
+            mock_fs.insert_file(
+                Utf8PathBuf::from(format!("pkg/package_a/{}", name)),
+                content,
+            );
+        }
+        for (name, content) in package_b {
+            mock_fs.insert_file(
+                Utf8PathBuf::from(format!("pkg/package_b/{}", name)),
+                content,
+            );
+        }
+
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, None);
+        module_builder.add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)?;
+        module_builder.add_fs_package("package_b", &Utf8PathBuf::from("pkg/package_b"), false)?;
+        module_builder.load_module_bodies().map(|_| ())
+    }
+
+    #[test]
+    fn test_reload_module_bodies() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            "use root::module_a::helper; fn main() { helper(); }",
+        );
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/module_a.ing"),
+            "pub fn helper() {}",
+        );
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/module_b.ing"),
+            "static b: int;",
+        );
+
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, None);
+        module_builder
+            .add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)
+            .unwrap();
+        assert_eq!(module_builder.load_module_bodies().unwrap().len(), 3);
+        assert!(module_builder.reload_module_bodies().unwrap().is_empty());
+
+        let reload = |module_builder: &mut ModuleBuilder<MockFileSystem>| {
+            let mut reloaded = module_builder.reload_module_bodies().unwrap();
+            reloaded.sort();
+            reloaded
+        };
+
+        // modules depending on the modified module are loaded again as well
+        module_builder.get_file_system().insert_file(
+            Utf8PathBuf::from("pkg/package_a/module_a.ing"),
+            "pub fn helper() {} pub fn other() {}",
+        );
+        assert_eq!(
+            reload(&mut module_builder),
+            vec!["package_a::main", "package_a::module_a"]
+        );
+
+        module_builder
+            .get_file_system()
+            .insert_file(Utf8PathBuf::from("pkg/package_a/module_c.ing"), "fn c() {}");
+        assert_eq!(reload(&mut module_builder), vec!["package_a::module_c"]);
+
+        module_builder
+            .get_file_system()
+            .remove_file(&Utf8PathBuf::from("pkg/package_a/module_b.ing"))
+            .unwrap();
+        assert!(reload(&mut module_builder).is_empty());
+        assert!(!module_builder
+            .get_module_graph()
+            .nodes
+            .contains_key("package_a::module_b"));
+    }
+
+    #[test]
+    fn test_invalid_modules() {
+        let result = build_packages(
+            &[
+                ("main.ing", "fn main() { missing(); }"),
+                ("module_a.ing", "fn a( {}"),
+                ("module_b.ing", "fn b() {}"),
+            ],
+            &[],
+        );
+        let Err(ModuleBuildError::InvalidModules(errors)) = result else {
+            panic!("expected invalid modules");
+        };
+        assert!(matches!(
+            &errors[..],
+            [
+                (main, FrontError::NameResolution(_)),
+                (module_a, FrontError::Parse(_))
+            ] if main == "package_a::main" && module_a == "package_a::module_a"
+        ));
+    }
+
+    #[test]
+    fn test_private_import() {
+        let main = (
+            "main.ing",
+            "use root::module_a::helper; fn main() { helper(); }",
+        );
+
+        assert!(build_packages(&[main, ("module_a.ing", "pub fn helper() {}")], &[]).is_ok());
+        assert!(matches!(
+            build_packages(&[main, ("module_a.ing", "fn helper() {}")], &[]),
+            Err(ModuleBuildError::PrivateImport(module, name))
+                if module == "package_a::main"
+                    && name == ResolvedName::new("package_a::module_a".to_string(), "helper".to_string())
+        ));
+        assert_eq!(
+            build_packages(&[main, ("module_a.ing", "fn helper() {}")], &[])
+                .err()
+                .unwrap()
+                .to_string(),
+            "`package_a::main` imports `package_a::module_a+helper`, which is private"
+        );
+
+        // unused imports and qualified paths are checked as well
+        assert!(matches!(
+            build_packages(
+                &[
+                    ("main.ing", "use root::module_a::a; fn main() {}"),
+                    ("module_a.ing", "static a: int;")
+                ],
+                &[]
+            ),
+            Err(ModuleBuildError::PrivateImport(..))
+        ));
+        assert!(matches!(
+            build_packages(
+                &[
+                    (
+                        "main.ing",
+                        "use root::module_a; fn main() { module_a::helper(); }"
+                    ),
+                    ("module_a.ing", "fn helper() {}")
+                ],
+                &[]
+            ),
+            Err(ModuleBuildError::PrivateImport(..))
+        ));
+
+        // the descendants of a module can use its private items, but not its siblings
+        let nested = "fn helper() {} mod inner { use super::helper; fn f() { helper(); } }";
+        assert!(build_packages(&[("main.ing", nested)], &[]).is_ok());
+        assert!(matches!(
+            build_packages(
+                &[
+                    ("main.ing", "use root::module_b::f; fn main() {}"),
+                    ("module_a.ing", "fn helper() {}"),
+                    ("module_b.ing", "use root::module_a::helper; pub fn f() { helper(); }")
+                ],
+                &[]
+            ),
+            Err(ModuleBuildError::PrivateImport(module, _)) if module == "package_a::module_b"
+        ));
+    }
+
+    #[test]
+    fn test_glob_import() {
+        let helpers = (
+            "helpers.ing",
+            "pub fn a() {} pub(package) fn b() {} fn c() {}",
+        );
+
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            "use root::helpers::*; fn main() { a(); b(); }",
+        );
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/package_a/helpers.ing"), helpers.1);
+
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, None);
+        module_builder
+            .add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)
+            .unwrap();
+        module_builder.load_module_bodies().unwrap();
+
+        let main = module_builder.get_module_graph().nodes["package_a::main"]
+            .body
+            .as_ref()
+            .unwrap();
+        assert!(main.direct_deps.contains("package_a::helpers"));
+        assert!(main.imports.contains(&ResolvedName::new(
+            "package_a::helpers".to_string(),
+            "b".to_string()
+        )));
+
+        // public items can be glob imported from other packages as well
+        assert!(build_packages(
+            &[("main.ing", "use package_b::helpers::*; fn main() { a(); }")],
+            &[helpers]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_package_visibility() {
+        let helpers = (
+            "helpers.ing",
+            "pub(package) fn helper() {} pub struct api { a: int, }",
+        );
+
+        // visible to modules of the same package
+        assert!(build_packages(
+            &[("main.ing", "fn main() {}")],
+            &[
+                helpers,
+                (
+                    "util.ing",
+                    "use package_b::helpers::helper; fn f() { helper(); }"
+                )
+            ]
+        )
+        .is_ok());
+
+        // but not to other packages
+        assert!(build_packages(
+            &[(
+                "main.ing",
+                "use package_b::helpers::api; fn main() { let x: api; }"
+            )],
+            &[helpers]
+        )
+        .is_ok());
+        assert!(matches!(
+            build_packages(
+                &[(
+                    "main.ing",
+                    "use package_b::helpers::helper; fn main() { helper(); }"
+                )],
+                &[helpers]
+            ),
+            Err(ModuleBuildError::PrivateImport(..))
+        ));
+    }
+
+    // pkg/main.ing, pkg/a.ing, pkg/a/b.ing, pkg/a/b/c.ing, written through the file system itself
+    fn check_nested_package<T: FileSystem>(file_system: &mut T, root: &Utf8PathBuf) {
+        let files = [
+            ("main.ing", "use root::a::b::c::deep; fn main() { deep(); }"),
+            ("a.ing", "fn shallow() {}"),
+            ("a/b.ing", "use super::shallow; fn middle() { shallow(); }"),
+            (
+                "a/b/c.ing",
+                "use super::middle; pub fn deep() { middle(); }",
+            ),
+        ];
+        for (path, content) in files {
+            file_system
+                .get_writer(&root.join(path))
+                .unwrap()
+                .write_all(content.as_bytes())
+                .unwrap();
+        }
+
+        let mut module_builder = ModuleBuilder::new(file_system, None);
+        module_builder.add_fs_package("pkg", root, true).unwrap();
+        module_builder.load_module_bodies().unwrap();
+
+        let module_graph = module_builder.get_module_graph();
+        let mut ids = module_graph.nodes.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec!["pkg::a", "pkg::a::b", "pkg::a::b::c", "pkg::main"]
+        );
+
+        let children = |id: &str| {
+            let mut children = module_graph.nodes[id]
+                .children
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            children.sort();
+            children
+        };
+        assert_eq!(children("pkg::main"), Vec::<String>::new());
+        assert_eq!(children("pkg::a"), vec!["pkg::a::b"]);
+        assert_eq!(children("pkg::a::b"), vec!["pkg::a::b::c"]);
+        assert_eq!(children("pkg::a::b::c"), Vec::<String>::new());
+
+        assert_eq!(
+            module_graph.nodes["pkg::a::b::c"].rel_path,
+            Utf8PathBuf::from("a/b/c.ing")
+        );
+        assert!(module_graph.nodes["pkg::a::b::c"]
+            .body
+            .as_ref()
+            .unwrap()
+            .direct_deps
+            .contains("pkg::a::b"));
+    }
+
+    #[test]
+    fn test_nested_package_mock_fs() {
+        let mut mock_fs = MockFileSystem::new();
+        check_nested_package(&mut mock_fs, &Utf8PathBuf::from("pkg/package_a"));
+    }
+
+    #[test]
+    fn test_nested_package_system_fs() {
+        let root = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!(
+                "blastfurnace_nested_package_{}",
+                std::process::id()
+            ));
+
+        check_nested_package(&mut SystemFs::new(), &root);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_inline_modules() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"
+            mod util {
+                pub fn f() {}
+                mod inner {
+                    use super::f;
+                    pub fn g() { f(); }
+                }
+            }
+            fn main() {
+                util::f();
+                util::inner::g();
+            }
+            "#,
+        );
+
+        let check = |module_builder: &ModuleBuilder<MockFileSystem>| {
+            let module_graph = module_builder.get_module_graph();
+            let mut ids = module_graph.nodes.keys().cloned().collect::<Vec<_>>();
+            ids.sort();
+            assert_eq!(
+                ids,
+                vec![
+                    "package_a::main",
+                    "package_a::main::util",
+                    "package_a::main::util::inner"
+                ]
+            );
+
+            let node = |id: &str| &module_graph.nodes[id];
+            let body = |id: &str| node(id).body.as_ref().unwrap();
+            assert!(node("package_a::main")
+                .children
+                .contains("package_a::main::util"));
+            assert!(node("package_a::main::util")
+                .children
+                .contains("package_a::main::util::inner"));
+            assert_eq!(
+                node("package_a::main::util::inner").rel_path,
+                Utf8PathBuf::from("main.ing")
+            );
+
+            assert_eq!(body("package_a::main").definitions.fn_map.len(), 1);
+            assert_eq!(body("package_a::main::util").definitions.fn_map.len(), 1);
+            assert!(body("package_a::main")
+                .direct_deps
+                .contains("package_a::main::util::inner"));
+            assert!(body("package_a::main::util::inner")
+                .direct_deps
+                .contains("package_a::main::util"));
+        };
+
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, Some(Utf8PathBuf::from("cache")));
+        module_builder
+            .add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)
+            .unwrap();
+        module_builder.load_module_bodies().unwrap();
+        check(&module_builder);
+        module_builder.save_cache();
+
+        // the inline modules are restored from the cache along with their file
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, Some(Utf8PathBuf::from("cache")));
+        module_builder.load_cache();
+        module_builder
+            .add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)
+            .unwrap();
+        module_builder.load_module_bodies().unwrap();
+        check(&module_builder);
+    }
+
+    #[test]
+    fn test_duplicate_inline_module() {
+        assert!(matches!(
+            build_packages(
+                &[
+                    ("main.ing", "fn main() {}"),
+                    ("a.ing", "mod b {}"),
+                    ("a/b.ing", "")
+                ],
+                &[]
+            ),
+            Err(ModuleBuildError::DuplicateModule(id)) if id == "package_a::a::b"
+        ));
+    }
+
     #[test]
     fn test_irregular_package_name() {
         let mut mock_fs = MockFileSystem::new();
@@ -1,11 +1,18 @@
 use crate::file_system::{FileSystem, FileSystemError};
-use crate::front::ast_types::{FullItemPath, ItemPath, PackageName};
-use crate::front::parse_file;
+use crate::front::ast_types::{InlineModule, ItemPath, PackageName};
+use crate::front::{parse_ast, FrontError};
 use crate::modules::types::ModuleCachableData;
 use crate::modules::{module_id_from_local, ModuleBuildError, ModuleBuildResult, ModuleId};
 use camino::Utf8PathBuf;
 use std::collections::HashMap;
 
+// the modules of a file. The first module is the file's module, the rest are its inline modules
+pub enum LoadedModule {
+    Cached(Vec<(ModuleId, ModuleCachableData)>),
+    Changed(u128, Vec<InlineModule>), // the file was modified at the given time, its names still have to be resolved
+    Invalid(FrontError),              // the file could not be parsed
+}
+
 pub struct BuildCacheLayer<'p, T: FileSystem> {
     pub file_system: &'p mut T,
     cache_location: Option<Utf8PathBuf>,
@@ -13,7 +20,7 @@ pub struct BuildCacheLayer<'p, T: FileSystem> {
 }
 
 impl<T: FileSystem> BuildCacheLayer<'_, T> {
-    pub fn new(file_system: &mut T, cache_location: Option<Utf8PathBuf>) -> BuildCacheLayer<T> {
+    pub fn new(file_system: &mut T, cache_location: Option<Utf8PathBuf>) -> BuildCacheLayer<'_, T> {
         BuildCacheLayer {
             file_system,
             cache_location,
@@ -56,29 +63,47 @@ impl<T: FileSystem> BuildCacheLayer<'_, T> {
         }
     }
 
+    // replaces the cache with modules kept in memory, to be taken again if their files were not modified
+    pub fn restore(&mut self, modules: HashMap<ModuleId, ModuleCachableData>) {
+        self.cache = Some(modules);
+    }
+
     pub fn take_module(
         &mut self,
         package_name: &PackageName,
         item_path: &ItemPath,
         abs_path: &Utf8PathBuf,
-    ) -> ModuleBuildResult<ModuleCachableData> {
+    ) -> ModuleBuildResult<LoadedModule> {
         let id = module_id_from_local(package_name, item_path);
         let age = self
             .file_system
-            .get_file_age(&abs_path)
+            .get_file_age(abs_path)
             .or(Err(ModuleBuildError::FileNoLongerExists))?;
 
         if let Some(cache) = &mut self.cache {
-            if let Some(cached_data) = cache.remove(&id) {
-                if cached_data.read_on == age {
-                    return Ok(cached_data);
+            if cache
+                .get(&id)
+                .is_some_and(|cached_data| cached_data.read_on == age)
+            {
+                // the inline modules were cached together with the file's module
+                let mut modules = vec![];
+                let mut pending = vec![id];
+                while let Some(id) = pending.pop() {
+                    let Some(cached_data) = cache.remove(&id) else {
+                        break;
+                    };
+                    pending.extend(cached_data.inline_modules.iter().cloned());
+                    modules.push((id, cached_data));
+                }
+                if pending.is_empty() {
+                    return Ok(LoadedModule::Cached(modules));
                 }
             }
         }
 
         let mut reader = self
             .file_system
-            .get_reader(&abs_path)
+            .get_reader(abs_path)
             .or(Err(ModuleBuildError::FileNoLongerExists))?;
 
         let mut file_content = String::new();
@@ -86,16 +111,12 @@ impl<T: FileSystem> BuildCacheLayer<'_, T> {
             .read_to_string(&mut file_content)
             .or(Err(ModuleBuildError::FileReadError))?;
 
-        let (direct_deps, definitions) = parse_file(
-            FullItemPath::new(package_name.clone(), item_path.clone()),
-            &file_content,
-        );
-
-        return Ok(ModuleCachableData {
-            read_on: age,
-            direct_deps,
-            definitions,
-            object: None,
-        });
+        let (module, inline_modules) = match parse_ast(package_name, &file_content) {
+            Ok(modules) => modules,
+            Err(error) => return Ok(LoadedModule::Invalid(error)),
+        };
+        let mut modules = vec![(vec![], module)];
+        modules.extend(inline_modules);
+        Ok(LoadedModule::Changed(age, modules))
     }
 }
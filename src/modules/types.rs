@@ -1,5 +1,8 @@
+use crate::front::ast_types::FullItemPath;
 use crate::front::definition_table::DefinitionTable;
-use crate::modules::{ModuleDependencies, ModuleId};
+use crate::front::Symbols;
+use crate::modules::utf8buf_utils::utf8path_buf_to_vec;
+use crate::modules::{module_id_from_local, ModuleDependencies, ModuleId, ModuleImports};
 use camino::Utf8PathBuf;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
@@ -21,6 +24,13 @@ pub struct ModuleCachableData {
     pub direct_deps: ModuleDependencies, // direct dependencies, used for computing the dependency graph
     pub definitions: DefinitionTable,    // front-end objects
     pub object: Option<String>,          // the object code created by the back-end
+
+    // items used from other modules, checked against their visibility
+    pub imports: ModuleImports,
+    // modules declared inside of this one with `mod name { ... }`
+    pub inline_modules: Vec<ModuleId>,
+    // the resolved names in the source, for navigating it
+    pub symbols: Symbols,
 }
 
 impl ModuleNode {
@@ -54,4 +64,43 @@ impl ModuleGraph {
         self.nodes
             .insert(id.clone(), ModuleNode::new(package_name, rel_path.clone()));
     }
+
+    // removes the modules that were declared inside of a file with `mod name { ... }`
+    pub fn remove_inline_nodes(&mut self) {
+        let inline_ids = self
+            .nodes
+            .iter()
+            .filter(|(id, node)| {
+                **id != module_id_from_local(
+                    &node.package_name,
+                    &utf8path_buf_to_vec(&node.rel_path),
+                )
+            })
+            .map(|(id, _)| id.clone())
+            .collect::<HashSet<_>>();
+
+        self.nodes.retain(|id, _| !inline_ids.contains(id));
+        for node in self.nodes.values_mut() {
+            node.children.retain(|id| !inline_ids.contains(id));
+        }
+    }
+
+    // the module of a file, given by its path on the file system
+    pub fn file_module(&self, path: &Utf8PathBuf) -> Option<FullItemPath> {
+        self.nodes.values().find_map(|node| {
+            let package_path = self.package_map.get(&node.package_name)?;
+            (&package_path.join(&node.rel_path) == path).then(|| {
+                FullItemPath::new(
+                    node.package_name.clone(),
+                    utf8path_buf_to_vec(&node.rel_path),
+                )
+            })
+        })
+    }
+
+    pub fn add_child(&mut self, parent: &str, child: &ModuleId) {
+        if let Some(node) = self.nodes.get_mut(parent) {
+            node.children.insert(child.clone());
+        }
+    }
 }
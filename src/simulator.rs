@@ -0,0 +1,1281 @@
+use crate::back::Datapack;
+use crate::middle::constant_folding::evaluate_operation;
+use crate::middle::types::ScoreOperation;
+use crate::simulator::nbt::{parse_path, parse_snbt, Nbt, NbtPath};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+pub mod nbt;
+
+#[derive(Debug, PartialEq)]
+pub enum SimulatorError {
+    UnknownFunction(String),
+    Unsupported(String), // a command the simulator does not know, though the game might
+    InvalidCommand(String), // a command the game would not accept either
+    MissingMacroArgument(String, String), // the function, and the argument that was not given
+    CommandLimit, // more commands were run than the game allows at once, e.g. because of an endless loop
+    CallDepth,    // functions were nested deeper than the simulator follows them
+}
+
+pub type SimulatorResult<T> = Result<T, SimulatorError>;
+
+// the default `maxCommandChainLength` of the game
+const MAX_COMMANDS: usize = 65536;
+/* The game has no limit, but nested functions are run recursively. They are run on a thread of their own, with a
+stack that is large enough for this many of them. Functions run with `return run function` are not nested, they are
+run once the function returning them is done.
+ */
+const MAX_DEPTH: usize = 10000;
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+// the result of a command, or None if it failed
+type Outcome = Option<i32>;
+
+// a function and its macro arguments
+type Call = (String, Option<BTreeMap<String, Nbt>>);
+
+// whether a command returned from the function running it
+enum Flow {
+    Continue(Outcome),
+    Return(Outcome),
+    TailCall(Call), // returns the result of the function, which is run by the caller
+}
+
+impl Flow {
+    fn outcome(&self) -> Outcome {
+        match self {
+            Flow::Continue(outcome) | Flow::Return(outcome) => *outcome,
+            Flow::TailCall(_) => unreachable!("Tail calls are run before their result is used"),
+        }
+    }
+}
+
+// where `execute store` writes the result of its command
+enum StoreTarget {
+    Score(String, String),
+    Storage(String, NbtPath, String, f64), // the storage, path, type and scale
+}
+
+/* Runs the functions of a datapack without the game, so that tests can check what compiled programs do.
+
+Only the commands that the backend generates are supported: scoreboards, data storage, `execute` with conditions on
+scores and storage, function calls with macro arguments and `return`. What `say` and `tellraw` print is collected
+as plain text. Commands that fail do not stop the function, like in the game, while commands that are not supported
+stop the simulation with an error.
+ */
+pub struct Simulator {
+    functions: HashMap<String, Vec<String>>, // the lines of each function, by location
+    tags: HashMap<String, Vec<String>>,      // the functions of each function tag, without the `#`
+
+    objectives: HashSet<String>,
+    scores: HashMap<(String, String), i32>, // by holder and objective
+    storages: HashMap<String, Nbt>,
+    output: Vec<String>,
+
+    commands_run: usize, // since the current entry point was called
+    depth: usize,        // of the function being run
+}
+
+impl Simulator {
+    pub fn new(datapack: &Datapack) -> Simulator {
+        let mut functions = HashMap::new();
+        let mut tags = HashMap::new();
+
+        for (path, content) in datapack.files.iter() {
+            let parts = path.iter().collect::<Vec<_>>();
+            let ["data", namespace, kind, rest @ ..] = parts.as_slice() else {
+                continue;
+            };
+            let location = |extension: &str| {
+                let path = rest.join("/");
+                Some(format!("{}:{}", namespace, path.strip_suffix(extension)?))
+            };
+
+            match (*kind, rest) {
+                ("function" | "functions", _) => {
+                    if let Some(location) = location(".mcfunction") {
+                        functions.insert(location, content.lines().map(str::to_string).collect());
+                    }
+                }
+                ("tags", ["function" | "functions", rest @ ..]) => {
+                    let path = rest.join("/");
+                    let (Some(path), Ok(tag)) = (
+                        path.strip_suffix(".json"),
+                        serde_json::from_str::<serde_json::Value>(content),
+                    ) else {
+                        continue;
+                    };
+                    let values = tag["values"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|value| value.as_str().or(value["id"].as_str()))
+                        .map(resource_location)
+                        .collect();
+                    tags.insert(format!("{}:{}", namespace, path), values);
+                }
+                _ => {}
+            }
+        }
+
+        Simulator {
+            functions,
+            tags,
+            objectives: HashSet::new(),
+            scores: HashMap::new(),
+            storages: HashMap::new(),
+            output: vec![],
+            commands_run: 0,
+            depth: 0,
+        }
+    }
+
+    // runs the `minecraft:load` tag, like the game does when the datapack is loaded
+    #[cfg(test)]
+    pub fn load(&mut self) -> SimulatorResult<()> {
+        self.run_function("#minecraft:load").map(|_| ())
+    }
+
+    // runs the `minecraft:tick` tag once, if there is one
+    #[cfg(test)]
+    pub fn tick(&mut self) -> SimulatorResult<()> {
+        if !self.tags.contains_key("minecraft:tick") {
+            return Ok(());
+        }
+        self.run_function("#minecraft:tick").map(|_| ())
+    }
+
+    // runs a function, or a function tag starting with `#`, returning its result if it did not fail
+    pub fn run_function(&mut self, location: &str) -> SimulatorResult<Option<i32>> {
+        self.on_large_stack(|simulator| simulator.call(location, None))
+    }
+
+    #[cfg(test)]
+    pub fn run_command(&mut self, command: &str) -> SimulatorResult<Option<i32>> {
+        self.on_large_stack(|simulator| {
+            let flow = simulator.execute(command)?;
+            simulator.finish(flow).map(|flow| flow.outcome())
+        })
+    }
+
+    fn on_large_stack<T: Send>(&mut self, run: impl FnOnce(&mut Simulator) -> T + Send) -> T {
+        self.commands_run = 0;
+        self.depth = 0;
+        std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn_scoped(scope, || run(self))
+                .expect("Cannot start the simulator thread")
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    pub fn score(&self, holder: &str, objective: &str) -> Option<i32> {
+        self.scores
+            .get(&(holder.to_string(), objective.to_string()))
+            .copied()
+    }
+
+    pub fn storage(&self, storage: &str, path: &str) -> Option<&Nbt> {
+        let (path, _) = parse_path(path)?;
+        self.storages.get(&resource_location(storage))?.get(&path)
+    }
+
+    // what was printed with `say` and `tellraw`, one line per command
+    #[cfg(test)]
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn call(
+        &mut self,
+        location: &str,
+        arguments: Option<&BTreeMap<String, Nbt>>,
+    ) -> SimulatorResult<Outcome> {
+        // every function of a tag is run, the result is how many there were
+        if let Some(tag) = location.strip_prefix('#') {
+            let functions = self
+                .tags
+                .get(&resource_location(tag))
+                .cloned()
+                .ok_or_else(|| SimulatorError::UnknownFunction(location.to_string()))?;
+            for function in functions.iter() {
+                self.call(function, None)?;
+            }
+            return Ok(Some(functions.len() as i32));
+        }
+
+        if self.depth == MAX_DEPTH {
+            return Err(SimulatorError::CallDepth);
+        }
+        self.depth += 1;
+        let outcome = self.run_tail_calls(resource_location(location), arguments.cloned());
+        self.depth -= 1;
+        outcome
+    }
+
+    // runs the function, and then every function it returns with `return run function`, at the same depth
+    fn run_tail_calls(
+        &mut self,
+        mut location: String,
+        mut arguments: Option<BTreeMap<String, Nbt>>,
+    ) -> SimulatorResult<Outcome> {
+        loop {
+            let lines = self
+                .functions
+                .get(&location)
+                .cloned()
+                .ok_or_else(|| SimulatorError::UnknownFunction(location.clone()))?;
+            match self.run_lines(&location, &lines, arguments.as_ref())? {
+                Flow::TailCall((next, _)) if next.starts_with('#') => {
+                    return self.call(&next, None)
+                }
+                Flow::TailCall((next, next_arguments)) => {
+                    location = resource_location(&next);
+                    arguments = next_arguments;
+                }
+                flow => return Ok(flow.outcome()),
+            }
+        }
+    }
+
+    // runs the function of a tail call, for when its result is needed right away
+    fn finish(&mut self, flow: Flow) -> SimulatorResult<Flow> {
+        match flow {
+            Flow::TailCall((location, arguments)) => {
+                Ok(Flow::Return(self.call(&location, arguments.as_ref())?))
+            }
+            flow => Ok(flow),
+        }
+    }
+
+    fn run_lines(
+        &mut self,
+        location: &str,
+        lines: &[String],
+        arguments: Option<&BTreeMap<String, Nbt>>,
+    ) -> SimulatorResult<Flow> {
+        for line in lines.iter() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let command = match line.strip_prefix('$') {
+                Some(macro_line) => substitute(location, macro_line, arguments)?,
+                None => line.to_string(),
+            };
+            match self.execute(&command)? {
+                Flow::Continue(_) => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Return(Some(0)))
+    }
+
+    fn execute(&mut self, command: &str) -> SimulatorResult<Flow> {
+        self.commands_run += 1;
+        if self.commands_run > MAX_COMMANDS {
+            return Err(SimulatorError::CommandLimit);
+        }
+
+        let invalid = || SimulatorError::InvalidCommand(command.to_string());
+        let mut rest = command.trim();
+        let name = next_word(&mut rest).ok_or_else(invalid)?;
+
+        let outcome = match name {
+            "return" => {
+                return Ok(match rest.trim() {
+                    "fail" => Flow::Return(None),
+                    value => match value.strip_prefix("run ") {
+                        Some(command) => match command.strip_prefix("function ") {
+                            Some(call) => match self.function_call(command, call)? {
+                                Some(call) => Flow::TailCall(call),
+                                None => Flow::Return(None),
+                            },
+                            None => match self.execute(command)? {
+                                Flow::TailCall(call) => Flow::TailCall(call),
+                                flow => Flow::Return(flow.outcome()),
+                            },
+                        },
+                        None => Flow::Return(Some(value.parse().map_err(|_| invalid())?)),
+                    },
+                })
+            }
+            "execute" => return self.execute_subcommands(command, rest, vec![]),
+            "function" => match self.function_call(command, rest)? {
+                Some((location, arguments)) => self.call(&location, arguments.as_ref())?,
+                None => None,
+            },
+            "scoreboard" => self.scoreboard(command, rest)?,
+            "data" => self.data(command, rest)?,
+            "say" => {
+                self.output.push(rest.trim().to_string());
+                Some(1)
+            }
+            "tellraw" => {
+                next_word(&mut rest).ok_or_else(invalid)?;
+                let component = serde_json::from_str(rest.trim()).map_err(|_| invalid())?;
+                let text = self.render_text(&component);
+                self.output.push(text);
+                Some(1)
+            }
+            _ => return Err(SimulatorError::Unsupported(command.to_string())),
+        };
+        Ok(Flow::Continue(outcome))
+    }
+
+    // `<location> [<arguments>]` of a `function` command. None if the macro arguments are not a compound
+    fn function_call(&mut self, command: &str, src: &str) -> SimulatorResult<Option<Call>> {
+        let mut rest = src;
+        let location = next_word(&mut rest)
+            .ok_or_else(|| SimulatorError::InvalidCommand(command.to_string()))?;
+        let arguments = match rest.trim_start() {
+            "" => None,
+            arguments => match self.macro_arguments(command, arguments)? {
+                Some(arguments) => Some(arguments),
+                None => return Ok(None),
+            },
+        };
+        Ok(Some((location.to_string(), arguments)))
+    }
+
+    // `with storage <storage> [<path>]` or an SNBT compound. None if the storage path is not a compound
+    fn macro_arguments(
+        &mut self,
+        command: &str,
+        src: &str,
+    ) -> SimulatorResult<Option<BTreeMap<String, Nbt>>> {
+        let invalid = || SimulatorError::InvalidCommand(command.to_string());
+        let mut rest = src;
+        let arguments = if rest.starts_with('{') {
+            parse_snbt(rest).map(|(nbt, _)| nbt).ok_or_else(invalid)?
+        } else {
+            if next_word(&mut rest) != Some("with") || next_word(&mut rest) != Some("storage") {
+                return Err(SimulatorError::Unsupported(command.to_string()));
+            }
+            let storage = next_word(&mut rest).ok_or_else(invalid)?;
+            let path = match rest.trim() {
+                "" => vec![],
+                path => parse_path(path).ok_or_else(invalid)?.0,
+            };
+            match self.get_storage(storage, &path) {
+                Some(nbt) => nbt.clone(),
+                None => return Ok(None),
+            }
+        };
+        match arguments {
+            Nbt::Compound(arguments) => Ok(Some(arguments)),
+            _ => Ok(None),
+        }
+    }
+
+    fn execute_subcommands(
+        &mut self,
+        command: &str,
+        src: &str,
+        mut stores: Vec<(bool, StoreTarget)>, // whether to store the result (or the success), and where
+    ) -> SimulatorResult<Flow> {
+        let invalid = || SimulatorError::InvalidCommand(command.to_string());
+        let mut rest = src;
+
+        loop {
+            let flow = match next_word(&mut rest).ok_or_else(invalid)? {
+                "run" => self.execute(rest)?,
+                "store" => {
+                    let is_result = match next_word(&mut rest) {
+                        Some("result") => true,
+                        Some("success") => false,
+                        _ => return Err(invalid()),
+                    };
+                    let target = match next_word(&mut rest) {
+                        Some("score") => {
+                            let holder = next_word(&mut rest).ok_or_else(invalid)?;
+                            let objective = next_word(&mut rest).ok_or_else(invalid)?;
+                            StoreTarget::Score(holder.to_string(), objective.to_string())
+                        }
+                        Some("storage") => {
+                            let storage = next_word(&mut rest).ok_or_else(invalid)?;
+                            let path = next_path(&mut rest).ok_or_else(invalid)?;
+                            let ty = next_word(&mut rest).ok_or_else(invalid)?;
+                            let scale = next_word(&mut rest)
+                                .and_then(|scale| scale.parse().ok())
+                                .ok_or_else(invalid)?;
+                            StoreTarget::Storage(
+                                resource_location(storage),
+                                path,
+                                ty.to_string(),
+                                scale,
+                            )
+                        }
+                        _ => return Err(SimulatorError::Unsupported(command.to_string())),
+                    };
+                    stores.push((is_result, target));
+                    continue;
+                }
+                condition @ ("if" | "unless") => {
+                    let holds = self.condition(command, &mut rest)? == (condition == "if");
+                    if !rest.trim().is_empty() {
+                        if holds {
+                            continue;
+                        }
+                        // the remaining subcommands are not run, and nothing is stored
+                        return Ok(Flow::Continue(None));
+                    }
+                    Flow::Continue(holds.then_some(1))
+                }
+                _ => return Err(SimulatorError::Unsupported(command.to_string())),
+            };
+
+            // the result of a tail call is only needed here if it is stored
+            if stores.is_empty() {
+                return Ok(flow);
+            }
+            let flow = self.finish(flow)?;
+            let outcome = flow.outcome();
+            for (is_result, target) in stores {
+                let value = match (is_result, outcome) {
+                    (true, outcome) => outcome.unwrap_or(0),
+                    (false, outcome) => outcome.is_some() as i32,
+                };
+                match target {
+                    StoreTarget::Score(holder, objective) => {
+                        if self.objectives.contains(&objective) {
+                            self.scores.insert((holder, objective), value);
+                        }
+                    }
+                    StoreTarget::Storage(storage, path, ty, scale) => {
+                        let scaled = value as f64 * scale;
+                        let nbt = match ty.as_str() {
+                            "byte" => Nbt::Byte(scaled as i8),
+                            "short" => Nbt::Short(scaled as i16),
+                            "int" => Nbt::Int(scaled as i32),
+                            "long" => Nbt::Long(scaled as i64),
+                            "float" => Nbt::Float(scaled as f32),
+                            "double" => Nbt::Double(scaled),
+                            _ => return Err(invalid()),
+                        };
+                        self.storages
+                            .entry(storage)
+                            .or_insert_with(Nbt::compound)
+                            .set(&path, nbt);
+                    }
+                }
+            }
+            return Ok(flow);
+        }
+    }
+
+    // `score <holder> <objective> (<operation> <holder> <objective> | matches <range>)` or `data storage <storage> <path>`
+    fn condition(&mut self, command: &str, rest: &mut &str) -> SimulatorResult<bool> {
+        let invalid = || SimulatorError::InvalidCommand(command.to_string());
+        match next_word(rest) {
+            Some("score") => {
+                let holder = next_word(rest).ok_or_else(invalid)?;
+                let objective = next_word(rest).ok_or_else(invalid)?;
+                let operation = next_word(rest).ok_or_else(invalid)?;
+                let Some(value) = self.score(holder, objective) else {
+                    // consumes the other operand
+                    next_word(rest);
+                    if operation != "matches" {
+                        next_word(rest);
+                    }
+                    return Ok(false);
+                };
+
+                if operation == "matches" {
+                    let range = next_word(rest).ok_or_else(invalid)?;
+                    let (min, max) = match range.split_once("..") {
+                        Some((min, max)) => (min, max),
+                        None => (range, range),
+                    };
+                    let bound = |bound: &str, default| match bound {
+                        "" => Ok(default),
+                        bound => bound.parse::<i32>().map_err(|_| invalid()),
+                    };
+                    return Ok(bound(min, i32::MIN)? <= value && value <= bound(max, i32::MAX)?);
+                }
+
+                let other_holder = next_word(rest).ok_or_else(invalid)?;
+                let other_objective = next_word(rest).ok_or_else(invalid)?;
+                let Some(other) = self.score(other_holder, other_objective) else {
+                    return Ok(false);
+                };
+                Ok(match operation {
+                    "=" => value == other,
+                    "<" => value < other,
+                    "<=" => value <= other,
+                    ">" => value > other,
+                    ">=" => value >= other,
+                    _ => return Err(invalid()),
+                })
+            }
+            Some("data") => {
+                if next_word(rest) != Some("storage") {
+                    return Err(SimulatorError::Unsupported(command.to_string()));
+                }
+                let storage = next_word(rest).ok_or_else(invalid)?;
+                let path = next_path(rest).ok_or_else(invalid)?;
+                Ok(self.get_storage(storage, &path).is_some())
+            }
+            Some("function") => {
+                let location = next_word(rest).ok_or_else(invalid)?;
+                Ok(self.call(location, None)?.is_some_and(|result| result != 0))
+            }
+            _ => Err(SimulatorError::Unsupported(command.to_string())),
+        }
+    }
+
+    fn scoreboard(&mut self, command: &str, src: &str) -> SimulatorResult<Outcome> {
+        let invalid = || SimulatorError::InvalidCommand(command.to_string());
+        let mut rest = src;
+        let mut word = || next_word(&mut rest).ok_or_else(invalid);
+
+        match (word()?, word()?) {
+            ("objectives", "add") => {
+                let objective = word()?;
+                word()?; // the criterion
+                Ok(self.objectives.insert(objective.to_string()).then_some(0))
+            }
+            ("objectives", "remove") => {
+                let objective = word()?;
+                self.scores.retain(|(_, other), _| other != objective);
+                Ok(self.objectives.remove(objective).then_some(0))
+            }
+            ("players", operation) => {
+                let holder = word()?;
+                if holder.starts_with('@') || holder == "*" {
+                    return Err(SimulatorError::Unsupported(command.to_string()));
+                }
+                let objective = word()?;
+                if !self.objectives.contains(objective) {
+                    return Ok(None);
+                }
+                let key = (holder.to_string(), objective.to_string());
+                let current = self.scores.get(&key).copied();
+
+                let value = match operation {
+                    "get" => return Ok(current),
+                    "reset" => return Ok(self.scores.remove(&key).map(|_| 0)),
+                    "set" | "add" | "remove" => {
+                        let amount = word()?.parse::<i32>().map_err(|_| invalid())?;
+                        let current = current.unwrap_or(0);
+                        match operation {
+                            "set" => amount,
+                            "add" => current.wrapping_add(amount),
+                            _ => current.wrapping_sub(amount),
+                        }
+                    }
+                    "operation" => {
+                        let operation = word()?;
+                        let source_key = (word()?.to_string(), word()?.to_string());
+                        if !self.objectives.contains(&source_key.1) {
+                            return Ok(None);
+                        }
+                        let (a, b) = (
+                            current.unwrap_or(0),
+                            self.scores.get(&source_key).copied().unwrap_or(0),
+                        );
+                        match operation {
+                            "><" => {
+                                self.scores.insert(source_key, a);
+                                b
+                            }
+                            _ => {
+                                let operation = score_operation(operation).ok_or_else(invalid)?;
+                                evaluate_operation(operation, a, b)
+                            }
+                        }
+                    }
+                    _ => return Err(SimulatorError::Unsupported(command.to_string())),
+                };
+                self.scores.insert(key, value);
+                Ok(Some(value))
+            }
+            _ => Err(SimulatorError::Unsupported(command.to_string())),
+        }
+    }
+
+    fn get_storage(&self, storage: &str, path: &NbtPath) -> Option<&Nbt> {
+        self.storages.get(&resource_location(storage))?.get(path)
+    }
+
+    fn data(&mut self, command: &str, src: &str) -> SimulatorResult<Outcome> {
+        let invalid = || SimulatorError::InvalidCommand(command.to_string());
+        let mut rest = src;
+
+        let operation = next_word(&mut rest).ok_or_else(invalid)?;
+        if next_word(&mut rest) != Some("storage") {
+            return Err(SimulatorError::Unsupported(command.to_string()));
+        }
+        let storage = resource_location(next_word(&mut rest).ok_or_else(invalid)?);
+
+        match operation {
+            "get" => {
+                let path = next_path(&mut rest).ok_or_else(invalid)?;
+                let scale = match next_word(&mut rest) {
+                    Some(scale) => scale.parse().map_err(|_| invalid())?,
+                    None => 1.0,
+                };
+                Ok(self
+                    .get_storage(&storage, &path)
+                    .map(|nbt| nbt.get_result(scale)))
+            }
+            "remove" => {
+                let path = next_path(&mut rest).ok_or_else(invalid)?;
+                let removed = self
+                    .storages
+                    .get_mut(&storage)
+                    .is_some_and(|nbt| nbt.remove(&path));
+                Ok(removed.then_some(1))
+            }
+            "merge" => {
+                let Some((Nbt::Compound(entries), _)) = parse_snbt(rest) else {
+                    return Err(invalid());
+                };
+                let target = self.storages.entry(storage).or_insert_with(Nbt::compound);
+                if let Nbt::Compound(target) = target {
+                    target.extend(entries);
+                }
+                Ok(Some(1))
+            }
+            "modify" => {
+                let path = next_path(&mut rest).ok_or_else(invalid)?;
+                let mode = next_word(&mut rest).ok_or_else(invalid)?;
+                let value = match next_word(&mut rest) {
+                    Some("value") => parse_snbt(rest).map(|(nbt, _)| nbt).ok_or_else(invalid)?,
+                    Some("from") => {
+                        if next_word(&mut rest) != Some("storage") {
+                            return Err(SimulatorError::Unsupported(command.to_string()));
+                        }
+                        let source = next_word(&mut rest).ok_or_else(invalid)?;
+                        let source_path = match rest.trim() {
+                            "" => vec![],
+                            _ => next_path(&mut rest).ok_or_else(invalid)?,
+                        };
+                        match self.get_storage(source, &source_path) {
+                            Some(nbt) => nbt.clone(),
+                            None => return Ok(None),
+                        }
+                    }
+                    _ => return Err(SimulatorError::Unsupported(command.to_string())),
+                };
+
+                let target = self.storages.entry(storage).or_insert_with(Nbt::compound);
+                let modified = match mode {
+                    "set" => target.set(&path, value),
+                    "append" | "prepend" => {
+                        if target.get(&path).is_none() {
+                            target.set(&path, Nbt::List(vec![]));
+                        }
+                        let mut list = target.get(&path).cloned();
+                        match &mut list {
+                            Some(Nbt::List(values)) if mode == "append" => values.push(value),
+                            Some(Nbt::List(values)) => values.insert(0, value),
+                            _ => return Ok(None),
+                        }
+                        target.set(&path, list.unwrap())
+                    }
+                    _ => return Err(SimulatorError::Unsupported(command.to_string())),
+                };
+                Ok(modified.then_some(1))
+            }
+            _ => Err(SimulatorError::Unsupported(command.to_string())),
+        }
+    }
+
+    // the plain text of a JSON text component
+    fn render_text(&self, component: &serde_json::Value) -> String {
+        use serde_json::Value;
+
+        let text = match component {
+            Value::String(text) => return text.clone(),
+            Value::Array(components) => {
+                return components
+                    .iter()
+                    .map(|component| self.render_text(component))
+                    .collect()
+            }
+            Value::Object(object) => object,
+            other => return other.to_string(),
+        };
+
+        let mut rendered = if let Some(value) = text.get("text") {
+            self.render_text(value)
+        } else if let Some(score) = text.get("score") {
+            score["name"]
+                .as_str()
+                .zip(score["objective"].as_str())
+                .and_then(|(holder, objective)| self.score(holder, objective))
+                .map_or(String::new(), |value| value.to_string())
+        } else if let (Some(path), Some(storage)) = (
+            text.get("nbt").and_then(Value::as_str),
+            text.get("storage").and_then(Value::as_str),
+        ) {
+            match self.storage(storage, path) {
+                Some(Nbt::String(value)) => value.clone(),
+                Some(nbt) => nbt.to_string(),
+                None => String::new(),
+            }
+        } else if let Some(key) = text.get("translate").and_then(Value::as_str) {
+            key.to_string()
+        } else {
+            String::new()
+        };
+
+        for extra in text
+            .get("extra")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            rendered += &self.render_text(extra);
+        }
+        rendered
+    }
+}
+
+// the game adds the `minecraft` namespace to locations without one
+fn resource_location(location: &str) -> String {
+    if location.contains(':') {
+        location.to_string()
+    } else {
+        format!("minecraft:{}", location)
+    }
+}
+
+// the operation of `scoreboard players operation`, other than swapping
+fn score_operation(symbol: &str) -> Option<ScoreOperation> {
+    Some(match symbol {
+        "=" => ScoreOperation::Assign,
+        "+=" => ScoreOperation::Add,
+        "-=" => ScoreOperation::Sub,
+        "*=" => ScoreOperation::Mul,
+        "/=" => ScoreOperation::Div,
+        "%=" => ScoreOperation::Mod,
+        "<" => ScoreOperation::Min,
+        ">" => ScoreOperation::Max,
+        _ => return None,
+    })
+}
+
+// replaces every `$(name)` with the value of the argument
+fn substitute(
+    function: &str,
+    line: &str,
+    arguments: Option<&BTreeMap<String, Nbt>>,
+) -> SimulatorResult<String> {
+    let mut substituted = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("$(") {
+        let end = rest[start..]
+            .find(')')
+            .ok_or_else(|| SimulatorError::InvalidCommand(line.to_string()))?;
+        let name = &rest[start + 2..start + end];
+        let value = arguments
+            .and_then(|arguments| arguments.get(name))
+            .ok_or_else(|| {
+                SimulatorError::MissingMacroArgument(function.to_string(), name.to_string())
+            })?;
+
+        substituted.push_str(&rest[..start]);
+        substituted.push_str(&value.macro_value());
+        rest = &rest[start + end + 1..];
+    }
+    substituted.push_str(rest);
+    Ok(substituted)
+}
+
+fn next_word<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    let trimmed = rest.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let end = trimmed.find(' ').unwrap_or(trimmed.len());
+    *rest = &trimmed[end..];
+    Some(&trimmed[..end])
+}
+
+fn next_path(rest: &mut &str) -> Option<NbtPath> {
+    let (path, after) = parse_path(rest.trim_start())?;
+    *rest = after;
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back::BackendOptions;
+    use crate::build::{compile, BuildOptions};
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use crate::middle::passes::{OptLevel, PassManager};
+    use crate::middle::IRGenOptions;
+    use camino::Utf8PathBuf;
+
+    fn datapack(functions: &[(&str, &str)]) -> Datapack {
+        let mut datapack = Datapack::default();
+        for (location, content) in functions {
+            let (namespace, path) = location.split_once(':').unwrap();
+            datapack.files.insert(
+                Utf8PathBuf::from(format!("data/{}/function/{}.mcfunction", namespace, path)),
+                content.to_string(),
+            );
+        }
+        datapack.files.insert(
+            Utf8PathBuf::from("data/minecraft/tags/function/load.json"),
+            "{\"values\": [\"test:load\"]}".to_string(),
+        );
+        datapack
+    }
+
+    #[test]
+    fn test_commands() {
+        let datapack = datapack(&[
+            (
+                "test:load",
+                "scoreboard objectives add s dummy\n\
+                 scoreboard players set $a s -7\n\
+                 scoreboard players set $b s 2\n\
+                 scoreboard players operation $a s /= $b s\n\
+                 execute store success score $c s if score $a s < $b s\n\
+                 execute if score $a s matches ..-4 run say floored\n\
+                 execute unless score $a s = $b s run return run function test:later\n\
+                 say unreachable",
+            ),
+            (
+                "test:later",
+                "data modify storage test:vars name set value \"steve\"\n\
+                 execute store result storage test:macro n int 2 run scoreboard players get $a s\n\
+                 data modify storage test:macro name set from storage test:vars name\n\
+                 function test:greet with storage test:macro",
+            ),
+            (
+                "test:greet",
+                "$tellraw @a [\"hi $(name) \", {\"score\": {\"name\": \"$a\", \"objective\": \"s\"}}, \" $(n)\"]\n\
+                 return 1\n\
+                 say unreachable",
+            ),
+        ]);
+
+        let mut simulator = Simulator::new(&datapack);
+        simulator.load().unwrap();
+        assert_eq!(simulator.score("$a", "s"), Some(-4));
+        assert_eq!(simulator.score("$c", "s"), Some(1));
+        assert_eq!(simulator.output(), ["floored", "hi steve -4 -8"]);
+        assert_eq!(
+            simulator.storage("test:vars", "name"),
+            Some(&Nbt::String("steve".to_string()))
+        );
+
+        // commands that fail do not stop the function
+        assert_eq!(
+            simulator.run_command("scoreboard players get $unset s"),
+            Ok(None)
+        );
+        assert_eq!(
+            simulator.run_command("function test:greet"),
+            Err(SimulatorError::MissingMacroArgument(
+                "test:greet".to_string(),
+                "name".to_string()
+            ))
+        );
+        assert_eq!(
+            simulator.run_command("tp @s ~ ~1 ~"),
+            Err(SimulatorError::Unsupported("tp @s ~ ~1 ~".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let datapack = datapack(&[
+            ("test:load", "function test:load"),
+            (
+                "test:loop",
+                "say loop\nexecute if score $a s matches 1 run function test:loop",
+            ),
+            (
+                "test:count",
+                "scoreboard players remove $a s 1\n\
+                 execute unless score $a s matches 0 run return run function test:count\n\
+                 return 7",
+            ),
+        ]);
+        let mut simulator = Simulator::new(&datapack);
+        assert_eq!(simulator.load(), Err(SimulatorError::CallDepth));
+
+        simulator
+            .run_command("scoreboard objectives add s dummy")
+            .unwrap();
+        simulator
+            .run_command("scoreboard players set $a s 1")
+            .unwrap();
+        assert_eq!(
+            simulator.run_function("test:loop"),
+            Err(SimulatorError::CallDepth)
+        );
+
+        // tail calls do not nest, only the commands they run are limited
+        simulator
+            .run_command("scoreboard players set $a s 20000")
+            .unwrap();
+        assert_eq!(simulator.run_function("test:count"), Ok(Some(7)));
+        assert_eq!(simulator.score("$a", "s"), Some(0));
+        simulator
+            .run_command("scoreboard players set $a s 40000")
+            .unwrap();
+        assert_eq!(
+            simulator.run_function("test:count"),
+            Err(SimulatorError::CommandLimit)
+        );
+    }
+
+    // runs a compiled program from start to end
+    #[test]
+    fn test_compiled_program() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"
+            static count: int;
+
+            fn add(a: int, b: int) -> int {
+                return a + b;
+            }
+
+            fn half(x: float) -> float {
+                return x / 2.0;
+            }
+
+            fn collatz(n: int) -> int {
+                let steps: int = 0;
+                while n != 1 {
+                    if n % 2 == 0 {
+                        n = n / 2;
+                    } else {
+                        n = 3 * n + 1;
+                    }
+                    steps = steps + 1;
+                }
+                return steps;
+            }
+
+            fn first_multiple_above(n: int, limit: int) -> int {
+                let i: int = 1;
+                while true {
+                    if i * n > limit {
+                        return i * n;
+                    }
+                    i = i + 1;
+                }
+                return 0;
+            }
+
+            // 2 * n is kept in a register during the recursive call
+            fn sum_of_evens(n: int) -> int {
+                if n <= 0 {
+                    return 0;
+                }
+                return 2 * n + sum_of_evens(n - 1);
+            }
+
+            // n and a are saved in a stack frame around the recursive calls
+            fn fib(n: int) -> int {
+                if n < 2 {
+                    return n;
+                }
+                let a: int = fib(n - 1);
+                return a + fib(n - 2);
+            }
+
+            fn main() {
+                let x: int = add(2, 3) * 4;
+                let h: float = half(3.0);
+                let big: bool = x > 10;
+                let name: string = "world";
+                cmd!("say {x} {h} {big} {name}");
+                let steps: int = collatz(6);
+                let multiple: int = first_multiple_above(7, 20);
+                let evens: int = sum_of_evens(4);
+                let fibonacci: int = fib(10);
+                cmd!("say {steps} {multiple} {evens} {fibonacci}");
+                count = 0;
+            }
+
+            #[tick]
+            fn on_tick() {
+                count = count + 1;
+            }
+            "#,
+        );
+
+        let options = BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions::default(),
+            backend: Default::default(),
+        };
+        let mut simulator = Simulator::new(&compile(&mut mock_fs, &options).unwrap());
+
+        simulator.load().unwrap();
+        assert_eq!(simulator.output(), ["20 1.5 1 world", "8 21 20 55"]);
+        for _ in 0..3 {
+            simulator.tick().unwrap();
+        }
+        assert_eq!(simulator.score("$package_a.main.0_0_count", "bf"), Some(3));
+
+        // hashed names behave the same
+        let options = BuildOptions {
+            backend: BackendOptions {
+                short_names: true,
+                ..BackendOptions::default()
+            },
+            ..options
+        };
+        let datapack = compile(&mut mock_fs, &options).unwrap();
+        assert!(datapack
+            .files
+            .values()
+            .all(|content| !content.contains("package_a")));
+        let mut simulator = Simulator::new(&datapack);
+        simulator.load().unwrap();
+        assert_eq!(simulator.output(), ["20 1.5 1 world", "8 21 20 55"]);
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"
+            static limit: int;
+
+            fn main() {
+                let big: int = 2147483647;
+                let wrapped: int = big + 1;
+                let product: int = 65536 * 65536 + 3;
+                let quotient: int = -7 / 2;
+                let remainder: int = -7 % 2;
+                let zero: int = 0;
+                let unchanged: int = 5 / zero;
+                cmd!("say {wrapped} {product} {quotient} {remainder} {unchanged}");
+
+                limit = 10;
+                let sum: int = 0;
+                let i: int = 0;
+                while i < limit {
+                    if limit > 5 {
+                        sum = sum + i;
+                    } else {
+                        sum = sum - i;
+                    }
+                    i = i + 1;
+                }
+                let half: float = 0.5 * 3.0;
+                cmd!("say {sum} {half} {limit}");
+            }
+            "#,
+        );
+
+        let options = |level| BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions {
+                passes: PassManager::new(level),
+                ..IRGenOptions::default()
+            },
+            backend: Default::default(),
+        };
+        let folded = compile(&mut mock_fs, &options(OptLevel::O1)).unwrap();
+        let unfolded = compile(&mut mock_fs, &options(OptLevel::O0)).unwrap();
+
+        // folding follows the wrapping and rounding of the scoreboard
+        let mut simulator = Simulator::new(&unfolded);
+        simulator.load().unwrap();
+        assert_eq!(simulator.output(), ["-2147483648 3 -4 1 5", "45 1.5 10"]);
+        let mut folded_simulator = Simulator::new(&folded);
+        folded_simulator.load().unwrap();
+        assert_eq!(folded_simulator.output(), simulator.output());
+
+        let command_count = |datapack: &Datapack| {
+            datapack
+                .files
+                .values()
+                .map(|content| content.lines().count())
+                .sum::<usize>()
+        };
+        assert!(command_count(&folded) < command_count(&unfolded));
+    }
+
+    #[test]
+    fn test_inlining() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"
+            fn square(x: int) -> int {
+                return x * x;
+            }
+
+            fn clamp(x: int, limit: int) -> int {
+                if x > limit {
+                    return limit;
+                }
+                return x;
+            }
+
+            #[inline(always)]
+            fn sum_below(n: int) -> int {
+                let sum: int = 0;
+                let i: int = 0;
+                while i < n {
+                    sum = sum + i;
+                    i = i + 1;
+                }
+                return sum;
+            }
+
+            // the inlined calls keep the values that are needed after the recursive call
+            fn sum_of_squares(n: int) -> int {
+                if n <= 0 {
+                    return 0;
+                }
+                let s: int = square(n);
+                let rest: int = sum_of_squares(n - 1);
+                return clamp(s + rest, 50);
+            }
+
+            fn main() {
+                let a: int = square(7);
+                let b: int = clamp(a, 40);
+                let c: int = sum_below(5);
+                let d: int = sum_of_squares(4);
+                let e: int = sum_of_squares(5);
+                cmd!("say {a} {b} {c} {d} {e}");
+            }
+            "#,
+        );
+
+        let options = |level| BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions {
+                passes: PassManager::new(level),
+                ..IRGenOptions::default()
+            },
+            backend: Default::default(),
+        };
+        let inlined = compile(&mut mock_fs, &options(OptLevel::O2)).unwrap();
+        let called = compile(&mut mock_fs, &options(OptLevel::O1)).unwrap();
+
+        let mut simulator = Simulator::new(&called);
+        simulator.load().unwrap();
+        assert_eq!(simulator.output(), ["49 40 10 30 50"]);
+        let mut inlined_simulator = Simulator::new(&inlined);
+        inlined_simulator.load().unwrap();
+        assert_eq!(inlined_simulator.output(), simulator.output());
+
+        // only the recursive function is left to call
+        let function_file = |name: &str| {
+            Utf8PathBuf::from(format!(
+                "data/blastfurnace/function/package_a/main/0_0_{}.mcfunction",
+                name
+            ))
+        };
+        for name in ["square", "clamp", "sum_below"] {
+            assert!(called.files.contains_key(&function_file(name)));
+            assert!(!inlined.files.contains_key(&function_file(name)));
+        }
+        assert!(inlined.files.contains_key(&function_file("sum_of_squares")));
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"
+            static counter: int;
+
+            fn bump() -> int {
+                counter = counter + 1;
+                return 10;
+            }
+
+            fn main() {
+                counter = 1;
+                let sum: int = counter + bump();
+                let difference: int = counter - bump() * 2;
+                cmd!("say {sum} {difference} {counter}");
+            }
+            "#,
+        );
+
+        // the lhs is read before the rhs calls `bump`
+        for level in [OptLevel::O0, OptLevel::O2] {
+            let options = BuildOptions {
+                package_name: "package_a".to_string(),
+                package_path: Utf8PathBuf::from("pkg/package_a"),
+                output_path: Utf8PathBuf::from("out"),
+                cache: None,
+                ir_gen: IRGenOptions {
+                    passes: PassManager::new(level),
+                    ..IRGenOptions::default()
+                },
+                backend: Default::default(),
+            };
+            let mut simulator = Simulator::new(&compile(&mut mock_fs, &options).unwrap());
+            simulator.load().unwrap();
+            assert_eq!(simulator.output(), ["11 -18 3"]);
+        }
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"
+            static calls: int;
+
+            fn bump(value: bool) -> bool {
+                calls = calls + 1;
+                return value;
+            }
+
+            fn main() {
+                calls = 0;
+                let a: bool = false && bump(true);
+                let b: bool = true || bump(false);
+                cmd!("say {a} {b} {calls}");
+                let c: bool = true && bump(false);
+                let d: bool = false || bump(true);
+                let e: bool = bump(true) && bump(true) || bump(false);
+                cmd!("say {c} {d} {e} {calls}");
+            }
+            "#,
+        );
+
+        // the rhs is only evaluated if the lhs does not decide the result
+        for level in [OptLevel::O0, OptLevel::O2] {
+            let options = BuildOptions {
+                package_name: "package_a".to_string(),
+                package_path: Utf8PathBuf::from("pkg/package_a"),
+                output_path: Utf8PathBuf::from("out"),
+                cache: None,
+                ir_gen: IRGenOptions {
+                    passes: PassManager::new(level),
+                    ..IRGenOptions::default()
+                },
+                backend: Default::default(),
+            };
+            let mut simulator = Simulator::new(&compile(&mut mock_fs, &options).unwrap());
+            simulator.load().unwrap();
+            assert_eq!(simulator.output(), ["0 1 0", "0 1 1 4"]);
+        }
+    }
+}
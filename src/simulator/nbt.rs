@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+// the value of a data storage path
+#[derive(Debug, PartialEq, Clone)]
+pub enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    List(Vec<Nbt>),
+    Compound(BTreeMap<String, Nbt>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PathNode {
+    Key(String),  // `name` or `"name"`
+    Index(isize), // `[0]`, or `[-1]` from the end
+}
+
+pub type NbtPath = Vec<PathNode>;
+
+impl Nbt {
+    pub fn compound() -> Nbt {
+        Nbt::Compound(BTreeMap::new())
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        Some(match self {
+            Nbt::Byte(value) => *value as f64,
+            Nbt::Short(value) => *value as f64,
+            Nbt::Int(value) => *value as f64,
+            Nbt::Long(value) => *value as f64,
+            Nbt::Float(value) => *value as f64,
+            Nbt::Double(value) => *value,
+            _ => return None,
+        })
+    }
+
+    // what `data get` returns: numbers are scaled and floored, everything else gives its length
+    pub fn get_result(&self, scale: f64) -> i32 {
+        match self {
+            Nbt::String(value) => value.chars().count() as i32,
+            Nbt::List(values) => values.len() as i32,
+            Nbt::Compound(entries) => entries.len() as i32,
+            number => (number.as_f64().unwrap() * scale).floor() as i32,
+        }
+    }
+
+    // how the value is inserted into a macro: strings without quotes, numbers without suffixes
+    pub fn macro_value(&self) -> String {
+        match self {
+            Nbt::String(value) => value.clone(),
+            Nbt::Float(value) => java_double(*value as f64),
+            Nbt::Double(value) => java_double(*value),
+            Nbt::Byte(_) | Nbt::Short(_) | Nbt::Int(_) | Nbt::Long(_) => {
+                (self.as_f64().unwrap() as i64).to_string()
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn get(&self, path: &[PathNode]) -> Option<&Nbt> {
+        let Some((first, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        match (self, first) {
+            (Nbt::Compound(entries), PathNode::Key(key)) => entries.get(key)?.get(rest),
+            (Nbt::List(values), PathNode::Index(index)) => {
+                values.get(list_index(values.len(), *index)?)?.get(rest)
+            }
+            _ => None,
+        }
+    }
+
+    // sets the value at the path, creating the compounds leading to it. Fails if the path goes through something else
+    pub fn set(&mut self, path: &[PathNode], value: Nbt) -> bool {
+        let Some((first, rest)) = path.split_first() else {
+            *self = value;
+            return true;
+        };
+        match (self, first) {
+            (Nbt::Compound(entries), PathNode::Key(key)) => {
+                let entry = entries.entry(key.clone()).or_insert_with(|| {
+                    if rest.is_empty() {
+                        value.clone()
+                    } else {
+                        Nbt::compound()
+                    }
+                });
+                entry.set(rest, value)
+            }
+            (Nbt::List(values), PathNode::Index(index)) => match list_index(values.len(), *index) {
+                Some(index) => values[index].set(rest, value),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    pub fn remove(&mut self, path: &[PathNode]) -> bool {
+        let Some((last, parents)) = path.split_last() else {
+            return false;
+        };
+        let parent = parents
+            .iter()
+            .try_fold(self, |nbt, node| match (nbt, node) {
+                (Nbt::Compound(entries), PathNode::Key(key)) => entries.get_mut(key),
+                (Nbt::List(values), PathNode::Index(index)) => {
+                    let index = list_index(values.len(), *index)?;
+                    values.get_mut(index)
+                }
+                _ => None,
+            });
+        match (parent, last) {
+            (Some(Nbt::Compound(entries)), PathNode::Key(key)) => entries.remove(key).is_some(),
+            (Some(Nbt::List(values)), PathNode::Index(index)) => {
+                match list_index(values.len(), *index) {
+                    Some(index) => {
+                        values.remove(index);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+fn list_index(len: usize, index: isize) -> Option<usize> {
+    let index = if index < 0 {
+        len as isize + index
+    } else {
+        index
+    };
+    (0..len as isize).contains(&index).then_some(index as usize)
+}
+
+// doubles are printed like Java does, always with a fractional part
+fn java_double(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e7 {
+        format!("{:.1}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn is_unquoted_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-' | '.' | '+')
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the value as SNBT
+impl fmt::Display for Nbt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Nbt::Byte(value) => write!(f, "{}b", value),
+            Nbt::Short(value) => write!(f, "{}s", value),
+            Nbt::Int(value) => write!(f, "{}", value),
+            Nbt::Long(value) => write!(f, "{}L", value),
+            Nbt::Float(value) => write!(f, "{}f", java_double(*value as f64)),
+            Nbt::Double(value) => write!(f, "{}d", java_double(*value)),
+            Nbt::String(value) => write!(f, "{}", quote(value)),
+            Nbt::List(values) => {
+                let values = values.iter().map(Nbt::to_string).collect::<Vec<_>>();
+                write!(f, "[{}]", values.join(","))
+            }
+            Nbt::Compound(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        if !key.is_empty() && key.chars().all(is_unquoted_char) {
+                            format!("{}:{}", key, value)
+                        } else {
+                            format!("{}:{}", quote(key), value)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", entries.join(","))
+            }
+        }
+    }
+}
+
+/* Reads SNBT, and NBT paths, from the start of a command argument. Each parse function returns what was read and
+the rest of the source, or None if it is invalid.
+ */
+pub fn parse_snbt(src: &str) -> Option<(Nbt, &str)> {
+    let src = src.trim_start();
+    match src.chars().next()? {
+        '{' => {
+            let mut entries = BTreeMap::new();
+            let mut rest = src[1..].trim_start();
+            if let Some(after) = rest.strip_prefix('}') {
+                return Some((Nbt::Compound(entries), after));
+            }
+            loop {
+                let (key, after) = parse_string(rest)?;
+                let after = after.trim_start().strip_prefix(':')?;
+                let (value, after) = parse_snbt(after)?;
+                entries.insert(key, value);
+
+                let after = after.trim_start();
+                if let Some(after) = after.strip_prefix(',') {
+                    rest = after.trim_start();
+                } else {
+                    return Some((Nbt::Compound(entries), after.strip_prefix('}')?));
+                }
+            }
+        }
+        '[' => {
+            let mut values = vec![];
+            let mut rest = src[1..].trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Some((Nbt::List(values), after));
+            }
+            loop {
+                let (value, after) = parse_snbt(rest)?;
+                values.push(value);
+
+                let after = after.trim_start();
+                if let Some(after) = after.strip_prefix(',') {
+                    rest = after;
+                } else {
+                    return Some((Nbt::List(values), after.strip_prefix(']')?));
+                }
+            }
+        }
+        '"' | '\'' => {
+            let (value, rest) = parse_string(src)?;
+            Some((Nbt::String(value), rest))
+        }
+        _ => {
+            let (word, rest) = parse_string(src)?;
+            Some((parse_unquoted(&word), rest))
+        }
+    }
+}
+
+// numbers with their suffix, booleans as bytes, and anything else as a string
+fn parse_unquoted(word: &str) -> Nbt {
+    let lower = word.to_ascii_lowercase();
+    let (number, suffix) = match lower.char_indices().last() {
+        Some((index, suffix @ ('b' | 's' | 'l' | 'f' | 'd'))) => (&lower[..index], Some(suffix)),
+        _ => (lower.as_str(), None),
+    };
+
+    let parsed = match suffix {
+        Some('b') => number.parse().ok().map(Nbt::Byte),
+        Some('s') => number.parse().ok().map(Nbt::Short),
+        Some('l') => number.parse().ok().map(Nbt::Long),
+        Some('f') => number.parse().ok().map(Nbt::Float),
+        Some('d') => number.parse().ok().map(Nbt::Double),
+        _ => number.parse().ok().map(Nbt::Int).or_else(|| {
+            number
+                .contains('.')
+                .then(|| number.parse().ok().map(Nbt::Double))
+                .flatten()
+        }),
+    };
+    match (parsed, lower.as_str()) {
+        (Some(nbt), _) => nbt,
+        (None, "true") => Nbt::Byte(1),
+        (None, "false") => Nbt::Byte(0),
+        (None, _) => Nbt::String(word.to_string()),
+    }
+}
+
+// a quoted string, or a word of unquoted characters
+fn parse_string(src: &str) -> Option<(String, &str)> {
+    let quote = src.chars().next()?;
+    if quote != '"' && quote != '\'' {
+        let end = src.find(|ch| !is_unquoted_char(ch)).unwrap_or(src.len());
+        return (end > 0).then(|| (src[..end].to_string(), &src[end..]));
+    }
+
+    let mut value = String::new();
+    let mut chars = src.char_indices().skip(1);
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '\\' => value.push(chars.next()?.1),
+            _ if ch == quote => return Some((value, &src[index + 1..])),
+            _ => value.push(ch),
+        }
+    }
+    None
+}
+
+// `a.b[0]."c d"`, up to the first space
+pub fn parse_path(src: &str) -> Option<(NbtPath, &str)> {
+    let mut path = vec![];
+    let mut rest = src;
+    loop {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            path.push(PathNode::Index(after[..end].trim().parse().ok()?));
+            rest = &after[end + 1..];
+        } else if rest.starts_with('"') {
+            let (key, after) = parse_string(rest)?;
+            path.push(PathNode::Key(key));
+            rest = after;
+        } else {
+            let end = rest.find(['.', '[', ' ']).unwrap_or(rest.len());
+            if end == 0 {
+                return None;
+            }
+            path.push(PathNode::Key(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+
+        if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+        } else if !rest.starts_with('[') {
+            break;
+        }
+    }
+    (rest.is_empty() || rest.starts_with(' ')).then_some((path, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snbt() {
+        let (nbt, rest) =
+            parse_snbt(r#"{a: 1, b: [1.5d, 2b, true], "c d": 'it\'s', e: {}, f: 3L} rest"#)
+                .unwrap();
+        assert_eq!(rest, " rest");
+        assert_eq!(
+            nbt.to_string(),
+            r#"{a:1,b:[1.5d,2b,1b],"c d":"it's",e:{},f:3L}"#
+        );
+        assert_eq!(parse_snbt(&nbt.to_string()).unwrap().0, nbt);
+        assert_eq!(parse_snbt("0.5").unwrap().0, Nbt::Double(0.5));
+        assert_eq!(parse_snbt("{a: 1"), None);
+
+        assert_eq!(Nbt::Double(2.0).macro_value(), "2.0");
+        assert_eq!(Nbt::String("hi".to_string()).macro_value(), "hi");
+        assert_eq!(Nbt::Double(-1.5).get_result(1.0), -2);
+    }
+
+    #[test]
+    fn test_paths() {
+        let (path, rest) = parse_path("pkg.main.0_0_x[-1].\"y z\"").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            path,
+            vec![
+                PathNode::Key("pkg".to_string()),
+                PathNode::Key("main".to_string()),
+                PathNode::Key("0_0_x".to_string()),
+                PathNode::Index(-1),
+                PathNode::Key("y z".to_string()),
+            ]
+        );
+
+        let mut nbt = Nbt::compound();
+        let (path, _) = parse_path("a.b.c").unwrap();
+        assert!(nbt.set(&path, Nbt::Int(1)));
+        assert_eq!(nbt.to_string(), "{a:{b:{c:1}}}");
+        assert_eq!(nbt.get(&path[..2]).unwrap().to_string(), "{c:1}");
+        // the path goes through a number
+        assert!(!nbt.set(&parse_path("a.b.c.d").unwrap().0, Nbt::Int(2)));
+
+        assert!(nbt.set(&path[..1], parse_snbt("[1, 2, 3]").unwrap().0));
+        assert!(nbt.remove(&parse_path("a[-1]").unwrap().0));
+        assert_eq!(nbt.to_string(), "{a:[1,2]}");
+        assert!(!nbt.remove(&parse_path("a[5]").unwrap().0));
+    }
+}
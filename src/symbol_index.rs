@@ -0,0 +1,263 @@
+use crate::front::ast_types::{Attribute, Definition, ResolvedName, Type, Visibility};
+use crate::middle::global_definition_table::GlobalDefinitionTable;
+use crate::modules::types::ModuleGraph;
+use camino::Utf8PathBuf;
+use std::collections::HashMap;
+
+// a name in a source file, between the byte offsets lo (inclusive) and hi (exclusive)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Location {
+    pub path: Utf8PathBuf,
+    pub lo: usize,
+    pub hi: usize,
+}
+
+// maps the names in the source files of every loaded package to the items they refer to, and back
+pub struct SymbolIndex {
+    files: HashMap<Utf8PathBuf, Vec<(Location, ResolvedName)>>,
+    definitions: HashMap<ResolvedName, Location>,
+    descriptions: HashMap<ResolvedName, String>, // what is shown when hovering over a name
+}
+
+impl SymbolIndex {
+    pub fn new(module_graph: &ModuleGraph) -> SymbolIndex {
+        let mut global_definition_table = GlobalDefinitionTable::new();
+        for (id, node) in module_graph.nodes.iter() {
+            if let Some(body) = &node.body {
+                global_definition_table.add_definition_table(id.clone(), &body.definitions);
+            }
+        }
+
+        let mut files: HashMap<Utf8PathBuf, Vec<(Location, ResolvedName)>> = HashMap::new();
+        let mut definitions = HashMap::new();
+        let mut descriptions = HashMap::new();
+        for node in module_graph.nodes.values() {
+            let Some(body) = &node.body else {
+                continue;
+            };
+            // inline modules are in the same file as their parent
+            let path = module_graph.package_map[&node.package_name].join(&node.rel_path);
+
+            for symbol in body.symbols.iter() {
+                // imported names refer to the definition in the module they were imported from
+                let name = global_definition_table.canonical_name(&symbol.name);
+                let location = Location {
+                    path: path.clone(),
+                    lo: symbol.span.lo,
+                    hi: symbol.span.hi + 1,
+                };
+
+                if !descriptions.contains_key(&name) {
+                    if let Some(definition) = global_definition_table.get_definition(&name) {
+                        descriptions.insert(name.clone(), describe(&definition));
+                    }
+                }
+                if symbol.is_definition {
+                    definitions.insert(name.clone(), location.clone());
+                }
+                files
+                    .entry(path.clone())
+                    .or_default()
+                    .push((location, name));
+            }
+        }
+
+        for symbols in files.values_mut() {
+            symbols.sort_by_key(|(location, _)| location.lo);
+        }
+
+        SymbolIndex {
+            files,
+            definitions,
+            descriptions,
+        }
+    }
+
+    // the item named at the offset, which may also be right after the name
+    pub fn name_at(&self, path: &Utf8PathBuf, offset: usize) -> Option<&ResolvedName> {
+        self.symbol_at(path, offset).map(|(_, name)| name)
+    }
+
+    fn symbol_at(&self, path: &Utf8PathBuf, offset: usize) -> Option<&(Location, ResolvedName)> {
+        self.files
+            .get(path)?
+            .iter()
+            .find(|(location, _)| location.lo <= offset && offset <= location.hi)
+    }
+
+    // the description of the item named at the offset, along with where the name is
+    pub fn hover(&self, path: &Utf8PathBuf, offset: usize) -> Option<(&Location, &str)> {
+        let (location, name) = self.symbol_at(path, offset)?;
+        Some((location, self.descriptions.get(name)?))
+    }
+
+    pub fn definition(&self, path: &Utf8PathBuf, offset: usize) -> Option<&Location> {
+        self.definitions.get(self.name_at(path, offset)?)
+    }
+
+    // every use of the item named at the offset, ordered by file and position
+    pub fn references(
+        &self,
+        path: &Utf8PathBuf,
+        offset: usize,
+        include_definition: bool,
+    ) -> Vec<Location> {
+        let Some(name) = self.name_at(path, offset) else {
+            return vec![];
+        };
+        let definition = self.definitions.get(name);
+
+        let mut references = self
+            .files
+            .values()
+            .flatten()
+            .filter(|(location, other)| {
+                other == name && (include_definition || Some(location) != definition)
+            })
+            .map(|(location, _)| location.clone())
+            .collect::<Vec<_>>();
+        references.sort_by(|a, b| (&a.path, a.lo).cmp(&(&b.path, b.lo)));
+        references
+    }
+}
+
+// the definition as it would be declared, without the body of functions
+pub fn describe(definition: &Definition) -> String {
+    let visibility = |visibility: &Visibility| match visibility {
+        Visibility::Private => "",
+        Visibility::Package => "pub(package) ",
+        Visibility::Public => "pub ",
+    };
+
+    match definition {
+        Definition::StaticVarDef(def) => format!(
+            "{}static {}: {}",
+            visibility(&def.visibility),
+            def.name.raw.0,
+            def.ty
+        ),
+        Definition::VarDef(def) => format!("{}: {}", def.name.raw.0, def.ty),
+        Definition::StructDef(def) => {
+            let mut fields = def.field_types.iter().collect::<Vec<_>>();
+            fields.sort_by_key(|(name, _)| name.as_str());
+            let fields = fields
+                .into_iter()
+                .map(|(name, ty)| format!("    {}: {},\n", name, ty))
+                .collect::<String>();
+            format!(
+                "{}struct {} {{\n{}}}",
+                visibility(&def.visibility),
+                def.name.raw.0,
+                fields
+            )
+        }
+        Definition::FnDef(def) => {
+            let attributes = def
+                .attributes
+                .iter()
+                .map(|attribute| match attribute {
+                    Attribute::Load => "#[load]\n".to_string(),
+                    Attribute::Tick => "#[tick]\n".to_string(),
+                    Attribute::Test => "#[test]\n".to_string(),
+                    Attribute::Export(path) => format!("#[export({:?})]\n", path),
+                    Attribute::Inline(hint) => format!("#[inline({})]\n", hint),
+                })
+                .collect::<String>();
+            let args = def
+                .args
+                .iter()
+                .map(|arg| format!("{}: {}", arg.name.raw.0, arg.ty))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let return_type = match def.return_type {
+                Type::Void => String::new(),
+                ref ty => format!(" -> {}", ty),
+            };
+            format!(
+                "{}{}fn {}({}){}",
+                attributes,
+                visibility(&def.visibility),
+                def.name.raw.0,
+                args,
+                return_type
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use crate::modules::ModuleBuilder;
+
+    fn location(path: &str, src: &str, name: &str, occurrence: usize) -> Location {
+        let lo = src.match_indices(name).nth(occurrence).unwrap().0;
+        Location {
+            path: Utf8PathBuf::from(path),
+            lo,
+            hi: lo + name.len(),
+        }
+    }
+
+    #[test]
+    fn test_symbol_index() {
+        let main = "use package_b::util::helper;\nfn main() {\n    let x: int = helper(1);\n    x = helper(x);\n}";
+        let util = "pub fn helper(a: int) -> int {\n    return a;\n}";
+
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/package_a/main.ing"), main);
+        mock_fs.insert_file(Utf8PathBuf::from("pkg/package_b/util.ing"), util);
+
+        let mut module_builder = ModuleBuilder::new(&mut mock_fs, None);
+        module_builder
+            .add_fs_package("package_a", &Utf8PathBuf::from("pkg/package_a"), true)
+            .unwrap();
+        module_builder
+            .add_fs_package("package_b", &Utf8PathBuf::from("pkg/package_b"), false)
+            .unwrap();
+        module_builder.load_module_bodies().unwrap();
+
+        let index = SymbolIndex::new(module_builder.get_module_graph());
+        let main_path = Utf8PathBuf::from("pkg/package_a/main.ing");
+        let util_path = Utf8PathBuf::from("pkg/package_b/util.ing");
+
+        // across packages
+        let call = location("pkg/package_a/main.ing", main, "helper", 2);
+        let definition = location("pkg/package_b/util.ing", util, "helper", 0);
+        assert_eq!(index.definition(&main_path, call.lo + 2), Some(&definition));
+        assert_eq!(
+            index.references(&util_path, definition.lo, false),
+            vec![
+                location("pkg/package_a/main.ing", main, "helper", 1),
+                call.clone()
+            ]
+        );
+
+        // local variables and arguments
+        let x = location("pkg/package_a/main.ing", main, "x", 1);
+        assert_eq!(
+            index.definition(&main_path, x.lo),
+            Some(&location("pkg/package_a/main.ing", main, "x", 0))
+        );
+        assert_eq!(index.references(&main_path, x.lo, true).len(), 3);
+        assert_eq!(
+            index.definition(&util_path, util.rfind('a').unwrap()),
+            Some(&location("pkg/package_b/util.ing", util, "a", 0))
+        );
+
+        assert_eq!(index.name_at(&main_path, 0), None);
+
+        // hovering shows the definition, wherever the name is used
+        assert_eq!(
+            index.hover(&main_path, call.lo),
+            Some((&call, "pub fn helper(a: int) -> int"))
+        );
+        assert_eq!(
+            index
+                .hover(&main_path, x.lo)
+                .map(|(_, description)| description),
+            Some("x: int")
+        );
+    }
+}
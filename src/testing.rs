@@ -0,0 +1,254 @@
+use crate::build::{compile_with_sources, BuildOptions, BuildResult};
+use crate::file_system::FileSystem;
+use crate::front::diagnostics::line_column;
+use crate::simulator::nbt::Nbt;
+use crate::simulator::{Simulator, SimulatorError};
+use camino::Utf8PathBuf;
+use std::io::Read;
+
+#[derive(Debug, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    Failed(Vec<String>), // the failed assertions, as `file:line:column: message`
+    Error(SimulatorError), // the test could not be run to its end
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TestResult {
+    pub name: String, // e.g. `pkg::main::adds`
+    pub outcome: TestOutcome,
+}
+
+/* Compiles the package together with its `#[test]` functions, and runs each test on a simulator of its own.
+
+Before a test, only the initialization function of the datapack is run, and neither `main` nor the `#[load]`
+functions are. A failed assertion records itself in the test storage and returns from the function it is in, so a
+test fails if anything was recorded once it is done. Only the tests whose name contains the filter are run.
+ */
+pub fn run_tests<T: FileSystem>(
+    file_system: &mut T,
+    mut options: BuildOptions,
+    filter: Option<&str>,
+) -> BuildResult<Vec<TestResult>> {
+    options.ir_gen.tests = true;
+    let (datapack, sources) = compile_with_sources(file_system, &options)?;
+    let namespace = &options.backend.namespace;
+
+    let mut results = vec![];
+    for (name, location) in datapack.tests.iter() {
+        if filter.is_some_and(|filter| !name.contains(filter)) {
+            continue;
+        }
+
+        let mut simulator = Simulator::new(&datapack);
+        let outcome = match simulator
+            .run_function(&format!("{}:__init", namespace))
+            .and_then(|_| simulator.run_function(location))
+        {
+            Err(e) => TestOutcome::Error(e),
+            Ok(_) => {
+                let failures = match simulator.storage(&format!("{}:test", namespace), "failures") {
+                    Some(Nbt::List(failures)) => failures.clone(),
+                    _ => vec![],
+                };
+                if failures.is_empty() {
+                    TestOutcome::Passed
+                } else {
+                    let read = |path: &Utf8PathBuf| {
+                        let mut src = String::new();
+                        file_system
+                            .get_reader(path)
+                            .ok()?
+                            .read_to_string(&mut src)
+                            .ok()?;
+                        Some(src)
+                    };
+                    TestOutcome::Failed(
+                        failures
+                            .iter()
+                            .map(|failure| {
+                                describe_failure(failure, |id| {
+                                    let path = sources.get(id)?;
+                                    Some((path.clone(), read(path)?))
+                                })
+                            })
+                            .collect(),
+                    )
+                }
+            }
+        };
+        results.push(TestResult {
+            name: name.clone(),
+            outcome,
+        });
+    }
+    Ok(results)
+}
+
+// `file:line:column: message`, followed by the two sides of `assert_eq!`
+fn describe_failure(
+    failure: &Nbt,
+    source: impl Fn(&String) -> Option<(Utf8PathBuf, String)>,
+) -> String {
+    let field = |key: &str| match failure {
+        Nbt::Compound(fields) => fields.get(key),
+        _ => None,
+    };
+    let text = |key: &str| match field(key) {
+        Some(Nbt::String(text)) => text.clone(),
+        _ => String::new(),
+    };
+
+    let module_id = text("module");
+    let offset = field("offset").and_then(Nbt::as_f64).unwrap_or(0.0) as usize;
+    let location = match source(&module_id) {
+        Some((path, src)) => {
+            let (line, column) = line_column(&src, offset);
+            format!("{}:{}:{}", path, line, column)
+        }
+        None => module_id,
+    };
+
+    let mut description = format!("{}: {}", location, text("message"));
+    if let (Some(left), Some(right)) = (field("left"), field("right")) {
+        description.push_str(&format!(
+            "\n  left: {}\n right: {}",
+            left.macro_value(),
+            right.macro_value()
+        ));
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back::BackendOptions;
+    use crate::file_system::concrete::mock_fs::MockFileSystem;
+    use crate::middle::IRGenOptions;
+
+    #[test]
+    fn test_run_tests() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"fn main() {}
+
+fn double(x: int) -> int {
+    return x * 2;
+}
+
+#[test]
+fn doubles() {
+    assert_eq!(double(2), 4);
+    assert!(double(-1) < 0);
+}
+
+#[test]
+fn halves() {
+    let half: float = 1.0 / 4.0;
+    assert_eq!(half, 0.5);
+    cmd!("say unreachable");
+}
+"#,
+        );
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/util.ing"),
+            "#[test] fn fails() { assert!(false); }",
+        );
+
+        let options = BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions::default(),
+            backend: BackendOptions::default(),
+        };
+        let results = run_tests(&mut mock_fs, options, None).unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                TestResult {
+                    name: "package_a::main::doubles".to_string(),
+                    outcome: TestOutcome::Passed,
+                },
+                TestResult {
+                    name: "package_a::main::halves".to_string(),
+                    outcome: TestOutcome::Failed(vec![
+                        "pkg/package_a/main.ing:16:5: assert_eq!(half, 0.5)\n  left: 0.25\n right: 0.5"
+                            .to_string()
+                    ]),
+                },
+                TestResult {
+                    name: "package_a::util::fails".to_string(),
+                    outcome: TestOutcome::Failed(vec![
+                        "pkg/package_a/util.ing:1:22: assert!(false)".to_string()
+                    ]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tests_are_not_built() {
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            "fn main() {} #[test] fn check() { assert!(true); }",
+        );
+
+        let options = BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions::default(),
+            backend: BackendOptions::default(),
+        };
+        let datapack = crate::build::compile(&mut mock_fs, &options).unwrap();
+
+        assert!(datapack.tests.is_empty());
+        assert!(!datapack
+            .files
+            .keys()
+            .any(|path| path.as_str().contains("check")));
+
+        let results = run_tests(&mut mock_fs, options, Some("check")).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].outcome, TestOutcome::Passed);
+    }
+
+    #[test]
+    fn test_long_loop() {
+        // every iteration returns into the next block, which must not count as nesting
+        let mut mock_fs = MockFileSystem::new();
+        mock_fs.insert_file(
+            Utf8PathBuf::from("pkg/package_a/main.ing"),
+            r#"fn main() {}
+
+#[test]
+fn counts() {
+    let x: int = 0;
+    while x < 6000 {
+        x = x + 1;
+    }
+    assert_eq!(x, 6000);
+}
+"#,
+        );
+
+        let options = BuildOptions {
+            package_name: "package_a".to_string(),
+            package_path: Utf8PathBuf::from("pkg/package_a"),
+            output_path: Utf8PathBuf::from("out"),
+            cache: None,
+            ir_gen: IRGenOptions::default(),
+            backend: BackendOptions::default(),
+        };
+        let results = run_tests(&mut mock_fs, options, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].outcome, TestOutcome::Passed);
+    }
+}